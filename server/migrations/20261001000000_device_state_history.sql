@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS device_state_history (
+    id BIGSERIAL PRIMARY KEY,
+    integration_id TEXT NOT NULL,
+    device_id TEXT NOT NULL,
+    recorded_at_ms BIGINT NOT NULL,
+    state TEXT NOT NULL,
+    raw TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_state_history_device_time
+    ON device_state_history (integration_id, device_id, recorded_at_ms);
+
+CREATE INDEX IF NOT EXISTS idx_device_state_history_recorded_at
+    ON device_state_history (recorded_at_ms);
+
+ALTER TABLE core_config
+    ADD COLUMN IF NOT EXISTS device_history_retention_days INTEGER DEFAULT 7;
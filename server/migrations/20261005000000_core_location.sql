@@ -0,0 +1,5 @@
+ALTER TABLE core_config
+    ADD COLUMN IF NOT EXISTS latitude DOUBLE PRECISION;
+
+ALTER TABLE core_config
+    ADD COLUMN IF NOT EXISTS longitude DOUBLE PRECISION;
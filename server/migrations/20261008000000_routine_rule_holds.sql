@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS routine_rule_holds (
+    routine_id TEXT NOT NULL,
+    rule_key TEXT NOT NULL, -- JSON of the rule
+    since_ms BIGINT NOT NULL,
+    fired BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (routine_id, rule_key)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE routines
+    ADD COLUMN IF NOT EXISTS cooldown_ms BIGINT;
+
+ALTER TABLE routines
+    ADD COLUMN IF NOT EXISTS debounce_ms BIGINT;
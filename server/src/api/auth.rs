@@ -0,0 +1,758 @@
+//! Authentication and role-based access control for the HTTP and WebSocket
+//! API.
+//!
+//! Provides endpoints for:
+//! - Login sessions: POST /api/v1/auth/login, POST /api/v1/auth/logout,
+//!   GET /api/v1/auth/me
+//! - Users: GET /api/v1/auth/users, PUT/DELETE /api/v1/auth/users/{username}
+//! - API tokens: GET/POST /api/v1/auth/tokens, DELETE /api/v1/auth/tokens/{id}
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use serde::Deserialize;
+use warp::{
+    http::{Method, StatusCode},
+    path::FullPath,
+    reply::Response,
+    Filter, Rejection, Reply,
+};
+
+use crate::core::auth::{
+    generate_secret, hash_password, hash_token, Auth, Principal, SESSION_LIFETIME_MS,
+};
+use crate::db::auth_queries::{
+    db_delete_api_token, db_delete_expired_sessions, db_delete_user, db_insert_api_token,
+    db_upsert_user, ApiTokenRow, UserRow,
+};
+use crate::types::{
+    auth::{ApiTokenInfo, CreatedApiToken, LoginResponse, Role, UserInfo},
+    websockets::WebSocketRequest,
+};
+
+use super::config::{decode_path_key, error_response, not_found, ApiResponse};
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+#[derive(Debug)]
+struct Forbidden {
+    required: Role,
+}
+
+impl warp::reject::Reject for Forbidden {}
+
+#[derive(Deserialize)]
+struct LoginPayload {
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct UserPayload {
+    /// Required when creating a user, keeps the current password if left out
+    /// when updating one.
+    #[serde(default)]
+    password: Option<String>,
+    role: Role,
+}
+
+#[derive(Deserialize)]
+struct ApiTokenPayload {
+    name: String,
+    role: Role,
+}
+
+/// Role needed for a request, or `None` if anyone may make it.
+pub fn required_role(method: &Method, path: &str, query: &HashMap<String, String>) -> Option<Role> {
+    let path = path.trim_end_matches('/');
+
+    if method == Method::OPTIONS || path == "/api/v1/auth/login" {
+        return None;
+    }
+
+    if path == "/ws" {
+        return Some(Role::Viewer);
+    }
+
+    // Health checks and the bundled UI
+    if path != "/api" && !path.starts_with("/api/") {
+        return None;
+    }
+
+    if path == "/api/v1/auth/me" || path == "/api/v1/auth/logout" {
+        return Some(Role::Viewer);
+    }
+
+    if path.starts_with("/api/v1/auth/") {
+        return Some(Role::Admin);
+    }
+
+    let read_only = method == Method::GET || method == Method::HEAD;
+
+    if read_only {
+        // These may contain credentials of integrations or server logs
+        let sensitive = path == "/api/v1/config/export"
+            || path == "/api/v1/config/webhook-deliveries"
+            || path == "/api/v1/integrations/status"
+            || path == "/api/v1/config/integrations"
+            || path.starts_with("/api/v1/config/integrations/")
+            || path == "/api/v1/config/versions"
+            || path.starts_with("/api/v1/config/versions/")
+            || path == "/api/v1/config/core"
+            || path == "/api/v1/config/logs";
+
+        return Some(if sensitive { Role::Admin } else { Role::Viewer });
+    }
+
+    let operator_paths = [
+        "/api/v1/actions",
+        "/api/v1/devices",
+        "/api/v1/config/scenes",
+        "/api/v1/config/sequences",
+    ];
+
+    let is_operator_path = operator_paths
+        .iter()
+        .any(|prefix| path == *prefix || path.starts_with(&format!("{prefix}/")));
+
+    // Replacing or cascading a scene rewrites and deletes routines, which
+    // only admins may edit
+    let rewrites_routines = path.starts_with("/api/v1/config/scenes/")
+        && ((method == Method::DELETE && query.get("cascade").map(String::as_str) == Some("true"))
+            || (method == Method::POST
+                && (path.ends_with("/replace") || path.ends_with("/rename"))));
+
+    Some(if is_operator_path && !rewrites_routines {
+        Role::Operator
+    } else {
+        Role::Admin
+    })
+}
+
+/// Role needed to make a request over the WebSocket API.
+pub fn required_request_role(request: &WebSocketRequest) -> Role {
+    match request {
+        WebSocketRequest::Action(_)
+        | WebSocketRequest::StoreUiState { .. }
+        | WebSocketRequest::SetDeviceState { .. }
+        | WebSocketRequest::StoreScene { .. }
+        | WebSocketRequest::RenameScene { .. }
+        | WebSocketRequest::DeleteScene { .. } => Role::Operator,
+        WebSocketRequest::Subscribe(_) => Role::Viewer,
+    }
+}
+
+/// Paths that accept the token as a query parameter, since browsers can't set
+/// headers on WebSocket connections or `EventSource` requests. Elsewhere it
+/// would only end up in proxy and access logs.
+const QUERY_TOKEN_PATHS: [&str; 2] = ["/ws", "/api/v1/events"];
+
+/// Reads the bearer token from the `Authorization` header, falling back to
+/// the `token` query parameter on [QUERY_TOKEN_PATHS].
+fn request_token(
+    path: &str,
+    authorization: Option<&str>,
+    query: &HashMap<String, String>,
+) -> Option<String> {
+    let query_token = || {
+        let path = path.trim_end_matches('/');
+        QUERY_TOKEN_PATHS
+            .contains(&path)
+            .then(|| query.get("token").cloned())
+            .flatten()
+    };
+
+    authorization
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .or_else(query_token)
+        .filter(|token| !token.is_empty())
+}
+
+fn authorize(
+    auth: &Auth,
+    method: &Method,
+    path: &str,
+    query: &HashMap<String, String>,
+    token: Option<&str>,
+) -> Result<Option<Principal>, Rejection> {
+    if !auth.is_enabled() {
+        return Ok(Some(Principal::anonymous()));
+    }
+
+    let principal = token.and_then(|token| auth.authenticate(token));
+
+    let Some(required) = required_role(method, path, query) else {
+        return Ok(principal);
+    };
+
+    match principal {
+        None => Err(warp::reject::custom(Unauthorized)),
+        Some(principal) if principal.role < required => {
+            Err(warp::reject::custom(Forbidden { required }))
+        }
+        Some(principal) => Ok(Some(principal)),
+    }
+}
+
+fn with_auth(
+    auth: &Auth,
+) -> impl Filter<Extract = (Auth,), Error = std::convert::Infallible> + Clone {
+    let auth = auth.clone();
+    warp::any().map(move || auth.clone())
+}
+
+fn request_query(
+) -> impl Filter<Extract = (HashMap<String, String>,), Error = std::convert::Infallible> + Clone {
+    warp::query::<HashMap<String, String>>()
+        .or(warp::any().map(HashMap::new))
+        .unify()
+}
+
+fn request_token_filter(
+) -> impl Filter<Extract = (Option<String>,), Error = std::convert::Infallible> + Clone {
+    warp::path::full()
+        .and(
+            warp::header::optional::<String>("authorization")
+                .or(warp::any().map(|| None))
+                .unify(),
+        )
+        .and(request_query())
+        .map(
+            |path: FullPath, authorization: Option<String>, query: HashMap<String, String>| {
+                request_token(path.as_str(), authorization.as_deref(), &query)
+            },
+        )
+}
+
+/// Authenticates the request and checks that it's allowed by the role of the
+/// caller. Extracts the caller, which is `None` for public requests made
+/// without valid credentials.
+pub fn principal(
+    auth: &Auth,
+) -> impl Filter<Extract = (Option<Principal>,), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and(request_query())
+        .and(request_token_filter())
+        .and(with_auth(auth))
+        .and_then(
+            |method: Method,
+             path: FullPath,
+             query: HashMap<String, String>,
+             token: Option<String>,
+             auth: Auth| async move {
+                authorize(&auth, &method, path.as_str(), &query, token.as_deref())
+            },
+        )
+}
+
+/// Rejects requests that the caller is not allowed to make.
+pub fn authorized(auth: &Auth) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    principal(auth).map(|_| ()).untuple_one()
+}
+
+pub async fn handle_rejection(rejection: Rejection) -> Result<Response, Rejection> {
+    if rejection.find::<Unauthorized>().is_some() {
+        let reply = error_response("Authentication required", StatusCode::UNAUTHORIZED);
+        return Ok(warp::reply::with_header(reply, "www-authenticate", "Bearer").into_response());
+    }
+
+    if let Some(Forbidden { required }) = rejection.find::<Forbidden>() {
+        let msg = format!("The {} role is required", required.as_str());
+        return Ok(error_response(&msg, StatusCode::FORBIDDEN).into_response());
+    }
+
+    Err(rejection)
+}
+
+pub fn auth_routes(
+    auth: &Auth,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("auth").and(
+        session_routes(auth)
+            .or(user_routes(auth))
+            .or(token_routes(auth)),
+    )
+}
+
+fn session_routes(
+    auth: &Auth,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let login = warp::path("login")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_auth(auth))
+        .and_then(login);
+
+    let logout = warp::path("logout")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(request_token_filter())
+        .and(with_auth(auth))
+        .and_then(logout);
+
+    let me = warp::path("me")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(principal(auth))
+        .and_then(me);
+
+    login.or(logout).or(me)
+}
+
+fn user_routes(
+    auth: &Auth,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let list = warp::path("users")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_auth(auth))
+        .and_then(list_users);
+
+    let set = warp::path!("users" / String)
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(with_auth(auth))
+        .and_then(set_user);
+
+    let delete = warp::path!("users" / String)
+        .and(warp::delete())
+        .and(with_auth(auth))
+        .and_then(delete_user);
+
+    list.or(set).or(delete)
+}
+
+fn token_routes(
+    auth: &Auth,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let list = warp::path("tokens")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_auth(auth))
+        .and_then(list_tokens);
+
+    let create = warp::path("tokens")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_auth(auth))
+        .and_then(create_token);
+
+    let delete = warp::path!("tokens" / String)
+        .and(warp::delete())
+        .and(with_auth(auth))
+        .and_then(delete_token);
+
+    list.or(create).or(delete)
+}
+
+/// Short random id that identifies a token without revealing it.
+fn generate_token_id() -> String {
+    generate_secret()[..16].to_string()
+}
+
+async fn login(payload: LoginPayload, auth: Auth) -> Result<Response, warp::Rejection> {
+    // Password hashing is slow on purpose, keep it off the async workers
+    let role = {
+        let auth = auth.clone();
+        let username = payload.username.clone();
+        tokio::task::spawn_blocking(move || auth.verify_login(&username, &payload.password))
+            .await
+            .ok()
+            .flatten()
+    };
+
+    let Some(role) = role else {
+        return Ok(
+            error_response("Invalid username or password", StatusCode::UNAUTHORIZED)
+                .into_response(),
+        );
+    };
+
+    let now_ms = Utc::now().timestamp_millis();
+    auth.remove_expired_sessions(now_ms);
+    if let Err(e) = db_delete_expired_sessions(now_ms).await {
+        warn!("Failed to delete expired sessions: {e}");
+    }
+
+    let token = generate_secret();
+    let session = ApiTokenRow {
+        id: generate_token_id(),
+        name: format!("{} session", payload.username),
+        token_hash: hash_token(&token),
+        role,
+        username: Some(payload.username.clone()),
+        expires_at_ms: Some(now_ms + SESSION_LIFETIME_MS),
+        created_at_ms: now_ms,
+    };
+
+    if let Err(e) = db_insert_api_token(&session).await {
+        return Ok(error_response(
+            &format!("Failed to persist login session: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response());
+    }
+    auth.set_token(session);
+
+    Ok(ApiResponse::success(LoginResponse {
+        token,
+        username: payload.username,
+        role,
+        expires_at_ms: now_ms + SESSION_LIFETIME_MS,
+    })
+    .into_response())
+}
+
+async fn logout(token: Option<String>, auth: Auth) -> Result<impl Reply, warp::Rejection> {
+    // API tokens are revoked through the tokens endpoint instead
+    let session_id = token.as_deref().and_then(|token| auth.session_id(token));
+
+    let Some(session_id) = session_id else {
+        return Ok(error_response(
+            "Not logged in with a session token",
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+
+    if let Err(e) = db_delete_api_token(&session_id).await {
+        return Ok(error_response(
+            &format!("Failed to persist session removal: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+    auth.remove_token(&session_id);
+
+    Ok(ApiResponse::success(true))
+}
+
+async fn me(principal: Option<Principal>) -> Result<impl Reply, warp::Rejection> {
+    let principal = principal.unwrap_or_else(Principal::anonymous);
+
+    Ok(ApiResponse::success(UserInfo {
+        username: principal.name,
+        role: principal.role,
+    }))
+}
+
+async fn list_users(auth: Auth) -> Result<impl Reply, warp::Rejection> {
+    Ok(ApiResponse::success(auth.users()))
+}
+
+async fn set_user(
+    username: String,
+    payload: UserPayload,
+    auth: Auth,
+) -> Result<impl Reply, warp::Rejection> {
+    let username = decode_path_key(username);
+    if username.trim().is_empty() {
+        return Ok(error_response(
+            "Username must not be empty",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let existing = auth.user(&username);
+
+    let password_hash = match (payload.password, &existing) {
+        (Some(password), _) if password.is_empty() => {
+            return Ok(error_response(
+                "Password must not be empty",
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+        (Some(password), _) => tokio::task::spawn_blocking(move || hash_password(&password))
+            .await
+            .map_err(|_| warp::reject::reject())?,
+        (None, Some(existing)) => existing.password_hash.clone(),
+        (None, None) => {
+            return Ok(error_response(
+                "Password is required for new users",
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    let user = UserRow {
+        username: username.clone(),
+        password_hash,
+        role: payload.role,
+        created_at_ms: existing
+            .map(|existing| existing.created_at_ms)
+            .unwrap_or_else(|| Utc::now().timestamp_millis()),
+    };
+
+    if !auth.can_set_user(&user) {
+        return Ok(error_response(
+            "At least one admin must remain",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    if let Err(e) = db_upsert_user(&user).await {
+        return Ok(error_response(
+            &format!("Failed to persist user {username}: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+    let role = user.role;
+    auth.set_user(user);
+
+    Ok(ApiResponse::success(UserInfo { username, role }))
+}
+
+async fn delete_user(username: String, auth: Auth) -> Result<impl Reply, warp::Rejection> {
+    let username = decode_path_key(username);
+
+    if auth.user(&username).is_none() {
+        return Ok(not_found("User"));
+    }
+
+    if !auth.can_remove_user(&username) {
+        return Ok(error_response(
+            "At least one admin must remain",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    if let Err(e) = db_delete_user(&username).await {
+        return Ok(error_response(
+            &format!("Failed to persist deletion of user {username}: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+    auth.remove_user(&username);
+
+    Ok(ApiResponse::success(true))
+}
+
+async fn list_tokens(auth: Auth) -> Result<impl Reply, warp::Rejection> {
+    Ok(ApiResponse::success(auth.api_tokens()))
+}
+
+async fn create_token(payload: ApiTokenPayload, auth: Auth) -> Result<impl Reply, warp::Rejection> {
+    if payload.name.trim().is_empty() {
+        return Ok(error_response(
+            "Token name must not be empty",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let token = generate_secret();
+    let row = ApiTokenRow {
+        id: generate_token_id(),
+        name: payload.name,
+        token_hash: hash_token(&token),
+        role: payload.role,
+        username: None,
+        expires_at_ms: None,
+        created_at_ms: Utc::now().timestamp_millis(),
+    };
+
+    if let Err(e) = db_insert_api_token(&row).await {
+        return Ok(error_response(
+            &format!("Failed to persist API token {}: {e}", row.name),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+    auth.set_token(row.clone());
+
+    Ok(ApiResponse::created(CreatedApiToken {
+        token,
+        info: ApiTokenInfo {
+            id: row.id,
+            name: row.name,
+            role: row.role,
+            created_at_ms: row.created_at_ms,
+        },
+    }))
+}
+
+async fn delete_token(id: String, auth: Auth) -> Result<impl Reply, warp::Rejection> {
+    let id = decode_path_key(id);
+
+    if !auth.api_tokens().iter().any(|token| token.id == id) {
+        return Ok(not_found("API token"));
+    }
+
+    if !auth.can_remove_token(&id) {
+        return Ok(error_response(
+            "At least one admin must remain",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    if let Err(e) = db_delete_api_token(&id).await {
+        return Ok(error_response(
+            &format!("Failed to persist deletion of API token {id}: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+    auth.remove_token(&id);
+
+    Ok(ApiResponse::success(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{authorize, request_token, required_role};
+    use crate::core::auth::{hash_token, Auth};
+    use crate::db::auth_queries::ApiTokenRow;
+    use crate::types::auth::Role;
+    use warp::http::Method;
+
+    #[test]
+    fn roles_required_by_endpoints() {
+        let get = Method::GET;
+        let post = Method::POST;
+        let query = HashMap::new();
+
+        assert_eq!(required_role(&get, "/health", &query), None);
+        assert_eq!(required_role(&get, "/index.html", &query), None);
+        assert_eq!(required_role(&post, "/api/v1/auth/login", &query), None);
+        assert_eq!(required_role(&get, "/ws", &query), Some(Role::Viewer));
+        assert_eq!(
+            required_role(&get, "/api/v1/devices", &query),
+            Some(Role::Viewer)
+        );
+        assert_eq!(
+            required_role(&get, "/api/v1/config/integrations/hue", &query),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&get, "/api/v1/integrations/status", &query),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&get, "/api/v1/config/versions/3", &query),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&post, "/api/v1/actions/trigger", &query),
+            Some(Role::Operator)
+        );
+        assert_eq!(
+            required_role(&Method::PUT, "/api/v1/config/scenes/evening", &query),
+            Some(Role::Operator)
+        );
+        assert_eq!(
+            required_role(&Method::PUT, "/api/v1/config/groups/kitchen", &query),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&get, "/api/v1/auth/users", &query),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&get, "/api/v1/config/core", &query),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&get, "/api/v1/config/logs", &query),
+            Some(Role::Admin)
+        );
+    }
+
+    #[test]
+    fn requires_admin_for_scene_changes_rewriting_routines() {
+        let no_query = HashMap::new();
+        let cascade = HashMap::from([("cascade".to_string(), "true".to_string())]);
+        let scene = "/api/v1/config/scenes/evening";
+
+        assert_eq!(
+            required_role(&Method::DELETE, scene, &no_query),
+            Some(Role::Operator)
+        );
+        assert_eq!(
+            required_role(&Method::DELETE, scene, &cascade),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&Method::POST, &format!("{scene}/replace"), &no_query),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            required_role(&Method::POST, &format!("{scene}/rename"), &no_query),
+            Some(Role::Admin)
+        );
+    }
+
+    #[test]
+    fn accepts_query_tokens_only_where_headers_cannot_be_set() {
+        let query = HashMap::from([("token".to_string(), "secret".to_string())]);
+
+        assert_eq!(
+            request_token("/ws", None, &query),
+            Some("secret".to_string())
+        );
+        assert_eq!(
+            request_token("/api/v1/events", None, &query),
+            Some("secret".to_string())
+        );
+        assert_eq!(request_token("/api/v1/devices", None, &query), None);
+        assert_eq!(
+            request_token("/api/v1/devices", Some("Bearer header"), &query),
+            Some("header".to_string())
+        );
+    }
+
+    #[test]
+    fn authorizes_by_token_role() {
+        let auth = Auth::default();
+        assert!(authorize(
+            &auth,
+            &Method::DELETE,
+            "/api/v1/config/groups/a",
+            &HashMap::new(),
+            None
+        )
+        .is_ok());
+
+        auth.set_token(ApiTokenRow {
+            id: "kiosk".to_string(),
+            name: "Kiosk".to_string(),
+            token_hash: hash_token("kiosk-secret"),
+            role: Role::Viewer,
+            username: None,
+            expires_at_ms: None,
+            created_at_ms: 0,
+        });
+
+        assert!(authorize(
+            &auth,
+            &Method::GET,
+            "/api/v1/devices",
+            &HashMap::new(),
+            None
+        )
+        .is_err());
+        assert!(authorize(&auth, &Method::GET, "/health", &HashMap::new(), None).is_ok());
+
+        let principal = authorize(
+            &auth,
+            &Method::GET,
+            "/api/v1/devices",
+            &HashMap::new(),
+            Some("kiosk-secret"),
+        )
+        .unwrap();
+        assert_eq!(principal.unwrap().role, Role::Viewer);
+
+        assert!(authorize(
+            &auth,
+            &Method::POST,
+            "/api/v1/actions/trigger",
+            &HashMap::new(),
+            Some("kiosk-secret")
+        )
+        .is_err());
+    }
+}
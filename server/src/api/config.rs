@@ -6,43 +6,59 @@
 //! - Scenes: GET/POST/PUT/DELETE /api/v1/config/scenes
 //! - Routines: GET/POST/PUT/DELETE /api/v1/config/routines
 //! - Import/Export: GET/POST /api/v1/config/export, /api/v1/config/import
+//! - Versions: GET /api/v1/config/versions, /api/v1/config/versions/{version},
+//!   /api/v1/config/versions/diff, POST /api/v1/config/versions/{version}/rollback
+//! - Validation: POST /api/v1/config/validate
 //! - Migration: POST /api/v1/config/migrate/preview, /api/v1/config/migrate/apply
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::core::state::StateHandle;
 use crate::core::{
-    integrations::integration_config_schemas, logs::recent_logs,
+    config_versions::{
+        config_version_recorder, diff_configs, with_current_images, without_images, ConfigDiff,
+    },
+    integrations::{
+        integration_config_schemas, status::integration_status, validate_integration_action,
+        validate_integration_config,
+    },
+    logs::recent_logs,
     routine_history::recent_routine_history,
+    scripting::ScriptEngine,
+    webhook_deliveries::recent_webhook_deliveries,
 };
 use crate::db::{
     self,
     actions::{db_delete_device, db_update_device},
     config_queries::{
-        self, ConfigExport, CoreConfigRow, DashboardLayoutRow, DashboardWidgetRow,
-        DeviceDisplayNameRow, DeviceSensorConfigRow, FloorplanExportRow, FloorplanMetadataRow,
-        FloorplanRow, GroupDeviceRow, GroupPositionRow, GroupRow, IntegrationRow, RoutineRow,
-        SceneRow,
+        self, ConfigExport, ConfigVersionExport, CoreConfigRow, DashboardLayoutRow,
+        DashboardWidgetRow, DeviceDisplayNameRow, DeviceSensorConfigRow, FloorplanExportRow,
+        FloorplanMetadataRow, FloorplanRow, GroupDeviceRow, GroupPositionRow, GroupRow,
+        IntegrationRow, RoutineRow, SceneRow,
     },
 };
 use crate::types::{
     action::{Action, Actions},
     device::{
-        ControllableState, Device, DeviceData, DeviceKey, DeviceRef, DevicesState, SensorDevice,
+        ControllableState, Device, DeviceData, DeviceId, DeviceKey, DeviceRef, DevicesState,
+        SensorDevice,
     },
-    integration::IntegrationId,
-    rule::{AnyRule, Rule, Rules},
+    group::GroupId,
+    integration::{CustomActionDescriptor, IntegrationId},
+    integration_status::IntegrationStatus,
+    rule::{AnyRule, IfDescriptor, RoutineId, Rule, Rules},
     scene::{
         ActivateSceneActionDescriptor, ActivateSceneDescriptor, CycleScenesDescriptor,
-        RolloutStyle, SceneDeviceConfig,
+        RolloutStyle, SceneDeviceConfig, SceneId,
     },
+    sequence::SequenceDescriptor,
 };
 use bytes::Buf;
 use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
 use warp::{http::StatusCode, Filter, Reply};
 
-use crate::core::snapshot::SnapshotHandle;
+use crate::core::snapshot::{RuntimeSnapshot, SnapshotHandle};
 
 use super::{
     widgets::{
@@ -58,7 +74,7 @@ use super::{
 // ============================================================================
 
 #[derive(Serialize)]
-struct ApiResponse<T> {
+pub(super) struct ApiResponse<T> {
     success: bool,
     data: Option<T>,
     error: Option<String>,
@@ -70,9 +86,19 @@ struct RuntimeStatusResponse {
     memory_only_mode: bool,
 }
 
+fn default_device_history_retention_days() -> i32 {
+    config_queries::DEFAULT_DEVICE_HISTORY_RETENTION_DAYS
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct CoreConfigPayload {
     warmup_time_seconds: i32,
+    #[serde(default = "default_device_history_retention_days")]
+    device_history_retention_days: i32,
+    #[serde(default)]
+    latitude: Option<f64>,
+    #[serde(default)]
+    longitude: Option<f64>,
     #[serde(default)]
     weather_api_url: String,
     #[serde(default)]
@@ -91,6 +117,9 @@ impl CoreConfigPayload {
 
         Self {
             warmup_time_seconds: config.core.warmup_time_seconds,
+            device_history_retention_days: config.core.device_history_retention_days,
+            latitude: config.core.latitude,
+            longitude: config.core.longitude,
             weather_api_url: widget_setting_string_or_env(
                 settings,
                 WEATHER_SETTING_KEY,
@@ -129,10 +158,26 @@ impl CoreConfigPayload {
         }
     }
 
-    fn core_config(&self) -> CoreConfigRow {
-        CoreConfigRow {
-            warmup_time_seconds: self.warmup_time_seconds,
+    fn core_config(&self) -> Result<CoreConfigRow, String> {
+        if self
+            .latitude
+            .is_some_and(|lat| !(-90.0..=90.0).contains(&lat))
+        {
+            return Err("Latitude must be between -90 and 90.".to_string());
         }
+        if self
+            .longitude
+            .is_some_and(|lon| !(-180.0..=180.0).contains(&lon))
+        {
+            return Err("Longitude must be between -180 and 180.".to_string());
+        }
+
+        Ok(CoreConfigRow {
+            warmup_time_seconds: self.warmup_time_seconds,
+            device_history_retention_days: self.device_history_retention_days.max(0),
+            latitude: self.latitude,
+            longitude: self.longitude,
+        })
     }
 
     fn widget_settings(&self) -> Vec<config_queries::WidgetSettingRow> {
@@ -160,6 +205,14 @@ impl CoreConfigPayload {
     }
 }
 
+#[derive(Serialize)]
+struct IntegrationResponseRow {
+    #[serde(flatten)]
+    integration: IntegrationRow,
+    /// Missing for disabled integrations.
+    status: Option<IntegrationStatus>,
+}
+
 #[derive(Serialize)]
 struct GroupResponseRow {
     #[serde(flatten)]
@@ -168,7 +221,7 @@ struct GroupResponseRow {
 }
 
 impl<T: Serialize> ApiResponse<T> {
-    fn success(data: T) -> warp::reply::WithStatus<warp::reply::Json> {
+    pub(super) fn success(data: T) -> warp::reply::WithStatus<warp::reply::Json> {
         warp::reply::with_status(
             warp::reply::json(&ApiResponse {
                 success: true,
@@ -179,7 +232,7 @@ impl<T: Serialize> ApiResponse<T> {
         )
     }
 
-    fn created(data: T) -> warp::reply::WithStatus<warp::reply::Json> {
+    pub(super) fn created(data: T) -> warp::reply::WithStatus<warp::reply::Json> {
         warp::reply::with_status(
             warp::reply::json(&ApiResponse {
                 success: true,
@@ -256,7 +309,10 @@ async fn apply_runtime_config_snapshot(
     Ok(())
 }
 
-fn error_response(msg: &str, status: StatusCode) -> warp::reply::WithStatus<warp::reply::Json> {
+pub(super) fn error_response(
+    msg: &str,
+    status: StatusCode,
+) -> warp::reply::WithStatus<warp::reply::Json> {
     warp::reply::with_status(
         warp::reply::json(&ApiResponse::<()> {
             success: false,
@@ -267,7 +323,7 @@ fn error_response(msg: &str, status: StatusCode) -> warp::reply::WithStatus<warp
     )
 }
 
-fn not_found(entity: &str) -> warp::reply::WithStatus<warp::reply::Json> {
+pub(super) fn not_found(entity: &str) -> warp::reply::WithStatus<warp::reply::Json> {
     error_response(&format!("{entity} not found"), StatusCode::NOT_FOUND)
 }
 
@@ -277,7 +333,7 @@ fn push_unique_error(errors: &mut Vec<String>, error: String) {
     }
 }
 
-fn decode_path_key(raw: String) -> String {
+pub(super) fn decode_path_key(raw: String) -> String {
     percent_decode_str(raw.as_str())
         .decode_utf8_lossy()
         .into_owned()
@@ -536,16 +592,75 @@ pub(crate) fn validate_action_rollout(action: &Action) -> Result<(), String> {
             rollout_source_device_key,
             rollout_duration_ms,
         ),
+        Action::Sequence(SequenceDescriptor { actions, .. }) => {
+            actions.iter().try_for_each(validate_action_rollout)
+        }
+        Action::If(IfDescriptor {
+            then, else_actions, ..
+        }) => then
+            .iter()
+            .chain(else_actions)
+            .try_for_each(validate_action_rollout),
+        _ => Ok(()),
+    }
+}
+
+/// Checks custom action payloads against the plugin of their integration.
+fn validate_custom_actions(action: &Action, integrations: &[IntegrationRow]) -> Result<(), String> {
+    match action {
+        Action::Custom(CustomActionDescriptor {
+            integration_id,
+            payload,
+        }) => {
+            let integration = integrations
+                .iter()
+                .find(|integration| integration.id == integration_id.to_string())
+                .ok_or_else(|| {
+                    format!("Custom action refers to unknown integration {integration_id}")
+                })?;
+
+            validate_integration_action(&integration.plugin, payload).map_err(|error| {
+                format!("Invalid custom action for integration {integration_id}: {error:#}")
+            })
+        }
+        Action::Sequence(SequenceDescriptor { actions, .. }) => actions
+            .iter()
+            .try_for_each(|action| validate_custom_actions(action, integrations)),
+        Action::If(IfDescriptor {
+            then, else_actions, ..
+        }) => then
+            .iter()
+            .chain(else_actions)
+            .try_for_each(|action| validate_custom_actions(action, integrations)),
         _ => Ok(()),
     }
 }
 
-fn validate_routine_actions(actions: &serde_json::Value) -> Result<(), String> {
+fn validate_routine_actions(
+    actions: &serde_json::Value,
+    integrations: &[IntegrationRow],
+) -> Result<(), String> {
     let parsed_actions: Actions = serde_json::from_value(actions.clone())
         .map_err(|error| format!("Invalid routine actions payload: {error}"))?;
 
-    for action in &parsed_actions {
-        validate_action_rollout(action)?;
+    parsed_actions
+        .iter()
+        .try_for_each(|action| validate_routine_action(action, integrations))
+}
+
+fn validate_routine_action(action: &Action, integrations: &[IntegrationRow]) -> Result<(), String> {
+    validate_action_rollout(action)?;
+    validate_custom_actions(action, integrations)
+}
+
+fn validate_routine_rate_limits(routine: &RoutineRow) -> Result<(), String> {
+    for (field, value) in [
+        ("cooldown_ms", routine.cooldown_ms),
+        ("debounce_ms", routine.debounce_ms),
+    ] {
+        if value.is_some_and(|value| value < 0) {
+            return Err(format!("Routine {field} must not be negative"));
+        }
     }
 
     Ok(())
@@ -627,14 +742,13 @@ fn rewrite_scene_device_config(
 
 fn rewrite_scene_config_value(
     value: &mut serde_json::Value,
-    source: &DeviceConfigTarget,
-    replacement: Option<&DeviceConfigTarget>,
+    rewrite: impl FnOnce(&mut SceneDeviceConfig) -> RewriteStatus,
 ) -> RewriteStatus {
     let Ok(mut config) = serde_json::from_value::<SceneDeviceConfig>(value.clone()) else {
         return RewriteStatus::Unchanged;
     };
 
-    match rewrite_scene_device_config(&mut config, source, replacement) {
+    match rewrite(&mut config) {
         RewriteStatus::Changed => match serde_json::to_value(&config) {
             Ok(next_value) => {
                 *value = next_value;
@@ -704,7 +818,9 @@ fn rewrite_rule(
                 RewriteStatus::Changed
             }
         }
-        Rule::Raw(_) | Rule::Group(_) | Rule::EvalExpr(_) => RewriteStatus::Unchanged,
+        Rule::Raw(_) | Rule::Group(_) | Rule::TimeWindow(_) | Rule::Sun(_) | Rule::EvalExpr(_) => {
+            RewriteStatus::Unchanged
+        }
     }
 }
 
@@ -741,13 +857,115 @@ fn rewrite_action(
         Action::ToggleDeviceOverride { device_keys, .. } => {
             rewrite_required_device_keys(device_keys, source, replacement)
         }
-        Action::Custom(_)
+        Action::Sequence(descriptor) => {
+            if sequence_loses_wait(&descriptor.actions, &|rule| {
+                rewrite_rule(&mut rule.clone(), source, replacement) == RewriteStatus::Remove
+            }) {
+                return RewriteStatus::Remove;
+            }
+
+            if !rewrite_actions(
+                &mut descriptor.actions,
+                source,
+                replacement,
+                replacement_device,
+            ) {
+                RewriteStatus::Unchanged
+            } else if descriptor.actions.is_empty() {
+                RewriteStatus::Remove
+            } else {
+                RewriteStatus::Changed
+            }
+        }
+        Action::WaitUntil(descriptor) => rewrite_rule(&mut descriptor.rule, source, replacement),
+        Action::If(IfDescriptor {
+            condition,
+            then,
+            else_actions,
+        }) => {
+            let mut changed = false;
+
+            // A condition without one of its rules would mean something else
+            for rule in condition.iter_mut() {
+                match rewrite_rule(rule, source, replacement) {
+                    RewriteStatus::Remove => return RewriteStatus::Remove,
+                    RewriteStatus::Changed => changed = true,
+                    RewriteStatus::Unchanged => {}
+                }
+            }
+
+            changed |= rewrite_actions(then, source, replacement, replacement_device);
+            changed |= rewrite_actions(else_actions, source, replacement, replacement_device);
+
+            if changed {
+                RewriteStatus::Changed
+            } else {
+                RewriteStatus::Unchanged
+            }
+        }
+        Action::CancelSequence(_)
+        | Action::Custom(_)
+        | Action::Delay { .. }
         | Action::ForceTriggerRoutine(_)
         | Action::Ui(_)
         | Action::EvalExpr(_) => RewriteStatus::Unchanged,
     }
 }
 
+/// Whether the sequence `actions` waits on a rule that `is_removed`. Without
+/// the wait, the steps after it would run right away, so the whole sequence
+/// has to go instead.
+fn sequence_loses_wait(actions: &[Action], is_removed: &impl Fn(&Rule) -> bool) -> bool {
+    actions.iter().any(|action| match action {
+        Action::WaitUntil(descriptor) => is_removed(&descriptor.rule),
+        // Branches pause the sequence they're in, nested sequences run on
+        // their own
+        Action::If(IfDescriptor {
+            then, else_actions, ..
+        }) => {
+            sequence_loses_wait(then, is_removed) || sequence_loses_wait(else_actions, is_removed)
+        }
+        _ => false,
+    })
+}
+
+/// Rewrites device references of `actions` in place, dropping actions that
+/// only targeted a removed device. Returns whether anything changed.
+fn rewrite_actions(
+    actions: &mut Actions,
+    source: &DeviceConfigTarget,
+    replacement: Option<&DeviceConfigTarget>,
+    replacement_device: Option<&Device>,
+) -> bool {
+    retain_rewritten(actions, |action| {
+        rewrite_action(action, source, replacement, replacement_device)
+    })
+}
+
+/// Applies `rewrite` to each item, dropping the ones it asks to remove.
+/// Returns whether anything changed.
+fn retain_rewritten<T>(
+    items: &mut Vec<T>,
+    mut rewrite: impl FnMut(&mut T) -> RewriteStatus,
+) -> bool {
+    let mut changed = false;
+    let mut next_items = Vec::with_capacity(items.len());
+
+    for mut item in items.drain(..) {
+        match rewrite(&mut item) {
+            RewriteStatus::Remove => changed = true,
+            RewriteStatus::Changed => {
+                changed = true;
+                next_items.push(item);
+            }
+            RewriteStatus::Unchanged => next_items.push(item),
+        }
+    }
+
+    *items = next_items;
+    changed
+}
+
 fn rewrite_group_device_refs(
     group: &mut GroupRow,
     source: &DeviceConfigTarget,
@@ -782,6 +1000,38 @@ fn rewrite_group_device_refs(
     }
 
     group.devices = next_devices;
+
+    if let Some(occupancy) = &mut group.occupancy {
+        for sensors in [&mut occupancy.motion_sensors, &mut occupancy.door_sensors] {
+            let mut next_sensors: Vec<DeviceKey> = Vec::with_capacity(sensors.len());
+
+            for device_key in std::mem::take(sensors) {
+                let next_device_key = if device_key.integration_id.to_string()
+                    == source.integration_id
+                    && device_key.device_id.to_string() == source.device_id
+                {
+                    changed = true;
+                    replacement.map(|replacement| {
+                        DeviceKey::new(
+                            IntegrationId::from(replacement.integration_id.clone()),
+                            DeviceId::new(&replacement.device_id),
+                        )
+                    })
+                } else {
+                    Some(device_key)
+                };
+
+                if let Some(next_device_key) = next_device_key {
+                    if !next_sensors.contains(&next_device_key) {
+                        next_sensors.push(next_device_key);
+                    }
+                }
+            }
+
+            *sensors = next_sensors;
+        }
+    }
+
     changed
 }
 
@@ -801,7 +1051,9 @@ fn rewrite_scene_device_refs(
             Some(device_key)
         };
 
-        match rewrite_scene_config_value(&mut config_value, source, replacement) {
+        match rewrite_scene_config_value(&mut config_value, |config| {
+            rewrite_scene_device_config(config, source, replacement)
+        }) {
             RewriteStatus::Changed => changed = true,
             RewriteStatus::Remove => {
                 changed = true;
@@ -823,7 +1075,9 @@ fn rewrite_scene_device_refs(
 
     let mut next_group_states = HashMap::with_capacity(scene.group_states.len());
     for (group_id, mut config_value) in std::mem::take(&mut scene.group_states) {
-        match rewrite_scene_config_value(&mut config_value, source, replacement) {
+        match rewrite_scene_config_value(&mut config_value, |config| {
+            rewrite_scene_device_config(config, source, replacement)
+        }) {
             RewriteStatus::Changed => changed = true,
             RewriteStatus::Remove => {
                 changed = true;
@@ -883,21 +1137,18 @@ fn rewrite_routine_device_refs(
         return changed;
     };
 
-    let mut actions_changed = false;
-    let mut next_actions = Vec::with_capacity(actions.len());
-    for mut action in actions.drain(..) {
-        match rewrite_action(&mut action, source, replacement, replacement_device) {
-            RewriteStatus::Remove => actions_changed = true,
-            RewriteStatus::Changed => {
-                actions_changed = true;
-                next_actions.push(action);
-            }
-            RewriteStatus::Unchanged => next_actions.push(action),
-        }
-    }
+    // Pausing routine actions run as a sequence
+    let actions_changed = if sequence_loses_wait(&actions, &|rule| {
+        rewrite_rule(&mut rule.clone(), source, replacement) == RewriteStatus::Remove
+    }) {
+        actions.clear();
+        true
+    } else {
+        rewrite_actions(&mut actions, source, replacement, replacement_device)
+    };
 
     if actions_changed {
-        match serde_json::to_value(&next_actions) {
+        match serde_json::to_value(&actions) {
             Ok(value) => routine.actions = value,
             Err(error) => warn!("Failed to serialize rewritten routine actions: {error}"),
         }
@@ -989,23 +1240,25 @@ fn rewrite_device_config_references(
     result
 }
 
-async fn persist_device_config_rewrite(
-    rewrite: &DeviceConfigRewriteResult,
-    source: &DeviceConfigTarget,
+async fn persist_changed_config_rows(
+    groups: &[GroupRow],
+    scenes: &[SceneRow],
+    routines: &[RoutineRow],
+    floorplans: &[ChangedFloorplan],
 ) {
-    for group in &rewrite.changed_groups {
+    for group in groups {
         if let Err(error) = config_queries::db_upsert_group(group).await {
             warn!("Failed to persist updated group '{}': {error}", group.id);
         }
     }
 
-    for scene in &rewrite.changed_scenes {
+    for scene in scenes {
         if let Err(error) = config_queries::db_upsert_config_scene(scene).await {
             warn!("Failed to persist updated scene '{}': {error}", scene.id);
         }
     }
 
-    for routine in &rewrite.changed_routines {
+    for routine in routines {
         if let Err(error) = config_queries::db_upsert_routine(routine).await {
             warn!(
                 "Failed to persist updated routine '{}': {error}",
@@ -1014,7 +1267,7 @@ async fn persist_device_config_rewrite(
         }
     }
 
-    for changed_floorplan in &rewrite.changed_floorplans {
+    for changed_floorplan in floorplans {
         if let Err(error) = config_queries::db_upsert_floorplan_export(
             &changed_floorplan.floorplan,
             changed_floorplan.sort_order,
@@ -1027,6 +1280,19 @@ async fn persist_device_config_rewrite(
             );
         }
     }
+}
+
+async fn persist_device_config_rewrite(
+    rewrite: &DeviceConfigRewriteResult,
+    source: &DeviceConfigTarget,
+) {
+    persist_changed_config_rows(
+        &rewrite.changed_groups,
+        &rewrite.changed_scenes,
+        &rewrite.changed_routines,
+        &rewrite.changed_floorplans,
+    )
+    .await;
 
     if rewrite.display_override_changed {
         if let Err(error) =
@@ -1050,173 +1316,1292 @@ async fn persist_device_config_rewrite(
     }
 }
 
-fn rewrite_force_trigger_routine_references(
-    actions_value: &mut serde_json::Value,
-    source_id: &str,
-    replacement_id: &str,
-) -> bool {
-    let Ok(mut actions) = serde_json::from_value::<Actions>(actions_value.clone()) else {
-        return false;
-    };
+/// Kinds of config entities that other entities refer to by id.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ConfigEntityKind {
+    Group,
+    Scene,
+    Routine,
+}
 
-    let mut changed = false;
-    for action in &mut actions {
-        if let Action::ForceTriggerRoutine(descriptor) = action {
-            if descriptor.routine_id.to_string() == source_id {
-                descriptor.routine_id = replacement_id.to_string().into();
-                changed = true;
-            }
+impl ConfigEntityKind {
+    fn label(self) -> &'static str {
+        match self {
+            ConfigEntityKind::Group => "Group",
+            ConfigEntityKind::Scene => "Scene",
+            ConfigEntityKind::Routine => "Routine",
         }
     }
 
-    if !changed {
-        return false;
-    }
-
-    match serde_json::to_value(&actions) {
-        Ok(value) => {
-            *actions_value = value;
-            true
-        }
-        Err(error) => {
-            warn!("Failed to serialize rewritten routine action references: {error}");
-            false
+    /// Names of the dashboard widget options holding one or several ids of
+    /// this kind, e.g. `groupId` of the controls widget.
+    fn widget_option_keys(self) -> (&'static str, &'static str) {
+        match self {
+            ConfigEntityKind::Group => ("groupId", "groupIds"),
+            ConfigEntityKind::Scene => ("sceneId", "sceneIds"),
+            ConfigEntityKind::Routine => ("routineId", "routineIds"),
         }
     }
 }
 
-fn group_response_row(
-    flattened_groups: &crate::types::group::FlattenedGroupsConfig,
-    group: GroupRow,
-) -> GroupResponseRow {
-    let device_keys = flattened_groups
-        .0
-        .iter()
-        .find_map(|(group_id, flattened_group)| {
-            (group_id.0 == group.id).then(|| {
-                flattened_group
-                    .device_keys
-                    .iter()
-                    .map(ToString::to_string)
-                    .collect::<Vec<_>>()
-            })
-        })
-        .unwrap_or_default();
-
-    GroupResponseRow { group, device_keys }
+#[derive(Clone, Debug)]
+struct ConfigEntityTarget {
+    kind: ConfigEntityKind,
+    id: String,
 }
 
-fn legacy_default_floorplan(config: &config_queries::ConfigExport) -> Option<FloorplanExportRow> {
-    let floorplan = config.floorplan.as_ref();
+/// Id types referring to a config entity.
+trait ConfigEntityId: ToString {
+    const KIND: ConfigEntityKind;
 
-    Some(FloorplanExportRow {
-        id: "default".to_string(),
-        name: "Default".to_string(),
-        image_data: floorplan.and_then(|floorplan| floorplan.image_data.clone()),
-        image_mime_type: floorplan.and_then(|floorplan| floorplan.image_mime_type.clone()),
-        width: floorplan.and_then(|floorplan| floorplan.width),
-        height: floorplan.and_then(|floorplan| floorplan.height),
-        grid_data: None,
-    })
+    fn from_id(id: &str) -> Self;
 }
 
-fn list_runtime_floorplans(config: &config_queries::ConfigExport) -> Vec<FloorplanMetadataRow> {
-    if !config.floorplans.is_empty() {
-        return config
-            .floorplans
-            .iter()
-            .map(|floorplan| FloorplanMetadataRow {
-                id: floorplan.id.clone(),
-                name: floorplan.name.clone(),
-            })
-            .collect();
-    }
-
-    legacy_default_floorplan(config)
-        .into_iter()
-        .map(|floorplan| FloorplanMetadataRow {
-            id: floorplan.id,
-            name: floorplan.name,
-        })
-        .collect()
-}
+impl ConfigEntityId for GroupId {
+    const KIND: ConfigEntityKind = ConfigEntityKind::Group;
 
-fn get_runtime_floorplan(
-    config: &config_queries::ConfigExport,
-    floorplan_id: &str,
-) -> Option<FloorplanExportRow> {
-    config
-        .floorplans
-        .iter()
-        .find(|floorplan| floorplan.id == floorplan_id)
-        .cloned()
-        .or_else(|| {
-            if floorplan_id == "default" {
-                legacy_default_floorplan(config)
-            } else {
-                None
-            }
-        })
+    fn from_id(id: &str) -> Self {
+        GroupId(id.to_string())
+    }
 }
 
-// ============================================================================
-// Main Config Routes
-// ============================================================================
+impl ConfigEntityId for SceneId {
+    const KIND: ConfigEntityKind = ConfigEntityKind::Scene;
 
-pub fn config(
-    snapshot: &SnapshotHandle,
-    handle: &StateHandle,
-) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    warp::path("config").and(
-        core_routes(snapshot, handle)
-            .or(runtime_status_routes())
-            .or(logs_routes())
-            .or(routine_history_routes())
-            .or(device_display_name_routes(snapshot, handle))
-            .or(device_sensor_config_routes(snapshot, handle))
-            .or(device_config_routes(handle))
-            .or(integration_schema_routes())
-            .or(integrations_routes(snapshot, handle))
-            .or(groups_routes(snapshot, handle))
-            .or(scenes_routes(snapshot, handle))
-            .or(routines_routes(snapshot, handle))
-            .or(floorplans_routes(snapshot, handle))
-            .or(floorplan_routes(snapshot, handle))
-            .or(dashboard_routes(snapshot, handle))
-            .or(export_import_routes(snapshot, handle))
-            .or(migrate_routes(snapshot, handle)),
-    )
+    fn from_id(id: &str) -> Self {
+        SceneId::new(id.to_string())
+    }
 }
 
-// ============================================================================
-// Core Config
-// ============================================================================
+impl ConfigEntityId for RoutineId {
+    const KIND: ConfigEntityKind = ConfigEntityKind::Routine;
 
-fn logs_routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    warp::path("logs")
-        .and(warp::path::end())
-        .and(warp::get())
-        .and_then(list_logs)
+    fn from_id(id: &str) -> Self {
+        RoutineId(id.to_string())
+    }
 }
 
-async fn list_logs() -> Result<impl Reply, warp::Rejection> {
-    Ok(ApiResponse::success(recent_logs()))
-}
+impl ConfigEntityTarget {
+    fn new(kind: ConfigEntityKind, id: String) -> Self {
+        Self { kind, id }
+    }
 
-fn routine_history_routes(
-) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    warp::path("routine-history")
-        .and(warp::path::end())
-        .and(warp::get())
-        .and_then(list_routine_history)
-}
+    fn is(&self, kind: ConfigEntityKind, id: &str) -> bool {
+        self.kind == kind && self.id == id
+    }
 
-async fn list_routine_history() -> Result<impl Reply, warp::Rejection> {
-    Ok(ApiResponse::success(recent_routine_history()))
+    fn matches<T: ConfigEntityId>(&self, id: &T) -> bool {
+        self.kind == T::KIND && id.to_string() == self.id
+    }
+
+    fn exists_in(&self, config: &ConfigExport) -> bool {
+        match self.kind {
+            ConfigEntityKind::Group => config.groups.iter().any(|group| group.id == self.id),
+            ConfigEntityKind::Scene => config.scenes.iter().any(|scene| scene.id == self.id),
+            ConfigEntityKind::Routine => {
+                config.routines.iter().any(|routine| routine.id == self.id)
+            }
+        }
+    }
 }
 
-fn core_routes(
-    snapshot: &SnapshotHandle,
+#[derive(Default)]
+struct EntityRewriteResult {
+    changed_groups: Vec<GroupRow>,
+    changed_scenes: Vec<SceneRow>,
+    changed_routines: Vec<RoutineRow>,
+    /// Routines that would have lost one of their rules, and are deleted
+    /// instead of triggering more often than configured.
+    deleted_routines: Vec<String>,
+    changed_floorplans: Vec<ChangedFloorplan>,
+    changed_widgets: Vec<DashboardWidgetRow>,
+    /// Set when the source group had a floorplan position. Holds the
+    /// position if it was moved to the replacement group.
+    group_position_changed: bool,
+    moved_group_position: Option<GroupPositionRow>,
+}
+
+/// Config entities referring to a group, scene or routine.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+struct ConfigDependents {
+    groups: Vec<String>,
+    scenes: Vec<String>,
+    routines: Vec<String>,
+}
+
+impl EntityRewriteResult {
+    fn dependents(&self) -> ConfigDependents {
+        ConfigDependents {
+            groups: self.changed_groups.iter().map(|g| g.id.clone()).collect(),
+            scenes: self.changed_scenes.iter().map(|s| s.id.clone()).collect(),
+            routines: self
+                .changed_routines
+                .iter()
+                .map(|r| r.id.clone())
+                .chain(self.deleted_routines.iter().cloned())
+                .collect(),
+        }
+    }
+}
+
+impl ConfigDependents {
+    fn is_empty(&self) -> bool {
+        self.groups.is_empty() && self.scenes.is_empty() && self.routines.is_empty()
+    }
+
+    fn describe(&self) -> String {
+        [
+            ("groups", &self.groups),
+            ("scenes", &self.scenes),
+            ("routines", &self.routines),
+        ]
+        .into_iter()
+        .filter(|(_, ids)| !ids.is_empty())
+        .map(|(label, ids)| format!("{label} {}", ids.join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ")
+    }
+}
+
+#[derive(Deserialize)]
+struct DeleteEntityQuery {
+    /// Remove references to the entity instead of refusing to delete it.
+    #[serde(default)]
+    cascade: bool,
+}
+
+#[derive(Deserialize)]
+struct ReplaceEntityRequest {
+    replacement_id: String,
+}
+
+#[derive(Deserialize)]
+struct RenameEntityRequest {
+    new_id: String,
+}
+
+#[derive(Serialize)]
+struct EntityRenameResponse {
+    id: String,
+    new_id: String,
+    updated: ConfigDependents,
+    updated_floorplans: usize,
+    updated_widgets: usize,
+    group_position_changed: bool,
+}
+
+#[derive(Serialize)]
+struct EntityMutationResponse {
+    deleted_id: String,
+    replacement_id: Option<String>,
+    updated_groups: usize,
+    updated_scenes: usize,
+    updated_routines: usize,
+    deleted_routines: Vec<String>,
+}
+
+fn rewrite_entity_id<T: ConfigEntityId>(
+    id: &mut T,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    if !source.matches(id) {
+        return RewriteStatus::Unchanged;
+    }
+
+    let Some(replacement) = replacement else {
+        return RewriteStatus::Remove;
+    };
+
+    *id = T::from_id(replacement);
+    RewriteStatus::Changed
+}
+
+fn rewrite_entity_ids<T: ConfigEntityId>(
+    ids: &mut Vec<T>,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    if !ids.iter().any(|id| source.matches(id)) {
+        return RewriteStatus::Unchanged;
+    }
+
+    let mut seen = HashSet::new();
+    retain_rewritten(ids, |id| rewrite_entity_id(id, source, replacement));
+    ids.retain(|id| seen.insert(id.to_string()));
+    RewriteStatus::Changed
+}
+
+fn rewrite_optional_entity_ids<T: ConfigEntityId>(
+    ids: &mut Option<Vec<T>>,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    match ids {
+        Some(ids) => rewrite_entity_ids(ids, source, replacement),
+        None => RewriteStatus::Unchanged,
+    }
+}
+
+/// Mirrored groups only pick the scene to activate, so a removed group is
+/// cleared rather than removing the activation.
+fn rewrite_mirror_from_group(
+    mirror_from_group: &mut Option<GroupId>,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    let Some(group_id) = mirror_from_group.as_mut() else {
+        return RewriteStatus::Unchanged;
+    };
+
+    match rewrite_entity_id(group_id, source, replacement) {
+        RewriteStatus::Remove => {
+            *mirror_from_group = None;
+            RewriteStatus::Changed
+        }
+        status => status,
+    }
+}
+
+fn rewrite_scene_descriptor_entity_refs(
+    descriptor: &mut ActivateSceneDescriptor,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    let mut changed = match rewrite_entity_id(&mut descriptor.scene_id, source, replacement) {
+        RewriteStatus::Remove => return RewriteStatus::Remove,
+        status => status == RewriteStatus::Changed,
+    };
+
+    changed |= rewrite_mirror_from_group(&mut descriptor.mirror_from_group, source, replacement)
+        == RewriteStatus::Changed;
+    changed |= rewrite_optional_entity_ids(&mut descriptor.group_keys, source, replacement)
+        == RewriteStatus::Changed;
+
+    if changed {
+        RewriteStatus::Changed
+    } else {
+        RewriteStatus::Unchanged
+    }
+}
+
+fn rewrite_scene_action_descriptor_entity_refs(
+    descriptor: &mut ActivateSceneActionDescriptor,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    let mut changed = match rewrite_entity_id(&mut descriptor.scene_id, source, replacement) {
+        RewriteStatus::Remove => return RewriteStatus::Remove,
+        status => status == RewriteStatus::Changed,
+    };
+
+    changed |= rewrite_mirror_from_group(&mut descriptor.mirror_from_group, source, replacement)
+        == RewriteStatus::Changed;
+    changed |= rewrite_optional_entity_ids(&mut descriptor.group_keys, source, replacement)
+        == RewriteStatus::Changed;
+
+    if changed {
+        RewriteStatus::Changed
+    } else {
+        RewriteStatus::Unchanged
+    }
+}
+
+fn rewrite_rule_entity_refs(
+    rule: &mut Rule,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    match rule {
+        Rule::Device(device_rule) => match &mut device_rule.scene {
+            Some(scene_id) => rewrite_entity_id(scene_id, source, replacement),
+            None => RewriteStatus::Unchanged,
+        },
+        Rule::Group(group_rule) => {
+            let group_status = rewrite_entity_id(&mut group_rule.group_id, source, replacement);
+            let scene_status = match &mut group_rule.scene {
+                Some(scene_id) => rewrite_entity_id(scene_id, source, replacement),
+                None => RewriteStatus::Unchanged,
+            };
+
+            if group_status == RewriteStatus::Remove || scene_status == RewriteStatus::Remove {
+                RewriteStatus::Remove
+            } else if group_status == RewriteStatus::Changed
+                || scene_status == RewriteStatus::Changed
+            {
+                RewriteStatus::Changed
+            } else {
+                RewriteStatus::Unchanged
+            }
+        }
+        Rule::Any(AnyRule { any }) => {
+            if !retain_rewritten(any, |rule| {
+                rewrite_rule_entity_refs(rule, source, replacement)
+            }) {
+                RewriteStatus::Unchanged
+            } else if any.is_empty() {
+                RewriteStatus::Remove
+            } else {
+                RewriteStatus::Changed
+            }
+        }
+        Rule::Sensor(_)
+        | Rule::Raw(_)
+        | Rule::TimeWindow(_)
+        | Rule::Sun(_)
+        | Rule::EvalExpr(_)
+        | Rule::Script(_) => RewriteStatus::Unchanged,
+    }
+}
+
+fn rewrite_action_entity_refs(
+    action: &mut Action,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    match action {
+        Action::ActivateScene(descriptor) => {
+            rewrite_scene_action_descriptor_entity_refs(descriptor, source, replacement)
+        }
+        Action::CycleScenes(descriptor) => {
+            let mut changed = retain_rewritten(&mut descriptor.scenes, |scene| {
+                rewrite_scene_descriptor_entity_refs(scene, source, replacement)
+            });
+            if changed && descriptor.scenes.is_empty() {
+                return RewriteStatus::Remove;
+            }
+
+            changed |= rewrite_optional_entity_ids(&mut descriptor.group_keys, source, replacement)
+                == RewriteStatus::Changed;
+
+            if changed {
+                RewriteStatus::Changed
+            } else {
+                RewriteStatus::Unchanged
+            }
+        }
+        Action::Dim(descriptor) => {
+            rewrite_optional_entity_ids(&mut descriptor.group_keys, source, replacement)
+        }
+        Action::ForceTriggerRoutine(descriptor) => {
+            rewrite_entity_id(&mut descriptor.routine_id, source, replacement)
+        }
+        Action::Sequence(descriptor) => {
+            if sequence_loses_wait(&descriptor.actions, &|rule| {
+                rewrite_rule_entity_refs(&mut rule.clone(), source, replacement)
+                    == RewriteStatus::Remove
+            }) {
+                return RewriteStatus::Remove;
+            }
+
+            if !rewrite_entity_ref_actions(&mut descriptor.actions, source, replacement) {
+                RewriteStatus::Unchanged
+            } else if descriptor.actions.is_empty() {
+                RewriteStatus::Remove
+            } else {
+                RewriteStatus::Changed
+            }
+        }
+        Action::WaitUntil(descriptor) => {
+            rewrite_rule_entity_refs(&mut descriptor.rule, source, replacement)
+        }
+        Action::If(IfDescriptor {
+            condition,
+            then,
+            else_actions,
+        }) => {
+            let mut changed = false;
+
+            // A condition without one of its rules would mean something else
+            for rule in condition.iter_mut() {
+                match rewrite_rule_entity_refs(rule, source, replacement) {
+                    RewriteStatus::Remove => return RewriteStatus::Remove,
+                    RewriteStatus::Changed => changed = true,
+                    RewriteStatus::Unchanged => {}
+                }
+            }
+
+            changed |= rewrite_entity_ref_actions(then, source, replacement);
+            changed |= rewrite_entity_ref_actions(else_actions, source, replacement);
+
+            if changed {
+                RewriteStatus::Changed
+            } else {
+                RewriteStatus::Unchanged
+            }
+        }
+        Action::CancelSequence(_)
+        | Action::Custom(_)
+        | Action::Delay { .. }
+        | Action::SetDeviceState(_)
+        | Action::ToggleDeviceOverride { .. }
+        | Action::Ui(_)
+        | Action::EvalExpr(_) => RewriteStatus::Unchanged,
+    }
+}
+
+/// Rewrites group, scene and routine references of `actions` in place,
+/// dropping actions that only targeted a removed entity. Returns whether
+/// anything changed.
+fn rewrite_entity_ref_actions(
+    actions: &mut Actions,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> bool {
+    retain_rewritten(actions, |action| {
+        rewrite_action_entity_refs(action, source, replacement)
+    })
+}
+
+fn rewrite_group_entity_refs(
+    group: &mut GroupRow,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> bool {
+    if source.kind != ConfigEntityKind::Group || !group.linked_groups.contains(&source.id) {
+        return false;
+    }
+
+    let mut next_linked_groups = Vec::with_capacity(group.linked_groups.len());
+    for linked_group in std::mem::take(&mut group.linked_groups) {
+        let linked_group = if linked_group == source.id {
+            match replacement {
+                Some(replacement) => replacement.to_string(),
+                None => continue,
+            }
+        } else {
+            linked_group
+        };
+
+        // Replacing a linked group with the group itself would make it link
+        // to itself.
+        if linked_group != group.id && !next_linked_groups.contains(&linked_group) {
+            next_linked_groups.push(linked_group);
+        }
+    }
+
+    group.linked_groups = next_linked_groups;
+    true
+}
+
+fn rewrite_scene_link_entity_refs(
+    config_value: &mut serde_json::Value,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    rewrite_scene_config_value(config_value, |config| match config {
+        SceneDeviceConfig::SceneLink(link) => {
+            rewrite_scene_descriptor_entity_refs(link, source, replacement)
+        }
+        SceneDeviceConfig::DeviceLink(_) | SceneDeviceConfig::DeviceState(_) => {
+            RewriteStatus::Unchanged
+        }
+    })
+}
+
+fn rewrite_scene_entity_refs(
+    scene: &mut SceneRow,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> bool {
+    let mut changed = false;
+
+    scene.device_states.retain(|_, config_value| {
+        match rewrite_scene_link_entity_refs(config_value, source, replacement) {
+            RewriteStatus::Changed => {
+                changed = true;
+                true
+            }
+            RewriteStatus::Remove => {
+                changed = true;
+                false
+            }
+            RewriteStatus::Unchanged => true,
+        }
+    });
+
+    let mut next_group_states = HashMap::with_capacity(scene.group_states.len());
+    for (group_id, mut config_value) in std::mem::take(&mut scene.group_states) {
+        match rewrite_scene_link_entity_refs(&mut config_value, source, replacement) {
+            RewriteStatus::Changed => changed = true,
+            RewriteStatus::Remove => {
+                changed = true;
+                continue;
+            }
+            RewriteStatus::Unchanged => {}
+        }
+
+        if !source.is(ConfigEntityKind::Group, &group_id) {
+            next_group_states.insert(group_id, config_value);
+            continue;
+        }
+
+        changed = true;
+        // States the scene already has for the replacement group win.
+        if let Some(replacement) = replacement {
+            next_group_states
+                .entry(replacement.to_string())
+                .or_insert(config_value);
+        }
+    }
+
+    scene.group_states = next_group_states;
+    changed
+}
+
+/// Rewrites references of `routine` in place. A routine losing one of its
+/// rules would trigger more often than configured, so it is removed instead.
+fn rewrite_routine_entity_refs(
+    routine: &mut RoutineRow,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> RewriteStatus {
+    let mut changed = false;
+
+    if let Ok(mut rules) = serde_json::from_value::<Rules>(routine.rules.clone()) {
+        let mut loses_rule = false;
+        let rules_changed = retain_rewritten(&mut rules, |rule| {
+            let status = rewrite_rule_entity_refs(rule, source, replacement);
+            loses_rule |= status == RewriteStatus::Remove;
+            status
+        });
+
+        if loses_rule {
+            return RewriteStatus::Remove;
+        }
+
+        if rules_changed {
+            match serde_json::to_value(&rules) {
+                Ok(value) => {
+                    routine.rules = value;
+                    changed = true;
+                }
+                Err(error) => warn!("Failed to serialize rewritten routine rules: {error}"),
+            }
+        }
+    }
+
+    if let Ok(mut actions) = serde_json::from_value::<Actions>(routine.actions.clone()) {
+        // Pausing routine actions run as a sequence
+        let actions_changed = if sequence_loses_wait(&actions, &|rule| {
+            rewrite_rule_entity_refs(&mut rule.clone(), source, replacement)
+                == RewriteStatus::Remove
+        }) {
+            actions.clear();
+            true
+        } else {
+            rewrite_entity_ref_actions(&mut actions, source, replacement)
+        };
+
+        if actions_changed {
+            match serde_json::to_value(&actions) {
+                Ok(value) => {
+                    routine.actions = value;
+                    changed = true;
+                }
+                Err(error) => warn!("Failed to serialize rewritten routine actions: {error}"),
+            }
+        }
+    }
+
+    if changed {
+        RewriteStatus::Changed
+    } else {
+        RewriteStatus::Unchanged
+    }
+}
+
+fn rewrite_widget_entity_refs(
+    value: &mut serde_json::Value,
+    source: &ConfigEntityTarget,
+    replacement: &str,
+) -> bool {
+    let (id_key, ids_key) = source.kind.widget_option_keys();
+
+    match value {
+        serde_json::Value::Object(options) => {
+            let mut changed = false;
+
+            for (key, value) in options.iter_mut() {
+                if key == id_key {
+                    if value.as_str() == Some(source.id.as_str()) {
+                        *value = replacement.into();
+                        changed = true;
+                    }
+                } else if key == ids_key {
+                    for id in value.as_array_mut().into_iter().flatten() {
+                        if id.as_str() == Some(source.id.as_str()) {
+                            *id = replacement.into();
+                            changed = true;
+                        }
+                    }
+                } else {
+                    changed |= rewrite_widget_entity_refs(value, source, replacement);
+                }
+            }
+
+            changed
+        }
+        serde_json::Value::Array(items) => {
+            let mut changed = false;
+
+            for item in items {
+                changed |= rewrite_widget_entity_refs(item, source, replacement);
+            }
+
+            changed
+        }
+        _ => false,
+    }
+}
+
+/// Rewrites references to `source` across groups, scenes and routines,
+/// pointing them to `replacement` or removing them. The source entity itself
+/// is left as is.
+///
+/// Floorplan positions and outlines of groups and dashboard widget options
+/// only follow a replacement, and are left as is when references are removed.
+fn rewrite_entity_config_references(
+    config: &mut ConfigExport,
+    source: &ConfigEntityTarget,
+    replacement: Option<&str>,
+) -> EntityRewriteResult {
+    let mut result = EntityRewriteResult::default();
+
+    for group in &mut config.groups {
+        if !source.is(ConfigEntityKind::Group, &group.id)
+            && rewrite_group_entity_refs(group, source, replacement)
+        {
+            result.changed_groups.push(group.clone());
+        }
+    }
+
+    for scene in &mut config.scenes {
+        if !source.is(ConfigEntityKind::Scene, &scene.id)
+            && rewrite_scene_entity_refs(scene, source, replacement)
+        {
+            result.changed_scenes.push(scene.clone());
+        }
+    }
+
+    let mut next_routines = Vec::with_capacity(config.routines.len());
+    for mut routine in std::mem::take(&mut config.routines) {
+        if source.is(ConfigEntityKind::Routine, &routine.id) {
+            next_routines.push(routine);
+            continue;
+        }
+
+        match rewrite_routine_entity_refs(&mut routine, source, replacement) {
+            RewriteStatus::Remove => result.deleted_routines.push(routine.id),
+            RewriteStatus::Changed => {
+                result.changed_routines.push(routine.clone());
+                next_routines.push(routine);
+            }
+            RewriteStatus::Unchanged => next_routines.push(routine),
+        }
+    }
+    config.routines = next_routines;
+
+    // Routines only refer to other routines in their actions, so this doesn't
+    // delete any further routines
+    for deleted_id in &result.deleted_routines {
+        let deleted = ConfigEntityTarget::new(ConfigEntityKind::Routine, deleted_id.clone());
+        for routine in &mut config.routines {
+            if rewrite_routine_entity_refs(routine, &deleted, None) == RewriteStatus::Unchanged {
+                continue;
+            }
+
+            match result
+                .changed_routines
+                .iter_mut()
+                .find(|r| r.id == routine.id)
+            {
+                Some(changed) => *changed = routine.clone(),
+                None => result.changed_routines.push(routine.clone()),
+            }
+        }
+    }
+
+    let Some(replacement) = replacement else {
+        return result;
+    };
+
+    for widget in &mut config.dashboard_widgets {
+        if rewrite_widget_entity_refs(&mut widget.config, source, replacement) {
+            result.changed_widgets.push(widget.clone());
+        }
+    }
+
+    if source.kind != ConfigEntityKind::Group {
+        return result;
+    }
+
+    for (sort_order, floorplan) in config.floorplans.iter_mut().enumerate() {
+        if config_queries::rewrite_floorplan_group_references_in_grid(
+            floorplan,
+            &source.id,
+            replacement,
+        ) {
+            result.changed_floorplans.push(ChangedFloorplan {
+                sort_order: sort_order as i32,
+                floorplan: floorplan.clone(),
+            });
+        }
+    }
+
+    let positions = &mut config.group_positions;
+    if let Some(index) = positions.iter().position(|p| p.group_id == source.id) {
+        result.group_position_changed = true;
+
+        // A position the replacement group already has is kept.
+        if positions.iter().any(|p| p.group_id == replacement) {
+            positions.remove(index);
+        } else {
+            positions[index].group_id = replacement.to_string();
+            result.moved_group_position = Some(positions[index].clone());
+            positions.sort_by(|left, right| left.group_id.cmp(&right.group_id));
+        }
+    }
+
+    result
+}
+
+/// Changes the id of `source` and rewrites references to it. The renamed
+/// entity comes first among the changed rows, so that it is persisted before
+/// the rows referring to it, and only appears once even if it refers to
+/// itself.
+fn rename_entity_in_config(
+    config: &mut ConfigExport,
+    source: &ConfigEntityTarget,
+    new_id: &str,
+) -> EntityRewriteResult {
+    let mut result = rewrite_entity_config_references(config, source, Some(new_id));
+
+    match source.kind {
+        ConfigEntityKind::Group => {
+            if let Some(group) = config.groups.iter_mut().find(|g| g.id == source.id) {
+                group.id = new_id.to_string();
+                rewrite_group_entity_refs(group, source, Some(new_id));
+                result.changed_groups.retain(|changed| changed.id != new_id);
+                result.changed_groups.insert(0, group.clone());
+            }
+            config.groups.sort_by(|left, right| left.id.cmp(&right.id));
+        }
+        ConfigEntityKind::Scene => {
+            if let Some(scene) = config.scenes.iter_mut().find(|s| s.id == source.id) {
+                scene.id = new_id.to_string();
+                rewrite_scene_entity_refs(scene, source, Some(new_id));
+                result.changed_scenes.retain(|changed| changed.id != new_id);
+                result.changed_scenes.insert(0, scene.clone());
+            }
+            config.scenes.sort_by(|left, right| left.id.cmp(&right.id));
+        }
+        ConfigEntityKind::Routine => {
+            if let Some(routine) = config.routines.iter_mut().find(|r| r.id == source.id) {
+                routine.id = new_id.to_string();
+                rewrite_routine_entity_refs(routine, source, Some(new_id));
+                result
+                    .changed_routines
+                    .retain(|changed| changed.id != new_id);
+                result.changed_routines.insert(0, routine.clone());
+            }
+            config
+                .routines
+                .sort_by(|left, right| left.id.cmp(&right.id));
+        }
+    }
+
+    result
+}
+
+/// Describes what refers to the given scene, or `None` if nothing does.
+pub(super) fn describe_scene_dependents(config: &ConfigExport, scene_id: &str) -> Option<String> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Scene, scene_id.to_string());
+    let dependents =
+        rewrite_entity_config_references(&mut config.clone(), &source, None).dependents();
+
+    (!dependents.is_empty()).then(|| dependents.describe())
+}
+
+fn dependents_conflict(
+    source: &ConfigEntityTarget,
+    dependents: ConfigDependents,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    let error = format!(
+        "{} '{}' is referenced by {}. Delete with ?cascade=true to remove these references \
+         and routines whose rules need them, or replace it with another {}.",
+        source.kind.label(),
+        source.id,
+        dependents.describe(),
+        source.kind.label().to_lowercase(),
+    );
+
+    warp::reply::with_status(
+        warp::reply::json(&ApiResponse {
+            success: false,
+            data: Some(dependents),
+            error: Some(error),
+        }),
+        StatusCode::CONFLICT,
+    )
+}
+
+/// Persists a rewrite of references to `source` in one transaction, also
+/// deleting `source` itself if `delete_source` is set. Without a database
+/// connection the change only lives in memory until it is backfilled.
+async fn persist_entity_config_rewrite(
+    rewrite: &EntityRewriteResult,
+    source: &ConfigEntityTarget,
+    delete_source: bool,
+) -> color_eyre::Result<()> {
+    if !db::is_db_connected() {
+        return Ok(());
+    }
+
+    let mut changes = config_queries::ConfigRowChanges {
+        groups: rewrite.changed_groups.clone(),
+        scenes: rewrite.changed_scenes.clone(),
+        routines: rewrite.changed_routines.clone(),
+        floorplans: rewrite
+            .changed_floorplans
+            .iter()
+            .map(|changed| (changed.floorplan.clone(), changed.sort_order))
+            .collect(),
+        dashboard_widgets: rewrite.changed_widgets.clone(),
+        group_positions: rewrite.moved_group_position.iter().cloned().collect(),
+        deleted_routines: rewrite.deleted_routines.clone(),
+        ..Default::default()
+    };
+
+    if rewrite.group_position_changed {
+        changes.deleted_group_positions.push(source.id.clone());
+    }
+
+    if delete_source {
+        match source.kind {
+            ConfigEntityKind::Group => changes.deleted_groups.push(source.id.clone()),
+            ConfigEntityKind::Scene => changes.deleted_scenes.push(source.id.clone()),
+            ConfigEntityKind::Routine => changes.deleted_routines.push(source.id.clone()),
+        }
+    }
+
+    config_queries::db_apply_config_row_changes(&changes).await
+}
+
+/// Changes the id of a group, scene or routine, rewriting references to it.
+async fn rename_config_entity(
+    source: ConfigEntityTarget,
+    request: RenameEntityRequest,
+    handle: StateHandle,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    let new_id = request.new_id.trim().to_string();
+    if new_id.is_empty() {
+        return Ok(error_response(
+            "New ID must not be empty.",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    enum RenameOutcome {
+        Ok(EntityRewriteResult),
+        NotFound,
+        Conflict,
+        PersistFailed(String),
+    }
+
+    let source_for_state = source.clone();
+    let new_id_for_state = new_id.clone();
+    let outcome = handle
+        .mutate(move |state| {
+            Box::pin(async move {
+                let source = source_for_state;
+                if !source.exists_in(&state.runtime_config) {
+                    return RenameOutcome::NotFound;
+                }
+
+                let target = ConfigEntityTarget::new(source.kind, new_id_for_state.clone());
+                if target.exists_in(&state.runtime_config) {
+                    return RenameOutcome::Conflict;
+                }
+
+                let mut runtime_config = state.get_runtime_config().clone();
+                let rewrite =
+                    rename_entity_in_config(&mut runtime_config, &source, &new_id_for_state);
+
+                // Persist first, so that a failed rename leaves both the
+                // database and the running config as they were
+                if let Err(error) = persist_entity_config_rewrite(&rewrite, &source, true).await {
+                    return RenameOutcome::PersistFailed(error.to_string());
+                }
+
+                state.runtime_config = runtime_config;
+                state.apply_runtime_groups();
+                state.apply_runtime_scenes();
+                state.apply_runtime_routines();
+
+                RenameOutcome::Ok(rewrite)
+            })
+        })
+        .await;
+
+    let rewrite = match outcome {
+        Ok(RenameOutcome::Ok(rewrite)) => rewrite,
+        Ok(RenameOutcome::NotFound) => return Ok(not_found(source.kind.label())),
+        Ok(RenameOutcome::Conflict) => {
+            return Ok(error_response(
+                &format!("{} '{new_id}' already exists.", source.kind.label()),
+                StatusCode::CONFLICT,
+            ));
+        }
+        Ok(RenameOutcome::PersistFailed(error)) => {
+            return Ok(error_response(
+                &format!(
+                    "Failed to persist renaming {} '{}': {error}",
+                    source.kind.label().to_lowercase(),
+                    source.id
+                ),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+        Err(_) => {
+            return Ok(error_response(
+                "State actor unavailable",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    // The renamed entity itself isn't one of its dependents
+    let mut updated = rewrite.dependents();
+    match source.kind {
+        ConfigEntityKind::Group => updated.groups.retain(|id| *id != new_id),
+        ConfigEntityKind::Scene => updated.scenes.retain(|id| *id != new_id),
+        ConfigEntityKind::Routine => updated.routines.retain(|id| *id != new_id),
+    }
+
+    Ok(ApiResponse::success(EntityRenameResponse {
+        id: source.id,
+        new_id,
+        updated,
+        updated_floorplans: rewrite.changed_floorplans.len(),
+        updated_widgets: rewrite.changed_widgets.len(),
+        group_position_changed: rewrite.group_position_changed,
+    }))
+}
+
+/// Deletes a group, scene or routine, pointing its references to
+/// `replacement` or removing them. Without a replacement, the deletion is
+/// refused if anything refers to the entity unless `cascade` is set, which
+/// also deletes routines whose rules refer to it.
+async fn delete_config_entity(
+    source: ConfigEntityTarget,
+    replacement: Option<String>,
+    cascade: bool,
+    handle: StateHandle,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    if replacement.as_deref() == Some(source.id.as_str()) {
+        return Ok(error_response(
+            &format!(
+                "Replacement {} must differ from the source.",
+                source.kind.label().to_lowercase()
+            ),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    enum DeleteOutcome {
+        Ok(EntityRewriteResult),
+        Referenced(ConfigDependents),
+        ReplacementMissing,
+        NotFound,
+        PersistFailed(String),
+    }
+
+    let source_for_state = source.clone();
+    let replacement_for_state = replacement.clone();
+    let outcome = handle
+        .mutate(move |state| {
+            Box::pin(async move {
+                let source = source_for_state;
+                let mut runtime_config = state.get_runtime_config().clone();
+                if !source.exists_in(&runtime_config) {
+                    return DeleteOutcome::NotFound;
+                }
+
+                if let Some(replacement) = &replacement_for_state {
+                    let target = ConfigEntityTarget::new(source.kind, replacement.clone());
+                    if !target.exists_in(&runtime_config) {
+                        return DeleteOutcome::ReplacementMissing;
+                    }
+                }
+
+                let rewrite = rewrite_entity_config_references(
+                    &mut runtime_config,
+                    &source,
+                    replacement_for_state.as_deref(),
+                );
+
+                let dependents = rewrite.dependents();
+                if replacement_for_state.is_none() && !cascade && !dependents.is_empty() {
+                    return DeleteOutcome::Referenced(dependents);
+                }
+
+                // Persist first, so that a failed delete leaves both the
+                // database and the running config as they were
+                if let Err(error) = persist_entity_config_rewrite(&rewrite, &source, true).await {
+                    return DeleteOutcome::PersistFailed(error.to_string());
+                }
+
+                state.runtime_config = runtime_config;
+                match source.kind {
+                    ConfigEntityKind::Group => state.delete_group(&source.id),
+                    ConfigEntityKind::Scene => state.delete_scene(&source.id),
+                    ConfigEntityKind::Routine => state.delete_routine(&source.id),
+                };
+                state.apply_runtime_groups();
+                state.apply_runtime_scenes();
+                state.apply_runtime_routines();
+
+                DeleteOutcome::Ok(rewrite)
+            })
+        })
+        .await;
+
+    let rewrite = match outcome {
+        Ok(DeleteOutcome::Ok(rewrite)) => rewrite,
+        Ok(DeleteOutcome::Referenced(dependents)) => {
+            return Ok(dependents_conflict(&source, dependents));
+        }
+        Ok(DeleteOutcome::ReplacementMissing) => {
+            return Ok(error_response(
+                &format!(
+                    "Replacement {} not found.",
+                    source.kind.label().to_lowercase()
+                ),
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+        Ok(DeleteOutcome::NotFound) => return Ok(not_found(source.kind.label())),
+        Ok(DeleteOutcome::PersistFailed(error)) => {
+            return Ok(error_response(
+                &format!(
+                    "Failed to persist deleting {} '{}': {error}",
+                    source.kind.label().to_lowercase(),
+                    source.id
+                ),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+        Err(_) => {
+            return Ok(error_response(
+                "State actor unavailable",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    Ok(ApiResponse::success(EntityMutationResponse {
+        deleted_id: source.id,
+        replacement_id: replacement,
+        updated_groups: rewrite.changed_groups.len(),
+        updated_scenes: rewrite.changed_scenes.len(),
+        updated_routines: rewrite.changed_routines.len(),
+        deleted_routines: rewrite.deleted_routines,
+    }))
+}
+
+fn group_response_row(
+    flattened_groups: &crate::types::group::FlattenedGroupsConfig,
+    group: GroupRow,
+) -> GroupResponseRow {
+    let device_keys = flattened_groups
+        .0
+        .iter()
+        .find_map(|(group_id, flattened_group)| {
+            (group_id.0 == group.id).then(|| {
+                flattened_group
+                    .device_keys
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            })
+        })
+        .unwrap_or_default();
+
+    GroupResponseRow { group, device_keys }
+}
+
+fn legacy_default_floorplan(config: &config_queries::ConfigExport) -> Option<FloorplanExportRow> {
+    let floorplan = config.floorplan.as_ref();
+
+    Some(FloorplanExportRow {
+        id: "default".to_string(),
+        name: "Default".to_string(),
+        image_data: floorplan.and_then(|floorplan| floorplan.image_data.clone()),
+        image_mime_type: floorplan.and_then(|floorplan| floorplan.image_mime_type.clone()),
+        width: floorplan.and_then(|floorplan| floorplan.width),
+        height: floorplan.and_then(|floorplan| floorplan.height),
+        grid_data: None,
+    })
+}
+
+fn list_runtime_floorplans(config: &config_queries::ConfigExport) -> Vec<FloorplanMetadataRow> {
+    if !config.floorplans.is_empty() {
+        return config
+            .floorplans
+            .iter()
+            .map(|floorplan| FloorplanMetadataRow {
+                id: floorplan.id.clone(),
+                name: floorplan.name.clone(),
+            })
+            .collect();
+    }
+
+    legacy_default_floorplan(config)
+        .into_iter()
+        .map(|floorplan| FloorplanMetadataRow {
+            id: floorplan.id,
+            name: floorplan.name,
+        })
+        .collect()
+}
+
+fn get_runtime_floorplan(
+    config: &config_queries::ConfigExport,
+    floorplan_id: &str,
+) -> Option<FloorplanExportRow> {
+    config
+        .floorplans
+        .iter()
+        .find(|floorplan| floorplan.id == floorplan_id)
+        .cloned()
+        .or_else(|| {
+            if floorplan_id == "default" {
+                legacy_default_floorplan(config)
+            } else {
+                None
+            }
+        })
+}
+
+// ============================================================================
+// Main Config Routes
+// ============================================================================
+
+pub fn config(
+    snapshot: &SnapshotHandle,
+    handle: &StateHandle,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("config").and(
+        core_routes(snapshot, handle)
+            .or(runtime_status_routes())
+            .or(logs_routes())
+            .or(routine_history_routes())
+            .or(webhook_delivery_routes())
+            .or(sequence_routes(handle))
+            .or(device_display_name_routes(snapshot, handle))
+            .or(device_sensor_config_routes(snapshot, handle))
+            .or(device_config_routes(handle))
+            .or(integration_schema_routes())
+            .or(integrations_routes(snapshot, handle))
+            .or(groups_routes(snapshot, handle))
+            .or(scenes_routes(snapshot, handle))
+            .or(routines_routes(snapshot, handle))
+            .or(floorplans_routes(snapshot, handle))
+            .or(floorplan_routes(snapshot, handle))
+            .or(dashboard_routes(snapshot, handle))
+            .or(export_import_routes(snapshot, handle))
+            .or(config_version_routes(snapshot, handle))
+            .or(validate_routes(snapshot))
+            .or(migrate_routes(snapshot, handle)),
+    )
+}
+
+// ============================================================================
+// Core Config
+// ============================================================================
+
+fn logs_routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("logs")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(list_logs)
+}
+
+async fn list_logs() -> Result<impl Reply, warp::Rejection> {
+    Ok(ApiResponse::success(recent_logs()))
+}
+
+fn routine_history_routes(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("routine-history")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(list_routine_history)
+}
+
+async fn list_routine_history() -> Result<impl Reply, warp::Rejection> {
+    Ok(ApiResponse::success(recent_routine_history()))
+}
+
+fn webhook_delivery_routes(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("webhook-deliveries")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(list_webhook_deliveries)
+}
+
+async fn list_webhook_deliveries() -> Result<impl Reply, warp::Rejection> {
+    Ok(ApiResponse::success(recent_webhook_deliveries()))
+}
+
+fn sequence_routes(
+    handle: &StateHandle,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let list = warp::path("sequences")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_handle(handle))
+        .and_then(list_sequences);
+
+    let cancel = warp::path!("sequences" / u64)
+        .and(warp::delete())
+        .and(with_handle(handle))
+        .and_then(cancel_sequence);
+
+    list.or(cancel)
+}
+
+async fn list_sequences(handle: StateHandle) -> Result<impl Reply, warp::Rejection> {
+    let result = handle
+        .mutate(|state| Box::pin(async move { state.sequences.pending() }))
+        .await;
+
+    match result {
+        Ok(sequences) => Ok(ApiResponse::success(sequences)),
+        Err(e) => Ok(error_response(
+            &e.to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    }
+}
+
+async fn cancel_sequence(id: u64, handle: StateHandle) -> Result<impl Reply, warp::Rejection> {
+    let cancelled = handle
+        .mutate(move |state| Box::pin(async move { state.sequences.cancel(id) }))
+        .await
+        .unwrap_or(false);
+
+    if !cancelled {
+        return Ok(not_found("Sequence"));
+    }
+
+    Ok(ApiResponse::success(()))
+}
+
+fn core_routes(
+    snapshot: &SnapshotHandle,
     handle: &StateHandle,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let get = warp::path("core")
@@ -1246,7 +2631,10 @@ async fn update_core_config(
     config: CoreConfigPayload,
     handle: StateHandle,
 ) -> Result<impl Reply, warp::Rejection> {
-    let core_config = config.core_config();
+    let core_config = match config.core_config() {
+        Ok(core_config) => core_config,
+        Err(error) => return Ok(error_response(&error, StatusCode::BAD_REQUEST)),
+    };
     let widget_settings = config.widget_settings();
 
     let core_for_state = core_config.clone();
@@ -1757,10 +3145,23 @@ fn integrations_routes(
     list.or(get).or(create).or(update).or(delete)
 }
 
+fn integration_response_row(integration: IntegrationRow) -> IntegrationResponseRow {
+    let status = integration_status(&IntegrationId::from(integration.id.clone()));
+    IntegrationResponseRow {
+        integration,
+        status,
+    }
+}
+
 async fn list_integrations(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
     let snap = snapshot.load();
     Ok(ApiResponse::success(
-        snap.runtime_config.integrations.clone(),
+        snap.runtime_config
+            .integrations
+            .iter()
+            .cloned()
+            .map(integration_response_row)
+            .collect::<Vec<_>>(),
     ))
 }
 
@@ -1776,7 +3177,7 @@ async fn get_integration(
         .find(|integration| integration.id == id)
         .cloned()
     {
-        Some(integration) => Ok(ApiResponse::success(integration)),
+        Some(integration) => Ok(ApiResponse::success(integration_response_row(integration))),
         None => Ok(not_found("Integration")),
     }
 }
@@ -1923,10 +3324,28 @@ fn groups_routes(
 
     let delete = warp::path!("groups" / String)
         .and(warp::delete())
+        .and(warp::query::<DeleteEntityQuery>())
         .and(with_handle(handle))
         .and_then(delete_group);
 
-    list.or(get).or(create).or(update).or(delete)
+    let rename = warp::path!("groups" / String / "rename")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(rename_group);
+
+    let replace = warp::path!("groups" / String / "replace")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(replace_group);
+
+    list.or(get)
+        .or(create)
+        .or(update)
+        .or(delete)
+        .or(replace)
+        .or(rename)
 }
 
 async fn list_groups(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
@@ -2000,30 +3419,32 @@ async fn update_group(
     Ok(ApiResponse::success(group))
 }
 
-async fn delete_group(id: String, handle: StateHandle) -> Result<impl Reply, warp::Rejection> {
-    let id_for_state = id.clone();
-    let deleted = handle
-        .mutate(move |state| {
-            Box::pin(async move {
-                let deleted = state.delete_group(&id_for_state);
-                if deleted {
-                    state.apply_runtime_groups();
-                }
-                deleted
-            })
-        })
-        .await
-        .unwrap_or(false);
-
-    if !deleted {
-        return Ok(not_found("Group"));
-    }
+async fn delete_group(
+    id: String,
+    query: DeleteEntityQuery,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Group, id);
+    delete_config_entity(source, None, query.cascade, handle).await
+}
 
-    if let Err(e) = config_queries::db_delete_group(&id).await {
-        warn!("Failed to persist group deletion: {e}");
-    }
+async fn rename_group(
+    id: String,
+    request: RenameEntityRequest,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Group, id);
+    rename_config_entity(source, request, handle).await
+}
 
-    Ok(ApiResponse::success(()))
+async fn replace_group(
+    id: String,
+    request: ReplaceEntityRequest,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Group, id);
+    let replacement = request.replacement_id.trim().to_string();
+    delete_config_entity(source, Some(replacement), false, handle).await
 }
 
 // ============================================================================
@@ -2060,10 +3481,28 @@ fn scenes_routes(
 
     let delete = warp::path!("scenes" / String)
         .and(warp::delete())
+        .and(warp::query::<DeleteEntityQuery>())
         .and(with_handle(handle))
         .and_then(delete_scene);
 
-    list.or(get).or(create).or(update).or(delete)
+    let rename = warp::path!("scenes" / String / "rename")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(rename_scene);
+
+    let replace = warp::path!("scenes" / String / "replace")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(replace_scene);
+
+    list.or(get)
+        .or(create)
+        .or(update)
+        .or(delete)
+        .or(replace)
+        .or(rename)
 }
 
 async fn list_scenes(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
@@ -2127,30 +3566,32 @@ async fn update_scene(
     Ok(ApiResponse::success(scene))
 }
 
-async fn delete_scene(id: String, handle: StateHandle) -> Result<impl Reply, warp::Rejection> {
-    let id_for_state = id.clone();
-    let deleted = handle
-        .mutate(move |state| {
-            Box::pin(async move {
-                let deleted = state.delete_scene(&id_for_state);
-                if deleted {
-                    state.apply_runtime_scenes();
-                }
-                deleted
-            })
-        })
-        .await
-        .unwrap_or(false);
-
-    if !deleted {
-        return Ok(not_found("Scene"));
-    }
+async fn delete_scene(
+    id: String,
+    query: DeleteEntityQuery,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Scene, id);
+    delete_config_entity(source, None, query.cascade, handle).await
+}
 
-    if let Err(e) = config_queries::db_delete_config_scene(&id).await {
-        warn!("Failed to persist scene deletion: {e}");
-    }
+async fn rename_scene(
+    id: String,
+    request: RenameEntityRequest,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Scene, id);
+    rename_config_entity(source, request, handle).await
+}
 
-    Ok(ApiResponse::success(()))
+async fn replace_scene(
+    id: String,
+    request: ReplaceEntityRequest,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Scene, id);
+    let replacement = request.replacement_id.trim().to_string();
+    delete_config_entity(source, Some(replacement), false, handle).await
 }
 
 // ============================================================================
@@ -2187,10 +3628,17 @@ fn routines_routes(
 
     let delete = warp::path!("routines" / String)
         .and(warp::delete())
+        .and(warp::query::<DeleteEntityQuery>())
         .and(with_handle(handle))
         .and_then(delete_routine);
 
-    list.or(get).or(create).or(update).or(delete)
+    let rename = warp::path!("routines" / String / "rename")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(rename_routine);
+
+    list.or(get).or(create).or(update).or(delete).or(rename)
 }
 
 async fn list_routines(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
@@ -2216,7 +3664,14 @@ async fn create_routine(
     routine: RoutineRow,
     handle: StateHandle,
 ) -> Result<impl Reply, warp::Rejection> {
-    if let Err(error) = validate_routine_actions(&routine.actions) {
+    let snap = handle.snapshot.load_full();
+    if let Err(error) =
+        validate_routine_actions(&routine.actions, &snap.runtime_config.integrations)
+    {
+        return Ok(error_response(&error, StatusCode::BAD_REQUEST));
+    }
+
+    if let Err(error) = validate_routine_rate_limits(&routine) {
         return Ok(error_response(&error, StatusCode::BAD_REQUEST));
     }
 
@@ -2242,7 +3697,14 @@ async fn update_routine(
     mut routine: RoutineRow,
     handle: StateHandle,
 ) -> Result<impl Reply, warp::Rejection> {
-    if let Err(error) = validate_routine_actions(&routine.actions) {
+    let snap = handle.snapshot.load_full();
+    if let Err(error) =
+        validate_routine_actions(&routine.actions, &snap.runtime_config.integrations)
+    {
+        return Ok(error_response(&error, StatusCode::BAD_REQUEST));
+    }
+
+    if let Err(error) = validate_routine_rate_limits(&routine) {
         return Ok(error_response(&error, StatusCode::BAD_REQUEST));
     }
 
@@ -2255,15 +3717,16 @@ async fn update_routine(
     let renamed = next_id != id;
 
     enum UpdateOutcome {
-        Updated(Vec<RoutineRow>),
+        Updated,
         NotFound,
         Conflict,
+        PersistFailed(String),
     }
 
-    let id_for_state = id.clone();
+    let source_for_state = ConfigEntityTarget::new(ConfigEntityKind::Routine, id.clone());
     let next_id_for_state = next_id.clone();
     let mut routine_for_state = routine.clone();
-    routine_for_state.id = next_id.clone();
+    routine_for_state.id = id.clone();
 
     let outcome = handle
         .mutate(move |state| {
@@ -2272,7 +3735,7 @@ async fn update_routine(
                     .runtime_config
                     .routines
                     .iter()
-                    .position(|existing| existing.id == id_for_state)
+                    .position(|existing| existing.id == source_for_state.id)
                 else {
                     return UpdateOutcome::NotFound;
                 };
@@ -2287,39 +3750,38 @@ async fn update_routine(
                     return UpdateOutcome::Conflict;
                 }
 
-                state.runtime_config.routines[existing_index] = routine_for_state.clone();
-
-                let mut changed_routines = vec![routine_for_state.clone()];
-
-                if renamed {
-                    for existing in &mut state.runtime_config.routines {
-                        if existing.id == next_id_for_state {
-                            continue;
-                        }
-
-                        if rewrite_force_trigger_routine_references(
-                            &mut existing.actions,
-                            &id_for_state,
-                            &next_id_for_state,
-                        ) {
-                            changed_routines.push(existing.clone());
-                        }
+                let mut runtime_config = state.get_runtime_config().clone();
+                runtime_config.routines[existing_index] = routine_for_state.clone();
+
+                let rewrite = if renamed {
+                    rename_entity_in_config(
+                        &mut runtime_config,
+                        &source_for_state,
+                        &next_id_for_state,
+                    )
+                } else {
+                    EntityRewriteResult {
+                        changed_routines: vec![routine_for_state],
+                        ..Default::default()
                     }
+                };
+
+                if let Err(error) =
+                    persist_entity_config_rewrite(&rewrite, &source_for_state, renamed).await
+                {
+                    return UpdateOutcome::PersistFailed(error.to_string());
                 }
 
-                state
-                    .runtime_config
-                    .routines
-                    .sort_by(|left, right| left.id.cmp(&right.id));
+                state.runtime_config = runtime_config;
                 state.apply_runtime_routines();
 
-                UpdateOutcome::Updated(changed_routines)
+                UpdateOutcome::Updated
             })
         })
         .await;
 
-    let changed_routines = match outcome {
-        Ok(UpdateOutcome::Updated(routines)) => routines,
+    match outcome {
+        Ok(UpdateOutcome::Updated) => {}
         Ok(UpdateOutcome::NotFound) => return Ok(not_found("Routine")),
         Ok(UpdateOutcome::Conflict) => {
             return Ok(error_response(
@@ -2327,6 +3789,12 @@ async fn update_routine(
                 StatusCode::BAD_REQUEST,
             ));
         }
+        Ok(UpdateOutcome::PersistFailed(error)) => {
+            return Ok(error_response(
+                &format!("Failed to persist routine update: {error}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
         Err(_) => {
             return Ok(error_response(
                 "State actor unavailable",
@@ -2335,50 +3803,27 @@ async fn update_routine(
         }
     };
 
-    routine.id = next_id.clone();
-
-    for changed_routine in &changed_routines {
-        if let Err(error) = config_queries::db_upsert_routine(changed_routine).await {
-            warn!(
-                "Failed to persist routine config update for '{}': {error}",
-                changed_routine.id
-            );
-        }
-    }
-
-    if renamed {
-        if let Err(error) = config_queries::db_delete_routine(&id).await {
-            warn!("Failed to delete old routine config '{}': {error}", id);
-        }
-    }
+    routine.id = next_id;
 
     Ok(ApiResponse::success(routine))
 }
 
-async fn delete_routine(id: String, handle: StateHandle) -> Result<impl Reply, warp::Rejection> {
-    let id_for_state = id.clone();
-    let deleted = handle
-        .mutate(move |state| {
-            Box::pin(async move {
-                let deleted = state.delete_routine(&id_for_state);
-                if deleted {
-                    state.apply_runtime_routines();
-                }
-                deleted
-            })
-        })
-        .await
-        .unwrap_or(false);
-
-    if !deleted {
-        return Ok(not_found("Routine"));
-    }
-
-    if let Err(e) = config_queries::db_delete_routine(&id).await {
-        warn!("Failed to persist routine deletion: {e}");
-    }
+async fn delete_routine(
+    id: String,
+    query: DeleteEntityQuery,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Routine, id);
+    delete_config_entity(source, None, query.cascade, handle).await
+}
 
-    Ok(ApiResponse::success(()))
+async fn rename_routine(
+    id: String,
+    request: RenameEntityRequest,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let source = ConfigEntityTarget::new(ConfigEntityKind::Routine, id);
+    rename_config_entity(source, request, handle).await
 }
 
 // ============================================================================
@@ -2838,251 +4283,934 @@ async fn head_floorplan_image(
     }
 }
 
-async fn delete_floorplan_image(
-    floorplan_id: String,
+async fn delete_floorplan_image(
+    floorplan_id: String,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let id_for_state = floorplan_id.clone();
+    let deleted = handle
+        .mutate(move |state| Box::pin(async move { state.clear_floorplan_image(&id_for_state) }))
+        .await
+        .unwrap_or(false);
+
+    if !deleted {
+        return Ok(not_found("Floorplan image"));
+    }
+
+    if let Err(e) = config_queries::db_clear_floorplan_image(&floorplan_id).await {
+        warn!("Failed to persist floorplan image deletion: {e}");
+    }
+
+    Ok(ApiResponse::success(()))
+}
+
+// ============================================================================
+// Dashboard
+// ============================================================================
+
+fn dashboard_routes(
+    snapshot: &SnapshotHandle,
+    handle: &StateHandle,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let get_layouts = warp::path!("dashboard" / "layouts")
+        .and(warp::get())
+        .and(with_snapshot(snapshot))
+        .and_then(get_dashboard_layouts);
+
+    let upsert_layout = warp::path!("dashboard" / "layouts")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(upsert_dashboard_layout);
+
+    let delete_layout = warp::path!("dashboard" / "layouts" / i32)
+        .and(warp::delete())
+        .and(with_handle(handle))
+        .and_then(delete_dashboard_layout);
+
+    let get_widgets = warp::path!("dashboard" / "layouts" / i32 / "widgets")
+        .and(warp::get())
+        .and(with_snapshot(snapshot))
+        .and_then(get_dashboard_widgets);
+
+    let upsert_widget = warp::path!("dashboard" / "widgets")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(upsert_dashboard_widget);
+
+    let upsert_widget_legacy = warp::path!("dashboard")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(upsert_dashboard_widget);
+
+    let delete_widget = warp::path!("dashboard" / "widgets" / i32)
+        .and(warp::delete())
+        .and(with_handle(handle))
+        .and_then(delete_dashboard_widget);
+
+    get_layouts
+        .or(upsert_layout)
+        .or(delete_layout)
+        .or(get_widgets)
+        .or(upsert_widget)
+        .or(upsert_widget_legacy)
+        .or(delete_widget)
+}
+
+async fn get_dashboard_layouts(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
+    let snap = snapshot.load();
+    Ok(ApiResponse::success(
+        snap.runtime_config.dashboard_layouts.clone(),
+    ))
+}
+
+async fn upsert_dashboard_layout(
+    layout: DashboardLayoutRow,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let layout_for_state = layout.clone();
+    let local_layout = handle
+        .mutate(move |state| {
+            Box::pin(async move { state.upsert_dashboard_layout(layout_for_state) })
+        })
+        .await
+        .unwrap_or_else(|_| layout.clone());
+
+    match config_queries::db_upsert_dashboard_layout(&layout).await {
+        Ok(id) => Ok(ApiResponse::success(DashboardLayoutRow {
+            id,
+            name: local_layout.name,
+            is_default: local_layout.is_default,
+        })),
+        Err(e) => {
+            warn!("Failed to persist dashboard layout: {e}");
+            Ok(ApiResponse::success(local_layout))
+        }
+    }
+}
+
+async fn delete_dashboard_layout(
+    id: i32,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let deleted = handle
+        .mutate(move |state| Box::pin(async move { state.delete_dashboard_layout(id) }))
+        .await
+        .unwrap_or(false);
+
+    if !deleted {
+        return Ok(not_found("Dashboard layout"));
+    }
+
+    if let Err(e) = config_queries::db_delete_dashboard_layout(id).await {
+        warn!("Failed to persist dashboard layout deletion: {e}");
+    }
+
+    Ok(ApiResponse::success(()))
+}
+
+async fn get_dashboard_widgets(
+    layout_id: i32,
+    snapshot: SnapshotHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let snap = snapshot.load();
+    Ok(ApiResponse::success(
+        snap.runtime_config
+            .dashboard_widgets
+            .iter()
+            .filter(|widget| widget.layout_id == layout_id)
+            .cloned()
+            .collect::<Vec<_>>(),
+    ))
+}
+
+async fn upsert_dashboard_widget(
+    widget: DashboardWidgetRow,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let widget_for_state = widget.clone();
+    let local_widget = handle
+        .mutate(move |state| {
+            Box::pin(async move { state.upsert_dashboard_widget(widget_for_state) })
+        })
+        .await
+        .unwrap_or_else(|_| widget.clone());
+
+    match config_queries::db_upsert_dashboard_widget(&widget).await {
+        Ok(id) => Ok(ApiResponse::success(DashboardWidgetRow {
+            id,
+            ..local_widget
+        })),
+        Err(e) => {
+            warn!("Failed to persist dashboard widget: {e}");
+            Ok(ApiResponse::success(local_widget))
+        }
+    }
+}
+
+async fn delete_dashboard_widget(
+    id: i32,
+    handle: StateHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    let deleted = handle
+        .mutate(move |state| Box::pin(async move { state.delete_dashboard_widget(id) }))
+        .await
+        .unwrap_or(false);
+
+    if !deleted {
+        return Ok(not_found("Dashboard widget"));
+    }
+
+    if let Err(e) = config_queries::db_delete_dashboard_widget(id).await {
+        warn!("Failed to persist dashboard widget deletion: {e}");
+    }
+
+    Ok(ApiResponse::success(()))
+}
+
+// ============================================================================
+// Export / Import
+// ============================================================================
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    #[serde(default)]
+    save_version: bool,
+}
+
+fn export_import_routes(
+    snapshot: &SnapshotHandle,
+    handle: &StateHandle,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let export = warp::path("export")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_snapshot(snapshot))
+        .and_then(export_config);
+
+    let import = warp::path("import")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::query::<ImportQuery>())
+        .and(warp::body::json())
+        .and(with_handle(handle))
+        .and_then(import_config);
+
+    export.or(import)
+}
+
+async fn export_config(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
+    let snap = snapshot.load();
+    Ok(ApiResponse::success((*snap.runtime_config).clone()))
+}
+
+async fn import_config(
+    query: ImportQuery,
+    config: ConfigExport,
     handle: StateHandle,
 ) -> Result<impl Reply, warp::Rejection> {
-    let id_for_state = floorplan_id.clone();
-    let deleted = handle
-        .mutate(move |state| Box::pin(async move { state.clear_floorplan_image(&id_for_state) }))
-        .await
-        .unwrap_or(false);
+    let mut recorder = config_version_recorder().await;
 
-    if !deleted {
-        return Ok(not_found("Floorplan image"));
+    // Optionally save version before import
+    if query.save_version {
+        let current = (*handle.snapshot.load().runtime_config).clone();
+        if let Err(e) = recorder.record(&current, "Before import").await {
+            warn!("Failed to save config version before import: {e}");
+        }
     }
 
-    if let Err(e) = config_queries::db_clear_floorplan_image(&floorplan_id).await {
-        warn!("Failed to persist floorplan image deletion: {e}");
+    if let Err(e) = apply_runtime_config_snapshot(&handle, config.clone()).await {
+        warn!("Failed to apply imported config to runtime state: {e}");
+    }
+
+    if let Err(e) = config_queries::db_import_config(&config).await {
+        warn!("Failed to persist imported config: {e}");
+    }
+
+    if let Err(e) = recorder.record(&config, "Imported config").await {
+        warn!("Failed to save imported config version: {e}");
     }
 
     Ok(ApiResponse::success(()))
 }
 
 // ============================================================================
-// Dashboard
+// Config Versions
 // ============================================================================
 
-fn dashboard_routes(
+/// Versions to diff, the current config is used for omitted versions.
+#[derive(Deserialize)]
+struct VersionDiffQuery {
+    from: Option<i32>,
+    to: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct RollbackResult {
+    /// Version recorded for the rolled back config, if it differs from the
+    /// latest version.
+    version: Option<i32>,
+    diff: ConfigDiff,
+}
+
+fn config_version_routes(
     snapshot: &SnapshotHandle,
     handle: &StateHandle,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    let get_layouts = warp::path!("dashboard" / "layouts")
+    let list = warp::path("versions")
+        .and(warp::path::end())
         .and(warp::get())
-        .and(with_snapshot(snapshot))
-        .and_then(get_dashboard_layouts);
-
-    let upsert_layout = warp::path!("dashboard" / "layouts")
-        .and(warp::post())
-        .and(warp::body::json())
-        .and(with_handle(handle))
-        .and_then(upsert_dashboard_layout);
-
-    let delete_layout = warp::path!("dashboard" / "layouts" / i32)
-        .and(warp::delete())
-        .and(with_handle(handle))
-        .and_then(delete_dashboard_layout);
+        .and_then(list_config_versions);
 
-    let get_widgets = warp::path!("dashboard" / "layouts" / i32 / "widgets")
+    let diff = warp::path!("versions" / "diff")
         .and(warp::get())
+        .and(warp::query::<VersionDiffQuery>())
         .and(with_snapshot(snapshot))
-        .and_then(get_dashboard_widgets);
+        .and_then(diff_config_versions);
 
-    let upsert_widget = warp::path!("dashboard" / "widgets")
-        .and(warp::post())
-        .and(warp::body::json())
-        .and(with_handle(handle))
-        .and_then(upsert_dashboard_widget);
+    let get = warp::path!("versions" / i32)
+        .and(warp::get())
+        .and_then(get_config_version);
 
-    let upsert_widget_legacy = warp::path!("dashboard")
+    let rollback = warp::path!("versions" / i32 / "rollback")
         .and(warp::post())
-        .and(warp::body::json())
         .and(with_handle(handle))
-        .and_then(upsert_dashboard_widget);
+        .and_then(rollback_config_version);
 
-    let delete_widget = warp::path!("dashboard" / "widgets" / i32)
-        .and(warp::delete())
-        .and(with_handle(handle))
-        .and_then(delete_dashboard_widget);
+    list.or(diff).or(get).or(rollback)
+}
 
-    get_layouts
-        .or(upsert_layout)
-        .or(delete_layout)
-        .or(get_widgets)
-        .or(upsert_widget)
-        .or(upsert_widget_legacy)
-        .or(delete_widget)
+fn config_versions_unavailable() -> Option<warp::reply::WithStatus<warp::reply::Json>> {
+    (!db::is_db_connected()).then(|| {
+        error_response(
+            "Config versions require a database connection",
+            StatusCode::SERVICE_UNAVAILABLE,
+        )
+    })
 }
 
-async fn get_dashboard_layouts(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
-    let snap = snapshot.load();
-    Ok(ApiResponse::success(
-        snap.runtime_config.dashboard_layouts.clone(),
-    ))
+/// Loads a version, or responds with why it couldn't be loaded.
+async fn load_config_version(
+    version: i32,
+) -> Result<ConfigVersionExport, warp::reply::WithStatus<warp::reply::Json>> {
+    match config_queries::db_get_config_version(version).await {
+        Ok(Some(version)) => Ok(version),
+        Ok(None) => Err(not_found("Config version")),
+        Err(e) => {
+            warn!("Failed to load config version {version}: {e}");
+            Err(error_response(
+                "Failed to load config version",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
 }
 
-async fn upsert_dashboard_layout(
-    layout: DashboardLayoutRow,
-    handle: StateHandle,
-) -> Result<impl Reply, warp::Rejection> {
-    let layout_for_state = layout.clone();
-    let local_layout = handle
-        .mutate(move |state| {
-            Box::pin(async move { state.upsert_dashboard_layout(layout_for_state) })
-        })
-        .await
-        .unwrap_or_else(|_| layout.clone());
+async fn list_config_versions() -> Result<impl Reply, warp::Rejection> {
+    if let Some(reply) = config_versions_unavailable() {
+        return Ok(reply);
+    }
 
-    match config_queries::db_upsert_dashboard_layout(&layout).await {
-        Ok(id) => Ok(ApiResponse::success(DashboardLayoutRow {
-            id,
-            name: local_layout.name,
-            is_default: local_layout.is_default,
-        })),
+    match config_queries::db_get_config_versions().await {
+        Ok(versions) => Ok(ApiResponse::success(versions)),
         Err(e) => {
-            warn!("Failed to persist dashboard layout: {e}");
-            Ok(ApiResponse::success(local_layout))
+            warn!("Failed to list config versions: {e}");
+            Ok(error_response(
+                "Failed to list config versions",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
         }
     }
 }
 
-async fn delete_dashboard_layout(
-    id: i32,
+async fn get_config_version(version: i32) -> Result<impl Reply, warp::Rejection> {
+    if let Some(reply) = config_versions_unavailable() {
+        return Ok(reply);
+    }
+
+    Ok(match load_config_version(version).await {
+        Ok(version) => ApiResponse::success(version),
+        Err(reply) => reply,
+    })
+}
+
+async fn diff_config_versions(
+    query: VersionDiffQuery,
+    snapshot: SnapshotHandle,
+) -> Result<impl Reply, warp::Rejection> {
+    if let Some(reply) = config_versions_unavailable() {
+        return Ok(reply);
+    }
+
+    let current = snapshot.load().runtime_config.clone();
+    let mut configs = Vec::with_capacity(2);
+    for version in [query.from, query.to] {
+        match version {
+            Some(version) => match load_config_version(version).await {
+                Ok(version) => configs.push(version.config),
+                Err(reply) => return Ok(reply),
+            },
+            None => configs.push((*current).clone()),
+        }
+    }
+
+    // Versions don't contain floorplan images
+    match diff_configs(&without_images(&configs[0]), &without_images(&configs[1])) {
+        Ok(diff) => Ok(ApiResponse::success(diff)),
+        Err(e) => Ok(error_response(
+            &format!("Failed to diff configs: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    }
+}
+
+async fn rollback_config_version(
+    version: i32,
     handle: StateHandle,
 ) -> Result<impl Reply, warp::Rejection> {
-    let deleted = handle
-        .mutate(move |state| Box::pin(async move { state.delete_dashboard_layout(id) }))
-        .await
-        .unwrap_or(false);
+    if let Some(reply) = config_versions_unavailable() {
+        return Ok(reply);
+    }
 
-    if !deleted {
-        return Ok(not_found("Dashboard layout"));
+    let target = match load_config_version(version).await {
+        Ok(target) => target.config,
+        Err(reply) => return Ok(reply),
+    };
+
+    // Holding the recorder keeps automatic versions from being recorded
+    // while the config is replaced
+    let mut recorder = config_version_recorder().await;
+    let current = (*handle.snapshot.load().runtime_config).clone();
+    let target = with_current_images(target, &current);
+    if let Err(e) = recorder.record(&current, "Before rollback").await {
+        warn!("Failed to save config version before rollback: {e}");
     }
 
-    if let Err(e) = config_queries::db_delete_dashboard_layout(id).await {
-        warn!("Failed to persist dashboard layout deletion: {e}");
+    let diff = match diff_configs(&current, &target) {
+        Ok(diff) => diff,
+        Err(e) => {
+            return Ok(error_response(
+                &format!("Failed to diff configs: {e}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    };
+
+    // Persist first, so that a failed rollback leaves both the database and
+    // the running config as they were
+    if let Err(e) = config_queries::db_replace_config(&current, &target).await {
+        return Ok(error_response(
+            &format!("Failed to persist config version {version}: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
     }
 
-    Ok(ApiResponse::success(()))
+    if let Err(e) = apply_runtime_config_snapshot(&handle, target.clone()).await {
+        if let Err(e) = config_queries::db_replace_config(&target, &current).await {
+            warn!("Failed to restore config after failed rollback: {e}");
+        }
+        return Ok(error_response(
+            &format!("Failed to apply config version {version}: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+
+    let recorded = match recorder
+        .record(&target, &format!("Rolled back to version {version}"))
+        .await
+    {
+        Ok(recorded) => recorded,
+        Err(e) => {
+            warn!("Failed to save rolled back config version: {e}");
+            None
+        }
+    };
+
+    Ok(ApiResponse::success(RollbackResult {
+        version: recorded,
+        diff,
+    }))
 }
 
-async fn get_dashboard_widgets(
-    layout_id: i32,
+// ============================================================================
+// Validation
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+struct ValidationIssue {
+    /// JSON pointer into the validated payload.
+    pointer: String,
+    message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ValidationReport {
+    valid: bool,
+    errors: Vec<ValidationIssue>,
+    /// Problems that don't prevent the config from being applied, e.g.
+    /// devices that haven't been discovered.
+    warnings: Vec<ValidationIssue>,
+}
+
+fn validate_routes(
+    snapshot: &SnapshotHandle,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("validate")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_snapshot(snapshot))
+        .and_then(validate_config)
+}
+
+async fn validate_config(
+    payload: serde_json::Value,
     snapshot: SnapshotHandle,
 ) -> Result<impl Reply, warp::Rejection> {
     let snap = snapshot.load();
-    Ok(ApiResponse::success(
-        snap.runtime_config
-            .dashboard_widgets
-            .iter()
-            .filter(|widget| widget.layout_id == layout_id)
-            .cloned()
-            .collect::<Vec<_>>(),
-    ))
+
+    let report = match serde_json::from_value::<ConfigExport>(payload) {
+        Ok(config) => {
+            ConfigValidator::new(&config, &snap.devices, &snap.runtime_config.integrations).run()
+        }
+        Err(e) => ValidationReport {
+            valid: false,
+            errors: vec![ValidationIssue {
+                pointer: String::new(),
+                message: format!("Invalid config: {e}"),
+            }],
+            warnings: Vec::new(),
+        },
+    };
+
+    Ok(ApiResponse::success(report))
 }
 
-async fn upsert_dashboard_widget(
-    widget: DashboardWidgetRow,
-    handle: StateHandle,
-) -> Result<impl Reply, warp::Rejection> {
-    let widget_for_state = widget.clone();
-    let local_widget = handle
-        .mutate(move |state| {
-            Box::pin(async move { state.upsert_dashboard_widget(widget_for_state) })
-        })
-        .await
-        .unwrap_or_else(|_| widget.clone());
+/// Errors that would prevent `config` from being applied, as JSON pointers
+/// and messages. Warnings are left out.
+pub(crate) fn config_validation_errors(
+    config: &ConfigExport,
+    snapshot: &RuntimeSnapshot,
+) -> Vec<(String, String)> {
+    ConfigValidator::new(
+        config,
+        &snapshot.devices,
+        &snapshot.runtime_config.integrations,
+    )
+    .run()
+    .errors
+    .into_iter()
+    .map(|issue| (issue.pointer, issue.message))
+    .collect()
+}
+
+/// Escapes a reference token of a JSON pointer.
+fn pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Runs every check of a config without applying it.
+struct ConfigValidator<'a> {
+    config: &'a ConfigExport,
+    devices: &'a DevicesState,
+    /// Integrations of the running config, whose devices should be known.
+    loaded_integrations: HashSet<&'a str>,
+    integration_ids: HashSet<&'a str>,
+    group_ids: HashSet<&'a str>,
+    scene_ids: HashSet<&'a str>,
+    routine_ids: HashSet<&'a str>,
+    scripts: ScriptEngine,
+    report: ValidationReport,
+}
+
+impl<'a> ConfigValidator<'a> {
+    fn new(
+        config: &'a ConfigExport,
+        devices: &'a DevicesState,
+        loaded_integrations: &'a [IntegrationRow],
+    ) -> Self {
+        Self {
+            config,
+            devices,
+            loaded_integrations: loaded_integrations.iter().map(|i| i.id.as_str()).collect(),
+            integration_ids: config.integrations.iter().map(|i| i.id.as_str()).collect(),
+            group_ids: config.groups.iter().map(|g| g.id.as_str()).collect(),
+            scene_ids: config.scenes.iter().map(|s| s.id.as_str()).collect(),
+            routine_ids: config.routines.iter().map(|r| r.id.as_str()).collect(),
+            scripts: ScriptEngine::new(),
+            report: ValidationReport::default(),
+        }
+    }
+
+    fn run(mut self) -> ValidationReport {
+        let config = self.config;
+        self.check_duplicate_ids("integrations", config.integrations.iter().map(|i| &i.id));
+        self.check_duplicate_ids("groups", config.groups.iter().map(|g| &g.id));
+        self.check_duplicate_ids("scenes", config.scenes.iter().map(|s| &s.id));
+        self.check_duplicate_ids("routines", config.routines.iter().map(|r| &r.id));
+
+        self.check_integrations();
+        self.check_groups();
+        self.check_scenes();
+        self.check_routines();
+
+        self.report.valid = self.report.errors.is_empty();
+        self.report
+    }
+
+    fn error(&mut self, pointer: impl Into<String>, message: impl Into<String>) {
+        self.report.errors.push(ValidationIssue {
+            pointer: pointer.into(),
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, pointer: impl Into<String>, message: impl Into<String>) {
+        self.report.warnings.push(ValidationIssue {
+            pointer: pointer.into(),
+            message: message.into(),
+        });
+    }
+
+    fn check_duplicate_ids<'b>(&mut self, section: &str, ids: impl Iterator<Item = &'b String>) {
+        let mut seen = HashSet::new();
+        for (index, id) in ids.enumerate() {
+            if !seen.insert(id) {
+                self.error(
+                    format!("/{section}/{index}/id"),
+                    format!("Duplicate id '{id}' in {section}"),
+                );
+            }
+        }
+    }
+
+    fn check_integrations(&mut self) {
+        let config = self.config;
+        for (index, integration) in config.integrations.iter().enumerate() {
+            let id = IntegrationId::from(integration.id.clone());
+            let Err(e) = validate_integration_config(&integration.plugin, &id, &integration.config)
+            else {
+                continue;
+            };
+
+            let message = format!("Invalid {} integration config: {e:#}", integration.plugin);
+            let pointer = format!("/integrations/{index}/config");
+            // Disabled integrations aren't loaded
+            if integration.enabled {
+                self.error(pointer, message);
+            } else {
+                self.warning(pointer, message);
+            }
+        }
+    }
+
+    fn check_groups(&mut self) {
+        let config = self.config;
+        let mut links = BTreeMap::new();
+
+        for (index, group) in config.groups.iter().enumerate() {
+            let pointer = format!("/groups/{index}");
+
+            for (device_index, device) in group.devices.iter().enumerate() {
+                self.check_device_ref(
+                    &format!("{pointer}/devices/{device_index}"),
+                    &device.integration_id,
+                    &device.device_id,
+                );
+            }
+
+            for (link_index, linked_group) in group.linked_groups.iter().enumerate() {
+                self.check_ref(
+                    &format!("{pointer}/linked_groups/{link_index}"),
+                    "group",
+                    linked_group,
+                );
+            }
+
+            if let Some(occupancy) = &group.occupancy {
+                if let Ok(occupancy) = serde_json::to_value(occupancy) {
+                    self.check_refs(&format!("{pointer}/occupancy"), &occupancy);
+                }
+            }
+
+            links.insert(group.id.as_str(), group.linked_groups.iter().collect());
+        }
+
+        for cycle in find_link_cycles(&links) {
+            self.error(
+                format!("/groups/{}/linked_groups", self.group_index(cycle[0])),
+                format!("Group links form a cycle: {}", cycle.join(" -> ")),
+            );
+        }
+    }
+
+    fn check_scenes(&mut self) {
+        let config = self.config;
+        let mut links = BTreeMap::new();
+
+        for (index, scene) in config.scenes.iter().enumerate() {
+            let pointer = format!("/scenes/{index}");
+            let mut linked_scenes = Vec::new();
+
+            for (device_key, value) in scene.device_states.iter().collect::<BTreeMap<_, _>>() {
+                let pointer = format!("{pointer}/device_states/{}", pointer_token(device_key));
+                self.check_device_key(&pointer, device_key);
+                linked_scenes.extend(self.check_scene_device_config(&pointer, value));
+            }
+
+            for (group_id, value) in scene.group_states.iter().collect::<BTreeMap<_, _>>() {
+                let pointer = format!("{pointer}/group_states/{}", pointer_token(group_id));
+                self.check_ref(&pointer, "group", group_id);
+                linked_scenes.extend(self.check_scene_device_config(&pointer, value));
+            }
+
+            if let Some(script) = &scene.script {
+                if let Err(e) = self.scripts.check_scene_script_syntax(script) {
+                    self.error(format!("{pointer}/script"), format!("{e}"));
+                }
+            }
 
-    match config_queries::db_upsert_dashboard_widget(&widget).await {
-        Ok(id) => Ok(ApiResponse::success(DashboardWidgetRow {
-            id,
-            ..local_widget
-        })),
-        Err(e) => {
-            warn!("Failed to persist dashboard widget: {e}");
-            Ok(ApiResponse::success(local_widget))
+            links.insert(scene.id.as_str(), linked_scenes);
+        }
+
+        for cycle in find_link_cycles(&links) {
+            let index = config
+                .scenes
+                .iter()
+                .position(|scene| scene.id == cycle[0])
+                .unwrap_or_default();
+            self.error(
+                format!("/scenes/{index}"),
+                format!("Scene links form a cycle: {}", cycle.join(" -> ")),
+            );
         }
     }
-}
 
-async fn delete_dashboard_widget(
-    id: i32,
-    handle: StateHandle,
-) -> Result<impl Reply, warp::Rejection> {
-    let deleted = handle
-        .mutate(move |state| Box::pin(async move { state.delete_dashboard_widget(id) }))
-        .await
-        .unwrap_or(false);
+    /// Returns the scene linked to by the config, if any.
+    fn check_scene_device_config(
+        &mut self,
+        pointer: &str,
+        value: &'a serde_json::Value,
+    ) -> Option<&'a String> {
+        self.check_refs(pointer, value);
 
-    if !deleted {
-        return Ok(not_found("Dashboard widget"));
+        match serde_json::from_value::<SceneDeviceConfig>(value.clone()) {
+            Ok(SceneDeviceConfig::SceneLink(_)) => value.get("scene_id").and_then(|id| match id {
+                serde_json::Value::String(id) => Some(id),
+                _ => None,
+            }),
+            Ok(_) => None,
+            Err(e) => {
+                self.error(pointer, format!("Invalid scene device config: {e}"));
+                None
+            }
+        }
     }
 
-    if let Err(e) = config_queries::db_delete_dashboard_widget(id).await {
-        warn!("Failed to persist dashboard widget deletion: {e}");
+    fn check_routines(&mut self) {
+        let config = self.config;
+        for (index, routine) in config.routines.iter().enumerate() {
+            let pointer = format!("/routines/{index}");
+
+            if let Err(error) = validate_routine_rate_limits(routine) {
+                self.error(pointer.clone(), error);
+            }
+
+            if let Err(e) = serde_json::from_value::<Rules>(routine.rules.clone()) {
+                self.error(
+                    format!("{pointer}/rules"),
+                    format!("Invalid routine rules: {e}"),
+                );
+            }
+            self.check_refs(&format!("{pointer}/rules"), &routine.rules);
+
+            let serde_json::Value::Array(actions) = &routine.actions else {
+                self.error(
+                    format!("{pointer}/actions"),
+                    "Routine actions must be an array",
+                );
+                continue;
+            };
+
+            for (action_index, value) in actions.iter().enumerate() {
+                let pointer = format!("{pointer}/actions/{action_index}");
+                let result = serde_json::from_value::<Action>(value.clone())
+                    .map_err(|e| format!("Invalid routine action: {e}"))
+                    .and_then(|action| validate_routine_action(&action, &config.integrations));
+
+                if let Err(error) = result {
+                    self.error(pointer.clone(), error);
+                }
+                self.check_refs(&pointer, value);
+            }
+        }
     }
 
-    Ok(ApiResponse::success(()))
-}
+    /// Checks references to other config entities and devices, and the
+    /// syntax of rule scripts, anywhere in `value`.
+    fn check_refs(&mut self, pointer: &str, value: &serde_json::Value) {
+        use serde_json::Value;
 
-// ============================================================================
-// Export / Import
-// ============================================================================
+        match value {
+            Value::Object(map) => {
+                if let (Some(Value::String(integration_id)), Some(Value::String(device_id))) =
+                    (map.get("integration_id"), map.get("device_id"))
+                {
+                    self.check_device_ref(pointer, integration_id, device_id);
+                }
 
-#[derive(Deserialize)]
-struct ImportQuery {
-    #[serde(default)]
-    save_version: bool,
-}
+                for (key, value) in map {
+                    let pointer = format!("{pointer}/{}", pointer_token(key));
 
-fn export_import_routes(
-    snapshot: &SnapshotHandle,
-    handle: &StateHandle,
-) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    let export = warp::path("export")
-        .and(warp::path::end())
-        .and(warp::get())
-        .and(with_snapshot(snapshot))
-        .and_then(export_config);
+                    match (key.as_str(), value) {
+                        ("scene_id" | "scene", Value::String(id)) => {
+                            self.check_ref(&pointer, "scene", id)
+                        }
+                        ("group_id" | "mirror_from_group", Value::String(id)) => {
+                            self.check_ref(&pointer, "group", id)
+                        }
+                        ("routine_id", Value::String(id)) => {
+                            self.check_ref(&pointer, "routine", id)
+                        }
+                        ("device_key" | "rollout_source_device_key", Value::String(key)) => {
+                            self.check_device_key(&pointer, key)
+                        }
+                        ("script", Value::String(script)) => {
+                            if let Err(e) = self.scripts.check_syntax(script) {
+                                self.error(pointer, format!("{e}"));
+                            }
+                        }
+                        (
+                            "device_keys" | "group_keys" | "motion_sensors" | "door_sensors",
+                            Value::Array(values),
+                        ) => {
+                            for (index, value) in values.iter().enumerate() {
+                                let Value::String(id) = value else {
+                                    continue;
+                                };
+                                let pointer = format!("{pointer}/{index}");
+
+                                if key == "group_keys" {
+                                    self.check_ref(&pointer, "group", id);
+                                } else {
+                                    self.check_device_key(&pointer, id);
+                                }
+                            }
+                        }
+                        _ => self.check_refs(&pointer, value),
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    self.check_refs(&format!("{pointer}/{index}"), value);
+                }
+            }
+            _ => {}
+        }
+    }
 
-    let import = warp::path("import")
-        .and(warp::path::end())
-        .and(warp::post())
-        .and(warp::query::<ImportQuery>())
-        .and(warp::body::json())
-        .and(with_handle(handle))
-        .and_then(import_config);
+    fn check_ref(&mut self, pointer: &str, kind: &str, id: &str) {
+        let ids = match kind {
+            "group" => &self.group_ids,
+            "scene" => &self.scene_ids,
+            _ => &self.routine_ids,
+        };
 
-    export.or(import)
-}
+        if !ids.contains(id) {
+            self.error(pointer, format!("Unknown {kind} '{id}'"));
+        }
+    }
 
-async fn export_config(snapshot: SnapshotHandle) -> Result<impl Reply, warp::Rejection> {
-    let snap = snapshot.load();
-    Ok(ApiResponse::success((*snap.runtime_config).clone()))
-}
+    fn check_device_key(&mut self, pointer: &str, device_key: &str) {
+        match device_key.split_once('/') {
+            Some((integration_id, device_id)) => {
+                self.check_device_ref(pointer, integration_id, device_id)
+            }
+            None => self.error(pointer, format!("Invalid device key '{device_key}'")),
+        }
+    }
 
-async fn import_config(
-    query: ImportQuery,
-    config: ConfigExport,
-    handle: StateHandle,
-) -> Result<impl Reply, warp::Rejection> {
-    // Optionally save version before import
-    if query.save_version {
-        if let Err(e) = config_queries::db_save_config_version(&config, Some("Before import")).await
+    fn check_device_ref(&mut self, pointer: &str, integration_id: &str, device_id: &str) {
+        if !self.integration_ids.contains(integration_id) {
+            self.error(
+                pointer,
+                format!("Unknown integration '{integration_id}' of device '{device_id}'"),
+            );
+            return;
+        }
+
+        // Devices of integrations that aren't running yet can't be known
+        let device_key = DeviceKey::new(
+            IntegrationId::from(integration_id.to_string()),
+            DeviceId::new(device_id),
+        );
+        if self.loaded_integrations.contains(integration_id)
+            && !self.devices.0.contains_key(&device_key)
         {
-            warn!("Failed to save config version before import: {e}");
+            self.warning(
+                pointer,
+                format!("Device '{device_key}' has not been discovered"),
+            );
         }
     }
 
-    if let Err(e) = apply_runtime_config_snapshot(&handle, config.clone()).await {
-        warn!("Failed to apply imported config to runtime state: {e}");
+    fn group_index(&self, group_id: &str) -> usize {
+        self.config
+            .groups
+            .iter()
+            .position(|group| group.id == group_id)
+            .unwrap_or_default()
+    }
+}
+
+/// Finds cycles in links between ids. Cycles start from their smallest id and
+/// end where they started.
+fn find_link_cycles<'a>(links: &BTreeMap<&'a str, Vec<&'a String>>) -> Vec<Vec<&'a str>> {
+    fn visit<'a>(
+        id: &'a str,
+        links: &BTreeMap<&'a str, Vec<&'a String>>,
+        path: &mut Vec<&'a str>,
+        visited: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<&'a str>>,
+    ) {
+        if let Some(start) = path.iter().position(|other| *other == id) {
+            let mut cycle = path[start..].to_vec();
+            let smallest = cycle
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, id)| **id)
+                .map(|(index, _)| index)
+                .unwrap_or_default();
+            cycle.rotate_left(smallest);
+            cycle.push(cycle[0]);
+
+            if !cycles.contains(&cycle) {
+                cycles.push(cycle);
+            }
+            return;
+        }
+
+        if !visited.insert(id) {
+            return;
+        }
+
+        path.push(id);
+        for linked in links.get(id).into_iter().flatten().copied() {
+            visit(linked, links, path, visited, cycles);
+        }
+        path.pop();
     }
 
-    if let Err(e) = config_queries::db_import_config(&config).await {
-        warn!("Failed to persist imported config: {e}");
+    let mut cycles = Vec::new();
+    let mut visited = HashSet::new();
+    for id in links.keys() {
+        visit(id, links, &mut Vec::new(), &mut visited, &mut cycles);
     }
 
-    Ok(ApiResponse::success(()))
+    cycles
 }
 
 // ============================================================================
@@ -3360,6 +5488,7 @@ pub fn parse_toml_config(toml_str: &str) -> Result<MigratePreviewResult, String>
     // Convert core config
     let core = CoreConfigRow {
         warmup_time_seconds: config.core.and_then(|c| c.warmup_time_seconds).unwrap_or(1) as i32,
+        ..Default::default()
     };
 
     // Convert integrations
@@ -3412,6 +5541,7 @@ pub fn parse_toml_config(toml_str: &str) -> Result<MigratePreviewResult, String>
                 hidden: group.hidden.unwrap_or(false),
                 devices,
                 linked_groups,
+                occupancy: None,
             }
         })
         .collect();
@@ -3475,6 +5605,8 @@ pub fn parse_toml_config(toml_str: &str) -> Result<MigratePreviewResult, String>
             enabled: true,
             rules: routine.rules.unwrap_or(serde_json::Value::Array(vec![])),
             actions: routine.actions.unwrap_or(serde_json::Value::Array(vec![])),
+            cooldown_ms: None,
+            debounce_ms: None,
         })
         .collect();
 
@@ -4060,6 +6192,7 @@ devices = [
                     device_id: "Kitchen light".to_string(),
                 }],
                 linked_groups: Vec::new(),
+                occupancy: None,
             }],
             scenes: vec![SceneRow {
                 id: "evening".to_string(),
@@ -4089,9 +6222,12 @@ devices = [
                     }
                 ]),
                 actions: serde_json::json!([]),
+                cooldown_ms: None,
+                debounce_ms: None,
             }],
             core: CoreConfigRow {
                 warmup_time_seconds: 1,
+                ..Default::default()
             },
         };
 
@@ -4141,6 +6277,7 @@ devices = [
                     device_id: "Missing group light".to_string(),
                 }],
                 linked_groups: Vec::new(),
+                occupancy: None,
             }],
             scenes: vec![SceneRow {
                 id: "evening".to_string(),
@@ -4170,9 +6307,12 @@ devices = [
                     }
                 ]),
                 actions: serde_json::json!([]),
+                cooldown_ms: None,
+                debounce_ms: None,
             }],
             core: CoreConfigRow {
                 warmup_time_seconds: 1,
+                ..Default::default()
             },
         };
 
@@ -4210,6 +6350,7 @@ devices = [
                     device_id: "Missing group light".to_string(),
                 }],
                 linked_groups: Vec::new(),
+                occupancy: None,
             }],
             scenes: vec![SceneRow {
                 id: "evening".to_string(),
@@ -4239,9 +6380,12 @@ devices = [
                     }
                 ]),
                 actions: serde_json::json!([]),
+                cooldown_ms: None,
+                debounce_ms: None,
             }],
             core: CoreConfigRow {
                 warmup_time_seconds: 1,
+                ..Default::default()
             },
         };
 
@@ -4273,6 +6417,7 @@ devices = [
             version: 1,
             core: CoreConfigRow {
                 warmup_time_seconds: 5,
+                ..Default::default()
             },
             integrations: vec![IntegrationRow {
                 id: "zigbee2mqtt".to_string(),
@@ -4304,11 +6449,13 @@ devices = [
                     device_id: "kitchen-light".to_string(),
                 }],
                 linked_groups: Vec::new(),
+                occupancy: None,
             }],
             scenes: Vec::new(),
             routines: Vec::new(),
             core: CoreConfigRow {
                 warmup_time_seconds: 9,
+                ..Default::default()
             },
         };
 
@@ -4355,9 +6502,12 @@ devices = [
                     }
                 ]),
                 actions: serde_json::json!([]),
+                cooldown_ms: None,
+                debounce_ms: None,
             }],
             core: CoreConfigRow {
                 warmup_time_seconds: 1,
+                ..Default::default()
             },
         };
 
@@ -4390,9 +6540,12 @@ devices = [
                     }
                 ]),
                 actions: serde_json::json!([]),
+                cooldown_ms: None,
+                debounce_ms: None,
             }],
             core: CoreConfigRow {
                 warmup_time_seconds: 1,
+                ..Default::default()
             },
         };
 
@@ -4438,4 +6591,451 @@ devices = [
             })
         );
     }
+
+    #[test]
+    fn validate_routine_actions_checks_custom_action_payloads() {
+        let integrations = vec![IntegrationRow {
+            id: "wled".to_string(),
+            plugin: "wled".to_string(),
+            config: serde_json::json!({ "host": "192.168.1.20" }),
+            enabled: true,
+        }];
+        let custom = |integration_id: &str, payload: serde_json::Value| {
+            serde_json::json!({
+                "action": "Custom",
+                "integration_id": integration_id,
+                "payload": payload
+            })
+        };
+
+        let valid = custom(
+            "wled",
+            serde_json::json!({ "action": "preset", "preset": 2 }),
+        );
+        assert!(validate_routine_actions(&serde_json::json!([valid]), &integrations).is_ok());
+
+        // Legacy string payloads are still accepted
+        let legacy = custom(
+            "wled",
+            serde_json::json!(r#"{"action":"preset","preset":2}"#),
+        );
+        assert!(validate_routine_actions(&serde_json::json!([legacy]), &integrations).is_ok());
+
+        let typo = custom(
+            "wled",
+            serde_json::json!({ "action": "prest", "preset": 2 }),
+        );
+        let nested = serde_json::json!([{ "action": "Sequence", "actions": [typo] }]);
+        let error = validate_routine_actions(&nested, &integrations).unwrap_err();
+        assert!(error.contains("integration wled"), "{error}");
+
+        let unknown = custom(
+            "wlde",
+            serde_json::json!({ "action": "preset", "preset": 2 }),
+        );
+        assert!(validate_routine_actions(&serde_json::json!([unknown]), &integrations).is_err());
+    }
+
+    #[test]
+    fn validator_reports_errors_with_pointers() {
+        let config: ConfigExport = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "core": {},
+            "integrations": [
+                { "id": "wled", "plugin": "wled", "config": { "host": "10.0.0.2" }, "enabled": true },
+                { "id": "mqtt", "plugin": "mqtt", "config": { "host": "broker" }, "enabled": true }
+            ],
+            "groups": [
+                { "id": "a", "name": "A", "hidden": false, "devices": [], "linked_groups": ["b"] },
+                { "id": "b", "name": "B", "hidden": false, "devices": [], "linked_groups": ["a"] }
+            ],
+            "scenes": [
+                {
+                    "id": "evening",
+                    "name": "Evening",
+                    "hidden": false,
+                    "script": "defineSceneScript(() => ({",
+                    "device_states": {
+                        "wled/0": { "scene_id": "evening" },
+                        "wled/1": { "power": true },
+                        "hue/1": { "power": true }
+                    },
+                    "group_states": {}
+                }
+            ],
+            "routines": [
+                {
+                    "id": "night",
+                    "name": "Night",
+                    "enabled": true,
+                    "rules": [{ "group_id": "c", "power": true }],
+                    "actions": [
+                        { "action": "ActivateScene", "scene_id": "morning" },
+                        { "action": "Custom", "integration_id": "wled", "payload": { "action": "prest" } }
+                    ]
+                }
+            ],
+            "floorplan": null,
+            "dashboard_layouts": [],
+            "dashboard_widgets": []
+        }))
+        .unwrap();
+        let devices = migration_test_devices(vec![migration_test_device("wled", "0", "Desk")]);
+
+        let report = ConfigValidator::new(&config, &devices, &config.integrations).run();
+        let errors = report
+            .errors
+            .iter()
+            .map(|issue| issue.pointer.as_str())
+            .collect::<Vec<_>>();
+
+        assert!(!report.valid);
+        assert_eq!(
+            errors,
+            vec![
+                "/integrations/1/config",
+                "/groups/0/linked_groups",
+                "/scenes/0/device_states/hue~11",
+                "/scenes/0/script",
+                "/scenes/0",
+                "/routines/0/rules/0/group_id",
+                "/routines/0/actions/0/scene_id",
+                "/routines/0/actions/1",
+            ]
+        );
+        assert_eq!(
+            report.errors[4].message,
+            "Scene links form a cycle: evening -> evening"
+        );
+        assert_eq!(
+            report
+                .warnings
+                .iter()
+                .map(|issue| issue.pointer.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/scenes/0/device_states/wled~11"]
+        );
+    }
+
+    #[test]
+    fn rewrites_references_to_deleted_and_replaced_entities() {
+        let config: ConfigExport = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "core": {},
+            "integrations": [],
+            "groups": [
+                { "id": "hall", "name": "Hall", "hidden": false, "devices": [], "linked_groups": [] },
+                { "id": "kitchen", "name": "Kitchen", "hidden": false, "devices": [], "linked_groups": ["hall"] }
+            ],
+            "scenes": [
+                {
+                    "id": "evening",
+                    "name": "Evening",
+                    "hidden": false,
+                    "script": null,
+                    "device_states": {},
+                    "group_states": { "hall": { "power": true } }
+                },
+                {
+                    "id": "night",
+                    "name": "Night",
+                    "hidden": false,
+                    "script": null,
+                    "device_states": { "hue/1": { "scene_id": "evening" } },
+                    "group_states": {}
+                }
+            ],
+            "routines": [
+                {
+                    "id": "dusk",
+                    "name": "Dusk",
+                    "enabled": true,
+                    "rules": [],
+                    "actions": [
+                        { "action": "ActivateScene", "scene_id": "evening", "group_keys": ["hall"] },
+                        {
+                            "action": "Sequence",
+                            "actions": [{ "action": "ActivateScene", "scene_id": "evening" }]
+                        }
+                    ]
+                },
+                {
+                    "id": "bedtime",
+                    "name": "Bedtime",
+                    "enabled": true,
+                    "rules": [{ "group_id": "hall", "scene": "evening" }],
+                    "actions": [{ "action": "ForceTriggerRoutine", "routine_id": "dusk" }]
+                },
+                {
+                    "id": "wake",
+                    "name": "Wake",
+                    "enabled": true,
+                    "rules": [{ "any": [{ "group_id": "hall", "scene": "evening" }, { "group_id": "kitchen", "power": true }] }],
+                    "actions": [
+                        { "action": "ForceTriggerRoutine", "routine_id": "bedtime" },
+                        { "action": "Dim", "group_keys": ["kitchen"] }
+                    ]
+                }
+            ],
+            "floorplan": null,
+            "dashboard_layouts": [],
+            "dashboard_widgets": []
+        }))
+        .unwrap();
+
+        let evening = ConfigEntityTarget::new(ConfigEntityKind::Scene, "evening".to_string());
+        let mut cascaded = config.clone();
+        let rewrite = rewrite_entity_config_references(&mut cascaded, &evening, None);
+        assert_eq!(
+            rewrite.dependents(),
+            ConfigDependents {
+                groups: vec![],
+                scenes: vec!["night".to_string()],
+                routines: vec![
+                    "dusk".to_string(),
+                    "wake".to_string(),
+                    "bedtime".to_string()
+                ],
+            }
+        );
+        assert!(cascaded.scenes[1].device_states.is_empty());
+        assert_eq!(cascaded.routines[0].actions, serde_json::json!([]));
+        // Bedtime would otherwise trigger regardless of the scene
+        assert_eq!(rewrite.deleted_routines, vec!["bedtime"]);
+        assert_eq!(cascaded.routines.len(), 2);
+        // Wake still triggers on one of its alternatives
+        assert_eq!(
+            cascaded.routines[1].rules[0]["any"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|rule| &rule["group_id"])
+                .collect::<Vec<_>>(),
+            vec!["kitchen"]
+        );
+        assert_eq!(
+            cascaded.routines[1].actions.as_array().unwrap().len(),
+            1,
+            "the trigger of bedtime is removed"
+        );
+
+        let hall = ConfigEntityTarget::new(ConfigEntityKind::Group, "hall".to_string());
+        let mut replaced = config.clone();
+        let rewrite = rewrite_entity_config_references(&mut replaced, &hall, Some("kitchen"));
+        assert_eq!(
+            rewrite.dependents(),
+            ConfigDependents {
+                groups: vec!["kitchen".to_string()],
+                scenes: vec!["evening".to_string()],
+                routines: vec![
+                    "dusk".to_string(),
+                    "bedtime".to_string(),
+                    "wake".to_string()
+                ],
+            }
+        );
+        // Kitchen would otherwise link to itself
+        assert!(replaced.groups[1].linked_groups.is_empty());
+        assert!(replaced.scenes[0].group_states.contains_key("kitchen"));
+        assert_eq!(
+            replaced.routines[0].actions[0]["group_keys"],
+            serde_json::json!(["kitchen"])
+        );
+        assert_eq!(
+            replaced.routines[1].rules[0]["group_id"],
+            serde_json::json!("kitchen")
+        );
+
+        assert_eq!(
+            describe_scene_dependents(&config, "night"),
+            None,
+            "nothing refers to the night scene"
+        );
+    }
+
+    #[test]
+    fn renames_group_across_config() {
+        let mut config: ConfigExport = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "core": {},
+            "integrations": [],
+            "groups": [
+                { "id": "hall", "name": "Hall", "hidden": false, "devices": [], "linked_groups": [] },
+                { "id": "upstairs", "name": "Upstairs", "hidden": false, "devices": [], "linked_groups": ["hall"] }
+            ],
+            "scenes": [],
+            "routines": [
+                {
+                    "id": "arrive",
+                    "name": "Arrive",
+                    "enabled": true,
+                    "rules": [{ "group_id": "hall", "power": false }],
+                    "actions": [{ "action": "Dim", "group_keys": ["hall"] }]
+                }
+            ],
+            "floorplan": null,
+            "floorplans": [
+                {
+                    "id": "default",
+                    "name": "Home",
+                    "image_data": null,
+                    "image_mime_type": null,
+                    "width": null,
+                    "height": null,
+                    "grid_data": "{\"devices\":[],\"groups\":{\"hall\":[{\"x\":1,\"y\":2}]}}"
+                }
+            ],
+            "group_positions": [
+                { "group_id": "hall", "x": 1.0, "y": 2.0, "width": 3.0, "height": 4.0, "z_index": 0 }
+            ],
+            "dashboard_layouts": [{ "id": 1, "name": "Home", "is_default": true }],
+            "dashboard_widgets": [
+                {
+                    "id": 1,
+                    "layout_id": 1,
+                    "widget_type": "controls",
+                    "config": { "title": "Controls", "options": { "groupId": "hall" } },
+                    "grid_x": 0,
+                    "grid_y": 0,
+                    "grid_w": 2,
+                    "grid_h": 2,
+                    "sort_order": 0
+                }
+            ]
+        }))
+        .unwrap();
+
+        let hall = ConfigEntityTarget::new(ConfigEntityKind::Group, "hall".to_string());
+        let rewrite = rename_entity_in_config(&mut config, &hall, "entrance");
+
+        assert_eq!(
+            rewrite.dependents(),
+            ConfigDependents {
+                groups: vec!["entrance".to_string(), "upstairs".to_string()],
+                scenes: vec![],
+                routines: vec!["arrive".to_string()],
+            }
+        );
+        assert_eq!(config.groups[0].id, "entrance");
+        assert_eq!(config.groups[1].linked_groups, vec!["entrance"]);
+        assert_eq!(
+            config.routines[0].rules[0]["group_id"],
+            serde_json::json!("entrance")
+        );
+        assert_eq!(
+            config.routines[0].actions[0]["group_keys"],
+            serde_json::json!(["entrance"])
+        );
+        assert_eq!(config.group_positions[0].group_id, "entrance");
+        assert!(rewrite.moved_group_position.is_some());
+        let grid_data: serde_json::Value =
+            serde_json::from_str(config.floorplans[0].grid_data.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            grid_data["groups"],
+            serde_json::json!({ "entrance": [{ "x": 1, "y": 2 }] })
+        );
+        assert_eq!(
+            config.dashboard_widgets[0].config["options"]["groupId"],
+            serde_json::json!("entrance")
+        );
+    }
+
+    #[test]
+    fn renames_self_referencing_routine_once() {
+        let mut config: ConfigExport = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "core": {},
+            "integrations": [],
+            "groups": [],
+            "scenes": [],
+            "routines": [
+                {
+                    "id": "loop",
+                    "name": "Loop",
+                    "enabled": true,
+                    "rules": [],
+                    "actions": [{ "action": "ForceTriggerRoutine", "routine_id": "loop" }]
+                }
+            ],
+            "floorplan": null,
+            "dashboard_layouts": [],
+            "dashboard_widgets": []
+        }))
+        .unwrap();
+
+        let source = ConfigEntityTarget::new(ConfigEntityKind::Routine, "loop".to_string());
+        let rewrite = rename_entity_in_config(&mut config, &source, "repeat");
+
+        assert_eq!(
+            rewrite
+                .changed_routines
+                .iter()
+                .map(|routine| routine.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["repeat"]
+        );
+        assert_eq!(
+            rewrite.changed_routines[0].actions[0]["routine_id"],
+            serde_json::json!("repeat")
+        );
+        assert_eq!(config.routines.len(), 1);
+        assert_eq!(config.routines[0].id, "repeat");
+    }
+
+    #[test]
+    fn removes_sequences_waiting_on_removed_rules() {
+        let wait = serde_json::json!({
+            "action": "WaitUntil",
+            "rule": { "integration_id": "hue", "device_id": "1", "power": true }
+        });
+        let activate = serde_json::json!({ "action": "ActivateScene", "scene_id": "evening" });
+        let mut routine: RoutineRow = serde_json::from_value(serde_json::json!({
+            "id": "arrive",
+            "name": "Arrive",
+            "enabled": true,
+            "rules": [],
+            "actions": [
+                { "action": "Sequence", "actions": [wait, activate] },
+                activate
+            ]
+        }))
+        .unwrap();
+        let source = DeviceConfigTarget::parse("hue/1").unwrap();
+
+        // The scene would otherwise be activated without waiting
+        assert!(rewrite_routine_device_refs(
+            &mut routine,
+            &source,
+            None,
+            None
+        ));
+        assert_eq!(routine.actions.as_array().unwrap().len(), 1);
+        assert_eq!(routine.actions[0]["action"], "ActivateScene");
+
+        routine.actions = serde_json::json!([wait, activate]);
+        assert!(rewrite_routine_device_refs(
+            &mut routine,
+            &source,
+            None,
+            None
+        ));
+        assert_eq!(routine.actions, serde_json::json!([]));
+
+        // Conditions aren't narrowed down to their remaining rules
+        routine.actions = serde_json::json!([{
+            "action": "If",
+            "condition": [
+                { "integration_id": "hue", "device_id": "1", "power": true },
+                { "integration_id": "hue", "device_id": "2", "power": true }
+            ],
+            "then": [activate]
+        }]);
+        assert!(rewrite_routine_device_refs(
+            &mut routine,
+            &source,
+            None,
+            None
+        ));
+        assert_eq!(routine.actions, serde_json::json!([]));
+    }
 }
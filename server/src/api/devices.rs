@@ -1,13 +1,18 @@
 use std::convert::Infallible;
 
+use jsonptr::PointerBuf;
 use percent_encoding::percent_decode_str;
 
+use crate::db::{
+    actions::{db_get_device_history, DeviceStateHistoryRow},
+    is_db_connected,
+};
 use crate::types::{
     color::ColorMode,
-    device::{Device, DeviceId},
+    device::{Device, DeviceData, DeviceId, DeviceKey, SensorDevice},
 };
 use serde::{Deserialize, Serialize};
-use warp::Filter;
+use warp::{http::StatusCode, Filter, Reply};
 
 use crate::core::snapshot::SnapshotHandle;
 use crate::core::state::StateHandle;
@@ -23,7 +28,11 @@ pub fn devices(
     snapshot: &SnapshotHandle,
     handle: &StateHandle,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    warp::path("devices").and(get_devices(snapshot).or(put_device(handle)))
+    warp::path("devices").and(
+        get_device_history()
+            .or(get_devices(snapshot))
+            .or(put_device(handle)),
+    )
 }
 
 #[derive(Serialize, Deserialize)]
@@ -98,3 +107,294 @@ async fn put_device_impl(
 
     Ok(warp::reply::json(&response))
 }
+
+/// Default time range for history queries when `from` is omitted.
+const DEFAULT_HISTORY_RANGE_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    /// Start of the range as unix epoch milliseconds, defaults to 24h before `to`.
+    from: Option<i64>,
+    /// End of the range as unix epoch milliseconds, defaults to now.
+    to: Option<i64>,
+    /// Downsample into buckets of this many milliseconds.
+    bucket_ms: Option<i64>,
+    /// JSON pointer into the raw device payload used to compute `value`.
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DeviceHistoryResponse {
+    device_key: DeviceKey,
+    from: i64,
+    to: i64,
+    bucket_ms: Option<i64>,
+    points: Vec<DeviceHistoryPoint>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct DeviceHistoryPoint {
+    /// Sample timestamp, or the bucket start when downsampling.
+    recorded_at_ms: i64,
+    /// Last state seen within the bucket.
+    state: DeviceData,
+    raw: Option<serde_json::Value>,
+    /// Numeric value of the sample, averaged over the bucket when downsampling.
+    value: Option<f64>,
+    samples: usize,
+}
+
+#[derive(Serialize)]
+struct HistoryErrorResponse {
+    error: String,
+}
+
+fn history_error(error: impl Into<String>, status: StatusCode) -> warp::reply::Response {
+    warp::reply::with_status(
+        warp::reply::json(&HistoryErrorResponse {
+            error: error.into(),
+        }),
+        status,
+    )
+    .into_response()
+}
+
+fn get_device_history(
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!(String / "history")
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .and_then(get_device_history_impl)
+}
+
+async fn get_device_history_impl(
+    device_key: String,
+    query: HistoryQuery,
+) -> Result<warp::reply::Response, Infallible> {
+    let decoded = percent_decode_str(&device_key).decode_utf8_lossy();
+    let Ok(device_key) = serde_json::from_value::<DeviceKey>(decoded.as_ref().into()) else {
+        return Ok(history_error(
+            "Device key must be of the form integration_id/device_id",
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+
+    let path = match query.path.as_deref().map(PointerBuf::parse).transpose() {
+        Ok(path) => path,
+        Err(error) => {
+            return Ok(history_error(
+                format!("Invalid JSON pointer in path: {error}"),
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    if query.bucket_ms.is_some_and(|bucket_ms| bucket_ms <= 0) {
+        return Ok(history_error(
+            "bucket_ms must be positive",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let to = query
+        .to
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    let from = query.from.unwrap_or(to - DEFAULT_HISTORY_RANGE_MS);
+    if from > to {
+        return Ok(history_error(
+            "from must not be later than to",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    if !is_db_connected() {
+        return Ok(history_error(
+            "Device history requires a database connection",
+            StatusCode::SERVICE_UNAVAILABLE,
+        ));
+    }
+
+    let rows = match db_get_device_history(&device_key, from, to).await {
+        Ok(rows) => rows,
+        Err(error) => {
+            warn!("Failed to query history for device {device_key}: {error}");
+            return Ok(history_error(
+                "Failed to query device history",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let points = match query.bucket_ms {
+        Some(bucket_ms) => downsample_history(rows, from, bucket_ms, path.as_deref()),
+        None => rows
+            .into_iter()
+            .map(|row| DeviceHistoryPoint {
+                recorded_at_ms: row.recorded_at_ms,
+                value: history_value(&row, path.as_deref()),
+                state: row.state,
+                raw: row.raw,
+                samples: 1,
+            })
+            .collect(),
+    };
+
+    Ok(warp::reply::json(&DeviceHistoryResponse {
+        device_key,
+        from,
+        to,
+        bucket_ms: query.bucket_ms,
+        points,
+    })
+    .into_response())
+}
+
+/// Extracts a numeric value from a history row: from the raw payload when a
+/// pointer is given, otherwise from the device state itself.
+fn history_value(row: &DeviceStateHistoryRow, path: Option<&jsonptr::Pointer>) -> Option<f64> {
+    if let Some(path) = path {
+        let raw = row.raw.as_ref()?;
+        let value = path.resolve(raw).ok()?;
+        return value
+            .as_f64()
+            .or_else(|| value.as_bool().map(|value| if value { 1.0 } else { 0.0 }));
+    }
+
+    match &row.state {
+        DeviceData::Sensor(SensorDevice::Number { value }) => Some(*value),
+        DeviceData::Sensor(SensorDevice::Boolean { value }) => Some(if *value { 1.0 } else { 0.0 }),
+        DeviceData::Sensor(_) => None,
+        DeviceData::Controllable(device) => Some(if device.state.power {
+            device
+                .state
+                .brightness
+                .map(|brightness| f64::from(*brightness))
+                .unwrap_or(1.0)
+        } else {
+            0.0
+        }),
+    }
+}
+
+/// Groups rows into fixed-size buckets starting at `from`. Each bucket keeps
+/// the last state seen in it and the mean of the numeric values.
+fn downsample_history(
+    rows: Vec<DeviceStateHistoryRow>,
+    from: i64,
+    bucket_ms: i64,
+    path: Option<&jsonptr::Pointer>,
+) -> Vec<DeviceHistoryPoint> {
+    let mut points: Vec<DeviceHistoryPoint> = Vec::new();
+    let mut value_sum = 0.0;
+    let mut value_count = 0usize;
+
+    for row in rows {
+        let bucket_start = from + (row.recorded_at_ms - from).div_euclid(bucket_ms) * bucket_ms;
+        let value = history_value(&row, path);
+
+        match points.last_mut() {
+            Some(point) if point.recorded_at_ms == bucket_start => {
+                point.state = row.state;
+                point.raw = row.raw;
+                point.samples += 1;
+            }
+            _ => {
+                value_sum = 0.0;
+                value_count = 0;
+                points.push(DeviceHistoryPoint {
+                    recorded_at_ms: bucket_start,
+                    state: row.state,
+                    raw: row.raw,
+                    value: None,
+                    samples: 1,
+                });
+            }
+        }
+
+        if let Some(value) = value {
+            value_sum += value;
+            value_count += 1;
+        }
+
+        if let Some(point) = points.last_mut() {
+            point.value = (value_count > 0).then(|| value_sum / value_count as f64);
+        }
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{downsample_history, DeviceHistoryPoint};
+    use crate::db::actions::DeviceStateHistoryRow;
+    use crate::types::device::{DeviceData, SensorDevice};
+    use jsonptr::PointerBuf;
+    use serde_json::json;
+
+    fn number_row(recorded_at_ms: i64, value: f64) -> DeviceStateHistoryRow {
+        DeviceStateHistoryRow {
+            recorded_at_ms,
+            state: DeviceData::Sensor(SensorDevice::Number { value }),
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn downsample_averages_values_and_keeps_last_state_per_bucket() {
+        let rows = vec![
+            number_row(1_000, 20.0),
+            number_row(1_500, 22.0),
+            number_row(2_100, 30.0),
+        ];
+
+        let points = downsample_history(rows, 1_000, 1_000, None);
+
+        assert_eq!(
+            points,
+            vec![
+                DeviceHistoryPoint {
+                    recorded_at_ms: 1_000,
+                    state: DeviceData::Sensor(SensorDevice::Number { value: 22.0 }),
+                    raw: None,
+                    value: Some(21.0),
+                    samples: 2,
+                },
+                DeviceHistoryPoint {
+                    recorded_at_ms: 2_000,
+                    state: DeviceData::Sensor(SensorDevice::Number { value: 30.0 }),
+                    raw: None,
+                    value: Some(30.0),
+                    samples: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn downsample_reads_values_from_raw_pointer() {
+        let rows = vec![
+            DeviceStateHistoryRow {
+                recorded_at_ms: 0,
+                state: DeviceData::Sensor(SensorDevice::Text {
+                    value: "ok".to_string(),
+                }),
+                raw: Some(json!({ "temperature": 19.5 })),
+            },
+            DeviceStateHistoryRow {
+                recorded_at_ms: 10,
+                state: DeviceData::Sensor(SensorDevice::Text {
+                    value: "ok".to_string(),
+                }),
+                raw: Some(json!({ "humidity": 40 })),
+            },
+        ];
+        let path = PointerBuf::from_tokens(["temperature"]);
+
+        let points = downsample_history(rows, 0, 60_000, Some(&*path));
+
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].samples, 2);
+        assert_eq!(points[0].value, Some(19.5));
+    }
+}
@@ -0,0 +1,271 @@
+//! Server-Sent Events alternative to the WebSocket state stream, for clients
+//! that can't hold a WebSocket connection.
+//!
+//! `GET /api/v1/events` first sends the full state as a
+//! `WebSocketResponse::State`, followed by `WebSocketResponse::Patch`
+//! messages. Query parameters work like a `WebSocketSubscription`, with
+//! lists given as comma separated values:
+//!
+//! `/api/v1/events?topics=devices,groups&groups=upstairs&feed=true`
+//!
+//! `GET /api/v1/events/poll` is a long-poll variant for clients that can't
+//! keep a connection open at all. It takes the same query parameters except
+//! `feed`, and replies with the full `WebSocketResponse::State` and an `ETag`
+//! identifying it. Passing that ETag back as `since` waits up to `wait_ms`
+//! (30 seconds by default, one minute at most) for the subscribed state to
+//! change before replying, or replies right away if it already changed since.
+//! It replies with `204 No Content` if nothing changed in time, and doesn't
+//! wait at all if `wait_ms` is 0:
+//!
+//! `/api/v1/events/poll?topics=devices&since=3f2a9c01d4e5b6a7`
+
+use std::{convert::Infallible, time::Duration};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use warp::{
+    http::{
+        header::{CONTENT_TYPE, ETAG},
+        StatusCode,
+    },
+    reply::Response,
+    Filter, Reply,
+};
+
+use crate::core::snapshot::SnapshotHandle;
+use crate::core::state::{send_state_ws_from_snapshot, state_ws_message};
+use crate::core::websockets::{next_user_id, WebSockets};
+use crate::types::websockets::WebSocketSubscription;
+
+use super::config::error_response;
+use super::with_snapshot;
+use super::ws::with_ws;
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    topics: Option<String>,
+    device_keys: Option<String>,
+    groups: Option<String>,
+    integration_ids: Option<String>,
+    #[serde(default)]
+    feed: bool,
+    /// Only used by the long-poll endpoint.
+    wait_ms: Option<u64>,
+    /// ETag of the state the long-poll client already has.
+    since: Option<String>,
+}
+
+impl EventsQuery {
+    fn subscription(&self) -> Result<WebSocketSubscription, String> {
+        Ok(WebSocketSubscription {
+            topics: parse_list("topics", &self.topics)?,
+            device_keys: parse_list("device_keys", &self.device_keys)?,
+            groups: parse_list("groups", &self.groups)?,
+            integration_ids: parse_list("integration_ids", &self.integration_ids)?,
+            feed: self.feed,
+        })
+    }
+}
+
+fn parse_list<T: DeserializeOwned>(
+    name: &str,
+    value: &Option<String>,
+) -> Result<Option<Vec<T>>, String> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            serde_json::from_value(serde_json::Value::String(item.to_string()))
+                .map_err(|e| format!("Invalid value '{item}' in {name}: {e}"))
+        })
+        .collect::<Result<Vec<T>, String>>()
+        // `?groups=` filters nothing, rather than everything
+        .map(|items| (!items.is_empty()).then_some(items))
+}
+
+/// Removes the client from the broadcast list once its stream is dropped.
+struct ConnectedClient {
+    id: usize,
+    ws: WebSockets,
+}
+
+impl Drop for ConnectedClient {
+    fn drop(&mut self) {
+        let ws = self.ws.clone();
+        let id = self.id;
+        tokio::spawn(async move { ws.user_disconnected(id).await });
+    }
+}
+
+pub fn events(
+    snapshot: &SnapshotHandle,
+    ws_handle: WebSockets,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let stream = warp::path("events")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<EventsQuery>())
+        .and(with_snapshot(snapshot))
+        .and(with_ws(ws_handle.clone()))
+        .and_then(events_stream);
+
+    let poll = warp::path!("events" / "poll")
+        .and(warp::get())
+        .and(warp::query::<EventsQuery>())
+        .and(with_snapshot(snapshot))
+        .and(with_ws(ws_handle))
+        .and_then(events_poll);
+
+    stream.or(poll)
+}
+
+async fn events_stream(
+    query: EventsQuery,
+    snapshot: SnapshotHandle,
+    ws_handle: WebSockets,
+) -> Result<Response, warp::Rejection> {
+    let subscription = match query.subscription() {
+        Ok(subscription) => subscription,
+        Err(e) => return Ok(error_response(&e, StatusCode::BAD_REQUEST).into_response()),
+    };
+
+    let id = next_user_id();
+    let (tx, rx) = mpsc::channel::<String>(100);
+
+    ws_handle.user_connected(id, tx).await;
+    ws_handle.set_subscription(id, subscription).await;
+    send_state_ws_from_snapshot(&snapshot, &ws_handle, Some(id)).await;
+
+    let client = ConnectedClient { id, ws: ws_handle };
+    let stream = futures::stream::unfold((rx, client), |(mut rx, client)| async move {
+        let message = rx.recv().await?;
+        let event = warp::sse::Event::default().data(message);
+
+        Some((Ok::<_, Infallible>(event), (rx, client)))
+    });
+
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(stream)).into_response())
+}
+
+const DEFAULT_POLL_WAIT: Duration = Duration::from_secs(30);
+const MAX_POLL_WAIT: Duration = Duration::from_secs(60);
+
+async fn events_poll(
+    query: EventsQuery,
+    snapshot: SnapshotHandle,
+    ws_handle: WebSockets,
+) -> Result<Response, warp::Rejection> {
+    let subscription = match query.subscription() {
+        // A single reply can't carry a feed of events
+        Ok(subscription) => WebSocketSubscription {
+            feed: false,
+            ..subscription
+        },
+        Err(e) => return Ok(error_response(&e, StatusCode::BAD_REQUEST).into_response()),
+    };
+    let wait = query
+        .wait_ms
+        .map_or(DEFAULT_POLL_WAIT, Duration::from_millis)
+        .min(MAX_POLL_WAIT);
+    let since = query.since.as_deref().map(|since| since.trim_matches('"'));
+
+    // Listen before reading the state, so that no change can slip in
+    // between. Patches are only sent when something the subscription
+    // includes changed, so the first one is enough to know that.
+    let (tx, mut rx) = mpsc::channel::<String>(100);
+    let client = ConnectedClient {
+        id: next_user_id(),
+        ws: ws_handle,
+    };
+    client.ws.user_connected(client.id, tx).await;
+    client
+        .ws
+        .set_subscription(client.id, subscription.clone())
+        .await;
+
+    let Some(mut message) = state_ws_message(&snapshot.load(), Some(&subscription)) else {
+        return Ok(state_serialization_failed());
+    };
+    let mut cursor = state_cursor(&message);
+
+    if since == Some(cursor.as_str()) {
+        if wait.is_zero() || tokio::time::timeout(wait, rx.recv()).await.is_err() {
+            return Ok(warp::reply::with_header(
+                StatusCode::NO_CONTENT,
+                ETAG,
+                format!("\"{cursor}\""),
+            )
+            .into_response());
+        }
+
+        let Some(changed) = state_ws_message(&snapshot.load(), Some(&subscription)) else {
+            return Ok(state_serialization_failed());
+        };
+        cursor = state_cursor(&changed);
+        message = changed;
+    }
+
+    let reply = warp::reply::with_header(message, CONTENT_TYPE, "application/json");
+    Ok(warp::reply::with_header(reply, ETAG, format!("\"{cursor}\"")).into_response())
+}
+
+/// Identifies the contents of a state message, regardless of the order in
+/// which its maps were serialized.
+fn state_cursor(message: &str) -> String {
+    let canonical = serde_json::from_str::<serde_json::Value>(message)
+        .map(|value| value.to_string())
+        .unwrap_or_else(|_| message.to_string());
+
+    hex::encode(&Sha256::digest(canonical.as_bytes())[..8])
+}
+
+fn state_serialization_failed() -> Response {
+    error_response(
+        "Failed to serialize state",
+        StatusCode::INTERNAL_SERVER_ERROR,
+    )
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventsQuery;
+    use crate::types::websockets::WebSocketTopic;
+
+    #[test]
+    fn parses_subscription_from_query() {
+        let query = EventsQuery {
+            topics: Some("devices, routine_statuses".to_string()),
+            device_keys: Some("hue/1,mqtt/hallway".to_string()),
+            groups: None,
+            integration_ids: Some(String::new()),
+            feed: true,
+            wait_ms: None,
+            since: None,
+        };
+
+        let subscription = query.subscription().unwrap();
+        assert_eq!(
+            subscription.topics,
+            Some(vec![
+                WebSocketTopic::Devices,
+                WebSocketTopic::RoutineStatuses
+            ])
+        );
+        assert_eq!(subscription.device_keys.unwrap().len(), 2);
+        assert_eq!(subscription.groups, None);
+        assert_eq!(subscription.integration_ids, None);
+
+        let invalid = EventsQuery {
+            device_keys: Some("missing-separator".to_string()),
+            ..query
+        };
+        assert!(invalid.subscription().is_err());
+    }
+}
@@ -1,5 +1,6 @@
-use crate::core::snapshot::SnapshotHandle;
+use crate::core::{integrations::status::integration_statuses, snapshot::SnapshotHandle};
 use serde::Serialize;
+use serde_json::json;
 use warp::{http::StatusCode, Filter};
 
 use super::with_snapshot;
@@ -52,9 +53,23 @@ async fn ready_impl(
         ));
     }
 
+    // Errors are left out as this endpoint doesn't require authentication,
+    // see /api/v1/integrations/status for those
+    let integrations = integration_statuses()
+        .into_iter()
+        .map(|status| {
+            json!({
+                "integration_id": status.integration_id,
+                "plugin": status.plugin,
+                "status": status.status,
+                "since": status.since,
+            })
+        })
+        .collect::<Vec<_>>();
+
     let body = HealthResponse {
         status: "ready",
-        details: None,
+        details: Some(json!({ "integrations": integrations })),
     };
     let reply = warp::reply::json(&body);
     Ok(warp::reply::with_status(reply, StatusCode::OK))
@@ -0,0 +1,15 @@
+use crate::core::integrations::status::integration_statuses;
+use warp::{Filter, Reply};
+
+use super::config::ApiResponse;
+
+pub fn integrations() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("integrations" / "status")
+        .and(warp::get())
+        .and_then(list_integration_statuses)
+}
+
+async fn list_integration_statuses() -> Result<impl Reply, warp::Rejection> {
+    Ok(ApiResponse::success(integration_statuses()))
+}
@@ -1,5 +1,6 @@
 use std::{env, path::PathBuf};
 
+use crate::core::auth::Auth;
 use crate::core::snapshot::SnapshotHandle;
 use crate::core::state::StateHandle;
 use crate::db::config_queries::WidgetSettingRow;
@@ -7,19 +8,25 @@ use crate::types::event::TxEventChannel;
 use serde::Serialize;
 
 mod actions;
+mod auth;
 pub mod config;
 mod devices;
+mod events;
 mod health;
+mod integrations;
 mod widgets;
 mod ws;
 
 use actions::*;
+use auth::{auth_routes, authorized, handle_rejection};
 use config::*;
 use devices::*;
+use events::events;
 use health::health;
+use integrations::integrations;
 use widgets::{
     widget_setting_string_or_env, API_URL_FIELD, CALENDAR_SETTING_KEY, ICS_URL_FIELD,
-    INFLUXDB_SETTING_KEY, TOKEN_FIELD, TRAIN_SCHEDULE_SETTING_KEY, URL_FIELD, WEATHER_SETTING_KEY,
+    INFLUXDB_SETTING_KEY, TRAIN_SCHEDULE_SETTING_KEY, URL_FIELD, WEATHER_SETTING_KEY,
 };
 
 use color_eyre::Result;
@@ -37,7 +44,6 @@ struct UiConfigResponse {
     weather_api_url: String,
     train_api_url: String,
     influx_url: String,
-    influx_token: String,
     calendar_api_url: String,
     calendar_ics_url: String,
 }
@@ -73,13 +79,6 @@ impl UiConfigResponse {
                 "INFLUX_URL",
             )
             .unwrap_or_default(),
-            influx_token: widget_setting_string_or_env(
-                widget_settings,
-                INFLUXDB_SETTING_KEY,
-                TOKEN_FIELD,
-                "INFLUX_TOKEN",
-            )
-            .unwrap_or_default(),
             calendar_api_url: env_var("CALENDAR_API_URL")
                 .unwrap_or_else(|| "/api/calendar".to_string()),
             calendar_ics_url: widget_setting_string_or_env(
@@ -113,24 +112,28 @@ pub fn init_api(
     handle: StateHandle,
     ws_handle: crate::core::websockets::WebSockets,
     event_tx: TxEventChannel,
+    auth: Auth,
     port: u16,
 ) -> Result<()> {
     let cors = warp::cors()
         .allow_any_origin()
         .allow_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
-        .allow_headers(vec!["Content-Type"]);
+        .allow_headers(vec!["Content-Type", "Authorization"]);
 
     let api = warp::path("api")
         .and(warp::path("v1"))
         .and(
             devices(&snapshot, &handle)
                 .or(actions(event_tx.clone()))
-                .or(config(&snapshot, &handle)),
+                .or(config(&snapshot, &handle))
+                .or(auth_routes(&auth))
+                .or(events(&snapshot, ws_handle.clone()))
+                .or(integrations()),
         )
         .map(Reply::into_response)
         .boxed();
 
-    let ws = ws(&snapshot, ws_handle, event_tx)
+    let ws = ws(&snapshot, ws_handle, event_tx, &auth)
         .map(Reply::into_response)
         .boxed();
     let health = health(&snapshot).map(Reply::into_response).boxed();
@@ -152,6 +155,14 @@ pub fn init_api(
         routes = routes.or(ui_routes).unify().boxed();
     }
 
+    // Checks credentials before any route runs, so that unauthorized
+    // requests never reach the handlers
+    let routes = authorized(&auth)
+        .and(routes)
+        .recover(handle_rejection)
+        .unify()
+        .boxed();
+
     info!("Starting API server on port {}", port);
     tokio::spawn(async move {
         warp::serve(routes.with(cors))
@@ -181,21 +192,23 @@ fn ui_config_route(snapshot: SnapshotHandle) -> BoxedFilter<(Response,)> {
         .and(warp::get())
         .and(warp::header::optional::<String>("host"))
         .and(warp::header::optional::<String>("x-forwarded-proto"))
-        .and_then(move |host: Option<String>, forwarded_proto: Option<String>| {
-            let snapshot = snapshot.clone();
-            async move {
-                let widget_settings = snapshot.load().runtime_config.widget_settings.clone();
-
-                Ok::<_, warp::Rejection>(
-                    warp::reply::json(&UiConfigResponse::from_request(
-                        host.as_deref(),
-                        forwarded_proto.as_deref(),
-                        &widget_settings,
-                    ))
-                    .into_response(),
-                )
-            }
-        })
+        .and_then(
+            move |host: Option<String>, forwarded_proto: Option<String>| {
+                let snapshot = snapshot.clone();
+                async move {
+                    let widget_settings = snapshot.load().runtime_config.widget_settings.clone();
+
+                    Ok::<_, warp::Rejection>(
+                        warp::reply::json(&UiConfigResponse::from_request(
+                            host.as_deref(),
+                            forwarded_proto.as_deref(),
+                            &widget_settings,
+                        ))
+                        .into_response(),
+                    )
+                }
+            },
+        )
         .boxed()
 }
 
@@ -1,19 +1,27 @@
+use super::auth::{principal, required_request_role};
+use super::config::{describe_scene_dependents, validate_action_rollout};
 use super::with_snapshot;
-use crate::core::snapshot::SnapshotHandle;
+use crate::core::auth::{Auth, Principal};
+use crate::core::snapshot::{RuntimeSnapshot, SnapshotHandle};
 use crate::core::state::send_state_ws_from_snapshot;
-use crate::core::websockets::WebSockets;
-use crate::types::event::TxEventChannel;
-use crate::types::websockets::WebSocketRequest;
+use crate::core::websockets::{next_user_id, WebSockets};
+use crate::types::action::Action;
+use crate::types::device::DeviceData;
+use crate::types::event::{Event, TxEventChannel};
+use crate::types::scene::SceneId;
+use crate::types::ui::UiActionDescriptor;
+use crate::types::websockets::{
+    WebSocketError, WebSocketErrorKind, WebSocketRequest, WebSocketResponse,
+};
 use futures::SinkExt;
 use futures_util::StreamExt;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::mpsc;
-use warp::{ws::WebSocket, Filter};
+use warp::{
+    ws::{Message, WebSocket},
+    Filter,
+};
 
-/// Our global unique user id counter.
-static NEXT_USER_ID: AtomicUsize = AtomicUsize::new(1);
-
-fn with_ws(
+pub(super) fn with_ws(
     ws: WebSockets,
 ) -> impl Filter<Extract = (WebSockets,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || ws.clone())
@@ -29,6 +37,7 @@ pub fn ws(
     snapshot: &SnapshotHandle,
     ws_handle: WebSockets,
     event_tx: TxEventChannel,
+    auth: &Auth,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path("ws")
         // The `ws()` filter will prepare the Websocket handshake.
@@ -36,14 +45,18 @@ pub fn ws(
         .and(with_snapshot(snapshot))
         .and(with_ws(ws_handle))
         .and(with_event_tx(event_tx))
+        .and(principal(auth))
         .map(
             |ws: warp::ws::Ws,
              snapshot: SnapshotHandle,
              ws_handle: WebSockets,
-             event_tx: TxEventChannel| {
+             event_tx: TxEventChannel,
+             principal: Option<Principal>| {
+                let principal = principal.unwrap_or_else(Principal::anonymous);
+
                 // This will call our function if the handshake succeeds.
                 ws.on_upgrade(move |socket| {
-                    user_connected(socket, snapshot, ws_handle, event_tx)
+                    user_connected(socket, snapshot, ws_handle, event_tx, principal)
                 })
             },
         )
@@ -55,20 +68,21 @@ async fn user_connected(
     snapshot: SnapshotHandle,
     ws_handle: WebSockets,
     event_tx: TxEventChannel,
+    principal: Principal,
 ) {
     // Use a counter to assign a new unique ID for this user.
-    let my_id = NEXT_USER_ID.fetch_add(1, Ordering::Relaxed);
+    let my_id = next_user_id();
 
     // Split the socket into a sender and receive of messages.
     let (mut user_ws_tx, mut user_ws_rx) = ws.split();
 
     // Use a bounded channel to handle buffering and flushing of messages
     // to the websocket. This prevents slow/dead clients from causing memory issues.
-    let (tx, mut rx) = mpsc::channel(100);
+    let (tx, mut rx) = mpsc::channel::<String>(100);
 
     tokio::task::spawn(async move {
         while let Some(message) = rx.recv().await {
-            match user_ws_tx.send(message).await {
+            match user_ws_tx.send(Message::text(message)).await {
                 Ok(_) => {}
                 Err(e) => {
                     warn!("websocket send error (uid={my_id}): {e}, closing connection");
@@ -97,13 +111,45 @@ async fn user_connected(
         let json = msg.to_str();
 
         if let Ok(json) = json {
-            let msg = serde_json::from_str::<WebSocketRequest>(json);
+            let request = serde_json::from_str::<WebSocketRequest>(json)
+                .map_err(|e| {
+                    WebSocketError::new(
+                        WebSocketErrorKind::InvalidMessage,
+                        format!("Error while deserializing websocket message: {e}"),
+                    )
+                })
+                .and_then(|request| {
+                    let required = required_request_role(&request);
+                    if principal.role < required {
+                        return Err(WebSocketError::new(
+                            WebSocketErrorKind::Forbidden,
+                            format!("The {} role is required", required.as_str()),
+                        ));
+                    }
 
-            match msg {
-                Ok(WebSocketRequest::EventMessage(event)) => {
-                    event_tx.send(event);
+                    Ok(request)
+                });
+
+            let result = match request {
+                Ok(WebSocketRequest::Subscribe(subscription)) => {
+                    ws_handle.set_subscription(my_id, subscription).await;
+                    send_state_ws_from_snapshot(&snapshot, &ws_handle, Some(my_id)).await;
+                    Ok(())
+                }
+                Ok(request) => {
+                    request_event(request, &snapshot.load()).map(|event| event_tx.send(event))
                 }
-                Err(e) => warn!("Error while deserializing websocket message: {e}"),
+                Err(error) => Err(error),
+            };
+
+            if let Err(error) = result {
+                warn!(
+                    "Rejected websocket request from {} (uid={my_id}): {}",
+                    principal.name, error.message
+                );
+                ws_handle
+                    .send(Some(my_id), &WebSocketResponse::Error(error))
+                    .await;
             }
         }
     }
@@ -112,3 +158,102 @@ async fn user_connected(
     // connected. Once they disconnect, then...
     ws_handle.user_disconnected(my_id).await;
 }
+
+fn invalid_request(message: impl Into<String>) -> WebSocketError {
+    WebSocketError::new(WebSocketErrorKind::InvalidRequest, message)
+}
+
+fn scene_exists(snapshot: &RuntimeSnapshot, scene_id: &SceneId) -> bool {
+    snapshot
+        .runtime_config
+        .scenes
+        .iter()
+        .any(|scene| scene.id == scene_id.to_string())
+}
+
+/// Validates a client request and translates it into the event it stands
+/// for.
+fn request_event(
+    request: WebSocketRequest,
+    snapshot: &RuntimeSnapshot,
+) -> Result<Event, WebSocketError> {
+    match request {
+        WebSocketRequest::Action(action) => {
+            validate_action_rollout(&action).map_err(invalid_request)?;
+            Ok(Event::Action(action))
+        }
+        WebSocketRequest::StoreUiState { key, value } => {
+            if key.is_empty() {
+                return Err(invalid_request("UI state key must not be empty"));
+            }
+
+            Ok(Event::Action(Action::Ui(
+                UiActionDescriptor::StoreUIState { key, value },
+            )))
+        }
+        WebSocketRequest::SetDeviceState {
+            device_key,
+            state,
+            keep_scene,
+        } => {
+            let Some(existing) = snapshot.devices.0.get(&device_key) else {
+                return Err(invalid_request(format!("Unknown device {device_key}")));
+            };
+
+            if existing.get_controllable_state().is_none() {
+                return Err(invalid_request(format!(
+                    "Device {device_key} is not controllable"
+                )));
+            }
+
+            let mut device = existing.set_controllable_state(state);
+            if !keep_scene {
+                if let DeviceData::Controllable(data) = &mut device.data {
+                    data.scene_id = None;
+                }
+            }
+
+            Ok(Event::SetInternalState {
+                device,
+                skip_external_update: None,
+                skip_db_update: None,
+            })
+        }
+        WebSocketRequest::StoreScene { scene_id, config } => {
+            if scene_id.to_string().trim().is_empty() {
+                return Err(invalid_request("Scene id must not be empty"));
+            }
+
+            Ok(Event::DbStoreScene { scene_id, config })
+        }
+        WebSocketRequest::RenameScene { scene_id, name } => {
+            if !scene_exists(snapshot, &scene_id) {
+                return Err(invalid_request(format!("Unknown scene {scene_id}")));
+            }
+            if name.trim().is_empty() {
+                return Err(invalid_request("Scene name must not be empty"));
+            }
+
+            Ok(Event::DbEditScene { scene_id, name })
+        }
+        WebSocketRequest::DeleteScene { scene_id } => {
+            if !scene_exists(snapshot, &scene_id) {
+                return Err(invalid_request(format!("Unknown scene {scene_id}")));
+            }
+
+            if let Some(dependents) =
+                describe_scene_dependents(&snapshot.runtime_config, &scene_id.to_string())
+            {
+                return Err(invalid_request(format!(
+                    "Scene {scene_id} is referenced by {dependents}"
+                )));
+            }
+
+            Ok(Event::DbDeleteScene { scene_id })
+        }
+        // Subscriptions only affect the connection they were made on
+        WebSocketRequest::Subscribe(_) => Err(invalid_request(
+            "Subscriptions are handled by the connection",
+        )),
+    }
+}
@@ -0,0 +1,419 @@
+//! Users, API tokens and login sessions of the HTTP and WebSocket API.
+//!
+//! Authentication is only enforced once at least one user or API token
+//! exists, so existing setups keep working until credentials are added.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use chrono::Utc;
+use pbkdf2::{
+    password_hash::{PasswordHash, PasswordHasher, SaltString},
+    Params, Pbkdf2,
+};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::db::auth_queries::{db_get_api_tokens, db_get_users, ApiTokenRow, UserRow};
+use crate::types::auth::{ApiTokenInfo, Role, UserInfo};
+
+const PASSWORD_HASH_ITERATIONS: u32 = 100_000;
+/// Stored hashes with fewer iterations are rejected, so that a tampered hash
+/// can't make any password match cheaply.
+const MIN_PASSWORD_HASH_ITERATIONS: u32 = 10_000;
+
+/// How long a login session stays valid.
+pub const SESSION_LIFETIME_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// The user or API token a request was authenticated as.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+impl Principal {
+    /// Used for all requests while authentication is disabled.
+    pub fn anonymous() -> Self {
+        Principal {
+            name: "anonymous".to_string(),
+            role: Role::Admin,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct AuthData {
+    users: BTreeMap<String, UserRow>,
+    tokens: BTreeMap<String, ApiTokenRow>,
+}
+
+impl AuthData {
+    fn is_enabled(&self) -> bool {
+        !self.users.is_empty() || self.tokens.values().any(|token| token.username.is_none())
+    }
+
+    /// Whether someone is still able to manage users and tokens.
+    fn has_admin(&self) -> bool {
+        let admin_user = self.users.values().any(|user| user.role == Role::Admin);
+        let admin_token = self
+            .tokens
+            .values()
+            .any(|token| token.username.is_none() && token.role == Role::Admin);
+
+        !self.is_enabled() || admin_user || admin_token
+    }
+}
+
+/// Shared, in-memory copy of all credentials. Changes are written to the
+/// database by the API handlers before being applied here.
+#[derive(Clone, Default)]
+pub struct Auth {
+    data: Arc<RwLock<AuthData>>,
+}
+
+impl Auth {
+    pub fn new(users: Vec<UserRow>, tokens: Vec<ApiTokenRow>) -> Self {
+        let data = AuthData {
+            users: users
+                .into_iter()
+                .map(|user| (user.username.clone(), user))
+                .collect(),
+            tokens: tokens
+                .into_iter()
+                .map(|token| (token.id.clone(), token))
+                .collect(),
+        };
+
+        Auth {
+            data: Arc::new(RwLock::new(data)),
+        }
+    }
+
+    pub async fn load() -> Self {
+        let users = db_get_users().await.unwrap_or_else(|e| {
+            warn!("Failed to load API users from DB: {e}");
+            Vec::new()
+        });
+        let tokens = db_get_api_tokens().await.unwrap_or_else(|e| {
+            warn!("Failed to load API tokens from DB: {e}");
+            Vec::new()
+        });
+
+        let auth = Auth::new(users, tokens);
+        if !auth.is_enabled() {
+            warn!("No API users or tokens configured, API authentication is disabled");
+        }
+
+        auth
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.read().is_enabled()
+    }
+
+    /// Resolves a bearer token to the user or API token it belongs to.
+    pub fn authenticate(&self, token: &str) -> Option<Principal> {
+        let token_hash = hash_token(token);
+        let now_ms = Utc::now().timestamp_millis();
+        let data = self.read();
+
+        let row = data
+            .tokens
+            .values()
+            .find(|row| row.token_hash == token_hash)?;
+
+        if row
+            .expires_at_ms
+            .is_some_and(|expires_at_ms| expires_at_ms <= now_ms)
+        {
+            return None;
+        }
+
+        match &row.username {
+            // Sessions always have the current role of their user
+            Some(username) => data.users.get(username).map(|user| Principal {
+                name: user.username.clone(),
+                role: user.role,
+            }),
+            None => Some(Principal {
+                name: row.name.clone(),
+                role: row.role,
+            }),
+        }
+    }
+
+    pub fn verify_login(&self, username: &str, password: &str) -> Option<Role> {
+        let data = self.read();
+        let user = data.users.get(username)?;
+
+        verify_password(password, &user.password_hash).then_some(user.role)
+    }
+
+    /// Id of the login session that a bearer token belongs to.
+    pub fn session_id(&self, token: &str) -> Option<String> {
+        let token_hash = hash_token(token);
+
+        self.read()
+            .tokens
+            .values()
+            .find(|row| row.token_hash == token_hash && row.username.is_some())
+            .map(|row| row.id.clone())
+    }
+
+    pub fn user(&self, username: &str) -> Option<UserRow> {
+        self.read().users.get(username).cloned()
+    }
+
+    pub fn users(&self) -> Vec<UserInfo> {
+        self.read()
+            .users
+            .values()
+            .map(|user| UserInfo {
+                username: user.username.clone(),
+                role: user.role,
+            })
+            .collect()
+    }
+
+    /// Lists API tokens, leaving out login sessions.
+    pub fn api_tokens(&self) -> Vec<ApiTokenInfo> {
+        self.read()
+            .tokens
+            .values()
+            .filter(|token| token.username.is_none())
+            .map(|token| ApiTokenInfo {
+                id: token.id.clone(),
+                name: token.name.clone(),
+                role: token.role,
+                created_at_ms: token.created_at_ms,
+            })
+            .collect()
+    }
+
+    /// Whether an admin would remain after storing the given user.
+    pub fn can_set_user(&self, user: &UserRow) -> bool {
+        self.admin_remains_after(|data| {
+            data.users.insert(user.username.clone(), user.clone());
+        })
+    }
+
+    /// Whether an admin would remain after removing the given user.
+    pub fn can_remove_user(&self, username: &str) -> bool {
+        self.admin_remains_after(|data| {
+            data.users.remove(username);
+        })
+    }
+
+    /// Whether an admin would remain after removing the given token.
+    pub fn can_remove_token(&self, id: &str) -> bool {
+        self.admin_remains_after(|data| {
+            data.tokens.remove(id);
+        })
+    }
+
+    pub fn set_user(&self, user: UserRow) {
+        self.write().users.insert(user.username.clone(), user);
+    }
+
+    /// Removes a user along with its login sessions.
+    pub fn remove_user(&self, username: &str) {
+        let mut data = self.write();
+        data.users.remove(username);
+        data.tokens
+            .retain(|_, token| token.username.as_deref() != Some(username));
+    }
+
+    pub fn set_token(&self, token: ApiTokenRow) {
+        self.write().tokens.insert(token.id.clone(), token);
+    }
+
+    pub fn remove_token(&self, id: &str) {
+        self.write().tokens.remove(id);
+    }
+
+    /// Forgets login sessions that have expired.
+    pub fn remove_expired_sessions(&self, now_ms: i64) {
+        self.write().tokens.retain(|_, token| {
+            token
+                .expires_at_ms
+                .is_none_or(|expires_at_ms| expires_at_ms > now_ms)
+        });
+    }
+
+    fn admin_remains_after(&self, change: impl FnOnce(&mut AuthData)) -> bool {
+        let mut data = self.read().clone();
+        change(&mut data);
+        data.has_admin()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, AuthData> {
+        self.data.read().expect("auth lock poisoned")
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, AuthData> {
+        self.data.write().expect("auth lock poisoned")
+    }
+}
+
+/// Generates a random secret suitable for tokens and salts.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Tokens are random, so a plain hash is enough to avoid storing them.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Hashes `password` as a PBKDF2-SHA256 PHC string.
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill(&mut salt);
+    let salt = SaltString::encode_b64(&salt).expect("16 bytes is a valid salt length");
+
+    Pbkdf2
+        .hash_password_customized(
+            password.as_bytes(),
+            None,
+            None,
+            password_hash_params(PASSWORD_HASH_ITERATIONS),
+            &salt,
+        )
+        .expect("PBKDF2 accepts any password")
+        .to_string()
+}
+
+pub fn verify_password(password: &str, encoded: &str) -> bool {
+    let Ok(stored) = PasswordHash::new(encoded) else {
+        return false;
+    };
+    let (Ok(params), Some(hash), Some(salt)) =
+        (Params::try_from(&stored), stored.hash, stored.salt)
+    else {
+        return false;
+    };
+    if params.rounds < MIN_PASSWORD_HASH_ITERATIONS {
+        return false;
+    }
+
+    let Ok(computed) = Pbkdf2.hash_password_customized(
+        password.as_bytes(),
+        Some(stored.algorithm),
+        None,
+        params,
+        salt,
+    ) else {
+        return false;
+    };
+
+    computed
+        .hash
+        .is_some_and(|computed| bool::from(computed.as_bytes().ct_eq(hash.as_bytes())))
+}
+
+fn password_hash_params(rounds: u32) -> Params {
+    Params {
+        rounds,
+        output_length: 32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_password, hash_token, password_hash_params, verify_password, Auth};
+    use crate::db::auth_queries::{ApiTokenRow, UserRow};
+    use crate::types::auth::Role;
+    use pbkdf2::{
+        password_hash::{PasswordHasher, SaltString},
+        Pbkdf2,
+    };
+
+    fn user(username: &str, role: Role) -> UserRow {
+        UserRow {
+            username: username.to_string(),
+            password_hash: "unused".to_string(),
+            role,
+            created_at_ms: 0,
+        }
+    }
+
+    fn token(id: &str, secret: &str, role: Role, username: Option<&str>) -> ApiTokenRow {
+        ApiTokenRow {
+            id: id.to_string(),
+            name: format!("{id} token"),
+            token_hash: hash_token(secret),
+            role,
+            username: username.map(str::to_string),
+            expires_at_ms: None,
+            created_at_ms: 0,
+        }
+    }
+
+    #[test]
+    fn verifies_hashed_passwords() {
+        let encoded = hash_password("correct horse");
+
+        assert!(verify_password("correct horse", &encoded));
+        assert!(!verify_password("battery staple", &encoded));
+        assert!(!verify_password("correct horse", "plaintext"));
+    }
+
+    #[test]
+    fn rejects_hashes_with_too_few_iterations() {
+        let salt = SaltString::encode_b64(b"0123456789abcdef").unwrap();
+        let weak = Pbkdf2
+            .hash_password_customized(b"password", None, None, password_hash_params(1), &salt)
+            .unwrap()
+            .to_string();
+        assert!(!verify_password("password", &weak));
+    }
+
+    #[test]
+    fn sessions_use_the_current_role_of_their_user() {
+        let auth = Auth::new(
+            vec![user("alice", Role::Admin)],
+            vec![
+                token("session", "session-secret", Role::Admin, Some("alice")),
+                token("kiosk", "kiosk-secret", Role::Viewer, None),
+            ],
+        );
+
+        assert_eq!(
+            auth.authenticate("kiosk-secret").unwrap().role,
+            Role::Viewer
+        );
+        assert_eq!(
+            auth.authenticate("session-secret").unwrap().role,
+            Role::Admin
+        );
+        assert!(auth.authenticate("unknown").is_none());
+
+        auth.set_user(user("alice", Role::Operator));
+        assert_eq!(
+            auth.authenticate("session-secret").unwrap().role,
+            Role::Operator
+        );
+
+        auth.remove_user("alice");
+        assert!(auth.authenticate("session-secret").is_none());
+    }
+
+    #[test]
+    fn last_admin_cannot_be_removed() {
+        let auth = Auth::new(
+            vec![user("alice", Role::Admin), user("bob", Role::Viewer)],
+            Vec::new(),
+        );
+
+        assert!(!auth.can_remove_user("alice"));
+        assert!(!auth.can_set_user(&user("alice", Role::Operator)));
+        assert!(auth.can_remove_user("bob"));
+
+        auth.set_token(token("admin", "admin-secret", Role::Admin, None));
+        assert!(auth.can_remove_user("alice"));
+    }
+}
@@ -0,0 +1,516 @@
+//! Config as code: groups, scenes and routines kept in a directory of TOML
+//! files.
+//!
+//! The directory has `groups/`, `scenes/` and `routines/` subdirectories with
+//! one `<id>.toml` file per entry, holding the fields of the entry except its
+//! id. Changed files are validated and applied to the running config. With
+//! write-back enabled, config changes made through homectl are written back
+//! to the files.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use color_eyre::Result;
+use eyre::{eyre, WrapErr};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::api::config::config_validation_errors;
+use crate::core::state::StateHandle;
+use crate::db::{
+    config_queries::{self, ConfigExport, GroupRow, RoutineRow, SceneRow},
+    is_db_connected,
+};
+
+const CONFIG_DIR_SYNC_INTERVAL_SECS: u64 = 2;
+const FILE_EXTENSION: &str = "toml";
+
+/// Entries stored in the config directory.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ConfigFiles {
+    pub groups: Vec<GroupRow>,
+    pub scenes: Vec<SceneRow>,
+    pub routines: Vec<RoutineRow>,
+}
+
+impl ConfigFiles {
+    pub fn from_config(config: &ConfigExport) -> Self {
+        Self {
+            groups: config.groups.clone(),
+            scenes: config.scenes.clone(),
+            routines: config.routines.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty() && self.scenes.is_empty() && self.routines.is_empty()
+    }
+
+    /// Replaces the groups, scenes and routines of `config`.
+    pub fn apply_to(self, config: &mut ConfigExport) {
+        config.groups = self.groups;
+        config.scenes = self.scenes;
+        config.routines = self.routines;
+    }
+}
+
+trait ConfigFileEntry: Serialize + DeserializeOwned {
+    /// Subdirectory holding entries of this type.
+    const SECTION: &'static str;
+
+    fn id(&self) -> &str;
+}
+
+impl ConfigFileEntry for GroupRow {
+    const SECTION: &'static str = "groups";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl ConfigFileEntry for SceneRow {
+    const SECTION: &'static str = "scenes";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl ConfigFileEntry for RoutineRow {
+    const SECTION: &'static str = "routines";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+pub fn read_config_dir(dir: &Path) -> Result<ConfigFiles> {
+    Ok(ConfigFiles {
+        groups: read_section(dir)?,
+        scenes: read_section(dir)?,
+        routines: read_section(dir)?,
+    })
+}
+
+/// Writes entries whose files differ from them, and removes files of
+/// entries that no longer exist. Returns the number of changed files.
+pub fn write_config_dir(dir: &Path, files: &ConfigFiles) -> Result<usize> {
+    // Checked up front, so that a bad id doesn't leave a partially written
+    // directory behind
+    let ids = files
+        .groups
+        .iter()
+        .map(|g| &g.id)
+        .chain(files.scenes.iter().map(|s| &s.id))
+        .chain(files.routines.iter().map(|r| &r.id));
+    for id in ids {
+        if !is_valid_file_id(id) {
+            return Err(eyre!("'{id}' can't be used as a file name"));
+        }
+    }
+
+    Ok(write_section(dir, &files.groups)?
+        + write_section(dir, &files.scenes)?
+        + write_section(dir, &files.routines)?)
+}
+
+fn is_valid_file_id(id: &str) -> bool {
+    !id.is_empty() && !id.starts_with('.') && !id.contains(['/', '\\'])
+}
+
+/// Paths of the entry files in a section directory, keyed by entry id.
+fn entry_paths(section_dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut paths = BTreeMap::new();
+    if !section_dir.is_dir() {
+        return Ok(paths);
+    }
+
+    for dir_entry in fs::read_dir(section_dir)? {
+        let path = dir_entry?.path();
+        if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some(FILE_EXTENSION) {
+            continue;
+        }
+
+        if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+            paths.insert(id.to_string(), path);
+        }
+    }
+
+    Ok(paths)
+}
+
+fn read_section<T: ConfigFileEntry>(dir: &Path) -> Result<Vec<T>> {
+    entry_paths(&dir.join(T::SECTION))?
+        .into_iter()
+        .map(|(id, path)| {
+            fs::read_to_string(&path)
+                .map_err(Into::into)
+                .and_then(|contents| parse_entry(&id, &contents))
+                .map_err(|e| eyre!("{}: {e}", path.display()))
+        })
+        .collect()
+}
+
+fn parse_entry<T: ConfigFileEntry>(id: &str, contents: &str) -> Result<T> {
+    let mut value: Value = toml::from_str(contents)?;
+    let fields = value
+        .as_object_mut()
+        .ok_or_else(|| eyre!("Expected a table"))?;
+
+    // The id comes from the file name
+    if let Some(file_id) = fields.insert("id".to_string(), Value::String(id.to_string())) {
+        if file_id.as_str() != Some(id) {
+            return Err(eyre!("id {file_id} doesn't match the file name"));
+        }
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+fn format_entry<T: ConfigFileEntry>(entry: &T) -> Result<String> {
+    let mut value = serde_json::to_value(entry)?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("id");
+    }
+    strip_nulls(&mut value);
+
+    toml::to_string_pretty(&value)
+        .wrap_err_with(|| format!("Failed to format {} '{}' as TOML", T::SECTION, entry.id()))
+}
+
+/// TOML has no null, so unset fields are left out. Nulls in arrays are kept,
+/// as dropping them would shift the items after them, and fail to format.
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|_, value| !value.is_null());
+            fields.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+fn write_section<T: ConfigFileEntry>(dir: &Path, entries: &[T]) -> Result<usize> {
+    let section_dir = dir.join(T::SECTION);
+    fs::create_dir_all(&section_dir)?;
+
+    let mut stale = entry_paths(&section_dir)?;
+    let mut changed = 0;
+
+    for entry in entries {
+        let id = entry.id();
+        let path = stale
+            .remove(id)
+            .unwrap_or_else(|| section_dir.join(format!("{id}.{FILE_EXTENSION}")));
+
+        // Files that parse to the same entry are left alone, keeping their
+        // comments and formatting
+        let existing = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| parse_entry::<T>(id, &contents).ok())
+            .and_then(|existing| serde_json::to_value(existing).ok());
+        if existing == Some(serde_json::to_value(entry)?) {
+            continue;
+        }
+
+        // Renamed into place, so that the sync never reads a partial file
+        let temp_path = section_dir.join(format!(".{id}.{FILE_EXTENSION}.tmp"));
+        fs::write(&temp_path, format_entry(entry)?)?;
+        fs::rename(&temp_path, &path)?;
+        changed += 1;
+    }
+
+    for path in stale.values() {
+        fs::remove_file(path)?;
+        changed += 1;
+    }
+
+    Ok(changed)
+}
+
+/// Points a validation error of the merged config at the file of the entry
+/// it concerns.
+fn describe_file_error(config: &ConfigExport, pointer: &str, message: &str) -> String {
+    let mut tokens = pointer.splitn(4, '/').skip(1);
+    let section = tokens.next().unwrap_or_default();
+    let index = tokens.next().and_then(|index| index.parse::<usize>().ok());
+    let rest = tokens.next().map(|rest| format!("/{rest}"));
+
+    let id = index.and_then(|index| match section {
+        "groups" => config.groups.get(index).map(|g| &g.id),
+        "scenes" => config.scenes.get(index).map(|s| &s.id),
+        "routines" => config.routines.get(index).map(|r| &r.id),
+        _ => None,
+    });
+
+    match (id, rest) {
+        (Some(id), Some(rest)) => format!("{section}/{id}.{FILE_EXTENSION} at {rest}: {message}"),
+        (Some(id), None) => format!("{section}/{id}.{FILE_EXTENSION}: {message}"),
+        (None, _) => format!("{pointer}: {message}"),
+    }
+}
+
+/// Keeps the config directory and the runtime config in sync.
+struct ConfigDirSync {
+    dir: PathBuf,
+    write_back: bool,
+    /// Entries the files and the runtime config last agreed on, as JSON.
+    synced: Option<Value>,
+    /// Files that were refused, so that they're only checked once.
+    rejected: Option<Value>,
+}
+
+/// Polls the config directory for changes, and with `write_back`, the
+/// runtime config for changes to write to it.
+///
+/// Changed files win over runtime changes made in the meantime. A directory
+/// without any entries is filled from the runtime config when writing back,
+/// and otherwise left unapplied.
+pub fn start_config_dir_sync(dir: PathBuf, write_back: bool, handle: StateHandle) {
+    info!(
+        "Syncing groups, scenes and routines with config directory {}",
+        dir.display()
+    );
+
+    let mut sync = ConfigDirSync {
+        dir,
+        write_back,
+        synced: None,
+        rejected: None,
+    };
+
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(CONFIG_DIR_SYNC_INTERVAL_SECS));
+        let mut last_error = None;
+
+        loop {
+            interval.tick().await;
+
+            match sync.sync(&handle).await {
+                Ok(()) => last_error = None,
+                Err(error) => {
+                    let error = error.to_string();
+                    if last_error.as_ref() != Some(&error) {
+                        warn!(
+                            "Failed to sync config directory {}: {error}",
+                            sync.dir.display()
+                        );
+                    }
+                    last_error = Some(error);
+                }
+            }
+        }
+    });
+}
+
+impl ConfigDirSync {
+    async fn sync(&mut self, handle: &StateHandle) -> Result<()> {
+        let dir = self.dir.clone();
+        let files = tokio::task::spawn_blocking(move || read_config_dir(&dir)).await??;
+        let on_disk = serde_json::to_value(&files)?;
+
+        if self.synced.as_ref() != Some(&on_disk) {
+            if self.rejected.as_ref() == Some(&on_disk) {
+                return Ok(());
+            }
+
+            if files.is_empty() && self.synced.is_none() {
+                if self.write_back {
+                    let runtime = ConfigFiles::from_config(&handle.snapshot.load().runtime_config);
+                    return self.write(runtime).await;
+                }
+
+                self.rejected = Some(on_disk);
+                return Err(eyre!(
+                    "No groups, scenes or routines found, keeping the current config"
+                ));
+            }
+
+            if let Err(error) = self.apply(files, handle).await {
+                self.rejected = Some(on_disk);
+                return Err(error);
+            }
+
+            self.synced = Some(on_disk);
+            self.rejected = None;
+            return Ok(());
+        }
+
+        if !self.write_back {
+            return Ok(());
+        }
+
+        let runtime = ConfigFiles::from_config(&handle.snapshot.load().runtime_config);
+        if serde_json::to_value(&runtime)? != on_disk {
+            self.write(runtime).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn apply(&self, files: ConfigFiles, handle: &StateHandle) -> Result<()> {
+        let snapshot = handle.snapshot.load_full();
+        let current = (*snapshot.runtime_config).clone();
+        let mut config = current.clone();
+        files.clone().apply_to(&mut config);
+
+        let errors: Vec<String> = config_validation_errors(&config, &snapshot)
+            .iter()
+            .map(|(pointer, message)| describe_file_error(&config, pointer, message))
+            .collect();
+        if !errors.is_empty() {
+            return Err(eyre!("Invalid config files:\n{}", errors.join("\n")));
+        }
+
+        handle
+            .mutate(move |state| {
+                Box::pin(async move {
+                    files.apply_to(&mut state.runtime_config);
+                    state.apply_runtime_config().await
+                })
+            })
+            .await??;
+
+        info!("Applied changed config files from {}", self.dir.display());
+
+        if is_db_connected() {
+            if let Err(error) = config_queries::db_replace_config(&current, &config).await {
+                warn!("Failed to persist config from config directory: {error}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write(&mut self, files: ConfigFiles) -> Result<()> {
+        let value = serde_json::to_value(&files)?;
+        let dir = self.dir.clone();
+        let changed = tokio::task::spawn_blocking(move || write_config_dir(&dir, &files)).await??;
+
+        if changed > 0 {
+            info!(
+                "Wrote {changed} changed config files to {}",
+                self.dir.display()
+            );
+        }
+
+        self.synced = Some(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_config_dir() -> PathBuf {
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time should be after unix epoch")
+            .as_nanos();
+        std::env::temp_dir().join(format!("homectl-config-dir-{unique}"))
+    }
+
+    fn files() -> ConfigFiles {
+        serde_json::from_value::<ConfigExport>(serde_json::json!({
+            "version": 1,
+            "core": {},
+            "integrations": [],
+            "groups": [
+                {
+                    "id": "hall",
+                    "name": "Hall",
+                    "hidden": false,
+                    "devices": [{ "integration_id": "hue", "device_id": "1" }],
+                    "linked_groups": []
+                }
+            ],
+            "scenes": [
+                {
+                    "id": "evening",
+                    "name": "Evening",
+                    "hidden": false,
+                    "script": null,
+                    "device_states": { "hue/1": { "power": true, "brightness": 0.5 } },
+                    "group_states": {}
+                }
+            ],
+            "routines": [
+                {
+                    "id": "arrive",
+                    "name": "Arrive",
+                    "enabled": true,
+                    "rules": [{ "group_id": "hall", "power": false }],
+                    "actions": [{ "action": "ActivateScene", "scene_id": "evening" }],
+                    "cooldown_ms": 1000
+                }
+            ],
+            "floorplan": null,
+            "dashboard_layouts": [],
+            "dashboard_widgets": []
+        }))
+        .map(|config| ConfigFiles::from_config(&config))
+        .unwrap()
+    }
+
+    #[test]
+    fn writes_and_reads_config_dir() {
+        let dir = temp_config_dir();
+        let mut files = files();
+
+        assert_eq!(write_config_dir(&dir, &files).unwrap(), 3);
+        assert!(dir.join("scenes/evening.toml").is_file());
+        assert_eq!(
+            serde_json::to_value(read_config_dir(&dir).unwrap()).unwrap(),
+            serde_json::to_value(&files).unwrap()
+        );
+
+        // Unchanged entries keep their files as written
+        let hall = dir.join("groups/hall.toml");
+        let edited = format!("# Downstairs\n{}", fs::read_to_string(&hall).unwrap());
+        fs::write(&hall, &edited).unwrap();
+        files.routines.clear();
+
+        assert_eq!(write_config_dir(&dir, &files).unwrap(), 1);
+        assert!(!dir.join("routines/arrive.toml").exists());
+        assert_eq!(fs::read_to_string(&hall).unwrap(), edited);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn strips_null_fields_only() {
+        let mut value = serde_json::json!({
+            "script": null,
+            "rules": [null, { "scene": null, "power": true }]
+        });
+        strip_nulls(&mut value);
+
+        assert_eq!(
+            value,
+            serde_json::json!({ "rules": [null, { "power": true }] })
+        );
+    }
+
+    #[test]
+    fn takes_entry_ids_from_file_names() {
+        let group: GroupRow = parse_entry(
+            "hall",
+            "name = \"Hall\"\nhidden = false\ndevices = []\nlinked_groups = []\n",
+        )
+        .unwrap();
+        assert_eq!(group.id, "hall");
+
+        let mismatch = parse_entry::<GroupRow>(
+            "hall",
+            "id = \"attic\"\nname = \"Hall\"\nhidden = false\ndevices = []\nlinked_groups = []\n",
+        );
+        assert!(mismatch.is_err());
+    }
+}
@@ -0,0 +1,369 @@
+//! Config version history.
+//!
+//! Versions are recorded whenever the runtime config changes, and before and
+//! after imports and rollbacks. Recording skips configs identical to the
+//! latest version, so callers don't need to track what has been saved.
+//!
+//! Floorplan images are left out of versions, as they can be large and would
+//! otherwise be stored again with every change. Rollbacks keep the current
+//! images.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use color_eyre::Result;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{Mutex, MutexGuard, Notify};
+
+use crate::db::{
+    config_queries::{self, ConfigExport},
+    is_db_connected,
+};
+
+/// Older versions are pruned when a new version is recorded.
+const MAX_CONFIG_VERSIONS: u64 = 200;
+
+/// Latest recorded config, `None` until it has been read from the database.
+static LATEST_VERSION: Lazy<Mutex<Option<Value>>> = Lazy::new(Default::default);
+
+/// Signalled whenever a changed runtime config is published.
+static CONFIG_CHANGED: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Wakes up [config_changed] waiters, or the next one if none is waiting.
+pub fn notify_config_changed() {
+    CONFIG_CHANGED.notify_one();
+}
+
+/// Waits until the runtime config has changed since the previous call.
+pub async fn config_changed() {
+    CONFIG_CHANGED.notified().await;
+}
+
+/// Waits until the runtime config has changed and then stayed unchanged for
+/// `quiet`, so that a burst of edits can be recorded as one version.
+pub async fn config_settled(quiet: Duration) {
+    config_changed().await;
+    while tokio::time::timeout(quiet, config_changed()).await.is_ok() {}
+}
+
+/// Copy of `config` without floorplan images, as stored in versions.
+pub fn without_images(config: &ConfigExport) -> ConfigExport {
+    let mut config = config.clone();
+
+    if let Some(floorplan) = &mut config.floorplan {
+        floorplan.image_data = None;
+        floorplan.image_mime_type = None;
+    }
+    for floorplan in &mut config.floorplans {
+        floorplan.image_data = None;
+        floorplan.image_mime_type = None;
+    }
+
+    config
+}
+
+/// Fills in the floorplan images that `version` leaves out from `current`.
+pub fn with_current_images(mut version: ConfigExport, current: &ConfigExport) -> ConfigExport {
+    if let (Some(floorplan), Some(current)) = (&mut version.floorplan, &current.floorplan) {
+        if floorplan.image_data.is_none() {
+            floorplan.image_data.clone_from(&current.image_data);
+            floorplan
+                .image_mime_type
+                .clone_from(&current.image_mime_type);
+        }
+    }
+
+    for floorplan in &mut version.floorplans {
+        let Some(current) = current.floorplans.iter().find(|f| f.id == floorplan.id) else {
+            continue;
+        };
+        if floorplan.image_data.is_none() {
+            floorplan.image_data.clone_from(&current.image_data);
+            floorplan
+                .image_mime_type
+                .clone_from(&current.image_mime_type);
+        }
+    }
+
+    version
+}
+
+/// Exclusive access to the version history, so that e.g. a rollback can
+/// record versions on both sides of applying a config without the automatic
+/// recorder getting in between.
+pub struct ConfigVersionRecorder(MutexGuard<'static, Option<Value>>);
+
+pub async fn config_version_recorder() -> ConfigVersionRecorder {
+    ConfigVersionRecorder(LATEST_VERSION.lock().await)
+}
+
+impl ConfigVersionRecorder {
+    /// Saves `config` without its floorplan images as a new version, unless
+    /// it equals the latest version or there is no database. Returns the new
+    /// version number.
+    pub async fn record(
+        &mut self,
+        config: &ConfigExport,
+        description: &str,
+    ) -> Result<Option<i32>> {
+        if !is_db_connected() {
+            return Ok(None);
+        }
+
+        // Compared as JSON values, as map fields don't serialize in a stable
+        // order.
+        let config = without_images(config);
+        let value = serde_json::to_value(&config)?;
+
+        if self.0.is_none() {
+            if let Some(latest) = config_queries::db_get_latest_config_version().await? {
+                *self.0 = Some(serde_json::to_value(without_images(&latest.config))?);
+            }
+        }
+
+        if self.0.as_ref() == Some(&value) {
+            return Ok(None);
+        }
+
+        let version = config_queries::db_save_config_version(&config, Some(description)).await?;
+        *self.0 = Some(value);
+
+        if let Err(e) = config_queries::db_prune_config_versions(MAX_CONFIG_VERSIONS).await {
+            warn!("Failed to prune config versions: {e}");
+        }
+
+        Ok(Some(version))
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ChangedEntry {
+    pub id: String,
+    /// Top-level fields of the entry that differ.
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SectionDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedEntry>,
+}
+
+/// Structural diff between two configs, with entries keyed by their id.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ConfigDiff {
+    /// Core settings that differ.
+    pub core: Vec<String>,
+    pub integrations: SectionDiff,
+    pub groups: SectionDiff,
+    pub scenes: SectionDiff,
+    pub routines: SectionDiff,
+    /// Names of the remaining sections that differ, e.g. `floorplans`.
+    pub other: Vec<String>,
+}
+
+pub fn diff_configs(from: &ConfigExport, to: &ConfigExport) -> Result<ConfigDiff> {
+    let mut other = Vec::new();
+    let other_sections = [
+        (
+            "floorplans",
+            serde_json::to_value((&from.floorplan, &from.floorplans))?,
+            serde_json::to_value((&to.floorplan, &to.floorplans))?,
+        ),
+        (
+            "group_positions",
+            serde_json::to_value(&from.group_positions)?,
+            serde_json::to_value(&to.group_positions)?,
+        ),
+        (
+            "device_display_overrides",
+            serde_json::to_value(&from.device_display_overrides)?,
+            serde_json::to_value(&to.device_display_overrides)?,
+        ),
+        (
+            "device_sensor_configs",
+            serde_json::to_value(&from.device_sensor_configs)?,
+            serde_json::to_value(&to.device_sensor_configs)?,
+        ),
+        (
+            "widget_settings",
+            serde_json::to_value(&from.widget_settings)?,
+            serde_json::to_value(&to.widget_settings)?,
+        ),
+        (
+            "dashboards",
+            serde_json::to_value((&from.dashboard_layouts, &from.dashboard_widgets))?,
+            serde_json::to_value((&to.dashboard_layouts, &to.dashboard_widgets))?,
+        ),
+    ];
+    for (name, from, to) in other_sections {
+        if from != to {
+            other.push(name.to_string());
+        }
+    }
+
+    Ok(ConfigDiff {
+        core: changed_fields(
+            &serde_json::to_value(&from.core)?,
+            &serde_json::to_value(&to.core)?,
+        ),
+        integrations: diff_section(&from.integrations, &to.integrations, |i| &i.id)?,
+        groups: diff_section(&from.groups, &to.groups, |g| &g.id)?,
+        scenes: diff_section(&from.scenes, &to.scenes, |s| &s.id)?,
+        routines: diff_section(&from.routines, &to.routines, |r| &r.id)?,
+        other,
+    })
+}
+
+fn diff_section<T: Serialize>(
+    from: &[T],
+    to: &[T],
+    id: impl Fn(&T) -> &String,
+) -> Result<SectionDiff> {
+    let keyed = |entries: &[T]| -> Result<BTreeMap<String, Value>> {
+        entries
+            .iter()
+            .map(|entry| Ok((id(entry).clone(), serde_json::to_value(entry)?)))
+            .collect()
+    };
+    let from = keyed(from)?;
+    let to = keyed(to)?;
+
+    let mut diff = SectionDiff::default();
+    for (id, to_entry) in &to {
+        match from.get(id) {
+            None => diff.added.push(id.clone()),
+            Some(from_entry) if from_entry != to_entry => diff.changed.push(ChangedEntry {
+                id: id.clone(),
+                fields: changed_fields(from_entry, to_entry),
+            }),
+            Some(_) => {}
+        }
+    }
+    diff.removed = from
+        .keys()
+        .filter(|id| !to.contains_key(*id))
+        .cloned()
+        .collect();
+
+    Ok(diff)
+}
+
+fn changed_fields(from: &Value, to: &Value) -> Vec<String> {
+    let (Value::Object(from), Value::Object(to)) = (from, to) else {
+        return Vec::new();
+    };
+
+    from.keys()
+        .chain(to.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| from.get(*key) != to.get(*key))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(value: Value) -> ConfigExport {
+        let mut config = json!({
+            "version": 1,
+            "core": {},
+            "integrations": [],
+            "groups": [],
+            "scenes": [],
+            "routines": [],
+            "floorplan": null,
+            "dashboard_layouts": [],
+            "dashboard_widgets": []
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(value.as_object().unwrap().clone());
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn diffs_sections_by_id() {
+        let from = config(json!({
+            "integrations": [
+                { "id": "mqtt", "plugin": "mqtt", "config": { "host": "a" }, "enabled": true },
+                { "id": "hue", "plugin": "hue", "config": {}, "enabled": true }
+            ],
+            "routines": [
+                { "id": "night", "name": "Night", "enabled": true, "rules": [], "actions": [] }
+            ]
+        }));
+        let to = config(json!({
+            "core": { "latitude": 60.0, "longitude": 25.0 },
+            "integrations": [
+                { "id": "mqtt", "plugin": "mqtt", "config": { "host": "b" }, "enabled": false },
+                { "id": "wled", "plugin": "wled", "config": {}, "enabled": true }
+            ],
+            "routines": [
+                { "id": "night", "name": "Night", "enabled": true, "rules": [], "actions": [] }
+            ],
+            "dashboard_layouts": [{ "id": 1, "name": "Home", "is_default": true }]
+        }));
+
+        let diff = diff_configs(&from, &to).unwrap();
+        assert_eq!(diff.core, vec!["latitude", "longitude"]);
+        assert_eq!(
+            diff.integrations,
+            SectionDiff {
+                added: vec!["wled".to_string()],
+                removed: vec!["hue".to_string()],
+                changed: vec![ChangedEntry {
+                    id: "mqtt".to_string(),
+                    fields: vec!["config".to_string(), "enabled".to_string()],
+                }],
+            }
+        );
+        assert_eq!(diff.routines, SectionDiff::default());
+        assert_eq!(diff.other, vec!["dashboards"]);
+
+        assert_eq!(diff_configs(&to, &to).unwrap(), ConfigDiff::default());
+    }
+
+    #[test]
+    fn versions_leave_out_floorplan_images() {
+        let floorplan = |id: &str, image: Option<Vec<u8>>| {
+            json!({
+                "id": id,
+                "name": id,
+                "image_data": image,
+                "image_mime_type": image.as_ref().map(|_| "image/png"),
+                "width": 10,
+                "height": 20,
+                "grid_data": null
+            })
+        };
+        let current = config(json!({
+            "floorplans": [floorplan("home", Some(vec![1, 2, 3])), floorplan("yard", None)]
+        }));
+
+        let version = without_images(&current);
+        assert!(version.floorplans.iter().all(|f| f.image_data.is_none()));
+        assert_eq!(version.floorplans[0].width, Some(10));
+
+        let mut version = version;
+        version
+            .floorplans
+            .push(serde_json::from_value(floorplan("attic", None)).unwrap());
+        let restored = with_current_images(version, &current);
+        assert_eq!(restored.floorplans[0].image_data, Some(vec![1, 2, 3]));
+        assert_eq!(
+            restored.floorplans[0].image_mime_type.as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(restored.floorplans[1].image_data, None);
+        assert_eq!(restored.floorplans[2].image_data, None);
+    }
+}
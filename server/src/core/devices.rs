@@ -1,5 +1,5 @@
 use crate::db::{
-    actions::{db_get_devices, db_update_device},
+    actions::{db_append_device_history, db_get_devices, db_update_device, DeviceHistoryEntry},
     config_queries::DevicePositionRow,
 };
 use crate::types::integration::IntegrationId;
@@ -162,6 +162,8 @@ pub struct Devices {
     state: DevicesState,
     cli: Cli,
     pending_db_updates: Arc<Mutex<BTreeMap<DeviceKey, Device>>>,
+    pending_history_entries: Arc<Mutex<Vec<DeviceHistoryEntry>>>,
+    history_enabled: Arc<AtomicBool>,
     db_write_flush_pending: Arc<AtomicBool>,
 }
 
@@ -172,16 +174,34 @@ impl Devices {
             state: Default::default(),
             cli: cli.clone(),
             pending_db_updates: Default::default(),
+            pending_history_entries: Default::default(),
+            history_enabled: Arc::new(AtomicBool::new(true)),
             db_write_flush_pending: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    fn schedule_db_update(&self, device: Device) {
+    /// Enables or disables appending state changes to the device history table.
+    pub fn set_history_enabled(&self, enabled: bool) {
+        self.history_enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    fn schedule_db_update(&self, device: Device, record_history: bool) {
         if self.cli.dry_run {
             debug!("(dry run) would store device: {device}");
             return;
         }
 
+        if record_history && self.history_enabled.load(Ordering::SeqCst) {
+            let mut pending = self
+                .pending_history_entries
+                .lock()
+                .expect("pending_history_entries lock poisoned");
+            pending.push(DeviceHistoryEntry {
+                recorded_at_ms: chrono::Utc::now().timestamp_millis(),
+                device: device.clone(),
+            });
+        }
+
         {
             let mut pending = self
                 .pending_db_updates
@@ -195,6 +215,7 @@ impl Devices {
         }
 
         let pending_db_updates = Arc::clone(&self.pending_db_updates);
+        let pending_history_entries = Arc::clone(&self.pending_history_entries);
         let db_write_flush_pending = Arc::clone(&self.db_write_flush_pending);
 
         tokio::spawn(async move {
@@ -208,6 +229,13 @@ impl Devices {
                     std::mem::take(&mut *pending)
                 };
 
+                let pending_history = {
+                    let mut pending = pending_history_entries
+                        .lock()
+                        .expect("pending_history_entries lock poisoned");
+                    std::mem::take(&mut *pending)
+                };
+
                 for (_, device) in pending_devices {
                     if let Err(error) = db_update_device(&device).await {
                         warn!(
@@ -217,6 +245,13 @@ impl Devices {
                     }
                 }
 
+                if let Err(error) = db_append_device_history(&pending_history).await {
+                    warn!(
+                        "Failed to append {} device history entries: {error}",
+                        pending_history.len()
+                    );
+                }
+
                 let should_stop = {
                     let pending = pending_db_updates
                         .lock()
@@ -513,7 +548,7 @@ impl Devices {
         }
 
         if !skip_db_update {
-            self.schedule_db_update(device.clone());
+            self.schedule_db_update(device.clone(), !state_eq);
         }
     }
 
@@ -559,7 +594,7 @@ impl Devices {
         self.state.0.get(device_key)
     }
 
-    fn resolve_scene_devices(&self, request: &ActivateSceneRequest<'_>) -> Option<Vec<Device>> {
+    pub fn resolve_scene_devices(&self, request: &ActivateSceneRequest<'_>) -> Option<Vec<Device>> {
         let scene_devices_config = request.scenes.find_scene_devices_config(
             self,
             request.groups,
@@ -771,6 +806,7 @@ impl Devices {
         Some(true)
     }
 
+    /// Activates the next scene of the cycle and returns it.
     #[allow(clippy::too_many_arguments)]
     pub async fn cycle_scenes(
         &mut self,
@@ -784,7 +820,7 @@ impl Devices {
         rollout_duration_ms: &Option<u64>,
         device_positions: &[DevicePositionRow],
         scenes: &Scenes,
-    ) -> Option<()> {
+    ) -> Option<ActivateSceneDescriptor> {
         let next_scene = {
             get_next_cycled_scene(
                 scene_descriptors,
@@ -812,7 +848,7 @@ impl Devices {
         })
         .await;
 
-        Some(())
+        Some(next_scene)
     }
 
     pub fn get_device_by_ref<'a>(&'a self, device_ref: &DeviceRef) -> Option<&'a Device> {
@@ -842,7 +878,7 @@ mod tests {
     use crate::types::scene::{
         SceneConfig, SceneDeviceConfig, SceneDeviceState, SceneDevicesSearchConfig, ScenesConfig,
     };
-    use crate::utils::cli::Cli;
+    use crate::utils::cli::dry_run_cli;
     use ordered_float::OrderedFloat;
     use serde_json::json;
     use std::{collections::BTreeMap, str::FromStr};
@@ -851,20 +887,9 @@ mod tests {
         DeviceKey::new(IntegrationId::from("dummy".to_string()), DeviceId::new(id))
     }
 
-    fn test_cli() -> Cli {
-        Cli {
-            dry_run: true,
-            port: 45289,
-            database_url: None,
-            config: None,
-            warmup_time: None,
-            command: None,
-        }
-    }
-
     fn test_devices() -> (Devices, RxEventChannel) {
         let (event_tx, event_rx) = mk_event_channel();
-        (Devices::new(event_tx, &test_cli()), event_rx)
+        (Devices::new(event_tx, &dry_run_cli()), event_rx)
     }
 
     fn create_scene_device_config(
@@ -5,6 +5,7 @@ use color_eyre::Result;
 use crate::db::actions::{db_store_scene_overrides, db_store_ui_state};
 use crate::types::{
     action::Action,
+    color::ColorMode,
     device::{Device, DeviceKey, DevicesState},
     dim::DimDescriptor,
     event::*,
@@ -15,12 +16,15 @@ use crate::types::{
         ActivateSceneActionDescriptor, ActivateSceneDescriptor, CycleScenesDescriptor, SceneConfig,
         SceneDevicesConfig, SceneId,
     },
+    sequence::{CancelSequenceDescriptor, SequenceDescriptor},
     ui::UiActionDescriptor,
+    websockets::FeedEvent,
 };
 
 use crate::db::config_queries;
 
 use super::devices::ActivateSceneRequest;
+use super::sequences::actions_pause;
 use super::snapshot::SnapshotChanges;
 use super::state::{AppState, PendingWsUpdate};
 use super::{groups::Groups, integrations::Integrations};
@@ -214,6 +218,8 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
             state.scenes.force_invalidate(&state.devices, &state.groups);
 
             state.refresh_routine_statuses();
+            state.rules.schedule_rule_hold_timers();
+            state.occupancy.update_all(&state.devices);
             let changes = SnapshotChanges::startup_completed();
             state.schedule_ws_broadcast(changes);
             outcome.mark_snapshot_changes(changes);
@@ -261,6 +267,19 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
                     &state.groups,
                 )
                 .await;
+            state
+                .sequences
+                .check_waits(&mut state.rules, &state.devices, &state.groups);
+            state
+                .occupancy
+                .handle_device_update(device_key, &state.devices);
+            state.fades.handle_state_update(device_key, new);
+            state
+                .ws
+                .publish_feed(FeedEvent::DeviceChanged {
+                    device: new.color_to_mode(ColorMode::Hs, true),
+                })
+                .await;
 
             let mut changes = SnapshotChanges {
                 devices: true,
@@ -397,6 +416,7 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
             rollout,
             rollout_source_device_key,
             rollout_duration_ms,
+            fade,
         })) => {
             let device_positions = state.effective_device_positions();
             let resolved_scene_id = resolve_mirrored_scene_id(
@@ -405,20 +425,43 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
                 &state.groups,
                 state.devices.get_state(),
             );
+            let request = ActivateSceneRequest {
+                scene_id: &resolved_scene_id,
+                device_keys,
+                group_keys,
+                use_scene_transition: *use_scene_transition,
+                transition,
+                rollout,
+                rollout_source_device_key,
+                rollout_duration_ms,
+                device_positions: &device_positions,
+                groups: &state.groups,
+                scenes: &state.scenes,
+            };
+
+            if let Some(fade) = fade {
+                info!(
+                    "Fading to scene {resolved_scene_id} over {duration_ms} ms",
+                    duration_ms = fade.duration_ms
+                );
+
+                match state.devices.resolve_scene_devices(&request) {
+                    Some(targets) => {
+                        state
+                            .fades
+                            .start(targets, fade, &mut state.devices, &state.integrations)
+                    }
+                    None => warn!("Could not resolve scene {resolved_scene_id}; fade skipped"),
+                }
+            } else {
+                state.devices.activate_scene(request).await;
+            }
             state
-                .devices
-                .activate_scene(ActivateSceneRequest {
-                    scene_id: &resolved_scene_id,
-                    device_keys,
-                    group_keys,
-                    use_scene_transition: *use_scene_transition,
-                    transition,
-                    rollout,
-                    rollout_source_device_key,
-                    rollout_duration_ms,
-                    device_positions: &device_positions,
-                    groups: &state.groups,
-                    scenes: &state.scenes,
+                .ws
+                .publish_feed(FeedEvent::SceneActivated {
+                    scene_id: resolved_scene_id,
+                    device_keys: device_keys.clone(),
+                    group_keys: group_keys.clone(),
                 })
                 .await;
             outcome.mark_snapshot_changes(SnapshotChanges::devices());
@@ -453,7 +496,7 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
                     }
                 })
                 .collect();
-            state
+            let activated = state
                 .devices
                 .cycle_scenes(
                     &resolved_scenes,
@@ -468,6 +511,16 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
                     &state.scenes,
                 )
                 .await;
+            if let Some(activated) = activated {
+                state
+                    .ws
+                    .publish_feed(FeedEvent::SceneActivated {
+                        scene_id: activated.scene_id,
+                        device_keys: activated.device_keys,
+                        group_keys: activated.group_keys,
+                    })
+                    .await;
+            }
             outcome.mark_snapshot_changes(SnapshotChanges::devices());
         }
         Event::Action(Action::Dim(DimDescriptor {
@@ -537,6 +590,38 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
             state.schedule_ws_broadcast(changes);
             outcome.mark_snapshot_changes(changes);
         }
+        Event::Action(Action::If(descriptor)) => {
+            let condition_matches =
+                state
+                    .rules
+                    .conditions_match(&descriptor.condition, &state.devices, &state.groups);
+            let branch = descriptor.branch(condition_matches);
+
+            if actions_pause(branch) {
+                let sequence = SequenceDescriptor {
+                    actions: branch.clone(),
+                    key: None,
+                };
+                state
+                    .sequences
+                    .start(&sequence, &mut state.rules, &state.devices, &state.groups);
+            } else {
+                for action in branch {
+                    state.event_tx.send(Event::Action(action.clone()));
+                }
+            }
+        }
+        Event::Action(Action::Sequence(descriptor)) => {
+            state
+                .sequences
+                .start(descriptor, &mut state.rules, &state.devices, &state.groups);
+        }
+        Event::Action(Action::CancelSequence(CancelSequenceDescriptor { key })) => {
+            state.sequences.cancel_key(key);
+        }
+        Event::Action(action @ (Action::Delay { .. } | Action::WaitUntil(_))) => {
+            warn!("Ignoring {action:?} outside of a sequence");
+        }
         Event::Action(Action::EvalExpr(expr)) => {
             warn!("Ignoring legacy evalexpr action: {expr}");
         }
@@ -551,6 +636,51 @@ pub async fn handle_event(state: &mut AppState, event: &Event) -> Result<EventOu
                 value: value.clone(),
             });
         }
+        Event::RoutineClockTick | Event::RoutineRuleHoldElapsed => {
+            if state.warming_up {
+                return Ok(outcome);
+            }
+
+            let statuses_changed = if matches!(event, Event::RoutineClockTick) {
+                state.rules.handle_clock_tick(&state.devices, &state.groups)
+            } else {
+                state
+                    .rules
+                    .handle_rule_hold_elapsed(&state.devices, &state.groups)
+            };
+            state
+                .sequences
+                .check_waits(&mut state.rules, &state.devices, &state.groups);
+
+            if statuses_changed {
+                let changes = SnapshotChanges {
+                    routine_statuses: true,
+                    ..SnapshotChanges::none()
+                };
+                state.schedule_ws_broadcast(changes);
+                outcome.mark_snapshot_changes(changes);
+            }
+        }
+        Event::FadeStep { fade_id } => {
+            state.fades.handle_step(*fade_id, &mut state.devices);
+            outcome.mark_snapshot_changes(SnapshotChanges::devices());
+        }
+        Event::OccupancyTimeoutElapsed => {
+            if state.warming_up {
+                return Ok(outcome);
+            }
+
+            state.occupancy.update_all(&state.devices);
+        }
+        Event::SequenceStep { sequence_id, step } => {
+            state.sequences.handle_step(
+                *sequence_id,
+                *step,
+                &mut state.rules,
+                &state.devices,
+                &state.groups,
+            );
+        }
     }
 
     Ok(outcome)
@@ -561,10 +691,13 @@ mod tests {
     use super::{handle_event, DeferredEventWork};
     use crate::core::{
         devices::Devices,
+        fades::Fades,
         groups::Groups,
         integrations::Integrations,
+        occupancy::Occupancy,
         routines::Routines,
         scenes::Scenes,
+        sequences::Sequences,
         snapshot::{new_snapshot_handle, RuntimeSnapshot},
         state::AppState,
         ui::Ui,
@@ -578,26 +711,16 @@ mod tests {
         integration::IntegrationId,
         scene::{SceneConfig, SceneId},
     };
-    use crate::utils::cli::Cli;
+    use crate::utils::cli::dry_run_cli;
     use std::sync::{atomic::AtomicBool, Arc};
     use tokio::sync::Mutex;
 
-    fn test_cli() -> Cli {
-        Cli {
-            dry_run: true,
-            port: 45289,
-            database_url: None,
-            config: None,
-            warmup_time: None,
-            command: None,
-        }
-    }
-
     fn empty_runtime_config() -> ConfigExport {
         ConfigExport {
             version: 1,
             core: CoreConfigRow {
                 warmup_time_seconds: 1,
+                ..Default::default()
             },
             integrations: Vec::new(),
             groups: Vec::new(),
@@ -615,7 +738,7 @@ mod tests {
     }
 
     fn test_state() -> (AppState, crate::types::event::RxEventChannel) {
-        let cli = test_cli();
+        let cli = dry_run_cli();
         let (event_tx, event_rx) = mk_event_channel();
         let runtime_config = empty_runtime_config();
         let devices = Devices::new(event_tx.clone(), &cli);
@@ -636,6 +759,9 @@ mod tests {
             scenes: Scenes::new(Default::default()),
             devices,
             rules: Routines::new(Default::default(), event_tx.clone()),
+            sequences: Sequences::new(event_tx.clone()),
+            occupancy: Occupancy::new(event_tx.clone()),
+            fades: Fades::new(event_tx.clone()),
             event_tx,
             ws: WebSockets::default(),
             ui: Ui::new(),
@@ -0,0 +1,415 @@
+//! Fades driven by homectl for [FadeDescriptor]. Each step sets the expected
+//! state of the device, so any other change to the device state can be told
+//! apart from the fade and cancels it.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ordered_float::OrderedFloat;
+
+use crate::types::{
+    color::{Capabilities, DeviceColor},
+    device::{ControllableState, Device, DeviceData, DeviceKey},
+    event::{Event, TxEventChannel},
+    fade::{Easing, FadeDescriptor},
+};
+
+use super::{devices::Devices, integrations::Integrations};
+
+const DEFAULT_STEP_MS: u64 = 1000;
+
+/// Tolerances used when checking whether a device still has the state that
+/// the fade last set.
+const BRIGHTNESS_TOLERANCE: f32 = 0.02;
+const COLOR_TOLERANCE: f32 = 0.01;
+
+struct RunningFade {
+    id: u64,
+    target: Device,
+    from: ControllableState,
+    to: ControllableState,
+    easing: Easing,
+    started_at: DateTime<Utc>,
+    duration_ms: u64,
+    step_ms: u64,
+    /// State that the device is expected to have, set by the previous step.
+    expected: ControllableState,
+}
+
+pub struct Fades {
+    event_tx: TxEventChannel,
+    next_id: u64,
+    running: BTreeMap<DeviceKey, RunningFade>,
+}
+
+impl Fades {
+    pub fn new(event_tx: TxEventChannel) -> Self {
+        Fades {
+            event_tx,
+            next_id: 1,
+            running: BTreeMap::new(),
+        }
+    }
+
+    /// Starts fading given devices to their target states, replacing any
+    /// running fades of the same devices.
+    pub fn start(
+        &mut self,
+        targets: Vec<Device>,
+        fade: &FadeDescriptor,
+        devices: &mut Devices,
+        integrations: &Integrations,
+    ) {
+        for target in targets {
+            let device_key = target.get_device_key();
+            self.running.remove(&device_key);
+
+            let min_step_ms = integrations
+                .device_update_min_interval(&target.integration_id)
+                .map(|interval| interval.as_millis() as u64)
+                .unwrap_or_default();
+            let step_ms = fade
+                .step_ms
+                .unwrap_or(DEFAULT_STEP_MS)
+                .max(min_step_ms)
+                .max(1);
+
+            let current = devices
+                .get_device(&device_key)
+                .and_then(Device::get_controllable_state)
+                .cloned();
+            let endpoints = current.as_ref().zip(target.get_controllable_state());
+
+            let fade_steps = endpoints
+                .and_then(|(current, target)| fade_endpoints(current, target))
+                .filter(|_| fade.duration_ms > step_ms);
+
+            let (Some(current), Some((from, to))) = (current, fade_steps) else {
+                // Nothing to fade, or short enough for the device to handle
+                let transition = fade.duration_ms as f32 / 1000.0;
+                devices.set_state(&target.set_transition(Some(transition)), false, false);
+                continue;
+            };
+
+            let id = self.next_id;
+            self.next_id += 1;
+
+            self.running.insert(
+                device_key,
+                RunningFade {
+                    id,
+                    target,
+                    from,
+                    to,
+                    easing: fade.easing,
+                    started_at: Utc::now(),
+                    duration_ms: fade.duration_ms,
+                    step_ms,
+                    expected: current,
+                },
+            );
+            schedule_step(&self.event_tx, id, step_ms);
+        }
+    }
+
+    /// Sets the next step of a fade, or the target state once the fade has
+    /// run its duration.
+    pub fn handle_step(&mut self, fade_id: u64, devices: &mut Devices) {
+        let Some(device_key) = self
+            .running
+            .iter()
+            .find(|(_, fade)| fade.id == fade_id)
+            .map(|(device_key, _)| device_key.clone())
+        else {
+            return;
+        };
+
+        if devices.get_device(&device_key).is_none() {
+            self.running.remove(&device_key);
+            return;
+        }
+
+        let Some(fade) = self.running.get_mut(&device_key) else {
+            return;
+        };
+
+        let elapsed_ms = (Utc::now() - fade.started_at).num_milliseconds().max(0) as u64;
+        let step_transition = Some(fade.step_ms as f32 / 1000.0);
+
+        if elapsed_ms >= fade.duration_ms {
+            if let Some(fade) = self.running.remove(&device_key) {
+                devices.set_state(&fade.target.set_transition(step_transition), false, false);
+            }
+            return;
+        }
+
+        let capabilities = match &fade.target.data {
+            DeviceData::Controllable(controllable) => controllable.capabilities.clone(),
+            DeviceData::Sensor(_) => Capabilities::default(),
+        };
+
+        let progress = elapsed_ms as f32 / fade.duration_ms as f32;
+        let mut state = interpolate(&fade.from, &fade.to, progress, fade.easing, &capabilities);
+        state.transition = step_transition.map(OrderedFloat);
+        fade.expected = state.clone();
+
+        let mut device = fade.target.clone();
+        if let DeviceData::Controllable(ref mut controllable) = device.data {
+            controllable.state = state;
+        }
+
+        let next_step_ms = fade.step_ms.min(fade.duration_ms - elapsed_ms);
+        schedule_step(&self.event_tx, fade_id, next_step_ms);
+
+        devices.set_state(&device, false, false);
+    }
+
+    /// Cancels the fade of a device when its state was changed by something
+    /// other than the fade.
+    pub fn handle_state_update(&mut self, device_key: &DeviceKey, device: &Device) {
+        let Some(fade) = self.running.get(device_key) else {
+            return;
+        };
+        let Some(state) = device.get_controllable_state() else {
+            return;
+        };
+
+        if !states_match(state, &fade.expected) {
+            info!("Cancelling fade of {device_key}, device state was changed");
+            self.running.remove(device_key);
+        }
+    }
+}
+
+/// Returns the states a fade interpolates between. Devices that turn on fade
+/// up from zero brightness, and devices that turn off fade down to zero
+/// brightness before powering off.
+fn fade_endpoints(
+    current: &ControllableState,
+    target: &ControllableState,
+) -> Option<(ControllableState, ControllableState)> {
+    let dark = |color: &Option<DeviceColor>| ControllableState {
+        power: true,
+        brightness: Some(OrderedFloat(0.0)),
+        color: color.clone(),
+        transition: None,
+    };
+
+    match (current.power, target.power) {
+        (false, false) => None,
+        (false, true) => Some((dark(&target.color), target.clone())),
+        (true, false) => Some((current.clone(), dark(&current.color))),
+        (true, true) => Some((current.clone(), target.clone())),
+    }
+}
+
+fn interpolate(
+    from: &ControllableState,
+    to: &ControllableState,
+    progress: f32,
+    easing: Easing,
+    capabilities: &Capabilities,
+) -> ControllableState {
+    let t = easing.apply(progress);
+
+    let from_brightness = from.brightness.map_or(1.0, |brightness| brightness.0);
+    let to_brightness = to.brightness.map_or(1.0, |brightness| brightness.0);
+    let brightness = match easing {
+        Easing::Perceptual => lightness_to_brightness(lerp(
+            brightness_to_lightness(from_brightness),
+            brightness_to_lightness(to_brightness),
+            t,
+        )),
+        Easing::Linear | Easing::EaseInOut => lerp(from_brightness, to_brightness, t),
+    };
+
+    let color = match (&from.color, &to.color) {
+        (Some(DeviceColor::Ct(from_ct)), Some(DeviceColor::Ct(to_ct))) => Some(
+            DeviceColor::new_from_ct(lerp(from_ct.ct as f32, to_ct.ct as f32, t).round() as u16),
+        ),
+        (Some(from_color), Some(to_color)) => {
+            let from_xy: palette::Yxy = from_color.into();
+            let to_xy: palette::Yxy = to_color.into();
+            let color =
+                DeviceColor::new_from_xy(lerp(from_xy.x, to_xy.x, t), lerp(from_xy.y, to_xy.y, t));
+
+            Some(
+                color
+                    .to_device_preferred_mode(capabilities)
+                    .unwrap_or(color),
+            )
+        }
+        (_, color) => color.clone(),
+    };
+
+    // Both ends of a fade are powered on, see [fade_endpoints]
+    ControllableState {
+        power: true,
+        brightness: Some(OrderedFloat(brightness.clamp(0.0, 1.0))),
+        color,
+        transition: None,
+    }
+}
+
+fn states_match(state: &ControllableState, expected: &ControllableState) -> bool {
+    if state.power != expected.power {
+        return false;
+    }
+
+    if !state.power {
+        return true;
+    }
+
+    let brightness_matches = match (state.brightness, expected.brightness) {
+        (Some(a), Some(b)) => (a.0 - b.0).abs() <= BRIGHTNESS_TOLERANCE,
+        (a, b) => a == b,
+    };
+
+    let color_matches = match (&state.color, &expected.color) {
+        (Some(a), Some(b)) => {
+            let a: palette::Yxy = a.into();
+            let b: palette::Yxy = b.into();
+            (a.x - b.x).abs() <= COLOR_TOLERANCE && (a.y - b.y).abs() <= COLOR_TOLERANCE
+        }
+        (a, b) => a == b,
+    };
+
+    brightness_matches && color_matches
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+/// CIE 1976 lightness of a relative luminance, both scaled to 0.0 - 1.0.
+fn brightness_to_lightness(brightness: f32) -> f32 {
+    if brightness > 0.008856 {
+        1.16 * brightness.cbrt() - 0.16
+    } else {
+        9.033 * brightness
+    }
+}
+
+fn lightness_to_brightness(lightness: f32) -> f32 {
+    if lightness > 0.08 {
+        ((lightness + 0.16) / 1.16).powi(3)
+    } else {
+        lightness / 9.033
+    }
+}
+
+fn schedule_step(event_tx: &TxEventChannel, fade_id: u64, delay_ms: u64) {
+    let event_tx = event_tx.clone();
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        event_tx.send(Event::FadeStep { fade_id });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fade_endpoints, interpolate, Fades};
+    use crate::core::{devices::Devices, integrations::Integrations};
+    use crate::types::{
+        color::{Capabilities, DeviceColor},
+        device::{ControllableDevice, ControllableState, Device, DeviceData, DeviceId, ManageKind},
+        event::{mk_event_channel, RxEventChannel},
+        fade::{Easing, FadeDescriptor},
+        integration::IntegrationId,
+    };
+    use crate::utils::cli::dry_run_cli;
+    use ordered_float::OrderedFloat;
+
+    fn light_state(power: bool, brightness: f32, color: Option<DeviceColor>) -> ControllableState {
+        ControllableState {
+            power,
+            brightness: Some(OrderedFloat(brightness)),
+            color,
+            transition: None,
+        }
+    }
+
+    fn light(state: ControllableState) -> Device {
+        Device::new(
+            IntegrationId::from("dummy".to_string()),
+            DeviceId::new("light"),
+            "Light".to_string(),
+            DeviceData::Controllable(ControllableDevice::new(
+                None,
+                state.power,
+                state.brightness.map(|brightness| brightness.0),
+                state.color,
+                None,
+                Capabilities::default(),
+                ManageKind::Full,
+            )),
+            None,
+        )
+    }
+
+    #[test]
+    fn interpolates_brightness_and_color_temperature() {
+        let from = light_state(true, 0.2, Some(DeviceColor::new_from_ct(2000)));
+        let to = light_state(true, 1.0, Some(DeviceColor::new_from_ct(4000)));
+        let capabilities = Capabilities::default();
+
+        let halfway = interpolate(&from, &to, 0.5, Easing::Linear, &capabilities);
+        assert!((halfway.brightness.unwrap().0 - 0.6).abs() < 1e-6);
+        assert_eq!(halfway.color, Some(DeviceColor::new_from_ct(3000)));
+
+        let eased = interpolate(&from, &to, 0.25, Easing::EaseInOut, &capabilities);
+        assert!(eased.brightness.unwrap().0 < 0.4);
+
+        let perceptual = interpolate(&from, &to, 0.5, Easing::Perceptual, &capabilities);
+        assert!(perceptual.brightness.unwrap().0 < 0.6);
+    }
+
+    #[test]
+    fn powered_off_devices_fade_through_zero_brightness() {
+        let off = light_state(false, 1.0, None);
+        let on = light_state(true, 0.8, None);
+
+        let (from, to) = fade_endpoints(&off, &on).unwrap();
+        assert_eq!(from, light_state(true, 0.0, None));
+        assert_eq!(to, on);
+
+        let (from, to) = fade_endpoints(&on, &off).unwrap();
+        assert_eq!(from, on);
+        assert_eq!(to, light_state(true, 0.0, None));
+
+        assert!(fade_endpoints(&off, &off).is_none());
+    }
+
+    #[tokio::test]
+    async fn changing_device_state_cancels_fade() {
+        let (event_tx, _event_rx): (_, RxEventChannel) = mk_event_channel();
+        let mut devices = Devices::new(event_tx.clone(), &dry_run_cli());
+        let integrations = Integrations::new(event_tx.clone(), &dry_run_cli());
+        let mut fades = Fades::new(event_tx);
+
+        let current = light(light_state(true, 0.2, None));
+        devices.set_state(&current, true, true);
+
+        let fade = FadeDescriptor {
+            duration_ms: 60_000,
+            easing: Easing::Linear,
+            step_ms: None,
+        };
+        fades.start(
+            vec![light(light_state(true, 1.0, None))],
+            &fade,
+            &mut devices,
+            &integrations,
+        );
+        assert_eq!(fades.running.len(), 1);
+
+        let device_key = current.get_device_key();
+        fades.handle_state_update(&device_key, &current);
+        assert_eq!(fades.running.len(), 1);
+
+        fades.handle_state_update(&device_key, &light(light_state(true, 0.7, None)));
+        assert!(fades.running.is_empty());
+    }
+}
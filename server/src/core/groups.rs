@@ -428,7 +428,7 @@ mod groups_runtime_tests {
     use crate::types::event::mk_event_channel;
     use crate::types::integration::IntegrationId;
     use crate::types::scene::SceneId;
-    use crate::utils::cli::Cli;
+    use crate::utils::cli::dry_run_cli;
 
     use super::*;
 
@@ -468,14 +468,7 @@ mod groups_runtime_tests {
 
     fn populated_groups(devices_vec: Vec<Device>, config: GroupsConfig) -> (Devices, Groups) {
         let (tx, _rx) = mk_event_channel();
-        let cli = Cli {
-            dry_run: true,
-            port: 45289,
-            database_url: None,
-            config: None,
-            warmup_time: None,
-            command: None,
-        };
+        let cli = dry_run_cli();
         let mut devices = Devices::new(tx, &cli);
         for device in devices_vec {
             devices.set_state(&device, true, true);
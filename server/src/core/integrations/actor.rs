@@ -19,7 +19,7 @@ use tokio::{
     time::{sleep_until, timeout, Instant},
 };
 
-use super::command::IntegrationCmd;
+use super::{command::IntegrationCmd, status};
 use crate::types::{
     device::{Device, DeviceKey},
     integration::{
@@ -39,6 +39,7 @@ pub struct IntegrationHandle {
     tx: mpsc::UnboundedSender<IntegrationCmd>,
     pub module_name: String,
     pub config: serde_json::Value,
+    pub device_update_policy: OutboundDeviceUpdatePolicy,
 }
 
 impl IntegrationHandle {
@@ -53,13 +54,14 @@ impl IntegrationHandle {
         tokio::spawn(run_integration_actor(
             integration_id,
             integration,
-            device_update_policy,
+            device_update_policy.clone(),
             rx,
         ));
         IntegrationHandle {
             tx,
             module_name,
             config,
+            device_update_policy,
         }
     }
 
@@ -265,10 +267,17 @@ async fn handle_integration_command(
         IntegrationCmd::Register { done } => {
             let result =
                 run_lifecycle_command(integration_id, "register", integration.register()).await;
+            if let Err(e) = &result {
+                status::mark_integration_failed(integration_id, e);
+            }
             let _ = done.send(result);
         }
         IntegrationCmd::Start { done } => {
             let result = run_lifecycle_command(integration_id, "start", integration.start()).await;
+            match &result {
+                Ok(()) => status::record_integration_success(integration_id),
+                Err(e) => status::mark_integration_failed(integration_id, e),
+            }
             let _ = done.send(result);
         }
         IntegrationCmd::Stop { done } => {
@@ -337,14 +346,20 @@ async fn run_data_plane_command<F>(
 ) where
     F: Future<Output = Result<()>>,
 {
-    match timeout(DATA_PLANE_COMMAND_TIMEOUT, future).await {
-        Ok(Ok(())) => {}
-        Ok(Err(err)) => warn!("Integration {integration_id} {command_name} failed: {err:#}"),
-        Err(_) => warn!(
-            "Integration {integration_id} {command_name} timed out after {:?}",
+    let error = match timeout(DATA_PLANE_COMMAND_TIMEOUT, future).await {
+        Ok(Ok(())) => {
+            status::record_integration_success(integration_id);
+            return;
+        }
+        Ok(Err(err)) => format!("{command_name} failed: {err:#}"),
+        Err(_) => format!(
+            "{command_name} timed out after {:?}",
             DATA_PLANE_COMMAND_TIMEOUT
         ),
-    }
+    };
+
+    warn!("Integration {integration_id} {error}");
+    status::record_integration_error(integration_id, error);
 }
 
 #[cfg(test)]
@@ -1,37 +1,64 @@
 pub mod actor;
 pub mod command;
+pub mod status;
 
 pub use actor::IntegrationHandle;
 
+use crate::core::snapshot::SnapshotHandle;
 use crate::db::config_queries;
 use crate::integrations::cron::Cron;
 use crate::integrations::{
-    circadian::Circadian, dummy::Dummy, mqtt::Mqtt, random::Random, timer::Timer,
+    circadian::Circadian,
+    dummy::Dummy,
+    homeassistant::HomeAssistant,
+    http::Http,
+    hue::Hue,
+    mqtt::{CustomMqttAction, Mqtt},
+    random::Random,
+    timer::{Timer, TimerAction},
+    webhook::Webhook,
+    wled::{Wled, WledAction},
+    zigbee2mqtt::Zigbee2Mqtt,
 };
 use crate::types::{
     device::Device,
-    event::TxEventChannel,
+    event::{mk_event_channel, TxEventChannel},
     integration::{
-        Integration, IntegrationActionPayload, IntegrationConfigFieldKind,
+        Integration, IntegrationActionPayload, IntegrationActionSchema, IntegrationConfigFieldKind,
         IntegrationConfigFieldOption, IntegrationConfigFieldSchema, IntegrationConfigSchema,
         IntegrationId, OutboundDeviceUpdatePolicy,
     },
 };
-use crate::utils::cli::Cli;
+use crate::utils::cli::{dry_run_cli, Cli};
 use color_eyre::Result;
 use eyre::eyre;
 use serde_json::json;
 use std::collections::HashMap;
+use std::time::Duration;
 
 pub type CustomIntegrationsMap = HashMap<IntegrationId, IntegrationHandle>;
 
-const BUILT_IN_PLUGIN_NAMES: [&str; 6] = ["mqtt", "circadian", "cron", "timer", "dummy", "random"];
+pub const BUILT_IN_PLUGIN_NAMES: [&str; 12] = [
+    "mqtt",
+    "http",
+    "hue",
+    "wled",
+    "zigbee2mqtt",
+    "homeassistant",
+    "circadian",
+    "cron",
+    "timer",
+    "dummy",
+    "random",
+    "webhook",
+];
 
 #[derive(Clone)]
 pub struct Integrations {
     custom_integrations: CustomIntegrationsMap,
     event_tx: TxEventChannel,
     cli: Cli,
+    snapshot: Option<SnapshotHandle>,
 }
 
 impl Integrations {
@@ -40,9 +67,16 @@ impl Integrations {
             custom_integrations: Default::default(),
             event_tx,
             cli: cli.clone(),
+            snapshot: None,
         }
     }
 
+    /// Gives integrations loaded from now on read access to the runtime
+    /// snapshot. Only needed by integrations that mirror homectl state.
+    pub fn set_snapshot(&mut self, snapshot: SnapshotHandle) {
+        self.snapshot = Some(snapshot);
+    }
+
     pub async fn load_integration(
         &mut self,
         module_name: &str,
@@ -51,11 +85,20 @@ impl Integrations {
         cli: &Cli,
     ) -> Result<()> {
         info!("loading integration with module_name {module_name}");
+        status::mark_integration_starting(integration_id, module_name);
 
         let event_tx = self.event_tx.clone();
-        let integration =
-            load_custom_integration(module_name, integration_id, config, cli, event_tx)?;
-        let device_update_policy = OutboundDeviceUpdatePolicy::from_config(config)?;
+        let integration = load_custom_integration(
+            module_name,
+            integration_id,
+            config,
+            cli,
+            event_tx,
+            self.snapshot.clone(),
+        )
+        .inspect_err(|e| status::mark_integration_failed(integration_id, e))?;
+        let device_update_policy = OutboundDeviceUpdatePolicy::from_config(config)
+            .inspect_err(|e| status::mark_integration_failed(integration_id, e))?;
 
         let handle = IntegrationHandle::new(
             integration,
@@ -123,6 +166,14 @@ impl Integrations {
         Ok(())
     }
 
+    /// Minimum interval between outbound device updates of the integration,
+    /// if rate limited.
+    pub fn device_update_min_interval(&self, integration_id: &IntegrationId) -> Option<Duration> {
+        self.custom_integrations
+            .get(integration_id)
+            .and_then(|handle| handle.device_update_policy.min_interval())
+    }
+
     pub async fn run_integration_action(
         &self,
         integration_id: &IntegrationId,
@@ -196,6 +247,9 @@ impl Integrations {
             .map(|row| (IntegrationId::from(row.id.clone()), row))
             .collect();
 
+        // Also forgets integrations that failed to load
+        status::retain_integration_statuses(|id| desired.contains_key(id));
+
         let current_ids: Vec<IntegrationId> = self.custom_integrations.keys().cloned().collect();
         for id in &current_ids {
             if !desired.contains_key(id) {
@@ -274,10 +328,43 @@ impl Integrations {
 pub fn integration_config_schemas() -> Vec<IntegrationConfigSchema> {
     BUILT_IN_PLUGIN_NAMES
         .iter()
-        .filter_map(|plugin| integration_config_schema(plugin))
+        .filter_map(|plugin| {
+            let mut schema = integration_config_schema(plugin)?;
+            schema.actions = integration_action_schemas(plugin);
+            Some(schema)
+        })
         .collect()
 }
 
+/// Checks that `payload` can be run by integrations of `plugin`, so that
+/// typos are caught when actions are saved rather than when they run.
+pub fn validate_integration_action(plugin: &str, payload: &IntegrationActionPayload) -> Result<()> {
+    match plugin {
+        "mqtt" | "zigbee2mqtt" => payload.parse::<CustomMqttAction>().map(drop),
+        "timer" => payload.parse::<TimerAction>().map(drop),
+        "wled" => payload.parse::<WledAction>().map(drop),
+        // Both ignore their actions. Dummy integrations also stand in for
+        // real ones in simulation, so they accept the actions of any plugin.
+        "dummy" | "cron" => Ok(()),
+        _ if BUILT_IN_PLUGIN_NAMES.contains(&plugin) => {
+            Err(eyre!("Integrations of plugin {plugin} have no actions"))
+        }
+        _ => Err(eyre!("Unknown module name: {plugin}")),
+    }
+}
+
+/// Checks that `config` is valid for `plugin` by constructing an integration
+/// that is never registered or started.
+pub fn validate_integration_config(
+    plugin: &str,
+    id: &IntegrationId,
+    config: &serde_json::Value,
+) -> Result<()> {
+    let (event_tx, _event_rx) = mk_event_channel();
+
+    load_custom_integration(plugin, id, config, &dry_run_cli(), event_tx, None).map(drop)
+}
+
 fn integration_config_schema(plugin: &str) -> Option<IntegrationConfigSchema> {
     match plugin {
         "mqtt" => Some(schema(
@@ -334,11 +421,108 @@ fn integration_config_schema(plugin: &str) -> Option<IntegrationConfigSchema> {
                     ),
                     "This topic is used for outbound commands. Include the same `{id}` placeholder unless every device should receive commands on one shared topic.",
                 ),
+            ]
+            .into_iter()
+            .chain(json_mapping_config_fields())
+            .collect(),
+        )),
+        "http" => Some(schema(
+            "http",
+            "HTTP",
+            "Poll devices from REST endpoints and send state changes as HTTP requests.",
+            vec![
+                with_help_text(
+                    json_config_field(
+                        "poll",
+                        "Poll endpoints",
+                        true,
+                        "JSON array of endpoints that are polled with GET requests for device state.",
+                        Some(json!([
+                            { "url": "http://inverter.local/api/status", "devices_field": "/inverters" },
+                            { "url": "http://plug.local/rpc/Switch.GetStatus?id=0", "device_id": "desk_plug" }
+                        ])),
+                    ),
+                    "Each endpoint needs a `url`. Use `devices_field` to point at an array of devices in the response, or `device_id` when the endpoint describes a single device without including its id.",
+                ),
+                number_config_field(
+                    "poll_interval_ms",
+                    "Poll interval (ms)",
+                    false,
+                    "How often every endpoint is polled.",
+                    (Some(100.0), None, Some(100.0)),
+                    Some("5000"),
+                ),
+                number_config_field(
+                    "timeout_ms",
+                    "Request timeout (ms)",
+                    false,
+                    "How long to wait for a response to each request.",
+                    (Some(100.0), None, Some(100.0)),
+                    Some("5000"),
+                ),
+                json_config_field(
+                    "headers",
+                    "Headers",
+                    false,
+                    "JSON object of headers sent with every request.",
+                    Some(json!({ "Authorization": "Bearer secret" })),
+                ),
+                with_help_text(
+                    json_config_field(
+                        "set_request",
+                        "Command request",
+                        false,
+                        "Request sent when homectl changes the state of a device. Devices are read-only without it.",
+                        Some(json!({
+                            "method": "POST",
+                            "url": "http://plug.local/rpc/Switch.Set?id=0",
+                            "body_template": { "id": 0 }
+                        })),
+                    ),
+                    "`method` is `PUT` or `POST`. `{id}` and `{name}` placeholders are replaced in `url` and in string values of `body_template`, and the mapped device state is written into the template.",
+                ),
+            ]
+            .into_iter()
+            .chain(json_mapping_config_fields())
+            .collect(),
+        )),
+        "hue" => Some(schema(
+            "hue",
+            "Philips Hue",
+            "Discover lights, rooms, zones and sensors from a Hue bridge using the CLIP v2 API.",
+            vec![
+                text_config_field(
+                    "addr",
+                    "Bridge address",
+                    true,
+                    "Hostname or IP address of the Hue bridge.",
+                    Some("192.168.1.10"),
+                ),
+                with_help_text(
+                    text_config_field(
+                        "bridge_id",
+                        "Bridge id",
+                        true,
+                        "Id of the bridge, used to verify its certificate.",
+                        Some("001788fffe123456"),
+                    ),
+                    "Shown in the Hue app under the bridge settings, and as `bridgeid` at `https://<bridge address>/api/config`.",
+                ),
+                with_help_text(
+                    password_config_field(
+                        "application_key",
+                        "Application key",
+                        true,
+                        "Key used to authenticate against the bridge.",
+                        None,
+                    ),
+                    "Create a key by pressing the link button on the bridge and then sending `POST /api` with `{\"devicetype\": \"homectl\", \"generateclientkey\": true}` to the bridge. Use the returned `username`.",
+                ),
                 select_config_field(
                     "managed",
                     "Management mode",
                     false,
-                    "Controls whether homectl corrects state drift for devices from this integration.",
+                    "Controls whether homectl corrects state drift for lights from this integration. Rooms and zones are never corrected.",
                     vec![
                         option("Full", json!("Full"), Some("Continuously correct state drift.")),
                         option(
@@ -346,100 +530,121 @@ fn integration_config_schema(plugin: &str) -> Option<IntegrationConfigSchema> {
                             json!("Unmanaged"),
                             Some("Send commands without correcting later drift."),
                         ),
-                        option(
-                            "Full read-only",
-                            json!("FullReadOnly"),
-                            Some("Track state but drop outbound commands."),
-                        ),
-                        option(
-                            "Unmanaged read-only",
-                            json!("UnmanagedReadOnly"),
-                            Some("Drop outbound commands and do not correct drift."),
-                        ),
                     ],
                 ),
-                with_help_text(
-                    text_config_field(
-                        "id_field",
-                        "ID field",
-                        false,
-                        "JSON pointer to the device id in incoming payloads.",
-                        Some("/id"),
-                    ),
-                    "JSON pointers start with `/` and follow RFC 6901. For `{ \"device\": { \"id\": \"kitchen\" } }`, use `/device/id`.",
-                ),
-                text_config_field(
-                    "name_field",
-                    "Name field",
+                number_config_field(
+                    "default_transition",
+                    "Default transition",
                     false,
-                    "JSON pointer to the device display name in incoming payloads.",
-                    Some("/name"),
+                    "Transition duration in seconds when none is provided by homectl.",
+                    (Some(0.0), None, Some(0.1)),
+                    Some("0.4"),
                 ),
+            ],
+        )),
+        "wled" => Some(schema(
+            "wled",
+            "WLED",
+            "Control the segments of a WLED LED controller, including its effects, palettes and presets.",
+            vec![
                 text_config_field(
-                    "power_field",
-                    "Power field",
-                    false,
-                    "JSON pointer to the power value in incoming and outgoing payloads.",
-                    Some("/power"),
+                    "host",
+                    "Host",
+                    true,
+                    "Hostname or IP address of the WLED controller.",
+                    Some("192.168.1.20"),
                 ),
-                json_config_field(
-                    "power_on_value",
-                    "Power on value",
+                select_config_field(
+                    "managed",
+                    "Management mode",
                     false,
-                    "Optional JSON value that represents an on state.",
-                    Some(json!(true)),
+                    "Controls whether homectl corrects state drift for segments from this integration.",
+                    vec![
+                        option("Full", json!("Full"), Some("Continuously correct state drift.")),
+                        option(
+                            "Unmanaged",
+                            json!("Unmanaged"),
+                            Some("Send commands without correcting later drift."),
+                        ),
+                    ],
                 ),
-                json_config_field(
-                    "power_off_value",
-                    "Power off value",
+                number_config_field(
+                    "default_transition",
+                    "Default transition",
                     false,
-                    "Optional JSON value that represents an off state.",
-                    Some(json!(false)),
+                    "Transition duration in seconds when none is provided by homectl.",
+                    (Some(0.0), None, Some(0.1)),
+                    Some("0.7"),
                 ),
+            ],
+        )),
+        "zigbee2mqtt" => Some(schema(
+            "zigbee2mqtt",
+            "Zigbee2MQTT",
+            "Discover Zigbee devices and their capabilities from a Zigbee2MQTT bridge.",
+            vec![
                 text_config_field(
-                    "color_field",
-                    "Color field",
-                    false,
-                    "JSON pointer to the color value.",
-                    Some("/color"),
+                    "host",
+                    "Host",
+                    true,
+                    "MQTT broker hostname or IP address.",
+                    Some("mqtt.example.org"),
+                ),
+                number_config_field(
+                    "port",
+                    "Port",
+                    true,
+                    "MQTT broker port.",
+                    (Some(1.0), Some(65535.0), Some(1.0)),
+                    Some("1883"),
                 ),
                 text_config_field(
-                    "brightness_field",
-                    "Brightness field",
+                    "username",
+                    "Username",
                     false,
-                    "JSON pointer to the brightness value.",
-                    Some("/brightness"),
+                    "Optional MQTT username.",
+                    None,
                 ),
-                json_config_field(
-                    "brightness_range",
-                    "Brightness range",
+                password_config_field(
+                    "password",
+                    "Password",
                     false,
-                    "Two-number JSON array describing the source brightness range.",
-                    Some(json!([0, 255])),
+                    "Optional MQTT password.",
+                    None,
                 ),
                 with_help_text(
-                    json_config_field(
-                        "sensor_value_fields",
-                        "Sensor value fields",
+                    text_config_field(
+                        "base_topic",
+                        "Base topic",
                         false,
-                        "JSON array of pointers to sensor values in incoming payloads.",
-                        Some(json!(["/temperature", "/humidity"])),
+                        "Zigbee2MQTT base topic.",
+                        Some("zigbee2mqtt"),
                     ),
-                    "List every numeric or boolean sensor value to keep. Example: `[\"/temperature\", \"/humidity\", \"/occupancy\"]`.",
+                    "Must match `mqtt.base_topic` in the Zigbee2MQTT configuration. Devices are discovered from `<base_topic>/bridge/devices` and keyed by their IEEE address.",
                 ),
-                text_config_field(
-                    "transition_field",
-                    "Transition field",
-                    false,
-                    "JSON pointer to transition/fade duration.",
-                    Some("/transition"),
-                ),
-                json_config_field(
-                    "transition_range",
-                    "Transition range",
+                select_config_field(
+                    "managed",
+                    "Management mode",
                     false,
-                    "Two-number JSON array describing the transition duration range.",
-                    Some(json!([0, 600])),
+                    "Controls whether homectl corrects state drift for devices from this integration.",
+                    vec![
+                        option("Full", json!("Full"), Some("Continuously correct state drift.")),
+                        option(
+                            "Unmanaged",
+                            json!("Unmanaged"),
+                            Some("Send commands without correcting later drift."),
+                        ),
+                        option(
+                            "Full read-only",
+                            json!("FullReadOnly"),
+                            Some("Track state but drop outbound commands."),
+                        ),
+                        option(
+                            "Unmanaged read-only",
+                            json!("UnmanagedReadOnly"),
+                            Some("Drop outbound commands and do not correct drift."),
+                        ),
+                    ],
                 ),
                 number_config_field(
                     "default_transition",
@@ -449,32 +654,69 @@ fn integration_config_schema(plugin: &str) -> Option<IntegrationConfigSchema> {
                     (Some(0.0), None, Some(0.1)),
                     Some("0.6"),
                 ),
+            ],
+        )),
+        "homeassistant" => Some(schema(
+            "homeassistant",
+            "Home Assistant",
+            "Export devices, groups and scenes to Home Assistant using MQTT discovery.",
+            vec![
                 text_config_field(
-                    "capabilities_field",
-                    "Capabilities field",
-                    false,
-                    "JSON pointer to advertised device capabilities.",
-                    Some("/capabilities"),
+                    "host",
+                    "Host",
+                    true,
+                    "MQTT broker hostname or IP address.",
+                    Some("mqtt.example.org"),
                 ),
-                json_config_field(
-                    "capabilities_override",
-                    "Capabilities override",
-                    false,
-                    "Optional capabilities object that overrides discovered capabilities.",
-                    Some(json!({ "xy": true, "hs": false, "rgb": false, "ct": { "start": 2000, "end": 6500 } })),
+                number_config_field(
+                    "port",
+                    "Port",
+                    true,
+                    "MQTT broker port.",
+                    (Some(1.0), Some(65535.0), Some(1.0)),
+                    Some("1883"),
                 ),
                 text_config_field(
-                    "raw_field",
-                    "Raw payload field",
+                    "username",
+                    "Username",
+                    false,
+                    "Optional MQTT username.",
+                    None,
+                ),
+                password_config_field(
+                    "password",
+                    "Password",
                     false,
-                    "JSON pointer to store as raw device metadata.",
-                    Some("/raw"),
+                    "Optional MQTT password.",
+                    None,
+                ),
+                with_help_text(
+                    text_config_field(
+                        "discovery_prefix",
+                        "Discovery prefix",
+                        false,
+                        "Home Assistant MQTT discovery prefix.",
+                        Some("homeassistant"),
+                    ),
+                    "Must match the discovery prefix configured in the Home Assistant MQTT integration.",
+                ),
+                with_help_text(
+                    text_config_field(
+                        "base_topic",
+                        "Base topic",
+                        false,
+                        "Prefix for exported state and command topics.",
+                        Some("homectl"),
+                    ),
+                    "Use a distinct base topic per homectl instance when several publish to the same broker.",
                 ),
-                boolean_config_field(
-                    "include_id_name_in_set_payload",
-                    "Include id/name in command payload",
+                number_config_field(
+                    "publish_interval_ms",
+                    "Publish interval (ms)",
                     false,
-                    "Include device id and name fields when publishing command payloads.",
+                    "How often state changes are published to Home Assistant.",
+                    (Some(50.0), None, Some(50.0)),
+                    Some("500"),
                 ),
             ],
         )),
@@ -672,10 +914,346 @@ fn integration_config_schema(plugin: &str) -> Option<IntegrationConfigSchema> {
                 ),
             ],
         )),
+        "webhook" => Some(schema(
+            "webhook",
+            "Webhook",
+            "POST device changes, routine triggers and scene activations as JSON to external URLs.",
+            vec![
+                with_help_text(
+                    json_config_field(
+                        "webhooks",
+                        "Webhooks",
+                        true,
+                        "JSON object mapping webhook ids to a URL, optional request headers, and the triggers it is called for.",
+                        Some(json!({
+                            "notify": {
+                                "url": "http://localhost:8080/homectl",
+                                "headers": { "Authorization": "Bearer secret" },
+                                "triggers": [
+                                    { "on": "device_changed", "device_keys": ["zigbee2mqtt/Front door"] },
+                                    { "on": "routine_triggered", "routine_ids": ["leave_home"] },
+                                    { "on": "scene_activated" }
+                                ]
+                            }
+                        })),
+                    ),
+                    "Triggers are `device_changed`, `routine_triggered` and `scene_activated`, each optionally limited with `device_keys`, `routine_ids` or `scene_ids`. Recent deliveries are listed at /api/v1/config/webhook-deliveries.",
+                ),
+                number_config_field(
+                    "max_attempts",
+                    "Maximum attempts",
+                    false,
+                    "How many times a delivery is attempted before it is given up.",
+                    (Some(1.0), None, Some(1.0)),
+                    Some("4"),
+                ),
+                number_config_field(
+                    "retry_backoff_ms",
+                    "Retry backoff (ms)",
+                    false,
+                    "Delay before the first retry, doubled for each following retry.",
+                    (Some(0.0), None, Some(100.0)),
+                    Some("1000"),
+                ),
+                number_config_field(
+                    "timeout_ms",
+                    "Request timeout (ms)",
+                    false,
+                    "How long to wait for a response to each attempt.",
+                    (Some(100.0), None, Some(100.0)),
+                    Some("10000"),
+                ),
+                number_config_field(
+                    "max_pending_deliveries",
+                    "Maximum pending deliveries",
+                    false,
+                    "How many deliveries to one webhook may be in flight at once, including ones waiting for a retry. Further deliveries are dropped.",
+                    (Some(1.0), None, Some(1.0)),
+                    Some("100"),
+                ),
+            ],
+        )),
         _ => None,
     }
 }
 
+fn integration_action_schemas(plugin: &str) -> Vec<IntegrationActionSchema> {
+    match plugin {
+        "mqtt" => vec![publish_action_schema(
+            "Publish a retained message to an MQTT topic.",
+            json!({ "topic": "home/hallway/chime", "json": { "ring": true } }),
+        )],
+        "zigbee2mqtt" => vec![publish_action_schema(
+            "Publish a message below the Zigbee2MQTT base topic, for example a bridge request.",
+            json!({ "topic": "bridge/request/permit_join", "json": { "value": true, "time": 120 } }),
+        )],
+        "timer" => vec![action_schema(
+            None,
+            "Start timer",
+            "Start the timer, restarting it if it is already running.",
+            vec![number_config_field(
+                "timeout_ms",
+                "Timeout (ms)",
+                true,
+                "How long the timer runs for.",
+                (Some(0.0), None, Some(100.0)),
+                Some("60000"),
+            )],
+            json!({ "timeout_ms": 60000 }),
+        )],
+        "wled" => {
+            let segment_field = || {
+                number_config_field(
+                    "segment",
+                    "Segment",
+                    false,
+                    "Segment id. Applies to all segments when omitted.",
+                    (Some(0.0), Some(255.0), Some(1.0)),
+                    None,
+                )
+            };
+            let palette_field = |required| {
+                text_config_field(
+                    "palette",
+                    "Palette",
+                    required,
+                    "Palette name or id.",
+                    Some("Ocean"),
+                )
+            };
+            let byte_field = |key, label, description| {
+                number_config_field(
+                    key,
+                    label,
+                    false,
+                    description,
+                    (Some(0.0), Some(255.0), Some(1.0)),
+                    Some("128"),
+                )
+            };
+
+            vec![
+                action_schema(
+                    Some("effect"),
+                    "Effect",
+                    "Start an effect.",
+                    vec![
+                        segment_field(),
+                        text_config_field(
+                            "effect",
+                            "Effect",
+                            true,
+                            "Effect name or id.",
+                            Some("Rainbow"),
+                        ),
+                        byte_field("speed", "Speed", "Effect speed."),
+                        byte_field("intensity", "Intensity", "Effect intensity."),
+                        palette_field(false),
+                    ],
+                    json!({ "action": "effect", "segment": 0, "effect": "Rainbow", "speed": 200 }),
+                ),
+                action_schema(
+                    Some("palette"),
+                    "Palette",
+                    "Change the color palette used by effects.",
+                    vec![segment_field(), palette_field(true)],
+                    json!({ "action": "palette", "palette": "Ocean" }),
+                ),
+                action_schema(
+                    Some("preset"),
+                    "Preset",
+                    "Apply a preset stored on the controller.",
+                    vec![number_config_field(
+                        "preset",
+                        "Preset",
+                        true,
+                        "Preset id.",
+                        (Some(1.0), Some(250.0), Some(1.0)),
+                        Some("1"),
+                    )],
+                    json!({ "action": "preset", "preset": 1 }),
+                ),
+            ]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Both mqtt and zigbee2mqtt integrations publish a `CustomMqttAction`.
+fn publish_action_schema(description: &str, example: serde_json::Value) -> IntegrationActionSchema {
+    action_schema(
+        None,
+        "Publish",
+        description,
+        vec![
+            text_config_field(
+                "topic",
+                "Topic",
+                true,
+                "Topic to publish to.",
+                example["topic"].as_str(),
+            ),
+            json_config_field(
+                "json",
+                "Payload",
+                true,
+                "Published as is when given as a string, otherwise serialized as JSON.",
+                None,
+            ),
+        ],
+        example,
+    )
+}
+
+/// Fields of `JsonDeviceMapping`, shared by integrations that map devices
+/// from arbitrary JSON payloads.
+fn json_mapping_config_fields() -> Vec<IntegrationConfigFieldSchema> {
+    vec![
+        select_config_field(
+            "managed",
+            "Management mode",
+            false,
+            "Controls whether homectl corrects state drift for devices from this integration.",
+            vec![
+                option("Full", json!("Full"), Some("Continuously correct state drift.")),
+                option(
+                    "Unmanaged",
+                    json!("Unmanaged"),
+                    Some("Send commands without correcting later drift."),
+                ),
+                option(
+                    "Full read-only",
+                    json!("FullReadOnly"),
+                    Some("Track state but drop outbound commands."),
+                ),
+                option(
+                    "Unmanaged read-only",
+                    json!("UnmanagedReadOnly"),
+                    Some("Drop outbound commands and do not correct drift."),
+                ),
+            ],
+        ),
+        with_help_text(
+            text_config_field(
+                "id_field",
+                "ID field",
+                false,
+                "JSON pointer to the device id in incoming payloads.",
+                Some("/id"),
+            ),
+            "JSON pointers start with `/` and follow RFC 6901. For `{ \"device\": { \"id\": \"kitchen\" } }`, use `/device/id`.",
+        ),
+        text_config_field(
+            "name_field",
+            "Name field",
+            false,
+            "JSON pointer to the device display name in incoming payloads.",
+            Some("/name"),
+        ),
+        text_config_field(
+            "power_field",
+            "Power field",
+            false,
+            "JSON pointer to the power value in incoming and outgoing payloads.",
+            Some("/power"),
+        ),
+        json_config_field(
+            "power_on_value",
+            "Power on value",
+            false,
+            "Optional JSON value that represents an on state.",
+            Some(json!(true)),
+        ),
+        json_config_field(
+            "power_off_value",
+            "Power off value",
+            false,
+            "Optional JSON value that represents an off state.",
+            Some(json!(false)),
+        ),
+        text_config_field(
+            "color_field",
+            "Color field",
+            false,
+            "JSON pointer to the color value.",
+            Some("/color"),
+        ),
+        text_config_field(
+            "brightness_field",
+            "Brightness field",
+            false,
+            "JSON pointer to the brightness value.",
+            Some("/brightness"),
+        ),
+        json_config_field(
+            "brightness_range",
+            "Brightness range",
+            false,
+            "Two-number JSON array describing the source brightness range.",
+            Some(json!([0, 255])),
+        ),
+        with_help_text(
+            json_config_field(
+                "sensor_value_fields",
+                "Sensor value fields",
+                false,
+                "JSON array of pointers to sensor values in incoming payloads.",
+                Some(json!(["/temperature", "/humidity"])),
+            ),
+            "List every numeric or boolean sensor value to keep. Example: `[\"/temperature\", \"/humidity\", \"/occupancy\"]`.",
+        ),
+        text_config_field(
+            "transition_field",
+            "Transition field",
+            false,
+            "JSON pointer to transition/fade duration.",
+            Some("/transition"),
+        ),
+        json_config_field(
+            "transition_range",
+            "Transition range",
+            false,
+            "Two-number JSON array describing the transition duration range.",
+            Some(json!([0, 600])),
+        ),
+        number_config_field(
+            "default_transition",
+            "Default transition",
+            false,
+            "Default transition duration in seconds when none is provided by homectl.",
+            (Some(0.0), None, Some(0.1)),
+            Some("0.6"),
+        ),
+        text_config_field(
+            "capabilities_field",
+            "Capabilities field",
+            false,
+            "JSON pointer to advertised device capabilities.",
+            Some("/capabilities"),
+        ),
+        json_config_field(
+            "capabilities_override",
+            "Capabilities override",
+            false,
+            "Optional capabilities object that overrides discovered capabilities.",
+            Some(json!({ "xy": true, "hs": false, "rgb": false, "ct": { "start": 2000, "end": 6500 } })),
+        ),
+        text_config_field(
+            "raw_field",
+            "Raw payload field",
+            false,
+            "JSON pointer to store as raw device metadata.",
+            Some("/raw"),
+        ),
+        boolean_config_field(
+            "include_id_name_in_set_payload",
+            "Include id/name in command payload",
+            false,
+            "Include device id and name fields when publishing command payloads.",
+        ),
+    ]
+}
+
 fn schema(
     plugin: &str,
     name: &str,
@@ -689,6 +1267,23 @@ fn schema(
         name: name.to_string(),
         description: description.to_string(),
         fields,
+        actions: Vec::new(),
+    }
+}
+
+fn action_schema(
+    action: Option<&str>,
+    label: &str,
+    description: &str,
+    fields: Vec<IntegrationConfigFieldSchema>,
+    example: serde_json::Value,
+) -> IntegrationActionSchema {
+    IntegrationActionSchema {
+        action: action.map(str::to_string),
+        label: label.to_string(),
+        description: Some(description.to_string()),
+        fields,
+        example,
     }
 }
 
@@ -897,14 +1492,23 @@ fn load_custom_integration(
     config: &serde_json::Value,
     cli: &Cli,
     event_tx: TxEventChannel,
+    snapshot: Option<SnapshotHandle>,
 ) -> Result<Box<dyn Integration>> {
     match module_name {
         "circadian" => Ok(Box::new(Circadian::new(id, config, cli, event_tx)?)),
         "random" => Ok(Box::new(Random::new(id, config, cli, event_tx)?)),
         "dummy" => Ok(Box::new(Dummy::new(id, config, cli, event_tx)?)),
         "mqtt" => Ok(Box::new(Mqtt::new(id, config, cli, event_tx)?)),
+        "http" => Ok(Box::new(Http::new(id, config, cli, event_tx)?)),
+        "hue" => Ok(Box::new(Hue::new(id, config, cli, event_tx)?)),
+        "wled" => Ok(Box::new(Wled::new(id, config, cli, event_tx)?)),
+        "zigbee2mqtt" => Ok(Box::new(Zigbee2Mqtt::new(id, config, cli, event_tx)?)),
+        "homeassistant" => Ok(Box::new(
+            HomeAssistant::new(id, config, cli, event_tx)?.with_snapshot(snapshot),
+        )),
         "timer" => Ok(Box::new(Timer::new(id, config, cli, event_tx)?)),
         "cron" => Ok(Box::new(Cron::new(id, config, cli, event_tx)?)),
+        "webhook" => Ok(Box::new(Webhook::new(id, config, cli, event_tx)?)),
         _ => Err(eyre!("Unknown module name: {module_name}")),
     }
 }
@@ -987,4 +1591,25 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn action_schema_examples_are_valid_actions() {
+        for schema in integration_config_schemas() {
+            for action in &schema.actions {
+                let payload = IntegrationActionPayload::from(action.example.clone());
+                if let Err(e) = validate_integration_action(&schema.plugin, &payload) {
+                    panic!("{} action example is invalid: {e:?}", schema.plugin);
+                }
+            }
+        }
+
+        // Timer payloads used to be strings containing milliseconds
+        let legacy = IntegrationActionPayload::from(json!("1000"));
+        assert!(validate_integration_action("timer", &legacy).is_ok());
+
+        let payload = IntegrationActionPayload::from(json!({ "timeout_ms": 1000 }));
+        assert!(validate_integration_action("dummy", &payload).is_ok());
+        assert!(validate_integration_action("cron", &payload).is_ok());
+        assert!(validate_integration_action("circadian", &payload).is_err());
+    }
 }
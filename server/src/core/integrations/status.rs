@@ -0,0 +1,251 @@
+//! Health of loaded integrations.
+//!
+//! The integration actor reports lifecycle and data-plane outcomes, while
+//! integrations holding a connection report connects, disconnects and
+//! received messages from their own tasks.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+
+use crate::types::{
+    integration::IntegrationId,
+    integration_status::{IntegrationHealth, IntegrationStatus},
+};
+
+/// Plugins that report their connection with `mark_integration_connected`,
+/// and are only connected once they do.
+const CONNECTION_PLUGINS: [&str; 5] = ["mqtt", "zigbee2mqtt", "hue", "wled", "http"];
+
+struct StatusEntry {
+    status: IntegrationStatus,
+    /// Set by `mark_integration_disconnected` until the next connect, so
+    /// that successful commands don't hide a lost connection.
+    disconnected: bool,
+    reports_connection: bool,
+}
+
+static INTEGRATION_STATUSES: Lazy<RwLock<BTreeMap<IntegrationId, StatusEntry>>> =
+    Lazy::new(Default::default);
+
+pub fn integration_statuses() -> Vec<IntegrationStatus> {
+    read_statuses()
+        .values()
+        .map(|entry| entry.status.clone())
+        .collect()
+}
+
+pub fn integration_status(integration_id: &IntegrationId) -> Option<IntegrationStatus> {
+    read_statuses()
+        .get(integration_id)
+        .map(|entry| entry.status.clone())
+}
+
+/// Resets the status of an integration that is being (re)loaded.
+pub fn mark_integration_starting(integration_id: &IntegrationId, plugin: &str) {
+    let status = IntegrationStatus {
+        integration_id: integration_id.clone(),
+        plugin: plugin.to_string(),
+        status: IntegrationHealth::Starting,
+        since: now(),
+        last_error: None,
+        last_error_at: None,
+        reconnect_count: 0,
+        last_message_at: None,
+    };
+
+    write_statuses().insert(
+        integration_id.clone(),
+        StatusEntry {
+            status,
+            disconnected: false,
+            reports_connection: CONNECTION_PLUGINS.contains(&plugin),
+        },
+    );
+}
+
+pub fn mark_integration_failed(integration_id: &IntegrationId, error: impl Display) {
+    update_status(integration_id, |entry| {
+        set_error(&mut entry.status, error);
+        set_health(&mut entry.status, IntegrationHealth::Failed);
+    });
+}
+
+pub fn mark_integration_connected(integration_id: &IntegrationId) {
+    update_status(integration_id, |entry| {
+        if entry.status.status == IntegrationHealth::Failed {
+            return;
+        }
+
+        if entry.disconnected {
+            entry.status.reconnect_count += 1;
+            entry.disconnected = false;
+        }
+        set_health(&mut entry.status, IntegrationHealth::Connected);
+    });
+}
+
+pub fn mark_integration_disconnected(integration_id: &IntegrationId, error: impl Display) {
+    update_status(integration_id, |entry| {
+        if entry.status.status == IntegrationHealth::Failed {
+            return;
+        }
+
+        entry.disconnected = true;
+        set_error(&mut entry.status, error);
+        set_health(&mut entry.status, IntegrationHealth::Degraded);
+    });
+}
+
+/// Records a failed command without the integration losing its connection.
+pub fn record_integration_error(integration_id: &IntegrationId, error: impl Display) {
+    update_status(integration_id, |entry| {
+        set_error(&mut entry.status, error);
+        if entry.status.status != IntegrationHealth::Failed {
+            set_health(&mut entry.status, IntegrationHealth::Degraded);
+        }
+    });
+}
+
+/// Records a successful start or command. Clears `Degraded` caused by
+/// failed commands, but not by a lost connection. Integrations that report
+/// their connection stay `Starting` until they connect.
+pub fn record_integration_success(integration_id: &IntegrationId) {
+    update_status(integration_id, |entry| {
+        let recovers = match entry.status.status {
+            IntegrationHealth::Starting => !entry.reports_connection,
+            IntegrationHealth::Degraded => true,
+            IntegrationHealth::Connected | IntegrationHealth::Failed => false,
+        };
+
+        if recovers && !entry.disconnected {
+            set_health(&mut entry.status, IntegrationHealth::Connected);
+        }
+    });
+}
+
+pub fn record_integration_message(integration_id: &IntegrationId) {
+    update_status(integration_id, |entry| {
+        entry.status.last_message_at = Some(now());
+    });
+}
+
+/// Shared by the integrations built on an MQTT event loop.
+pub fn record_mqtt_notification(
+    integration_id: &IntegrationId,
+    notification: &Result<rumqttc::Event, rumqttc::ConnectionError>,
+) {
+    match notification {
+        Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+            mark_integration_connected(integration_id)
+        }
+        Ok(rumqttc::Event::Incoming(rumqttc::Packet::Publish(_))) => {
+            record_integration_message(integration_id)
+        }
+        Ok(_) => {}
+        Err(e) => mark_integration_disconnected(integration_id, e),
+    }
+}
+
+/// Forgets integrations that are no longer configured.
+pub fn retain_integration_statuses(keep: impl Fn(&IntegrationId) -> bool) {
+    write_statuses().retain(|integration_id, _| keep(integration_id));
+}
+
+fn update_status(integration_id: &IntegrationId, f: impl FnOnce(&mut StatusEntry)) {
+    if let Some(entry) = write_statuses().get_mut(integration_id) {
+        f(entry);
+    }
+}
+
+fn set_health(status: &mut IntegrationStatus, health: IntegrationHealth) {
+    if status.status != health {
+        status.status = health;
+        status.since = now();
+    }
+}
+
+fn set_error(status: &mut IntegrationStatus, error: impl Display) {
+    status.last_error = Some(format!("{error:#}"));
+    status.last_error_at = Some(now());
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339()
+}
+
+fn read_statuses() -> RwLockReadGuard<'static, BTreeMap<IntegrationId, StatusEntry>> {
+    match INTEGRATION_STATUSES.read() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn write_statuses() -> RwLockWriteGuard<'static, BTreeMap<IntegrationId, StatusEntry>> {
+    match INTEGRATION_STATUSES.write() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_connection_lifecycle() {
+        let id = IntegrationId::from("status-test".to_string());
+        mark_integration_starting(&id, "mqtt");
+        record_integration_success(&id);
+        assert_eq!(
+            integration_status(&id).map(|status| status.status),
+            Some(IntegrationHealth::Starting)
+        );
+
+        mark_integration_connected(&id);
+        assert_eq!(
+            integration_status(&id).map(|status| status.status),
+            Some(IntegrationHealth::Connected)
+        );
+
+        // A successful command doesn't hide a lost connection
+        mark_integration_disconnected(&id, "connection refused");
+        record_integration_success(&id);
+        let status = integration_status(&id).unwrap();
+        assert_eq!(status.status, IntegrationHealth::Degraded);
+        assert_eq!(status.last_error.as_deref(), Some("connection refused"));
+
+        mark_integration_connected(&id);
+        record_integration_message(&id);
+        let status = integration_status(&id).unwrap();
+        assert_eq!(status.status, IntegrationHealth::Connected);
+        assert_eq!(status.reconnect_count, 1);
+        assert!(status.last_message_at.is_some());
+
+        mark_integration_failed(&id, "start timed out");
+        mark_integration_connected(&id);
+        assert_eq!(
+            integration_status(&id).map(|status| status.status),
+            Some(IntegrationHealth::Failed)
+        );
+
+        retain_integration_statuses(|other| other != &id);
+        assert!(integration_status(&id).is_none());
+    }
+
+    #[test]
+    fn integrations_without_connection_start_connected() {
+        let id = IntegrationId::from("status-test-dummy".to_string());
+        mark_integration_starting(&id, "dummy");
+        record_integration_success(&id);
+        assert_eq!(
+            integration_status(&id).map(|status| status.status),
+            Some(IntegrationHealth::Connected)
+        );
+
+        retain_integration_statuses(|other| other != &id);
+    }
+}
@@ -1,15 +1,22 @@
+pub mod auth;
 pub mod config;
+pub mod config_files;
+pub mod config_versions;
 pub mod devices;
 pub mod event;
+pub mod fades;
 pub mod groups;
 pub mod integrations;
 pub mod logs;
+pub mod occupancy;
 pub mod routine_history;
 pub mod routines;
 pub mod scenes;
 pub mod scripting;
+pub mod sequences;
 pub mod simulate;
 pub mod snapshot;
 pub mod state;
 pub mod ui;
+pub mod webhook_deliveries;
 pub mod websockets;
@@ -0,0 +1,346 @@
+//! Virtual occupancy sensors for groups with an occupancy config. Each zone
+//! is published as a boolean sensor device under the synthetic `occupancy`
+//! integration, so that routines and scenes can refer to it like any other
+//! sensor.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::Utc;
+use serde_json::json;
+
+use crate::db::config_queries::GroupRow;
+use crate::types::{
+    device::{Device, DeviceData, DeviceId, DeviceKey, SensorDevice},
+    event::{Event, TxEventChannel},
+    group::GroupId,
+    integration::IntegrationId,
+    occupancy::OccupancyConfig,
+};
+
+use super::devices::Devices;
+
+/// Integration id that occupancy sensor devices are published under.
+pub const OCCUPANCY_INTEGRATION_ID: &str = "occupancy";
+
+struct OccupancyZone {
+    name: String,
+    config: OccupancyConfig,
+    occupied: bool,
+    /// Motion was detected while all doors were closed, so someone is still
+    /// inside until a door opens.
+    sealed: bool,
+    motion_active: bool,
+    /// Whether any door is open, `None` until a door sensor has reported.
+    door_open: Option<bool>,
+    /// When motion or a door opening was last seen, in unix milliseconds.
+    last_activity_ms: i64,
+}
+
+struct ZoneUpdate {
+    occupancy_changed: bool,
+    /// The zone is now waiting for its timeout to pass without activity.
+    timeout_started: bool,
+}
+
+impl OccupancyZone {
+    fn new(name: String, config: OccupancyConfig, devices: &Devices, now_ms: i64) -> Self {
+        let door_open = door_open(&config, devices);
+
+        OccupancyZone {
+            name,
+            config,
+            occupied: false,
+            sealed: false,
+            motion_active: false,
+            door_open,
+            last_activity_ms: now_ms,
+        }
+    }
+
+    fn update(&mut self, devices: &Devices, now_ms: i64) -> ZoneUpdate {
+        let was_occupied = self.occupied;
+        let mut timeout_started = false;
+
+        let motion_active = self
+            .config
+            .motion_sensors
+            .iter()
+            .any(|device_key| boolean_sensor_value(devices, device_key) == Some(true));
+        let door_open = door_open(&self.config, devices);
+
+        if door_open == Some(true) && self.door_open == Some(false) {
+            // Someone entered or left, so we can no longer tell whether the
+            // room is empty before the timeout passes
+            self.occupied = true;
+            self.sealed = false;
+            self.last_activity_ms = now_ms;
+            timeout_started = true;
+        }
+
+        if motion_active {
+            self.occupied = true;
+            self.last_activity_ms = now_ms;
+
+            if door_open == Some(false) {
+                self.sealed = true;
+            }
+        } else if self.motion_active {
+            self.last_activity_ms = now_ms;
+            timeout_started = true;
+        }
+
+        self.motion_active = motion_active;
+        self.door_open = door_open;
+
+        let idle_ms = now_ms - self.last_activity_ms;
+        if self.occupied
+            && !self.sealed
+            && !motion_active
+            && idle_ms >= i64::try_from(self.config.timeout_ms).unwrap_or(i64::MAX)
+        {
+            self.occupied = false;
+        }
+
+        ZoneUpdate {
+            occupancy_changed: self.occupied != was_occupied,
+            timeout_started: timeout_started && self.occupied && !self.sealed,
+        }
+    }
+}
+
+pub struct Occupancy {
+    event_tx: TxEventChannel,
+    zones: BTreeMap<GroupId, OccupancyZone>,
+}
+
+impl Occupancy {
+    pub fn new(event_tx: TxEventChannel) -> Self {
+        Occupancy {
+            event_tx,
+            zones: BTreeMap::new(),
+        }
+    }
+
+    /// Loads zones from groups with an occupancy config. Zones whose config
+    /// did not change keep their state. Returns device keys of removed
+    /// zones.
+    pub fn load_config_rows(&mut self, groups: &[GroupRow], devices: &Devices) -> Vec<DeviceKey> {
+        let now_ms = Utc::now().timestamp_millis();
+        let mut zones = BTreeMap::new();
+
+        for group in groups {
+            let Some(config) = &group.occupancy else {
+                continue;
+            };
+            let group_id = GroupId(group.id.clone());
+
+            let zone = match self.zones.remove(&group_id) {
+                Some(mut zone) if zone.config == *config => {
+                    if zone.name != group.name {
+                        zone.name = group.name.clone();
+                        publish(&self.event_tx, &group_id, &zone);
+                    }
+                    zone
+                }
+                _ => {
+                    let mut zone =
+                        OccupancyZone::new(group.name.clone(), config.clone(), devices, now_ms);
+                    let update = zone.update(devices, now_ms);
+                    if update.timeout_started {
+                        schedule_timeout(&self.event_tx, zone.config.timeout_ms);
+                    }
+                    publish(&self.event_tx, &group_id, &zone);
+                    zone
+                }
+            };
+
+            zones.insert(group_id, zone);
+        }
+
+        let removed = std::mem::replace(&mut self.zones, zones);
+        removed.keys().map(occupancy_device_key).collect()
+    }
+
+    /// A device was updated, re-evaluates zones that use it as a sensor.
+    pub fn handle_device_update(&mut self, device_key: &DeviceKey, devices: &Devices) {
+        let now_ms = Utc::now().timestamp_millis();
+
+        for (group_id, zone) in &mut self.zones {
+            let uses_device = zone.config.motion_sensors.contains(device_key)
+                || zone.config.door_sensors.contains(device_key);
+
+            if uses_device {
+                let update = zone.update(devices, now_ms);
+                Self::apply_update(&self.event_tx, group_id, zone, update);
+            }
+        }
+    }
+
+    /// Re-evaluates all zones, used once startup has completed and when a
+    /// zone timeout may have passed.
+    pub fn update_all(&mut self, devices: &Devices) {
+        let now_ms = Utc::now().timestamp_millis();
+
+        for (group_id, zone) in &mut self.zones {
+            let update = zone.update(devices, now_ms);
+            Self::apply_update(&self.event_tx, group_id, zone, update);
+        }
+    }
+
+    fn apply_update(
+        event_tx: &TxEventChannel,
+        group_id: &GroupId,
+        zone: &OccupancyZone,
+        update: ZoneUpdate,
+    ) {
+        if update.timeout_started {
+            schedule_timeout(event_tx, zone.config.timeout_ms);
+        }
+
+        if update.occupancy_changed {
+            info!(
+                "Occupancy of {group_id} changed to {}",
+                if zone.occupied { "occupied" } else { "vacant" }
+            );
+            publish(event_tx, group_id, zone);
+        }
+    }
+}
+
+fn publish(event_tx: &TxEventChannel, group_id: &GroupId, zone: &OccupancyZone) {
+    let device = Device {
+        id: DeviceId::new(&group_id.to_string()),
+        name: format!("{} occupancy", zone.name),
+        integration_id: IntegrationId::from(OCCUPANCY_INTEGRATION_ID.to_string()),
+        data: DeviceData::Sensor(SensorDevice::Boolean {
+            value: zone.occupied,
+        }),
+        raw: Some(json!({
+            "sealed": zone.sealed,
+            "last_activity_ms": zone.last_activity_ms,
+            "timeout_ms": zone.config.timeout_ms,
+        })),
+    };
+
+    event_tx.send(Event::ExternalStateUpdate { device });
+}
+
+fn schedule_timeout(event_tx: &TxEventChannel, timeout_ms: u64) {
+    let event_tx = event_tx.clone();
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(timeout_ms)).await;
+        event_tx.send(Event::OccupancyTimeoutElapsed);
+    });
+}
+
+fn occupancy_device_key(group_id: &GroupId) -> DeviceKey {
+    DeviceKey::new(
+        IntegrationId::from(OCCUPANCY_INTEGRATION_ID.to_string()),
+        DeviceId::new(&group_id.to_string()),
+    )
+}
+
+fn boolean_sensor_value(devices: &Devices, device_key: &DeviceKey) -> Option<bool> {
+    match devices.get_device(device_key)?.get_sensor_state()? {
+        SensorDevice::Boolean { value } => Some(*value),
+        _ => None,
+    }
+}
+
+fn door_open(config: &OccupancyConfig, devices: &Devices) -> Option<bool> {
+    let mut door_values = config
+        .door_sensors
+        .iter()
+        .filter_map(|device_key| boolean_sensor_value(devices, device_key))
+        .peekable();
+
+    door_values.peek()?;
+    Some(door_values.any(|value| value == config.door_open_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OccupancyZone;
+    use crate::core::devices::Devices;
+    use crate::types::device::{Device, DeviceData, DeviceId, DeviceKey, SensorDevice};
+    use crate::types::event::{mk_event_channel, RxEventChannel};
+    use crate::types::integration::IntegrationId;
+    use crate::types::occupancy::OccupancyConfig;
+    use crate::utils::cli::dry_run_cli;
+
+    fn test_devices() -> (Devices, RxEventChannel) {
+        let (event_tx, event_rx) = mk_event_channel();
+        (Devices::new(event_tx, &dry_run_cli()), event_rx)
+    }
+
+    fn sensor_key(id: &str) -> DeviceKey {
+        DeviceKey::new(IntegrationId::from("mqtt".to_string()), DeviceId::new(id))
+    }
+
+    fn set_sensor(devices: &mut Devices, id: &str, value: bool) {
+        let device = Device::new(
+            IntegrationId::from("mqtt".to_string()),
+            DeviceId::new(id),
+            id.to_string(),
+            DeviceData::Sensor(SensorDevice::Boolean { value }),
+            None,
+        );
+        devices.set_state(&device, true, true);
+    }
+
+    fn test_zone(devices: &Devices) -> OccupancyZone {
+        let config = OccupancyConfig {
+            motion_sensors: vec![sensor_key("motion")],
+            door_sensors: vec![sensor_key("door")],
+            door_open_value: false,
+            timeout_ms: 1000,
+        };
+        OccupancyZone::new("Bathroom".to_string(), config, devices, 0)
+    }
+
+    #[test]
+    fn zone_becomes_vacant_after_timeout_without_motion() {
+        let (mut devices, _event_rx) = test_devices();
+        // The door is left open, so the zone never gets sealed
+        set_sensor(&mut devices, "door", false);
+        let mut zone = test_zone(&devices);
+
+        set_sensor(&mut devices, "motion", true);
+        assert!(zone.update(&devices, 100).occupancy_changed);
+        assert!(zone.occupied);
+
+        set_sensor(&mut devices, "motion", false);
+        assert!(zone.update(&devices, 200).timeout_started);
+
+        zone.update(&devices, 1100);
+        assert!(zone.occupied);
+
+        assert!(zone.update(&devices, 1200).occupancy_changed);
+        assert!(!zone.occupied);
+    }
+
+    #[test]
+    fn sealed_zone_stays_occupied_until_door_opens() {
+        let (mut devices, _event_rx) = test_devices();
+        set_sensor(&mut devices, "door", true);
+        let mut zone = test_zone(&devices);
+
+        set_sensor(&mut devices, "motion", true);
+        zone.update(&devices, 100);
+        assert!(zone.sealed);
+
+        set_sensor(&mut devices, "motion", false);
+        assert!(!zone.update(&devices, 200).timeout_started);
+        zone.update(&devices, 60_000);
+        assert!(zone.occupied);
+
+        set_sensor(&mut devices, "door", false);
+        assert!(zone.update(&devices, 60_100).timeout_started);
+        assert!(!zone.sealed);
+
+        zone.update(&devices, 61_100);
+        assert!(!zone.occupied);
+    }
+}
@@ -4,6 +4,7 @@ use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use chrono::Utc;
 use once_cell::sync::Lazy;
+use tokio::sync::broadcast;
 
 use crate::types::{
     device::DeviceKey,
@@ -17,6 +18,13 @@ const MAX_ROUTINE_HISTORY_ENTRIES: usize = 500;
 static ROUTINE_HISTORY_BUFFER: Lazy<RwLock<VecDeque<RoutineHistoryEntry>>> =
     Lazy::new(|| RwLock::new(VecDeque::with_capacity(MAX_ROUTINE_HISTORY_ENTRIES)));
 static NEXT_ROUTINE_HISTORY_ID: AtomicU64 = AtomicU64::new(1);
+static ROUTINE_HISTORY_TX: Lazy<broadcast::Sender<RoutineHistoryEntry>> =
+    Lazy::new(|| broadcast::channel(64).0);
+
+/// Receives history entries as they are recorded.
+pub fn subscribe_routine_history() -> broadcast::Receiver<RoutineHistoryEntry> {
+    ROUTINE_HISTORY_TX.subscribe()
+}
 
 pub fn recent_routine_history() -> Vec<RoutineHistoryEntry> {
     read_history_buffer().iter().cloned().collect()
@@ -38,6 +46,7 @@ pub fn record_rule_match(
         event_source_device_key: event_source_device_key.cloned(),
         action_count,
         status: Some(status.clone()),
+        suppressed_reason: None,
     });
 }
 
@@ -56,6 +65,28 @@ pub fn record_force_trigger(
         event_source_device_key: None,
         action_count,
         status: status.cloned(),
+        suppressed_reason: None,
+    });
+}
+
+pub fn record_suppressed(
+    routine_id: &RoutineId,
+    routine_name: &str,
+    event_source_device_key: Option<&DeviceKey>,
+    action_count: usize,
+    status: &RoutineRuntimeStatus,
+    reason: String,
+) {
+    push_history_entry(RoutineHistoryEntry {
+        id: next_history_id(),
+        timestamp: Utc::now().to_rfc3339(),
+        routine_id: routine_id.clone(),
+        routine_name: routine_name.to_string(),
+        trigger_kind: RoutineHistoryTriggerKind::Suppressed,
+        event_source_device_key: event_source_device_key.cloned(),
+        action_count,
+        status: Some(status.clone()),
+        suppressed_reason: Some(reason),
     });
 }
 
@@ -66,6 +97,9 @@ fn next_history_id() -> String {
 }
 
 fn push_history_entry(entry: RoutineHistoryEntry) {
+    // Fails only when nobody is subscribed
+    let _ = ROUTINE_HISTORY_TX.send(entry.clone());
+
     let mut buffer = write_history_buffer();
     if buffer.len() == MAX_ROUTINE_HISTORY_ENTRIES {
         buffer.pop_front();
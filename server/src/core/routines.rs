@@ -1,9 +1,16 @@
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, Utc};
 use color_eyre::Result;
 use eyre::ContextCompat;
 use regex::Regex;
 use serde_json::Value;
 
-use crate::db::config_queries;
+use crate::db::{
+    actions::{
+        db_delete_routine_rule_hold, db_get_routine_rule_holds, db_upsert_routine_rule_hold,
+        RoutineRuleHoldRow,
+    },
+    config_queries, is_db_connected,
+};
 use crate::types::{
     action::{Action, Actions},
     device::{Device, DeviceKey, SensorDevice},
@@ -12,20 +19,33 @@ use crate::types::{
     group::GroupId,
     routine_status::{RoutineRuntimeStatus, RoutineStatuses, RuleRuntimeStatus},
     rule::{
-        AnyRule, DeviceRule, GroupRule, RawRule, RawRuleOperator, Routine, RoutineId,
-        RoutinesConfig, Rule, ScriptRule, SensorRule, TriggerMode,
+        AnyRule, DeviceRule, GroupRule, IfDescriptor, RawRule, RawRuleOperator, Routine, RoutineId,
+        RoutinesConfig, Rule, Rules, ScriptRule, SensorRule, SunRelation, SunRule, TimeWindow,
+        TimeWindowRule, TriggerMode,
     },
     scene::{ActivateSceneActionDescriptor, CycleScenesDescriptor},
+    sequence::SequenceDescriptor,
 };
+use crate::utils::sun::{sun_event_time, Coordinates};
 use std::{
     collections::{HashMap, HashSet},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
-use super::{devices::Devices, groups::Groups, routine_history, scripting::ScriptEngine};
+use super::{
+    devices::Devices, groups::Groups, routine_history, scripting::ScriptEngine,
+    sequences::actions_pause,
+};
 
 const TRIGGERING_DEVICE_ROLLOUT_SOURCE: &str = "__homectl_runtime__/triggering_device";
 
+/// Routine id that rules of `WaitUntil` actions are evaluated under.
+const WAIT_UNTIL_ROUTINE_ID: &str = "__homectl_runtime__/wait_until";
+
 /// Merges `source_groups` into `group_keys` (without duplicates), sets the
 /// option to `Some(..)` if anything was merged.
 fn merge_source_groups(group_keys: &mut Option<Vec<GroupId>>, source_groups: &[GroupId]) {
@@ -114,12 +134,57 @@ fn expand_action_source_context(
                 *include_source_groups = false;
             }
         }
+        Action::Sequence(SequenceDescriptor { actions, .. }) => {
+            expand_actions_source_context(actions, event_source, groups);
+        }
+        Action::If(IfDescriptor {
+            then, else_actions, ..
+        }) => {
+            expand_actions_source_context(then, event_source, groups);
+            expand_actions_source_context(else_actions, event_source, groups);
+        }
         _ => {}
     }
 
     action
 }
 
+fn expand_actions_source_context(
+    actions: &mut Actions,
+    event_source: Option<&DeviceKey>,
+    groups: &Groups,
+) {
+    *actions = std::mem::take(actions)
+        .into_iter()
+        .map(|action| expand_action_source_context(action, event_source, groups))
+        .collect();
+}
+
+/// Runs routine actions that contain delays or waits as a sequence keyed by
+/// the routine, so that re-triggering the routine restarts the sequence.
+fn sequence_routine_actions(routine_id: &RoutineId, actions: Actions) -> Actions {
+    if !actions_pause(&actions) {
+        return actions;
+    }
+
+    vec![Action::Sequence(SequenceDescriptor {
+        actions,
+        key: Some(format!("routine/{routine_id}")),
+    })]
+}
+
+/// Identifies a duration-qualified rule by its routine and serialized rule,
+/// so that editing a rule resets its hold.
+type RuleHoldKey = (RoutineId, String);
+
+#[derive(Clone, Copy, Debug)]
+struct RuleHold {
+    /// When the rule's condition started holding, in unix milliseconds.
+    since_ms: i64,
+    /// Whether the rule already triggered for this hold.
+    fired: bool,
+}
+
 #[derive(Clone)]
 pub struct Routines {
     config: RoutinesConfig,
@@ -128,6 +193,20 @@ pub struct Routines {
     /// Tracks which (routine_id, device_key) pairs have been triggered.
     /// Used for edge-triggered rules to prevent re-triggering until state changes away.
     prev_edge_triggered: HashSet<(RoutineId, DeviceKey)>,
+    /// Location used by sun rules, from core config.
+    location: Option<Coordinates>,
+    /// Time of the previous clock tick, used to detect time rules that
+    /// started matching in between ticks.
+    last_clock_tick: Option<DateTime<FixedOffset>>,
+    /// Duration-qualified rules whose condition currently holds.
+    rule_holds: HashMap<RuleHoldKey, RuleHold>,
+    /// Hold changes waiting to be persisted, `None` deletes the hold.
+    pending_hold_writes: Arc<Mutex<HashMap<RuleHoldKey, Option<RuleHold>>>>,
+    hold_flush_pending: Arc<AtomicBool>,
+    /// When each routine last triggered, including suppressed triggers.
+    last_triggered_ms: HashMap<RoutineId, i64>,
+    /// When each routine last fired its actions.
+    last_fired_ms: HashMap<RoutineId, i64>,
 }
 
 struct RuleEvaluationContext<'a> {
@@ -135,7 +214,16 @@ struct RuleEvaluationContext<'a> {
     old_event_source: Option<&'a Device>,
     devices: &'a Devices,
     groups: &'a Groups,
+    /// Whether triggered actions get dispatched, so edge, hold and rate
+    /// limit state should be updated.
     update_edge_state: bool,
+    /// Local time that time based rules are evaluated against.
+    now: DateTime<FixedOffset>,
+    /// Whether this evaluation was caused by a clock tick or a rule hold
+    /// timer rather than a device update.
+    time_driven: bool,
+    /// Time of the previous clock tick, only set for clock ticks.
+    previous_clock_tick: Option<DateTime<FixedOffset>>,
 }
 
 impl RuleEvaluationContext<'_> {
@@ -161,6 +249,7 @@ impl RuleRuntimeStatus {
             trigger_match,
             error: None,
             children: None,
+            hold_remaining_ms: None,
         }
     }
 
@@ -174,6 +263,7 @@ impl RuleRuntimeStatus {
             trigger_match,
             error: None,
             children: Some(children),
+            hold_remaining_ms: None,
         }
     }
 
@@ -183,6 +273,7 @@ impl RuleRuntimeStatus {
             trigger_match: false,
             error: Some(error.into()),
             children: None,
+            hold_remaining_ms: None,
         }
     }
 }
@@ -194,9 +285,20 @@ impl Routines {
             event_tx,
             runtime_statuses: Arc::new(RoutineStatuses::default()),
             prev_edge_triggered: HashSet::new(),
+            location: None,
+            last_clock_tick: None,
+            rule_holds: HashMap::new(),
+            pending_hold_writes: Default::default(),
+            hold_flush_pending: Arc::new(AtomicBool::new(false)),
+            last_triggered_ms: HashMap::new(),
+            last_fired_ms: HashMap::new(),
         }
     }
 
+    pub fn set_location(&mut self, location: Option<Coordinates>) {
+        self.location = location;
+    }
+
     pub fn load_config_rows(&mut self, routines: &[config_queries::RoutineRow]) {
         let mut new_config = RoutinesConfig::new();
         for routine in routines {
@@ -215,15 +317,88 @@ impl Routines {
                     name: routine.name.clone(),
                     rules,
                     actions,
+                    cooldown_ms: routine.cooldown_ms.and_then(|ms| u64::try_from(ms).ok()),
+                    debounce_ms: routine.debounce_ms.and_then(|ms| u64::try_from(ms).ok()),
                 },
             );
         }
 
+        // Keep holds of rules that survived the reload
+        let hold_durations = rule_hold_durations(&new_config);
+        let removed_holds: Vec<RuleHoldKey> = self
+            .rule_holds
+            .keys()
+            .filter(|key| !hold_durations.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in removed_holds {
+            self.rule_holds.remove(&key);
+            self.persist_rule_hold(key, None);
+        }
+
+        self.last_triggered_ms
+            .retain(|routine_id, _| new_config.contains_key(routine_id));
+        self.last_fired_ms
+            .retain(|routine_id, _| new_config.contains_key(routine_id));
+
         self.config = new_config;
         self.runtime_statuses = Arc::new(RoutineStatuses::default());
         self.prev_edge_triggered.clear();
     }
 
+    /// Restores holds of duration-qualified rules from the database, so that
+    /// holds started before a restart still elapse on time.
+    pub async fn restore_rule_holds(&mut self) {
+        if !is_db_connected() {
+            return;
+        }
+
+        let rows = match db_get_routine_rule_holds().await {
+            Ok(rows) => rows,
+            Err(e) => {
+                warn!("Failed to restore routine rule holds from DB: {e}");
+                return;
+            }
+        };
+
+        let hold_durations = rule_hold_durations(&self.config);
+        for row in rows {
+            let key = (RoutineId::from(row.routine_id), row.rule_key);
+
+            if hold_durations.contains_key(&key) {
+                let hold = RuleHold {
+                    since_ms: row.since_ms,
+                    fired: row.fired,
+                };
+                self.rule_holds.insert(key, hold);
+            } else {
+                self.persist_rule_hold(key, None);
+            }
+        }
+
+        info!(
+            "Restored {} routine rule holds from DB",
+            self.rule_holds.len()
+        );
+    }
+
+    /// Schedules re-evaluation for every pending rule hold, used once
+    /// startup has completed.
+    pub fn schedule_rule_hold_timers(&self) {
+        let hold_durations = rule_hold_durations(&self.config);
+        let now_ms = Utc::now().timestamp_millis();
+
+        for (key, hold) in &self.rule_holds {
+            let Some(for_ms) = hold_durations.get(key) else {
+                continue;
+            };
+
+            if !hold.fired {
+                self.schedule_rule_hold_timer(remaining_hold_ms(*for_ms, hold.since_ms, now_ms));
+            }
+        }
+    }
+
     /// Hot-reload routines configuration from the database
     pub async fn reload_from_db(&mut self) -> Result<()> {
         let db_routines = config_queries::db_get_routines().await?;
@@ -240,6 +415,9 @@ impl Routines {
             devices,
             groups,
             update_edge_state: false,
+            now: Local::now().fixed_offset(),
+            time_driven: false,
+            previous_clock_tick: None,
         };
         let evaluation = self.evaluate_routines(&ctx);
         self.runtime_statuses = Arc::new(evaluation.statuses);
@@ -268,6 +446,9 @@ impl Routines {
                 devices,
                 groups,
                 update_edge_state: true,
+                now: Local::now().fixed_offset(),
+                time_driven: false,
+                previous_clock_tick: None,
             };
             let evaluation = self.evaluate_routines(&ctx);
             self.runtime_statuses = Arc::new(evaluation.statuses);
@@ -280,25 +461,73 @@ impl Routines {
         }
     }
 
+    /// Periodic clock tick, triggers routines whose time based rules started
+    /// matching since the previous tick. Returns whether any routine status
+    /// changed.
+    pub fn handle_clock_tick(&mut self, devices: &Devices, groups: &Groups) -> bool {
+        let now = Local::now().fixed_offset();
+        let ctx = RuleEvaluationContext {
+            event_source: None,
+            old_event_source: None,
+            devices,
+            groups,
+            update_edge_state: true,
+            now,
+            time_driven: true,
+            previous_clock_tick: self.last_clock_tick.replace(now),
+        };
+
+        self.evaluate_time_driven(&ctx)
+    }
+
+    /// The hold duration of some duration-qualified rule may have elapsed.
+    /// Returns whether any routine status changed.
+    pub fn handle_rule_hold_elapsed(&mut self, devices: &Devices, groups: &Groups) -> bool {
+        let ctx = RuleEvaluationContext {
+            event_source: None,
+            old_event_source: None,
+            devices,
+            groups,
+            update_edge_state: true,
+            now: Local::now().fixed_offset(),
+            time_driven: true,
+            previous_clock_tick: None,
+        };
+
+        self.evaluate_time_driven(&ctx)
+    }
+
+    fn evaluate_time_driven(&mut self, ctx: &RuleEvaluationContext<'_>) -> bool {
+        let evaluation = self.evaluate_routines(ctx);
+
+        for action in evaluation.actions {
+            self.event_tx.send(Event::Action(action));
+        }
+
+        let changed = *self.runtime_statuses != evaluation.statuses;
+        self.runtime_statuses = Arc::new(evaluation.statuses);
+        changed
+    }
+
     pub fn force_trigger_routine(&self, routine_id: &RoutineId) -> Result<()> {
         let routine = self
             .config
             .get(routine_id)
             .with_context(|| eyre!("Routine not found"))?;
 
-        let routine_actions = routine.actions.clone();
+        let routine_actions = sequence_routine_actions(routine_id, routine.actions.clone());
 
         info!(
             "Routine force-triggered: id={} name={:?} actions={}",
             routine_id.0,
             routine.name,
-            routine_actions.len(),
+            routine.actions.len(),
         );
 
         routine_history::record_force_trigger(
             routine_id,
             &routine.name,
-            routine_actions.len(),
+            routine.actions.len(),
             self.runtime_statuses.0.get(routine_id),
         );
 
@@ -309,6 +538,39 @@ impl Routines {
         Ok(())
     }
 
+    /// Whether the condition of `rule` currently matches, used by sequences
+    /// waiting on a rule. Trigger modes and hold durations are ignored.
+    pub fn rule_condition_matches(
+        &mut self,
+        rule: &Rule,
+        devices: &Devices,
+        groups: &Groups,
+    ) -> bool {
+        let ctx = RuleEvaluationContext {
+            event_source: None,
+            old_event_source: None,
+            devices,
+            groups,
+            update_edge_state: false,
+            now: Local::now().fixed_offset(),
+            time_driven: false,
+            previous_clock_tick: None,
+        };
+        let routine_id = RoutineId::from(WAIT_UNTIL_ROUTINE_ID.to_string());
+
+        self.evaluate_rule_status(&routine_id, &without_hold_duration(rule), &ctx)
+            .condition_match
+    }
+
+    /// Whether all of `rules` currently match, used by conditional actions.
+    /// Like routines, an empty list of rules never matches.
+    pub fn conditions_match(&mut self, rules: &Rules, devices: &Devices, groups: &Groups) -> bool {
+        !rules.is_empty()
+            && rules
+                .iter()
+                .all(|rule| self.rule_condition_matches(rule, devices, groups))
+    }
+
     fn evaluate_routines(&mut self, ctx: &RuleEvaluationContext<'_>) -> EvaluationResult {
         let mut triggered_actions = Vec::new();
         let mut routine_statuses = HashMap::new();
@@ -317,9 +579,44 @@ impl Routines {
 
         for routine_id in routine_ids {
             let routine = self.config.get(&routine_id).unwrap().clone();
+            let pending_level_holds = self.pending_level_holds(&routine_id, &routine.rules);
             let status = self.evaluate_routine_status(&routine_id, &routine, ctx);
 
-            if status.will_trigger {
+            // Time driven evaluations have no triggering device, so they may
+            // only trigger routines through their time based rules, or once
+            // when the hold of a `level` rule elapses.
+            let triggered_by_event = !ctx.time_driven
+                || has_timed_trigger(&routine.rules)
+                || pending_level_holds
+                    .iter()
+                    .any(|key| self.rule_holds.get(key).is_some_and(|hold| hold.fired));
+
+            if status.will_trigger && triggered_by_event {
+                // Only evaluations that dispatch actions count towards rate
+                // limits
+                let suppressed_reason = if ctx.update_edge_state {
+                    self.check_rate_limits(&routine_id, &routine, ctx.now.timestamp_millis())
+                } else {
+                    None
+                };
+
+                if let Some(reason) = suppressed_reason {
+                    info!(
+                        "Routine suppressed: id={} name={:?} reason={reason}",
+                        routine_id.0, routine.name,
+                    );
+                    routine_history::record_suppressed(
+                        &routine_id,
+                        &routine.name,
+                        ctx.event_source,
+                        routine.actions.len(),
+                        &status,
+                        reason,
+                    );
+                    routine_statuses.insert(routine_id, status);
+                    continue;
+                }
+
                 info!(
                     "Routine triggered: id={} name={:?} actions={} event_source={:?}",
                     routine_id.0,
@@ -334,9 +631,15 @@ impl Routines {
                     routine.actions.len(),
                     &status,
                 );
-                triggered_actions.extend(routine.actions.iter().cloned().map(|action| {
-                    expand_action_source_context(action, ctx.event_source, ctx.groups)
-                }));
+                let actions = routine
+                    .actions
+                    .iter()
+                    .cloned()
+                    .map(|action| {
+                        expand_action_source_context(action, ctx.event_source, ctx.groups)
+                    })
+                    .collect();
+                triggered_actions.extend(sequence_routine_actions(&routine_id, actions));
             }
 
             routine_statuses.insert(routine_id, status);
@@ -348,6 +651,42 @@ impl Routines {
         }
     }
 
+    /// Applies the routine's debounce and cooldown to a trigger at `now_ms`,
+    /// returning why the trigger should be suppressed.
+    fn check_rate_limits(
+        &mut self,
+        routine_id: &RoutineId,
+        routine: &Routine,
+        now_ms: i64,
+    ) -> Option<String> {
+        let previous_trigger_ms = self.last_triggered_ms.insert(routine_id.clone(), now_ms);
+
+        if let (Some(debounce_ms), Some(previous_trigger_ms)) =
+            (routine.debounce_ms, previous_trigger_ms)
+        {
+            let elapsed_ms = now_ms - previous_trigger_ms;
+            if elapsed_ms < i64::try_from(debounce_ms).unwrap_or(i64::MAX) {
+                return Some(format!(
+                    "debounced, previous trigger {elapsed_ms} ms ago (debounce {debounce_ms} ms)"
+                ));
+            }
+        }
+
+        if let (Some(cooldown_ms), Some(last_fired_ms)) =
+            (routine.cooldown_ms, self.last_fired_ms.get(routine_id))
+        {
+            let elapsed_ms = now_ms - last_fired_ms;
+            if elapsed_ms < i64::try_from(cooldown_ms).unwrap_or(i64::MAX) {
+                return Some(format!(
+                    "cooling down, fired {elapsed_ms} ms ago (cooldown {cooldown_ms} ms)"
+                ));
+            }
+        }
+
+        self.last_fired_ms.insert(routine_id.clone(), now_ms);
+        None
+    }
+
     fn evaluate_routine_status(
         &mut self,
         routine_id: &RoutineId,
@@ -394,7 +733,7 @@ impl Routines {
         rule: &Rule,
         ctx: &RuleEvaluationContext<'_>,
     ) -> Result<RuleRuntimeStatus> {
-        match rule {
+        let status = match rule {
             Rule::Any(AnyRule { any: rules }) => {
                 let children = rules
                     .iter()
@@ -415,6 +754,18 @@ impl Routines {
                 self.evaluate_device_rule_status(routine_id, device_rule, ctx)
             }
             Rule::Group(group_rule) => self.evaluate_group_rule_status(routine_id, group_rule, ctx),
+            Rule::TimeWindow(TimeWindowRule {
+                time_window,
+                trigger_mode,
+            }) => evaluate_time_rule_status(trigger_mode, ctx, |at| {
+                Ok(time_window_matches(time_window, at.naive_local()))
+            }),
+            Rule::Sun(sun_rule) => {
+                let location = self.location;
+                evaluate_time_rule_status(&sun_rule.trigger_mode, ctx, |at| {
+                    sun_rule_matches(sun_rule, at, location)
+                })
+            }
             Rule::EvalExpr(expr) => Err(eyre!(
                 "Legacy evalexpr rules are no longer supported: {expr}"
             )),
@@ -427,7 +778,172 @@ impl Routines {
                     Err(error) => Err(eyre!("Script rule evaluation error: {error}")),
                 }
             }
+        }?;
+
+        Ok(match rule_hold_duration(rule) {
+            Some((for_ms, trigger_mode)) => {
+                self.apply_rule_hold(routine_id, rule, for_ms, trigger_mode, status, ctx)
+            }
+            None => status,
+        })
+    }
+
+    /// Delays matching of a duration-qualified rule until its condition has
+    /// held for `for_ms`. Once elapsed, `pulse` and `edge` rules trigger once
+    /// per hold while `level` rules keep triggering.
+    fn apply_rule_hold(
+        &mut self,
+        routine_id: &RoutineId,
+        rule: &Rule,
+        for_ms: u64,
+        trigger_mode: &TriggerMode,
+        status: RuleRuntimeStatus,
+        ctx: &RuleEvaluationContext<'_>,
+    ) -> RuleRuntimeStatus {
+        let key = (routine_id.clone(), rule_hold_key(rule));
+
+        if !status.condition_match {
+            if self.rule_holds.remove(&key).is_some() {
+                self.persist_rule_hold(key, None);
+            }
+            return status;
+        }
+
+        let now_ms = ctx.now.timestamp_millis();
+        let hold = match self.rule_holds.get(&key) {
+            Some(hold) => *hold,
+            None => {
+                let hold = RuleHold {
+                    since_ms: now_ms,
+                    fired: false,
+                };
+                self.rule_holds.insert(key.clone(), hold);
+                self.persist_rule_hold(key.clone(), Some(hold));
+                if for_ms > 0 {
+                    self.schedule_rule_hold_timer(for_ms);
+                }
+                hold
+            }
+        };
+
+        let remaining_ms = remaining_hold_ms(for_ms, hold.since_ms, now_ms);
+        if remaining_ms > 0 {
+            return RuleRuntimeStatus {
+                hold_remaining_ms: Some(remaining_ms),
+                ..RuleRuntimeStatus::from_match(false, false)
+            };
+        }
+
+        let trigger_match = match trigger_mode {
+            TriggerMode::Level => true,
+            TriggerMode::Pulse | TriggerMode::Edge => !hold.fired,
+        };
+
+        if trigger_match && !hold.fired && ctx.update_edge_state {
+            let hold = RuleHold {
+                fired: true,
+                ..hold
+            };
+            self.rule_holds.insert(key.clone(), hold);
+            self.persist_rule_hold(key, Some(hold));
+        }
+
+        RuleRuntimeStatus::from_match(true, trigger_match)
+    }
+
+    /// Keys of the duration-qualified `level` rules in `rules` whose hold
+    /// has not fired yet.
+    fn pending_level_holds(&self, routine_id: &RoutineId, rules: &Rules) -> Vec<RuleHoldKey> {
+        let mut keys = Vec::new();
+
+        for rule in rules {
+            if let Rule::Any(AnyRule { any }) = rule {
+                keys.extend(self.pending_level_holds(routine_id, any));
+            } else if let Some((_, TriggerMode::Level)) = rule_hold_duration(rule) {
+                let key = (routine_id.clone(), rule_hold_key(rule));
+                if !self.rule_holds.get(&key).is_some_and(|hold| hold.fired) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        keys
+    }
+
+    fn schedule_rule_hold_timer(&self, delay_ms: u64) {
+        let event_tx = self.event_tx.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            event_tx.send(Event::RoutineRuleHoldElapsed);
+        });
+    }
+
+    fn persist_rule_hold(&self, key: RuleHoldKey, hold: Option<RuleHold>) {
+        if !is_db_connected() {
+            return;
+        }
+
+        {
+            let mut pending = self
+                .pending_hold_writes
+                .lock()
+                .expect("pending_hold_writes lock poisoned");
+            pending.insert(key, hold);
+        }
+
+        if self.hold_flush_pending.swap(true, Ordering::SeqCst) {
+            return;
         }
+
+        let pending_hold_writes = Arc::clone(&self.pending_hold_writes);
+        let hold_flush_pending = Arc::clone(&self.hold_flush_pending);
+
+        tokio::spawn(async move {
+            loop {
+                let pending_writes = {
+                    let mut pending = pending_hold_writes
+                        .lock()
+                        .expect("pending_hold_writes lock poisoned");
+                    std::mem::take(&mut *pending)
+                };
+
+                for ((routine_id, rule_key), hold) in pending_writes {
+                    let result = match hold {
+                        Some(hold) => {
+                            db_upsert_routine_rule_hold(&RoutineRuleHoldRow {
+                                routine_id: routine_id.to_string(),
+                                rule_key,
+                                since_ms: hold.since_ms,
+                                fired: hold.fired,
+                            })
+                            .await
+                        }
+                        None => db_delete_routine_rule_hold(&routine_id.0, &rule_key).await,
+                    };
+
+                    if let Err(error) = result {
+                        warn!("Failed to persist rule hold for routine {routine_id}: {error}");
+                    }
+                }
+
+                let should_stop = {
+                    let pending = pending_hold_writes
+                        .lock()
+                        .expect("pending_hold_writes lock poisoned");
+                    if pending.is_empty() {
+                        hold_flush_pending.store(false, Ordering::SeqCst);
+                        true
+                    } else {
+                        false
+                    }
+                };
+
+                if should_stop {
+                    break;
+                }
+            }
+        });
     }
 
     fn evaluate_sensor_rule_status(
@@ -703,6 +1219,156 @@ impl Routines {
     }
 }
 
+/// Whether any time based or duration-qualified rule in `rules` triggers on
+/// its own rather than acting as a plain condition.
+fn has_timed_trigger(rules: &Rules) -> bool {
+    rules.iter().any(|rule| match rule {
+        Rule::TimeWindow(TimeWindowRule { trigger_mode, .. })
+        | Rule::Sun(SunRule { trigger_mode, .. }) => trigger_mode != &TriggerMode::Level,
+        Rule::Any(AnyRule { any }) => has_timed_trigger(any),
+        _ => rule_hold_duration(rule)
+            .is_some_and(|(_, trigger_mode)| trigger_mode != &TriggerMode::Level),
+    })
+}
+
+fn rule_hold_duration(rule: &Rule) -> Option<(u64, &TriggerMode)> {
+    let (for_ms, trigger_mode) = match rule {
+        Rule::Sensor(SensorRule {
+            for_ms,
+            trigger_mode,
+            ..
+        })
+        | Rule::Raw(RawRule {
+            for_ms,
+            trigger_mode,
+            ..
+        })
+        | Rule::Device(DeviceRule {
+            for_ms,
+            trigger_mode,
+            ..
+        })
+        | Rule::Group(GroupRule {
+            for_ms,
+            trigger_mode,
+            ..
+        }) => (for_ms, trigger_mode),
+        _ => return None,
+    };
+
+    Some(((*for_ms)?, trigger_mode))
+}
+
+/// Clones `rule` without its duration qualifiers.
+fn without_hold_duration(rule: &Rule) -> Rule {
+    let mut rule = rule.clone();
+
+    match &mut rule {
+        Rule::Sensor(SensorRule { for_ms, .. })
+        | Rule::Raw(RawRule { for_ms, .. })
+        | Rule::Device(DeviceRule { for_ms, .. })
+        | Rule::Group(GroupRule { for_ms, .. }) => *for_ms = None,
+        Rule::Any(AnyRule { any }) => {
+            *any = any.iter().map(without_hold_duration).collect();
+        }
+        _ => {}
+    }
+
+    rule
+}
+
+fn rule_hold_key(rule: &Rule) -> String {
+    serde_json::to_string(rule).unwrap_or_default()
+}
+
+/// Hold durations of all duration-qualified rules in `config`.
+fn rule_hold_durations(config: &RoutinesConfig) -> HashMap<RuleHoldKey, u64> {
+    fn collect(routine_id: &RoutineId, rules: &Rules, durations: &mut HashMap<RuleHoldKey, u64>) {
+        for rule in rules {
+            if let Rule::Any(AnyRule { any }) = rule {
+                collect(routine_id, any, durations);
+            } else if let Some((for_ms, _)) = rule_hold_duration(rule) {
+                durations.insert((routine_id.clone(), rule_hold_key(rule)), for_ms);
+            }
+        }
+    }
+
+    let mut durations = HashMap::new();
+    for (routine_id, routine) in config {
+        collect(routine_id, &routine.rules, &mut durations);
+    }
+    durations
+}
+
+fn remaining_hold_ms(for_ms: u64, since_ms: i64, now_ms: i64) -> u64 {
+    let held_ms = u64::try_from(now_ms - since_ms).unwrap_or(0);
+    for_ms.saturating_sub(held_ms)
+}
+
+/// Shared trigger logic for rules that only depend on the current time.
+/// There is no device to act as event source, so `edge` and `pulse` rules
+/// trigger on the clock tick during which they started matching.
+fn evaluate_time_rule_status(
+    trigger_mode: &TriggerMode,
+    ctx: &RuleEvaluationContext<'_>,
+    matches_at: impl Fn(&DateTime<FixedOffset>) -> Result<bool>,
+) -> Result<RuleRuntimeStatus> {
+    if !matches_at(&ctx.now)? {
+        return Ok(RuleRuntimeStatus::from_match(false, false));
+    }
+
+    let trigger_match = match trigger_mode {
+        TriggerMode::Level => true,
+        TriggerMode::Pulse | TriggerMode::Edge => match &ctx.previous_clock_tick {
+            Some(previous) => !matches_at(previous)?,
+            None => false,
+        },
+    };
+
+    Ok(RuleRuntimeStatus::from_match(true, trigger_match))
+}
+
+fn time_window_matches(window: &TimeWindow, at: NaiveDateTime) -> bool {
+    let opens_on = |date: NaiveDate| {
+        window
+            .weekdays
+            .as_ref()
+            .map(|weekdays| weekdays.contains(&date.weekday().into()))
+            .unwrap_or(true)
+    };
+    let time = at.time();
+
+    if window.start < window.end {
+        window.start <= time && time < window.end && opens_on(at.date())
+    } else if window.start == window.end || time >= window.start {
+        opens_on(at.date())
+    } else if time < window.end {
+        // Past midnight, the window opened the previous day
+        at.date().pred_opt().is_some_and(opens_on)
+    } else {
+        false
+    }
+}
+
+fn sun_rule_matches(
+    rule: &SunRule,
+    at: &DateTime<FixedOffset>,
+    location: Option<Coordinates>,
+) -> Result<bool> {
+    let location =
+        location.ok_or_else(|| eyre!("Sun rules require latitude and longitude in core config"))?;
+    let date = at.date_naive();
+    let event = sun_event_time(rule.sun, date, location)
+        .ok_or_else(|| eyre!("No {:?} on {date} at the configured location", rule.sun))?;
+    let threshold = event + chrono::Duration::minutes(rule.offset_minutes.into());
+    let at = at.with_timezone(&Utc);
+
+    Ok(match rule.relation {
+        SunRelation::After => at >= threshold,
+        SunRelation::Before => at < threshold,
+    })
+}
+
 /// Helper function to check if a device matches scene/power criteria.
 fn check_device_state_matches(
     device: &Device,
@@ -830,7 +1496,8 @@ fn is_json_truthy(value: &Value) -> bool {
 #[cfg(test)]
 mod tests {
     use super::{
-        evaluate_raw_rule_match, expand_action_source_context, Routines, RuleEvaluationContext,
+        evaluate_raw_rule_match, expand_action_source_context, time_window_matches, Routines,
+        RuleEvaluationContext,
     };
     use crate::core::{devices::Devices, groups::Groups};
     use crate::types::action::{Action, Actions};
@@ -839,24 +1506,15 @@ mod tests {
     use crate::types::group::GroupsConfig;
     use crate::types::integration::IntegrationId;
     use crate::types::rule::{
-        RawRule, RawRuleOperator, Routine, RoutineId, RoutinesConfig, Rule, TriggerMode,
+        RawRule, RawRuleOperator, Routine, RoutineId, RoutinesConfig, Rule, TimeWindow,
+        TimeWindowRule, TriggerMode, Weekday,
     };
-    use crate::utils::cli::Cli;
+    use crate::utils::cli::dry_run_cli;
+    use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, NaiveTime};
     use jsonptr::PointerBuf;
     use serde_json::json;
     use std::str::FromStr;
 
-    fn test_cli() -> Cli {
-        Cli {
-            dry_run: true,
-            port: 45289,
-            database_url: None,
-            config: None,
-            warmup_time: None,
-            command: None,
-        }
-    }
-
     fn sensor_device(raw: serde_json::Value) -> Device {
         Device::new(
             IntegrationId::from("mqtt".to_string()),
@@ -875,6 +1533,7 @@ mod tests {
             operator,
             value,
             trigger_mode: TriggerMode::Pulse,
+            for_ms: None,
             device_ref: DeviceRef::new_with_id(
                 IntegrationId::from("mqtt".to_string()),
                 DeviceId::new("sensor"),
@@ -884,7 +1543,7 @@ mod tests {
 
     fn test_devices() -> (Devices, RxEventChannel) {
         let (event_tx, _event_rx) = mk_event_channel();
-        (Devices::new(event_tx, &test_cli()), _event_rx)
+        (Devices::new(event_tx, &dry_run_cli()), _event_rx)
     }
 
     fn test_routines(rule: Rule) -> (Routines, RoutineId, RxEventChannel) {
@@ -897,6 +1556,8 @@ mod tests {
                 name: "Raw rule".to_string(),
                 rules: vec![rule],
                 actions: Actions::default(),
+                cooldown_ms: None,
+                debounce_ms: None,
             },
         );
         (Routines::new(config, event_tx), routine_id, event_rx)
@@ -921,9 +1582,40 @@ mod tests {
             devices,
             groups,
             update_edge_state: true,
+            now: Local::now().fixed_offset(),
+            time_driven: false,
+            previous_clock_tick: None,
+        }
+    }
+
+    fn clock_tick_ctx<'a>(
+        now: &str,
+        previous: Option<&str>,
+        devices: &'a Devices,
+        groups: &'a Groups,
+    ) -> RuleEvaluationContext<'a> {
+        let parse = |time: &str| DateTime::<FixedOffset>::parse_from_rfc3339(time).unwrap();
+
+        RuleEvaluationContext {
+            event_source: None,
+            old_event_source: None,
+            devices,
+            groups,
+            update_edge_state: false,
+            now: parse(now),
+            time_driven: true,
+            previous_clock_tick: previous.map(parse),
         }
     }
 
+    fn hh_mm(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    fn local_time(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
     #[test]
     fn raw_rule_matches_numeric_thresholds() {
         let rule = raw_rule(RawRuleOperator::Gt, Some(json!(20)));
@@ -939,6 +1631,7 @@ mod tests {
             operator: RawRuleOperator::Regex,
             value: Some(json!("^button_(press|hold)$")),
             trigger_mode: TriggerMode::Pulse,
+            for_ms: None,
             device_ref: DeviceRef::new_with_id(
                 IntegrationId::from("mqtt".to_string()),
                 DeviceId::new("sensor"),
@@ -992,6 +1685,215 @@ mod tests {
         assert!(retriggered.trigger_match);
     }
 
+    #[test]
+    fn overnight_time_windows_wrap_past_midnight() {
+        let window = TimeWindow {
+            start: hh_mm("22:00"),
+            end: hh_mm("06:30"),
+            weekdays: None,
+        };
+
+        assert!(time_window_matches(&window, local_time("2024-06-21 23:15")));
+        assert!(time_window_matches(&window, local_time("2024-06-22 05:00")));
+        assert!(!time_window_matches(
+            &window,
+            local_time("2024-06-22 06:30")
+        ));
+        assert!(!time_window_matches(
+            &window,
+            local_time("2024-06-22 12:00")
+        ));
+    }
+
+    #[test]
+    fn time_window_weekdays_apply_to_the_opening_day() {
+        // 2024-06-21 is a Friday
+        let window = TimeWindow {
+            start: hh_mm("22:00"),
+            end: hh_mm("02:00"),
+            weekdays: Some(vec![Weekday::Fri]),
+        };
+
+        assert!(time_window_matches(&window, local_time("2024-06-21 22:30")));
+        assert!(time_window_matches(&window, local_time("2024-06-22 01:00")));
+        assert!(!time_window_matches(
+            &window,
+            local_time("2024-06-22 22:30")
+        ));
+        assert!(!time_window_matches(
+            &window,
+            local_time("2024-06-21 01:00")
+        ));
+    }
+
+    #[test]
+    fn edge_time_rules_trigger_once_when_window_opens() {
+        let groups = Groups::new(GroupsConfig::default());
+        let (devices, _device_events) = test_devices();
+        let rule = Rule::TimeWindow(TimeWindowRule {
+            time_window: TimeWindow {
+                start: hh_mm("07:00"),
+                end: hh_mm("08:00"),
+                weekdays: None,
+            },
+            trigger_mode: TriggerMode::Edge,
+        });
+        let (mut routines, routine_id, _routine_events) = test_routines(rule.clone());
+
+        let first_tick = clock_tick_ctx("2024-06-21T07:00:00+03:00", None, &devices, &groups);
+        let first = routines.evaluate_rule_status(&routine_id, &rule, &first_tick);
+        assert!(first.condition_match);
+        assert!(!first.trigger_match);
+
+        let opened = clock_tick_ctx(
+            "2024-06-21T07:00:00+03:00",
+            Some("2024-06-21T06:59:00+03:00"),
+            &devices,
+            &groups,
+        );
+        let opened = routines.evaluate_rule_status(&routine_id, &rule, &opened);
+        assert!(opened.condition_match);
+        assert!(opened.trigger_match);
+
+        let still_open = clock_tick_ctx(
+            "2024-06-21T07:01:00+03:00",
+            Some("2024-06-21T07:00:00+03:00"),
+            &devices,
+            &groups,
+        );
+        let still_open = routines.evaluate_rule_status(&routine_id, &rule, &still_open);
+        assert!(still_open.condition_match);
+        assert!(!still_open.trigger_match);
+    }
+
+    #[tokio::test]
+    async fn level_duration_rules_trigger_when_hold_elapses() {
+        let groups = Groups::new(GroupsConfig::default());
+        let rule = Rule::Raw(RawRule {
+            trigger_mode: TriggerMode::Level,
+            for_ms: Some(60_000),
+            ..raw_rule(RawRuleOperator::Gt, Some(json!(20)))
+        });
+        let (mut routines, routine_id, _routine_events) = test_routines(rule);
+        routines.config.get_mut(&routine_id).unwrap().actions = vec![Action::Delay { ms: 0 }];
+        let (mut devices, _device_events) = test_devices();
+        let device_key = sensor_key();
+
+        let cold_device = sensor_device(json!({ "payload": { "temperature": 18 } }));
+        let warm_device = sensor_device(json!({ "payload": { "temperature": 21 } }));
+        devices.set_state(&warm_device, true, true);
+
+        let parse = |time: &str| DateTime::<FixedOffset>::parse_from_rfc3339(time).unwrap();
+        let mut ctx = rule_eval_ctx(Some(&cold_device), &device_key, &devices, &groups);
+        ctx.now = parse("2024-06-21T12:00:00+00:00");
+        assert!(routines.evaluate_routines(&ctx).actions.is_empty());
+
+        let hold_elapsed_ctx = |now: &str| RuleEvaluationContext {
+            event_source: None,
+            old_event_source: None,
+            devices: &devices,
+            groups: &groups,
+            update_edge_state: true,
+            now: parse(now),
+            time_driven: true,
+            previous_clock_tick: None,
+        };
+        let elapsed = routines.evaluate_routines(&hold_elapsed_ctx("2024-06-21T12:01:00+00:00"));
+        assert!(!elapsed.actions.is_empty());
+
+        let later_tick = routines.evaluate_routines(&hold_elapsed_ctx("2024-06-21T12:02:00+00:00"));
+        assert!(later_tick.actions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn duration_rules_match_once_condition_has_held() {
+        let groups = Groups::new(GroupsConfig::default());
+        let rule = Rule::Raw(RawRule {
+            for_ms: Some(60_000),
+            ..raw_rule(RawRuleOperator::Gt, Some(json!(20)))
+        });
+        let (mut routines, routine_id, _routine_events) = test_routines(rule.clone());
+        let (mut devices, _device_events) = test_devices();
+        let device_key = sensor_key();
+
+        let cold_device = sensor_device(json!({ "payload": { "temperature": 18 } }));
+        let warm_device = sensor_device(json!({ "payload": { "temperature": 21 } }));
+        devices.set_state(&warm_device, true, true);
+
+        let evaluate_at = |routines: &mut Routines, devices: &Devices, now: &str| {
+            let mut ctx = rule_eval_ctx(Some(&cold_device), &device_key, devices, &groups);
+            ctx.now = DateTime::<FixedOffset>::parse_from_rfc3339(now).unwrap();
+            routines.evaluate_rule_status(&routine_id, &rule, &ctx)
+        };
+
+        let started = evaluate_at(&mut routines, &devices, "2024-06-21T12:00:00+00:00");
+        assert!(!started.condition_match);
+        assert_eq!(started.hold_remaining_ms, Some(60_000));
+
+        let holding = evaluate_at(&mut routines, &devices, "2024-06-21T12:00:30+00:00");
+        assert!(!holding.trigger_match);
+        assert_eq!(holding.hold_remaining_ms, Some(30_000));
+
+        let elapsed = evaluate_at(&mut routines, &devices, "2024-06-21T12:01:00+00:00");
+        assert!(elapsed.condition_match);
+        assert!(elapsed.trigger_match);
+        assert_eq!(elapsed.hold_remaining_ms, None);
+
+        let still_held = evaluate_at(&mut routines, &devices, "2024-06-21T12:02:00+00:00");
+        assert!(still_held.condition_match);
+        assert!(!still_held.trigger_match);
+
+        devices.set_state(&cold_device, true, true);
+        let cleared = evaluate_at(&mut routines, &devices, "2024-06-21T12:03:00+00:00");
+        assert!(!cleared.condition_match);
+        assert_eq!(cleared.hold_remaining_ms, None);
+
+        devices.set_state(&warm_device, true, true);
+        let restarted = evaluate_at(&mut routines, &devices, "2024-06-21T12:04:00+00:00");
+        assert_eq!(restarted.hold_remaining_ms, Some(60_000));
+    }
+
+    #[test]
+    fn cooldown_suppresses_triggers_until_elapsed() {
+        let rule = raw_rule(RawRuleOperator::Exists, None);
+        let (mut routines, routine_id, _routine_events) = test_routines(Rule::Raw(rule));
+        let routine = Routine {
+            cooldown_ms: Some(1_000),
+            ..routines.config[&routine_id].clone()
+        };
+
+        assert_eq!(routines.check_rate_limits(&routine_id, &routine, 0), None);
+        assert!(routines
+            .check_rate_limits(&routine_id, &routine, 500)
+            .is_some_and(|reason| reason.starts_with("cooling down")));
+        assert_eq!(
+            routines.check_rate_limits(&routine_id, &routine, 1_000),
+            None
+        );
+    }
+
+    #[test]
+    fn debounce_keeps_suppressing_while_triggers_continue() {
+        let rule = raw_rule(RawRuleOperator::Exists, None);
+        let (mut routines, routine_id, _routine_events) = test_routines(Rule::Raw(rule));
+        let routine = Routine {
+            debounce_ms: Some(1_000),
+            ..routines.config[&routine_id].clone()
+        };
+
+        assert_eq!(routines.check_rate_limits(&routine_id, &routine, 0), None);
+        assert!(routines
+            .check_rate_limits(&routine_id, &routine, 800)
+            .is_some_and(|reason| reason.starts_with("debounced")));
+        assert!(routines
+            .check_rate_limits(&routine_id, &routine, 1_600)
+            .is_some());
+        assert_eq!(
+            routines.check_rate_limits(&routine_id, &routine, 2_600),
+            None
+        );
+    }
+
     #[test]
     fn expand_action_source_context_merges_memberships_into_activate_scene() {
         use crate::types::group::{GroupConfig, GroupLink};
@@ -1056,6 +1958,7 @@ mod tests {
             rollout: None::<RolloutStyle>,
             rollout_source_device_key: None,
             rollout_duration_ms: None,
+            fade: None,
         });
 
         let expanded = expand_action_source_context(action, Some(&switch_key), &groups);
@@ -1092,6 +1995,7 @@ mod tests {
             rollout: None,
             rollout_source_device_key: None,
             rollout_duration_ms: None,
+            fade: None,
         });
 
         let expanded = expand_action_source_context(
@@ -1132,6 +2036,7 @@ mod tests {
                 DeviceId::new("triggering_device"),
             )),
             rollout_duration_ms: Some(1500),
+            fade: None,
         });
 
         let expanded = expand_action_source_context(
@@ -1158,26 +1158,15 @@ mod tests {
                 ScenesConfig,
             },
         },
-        utils::cli::Cli,
+        utils::cli::dry_run_cli,
     };
 
     use super::{extract_bracket_string_refs, normalize_scene_script_config_value, Scenes};
     use serde_json::json;
 
-    fn test_cli() -> Cli {
-        Cli {
-            dry_run: true,
-            port: 45289,
-            database_url: None,
-            config: None,
-            warmup_time: None,
-            command: None,
-        }
-    }
-
     fn test_devices() -> (Devices, RxEventChannel) {
         let (event_tx, event_rx) = mk_event_channel();
-        (Devices::new(event_tx, &test_cli()), event_rx)
+        (Devices::new(event_tx, &dry_run_cli()), event_rx)
     }
 
     fn create_test_device(integration_id: &str, device_id: &str) -> Device {
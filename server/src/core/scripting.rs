@@ -4,7 +4,7 @@
 //! the previous evalexpr-based expression evaluation. It exposes device, group,
 //! and scene state to scripts for dynamic automation logic.
 
-use boa_engine::{Context, Source};
+use boa_engine::{Context, Script, Source};
 use color_eyre::Result;
 use std::collections::HashMap;
 
@@ -154,6 +154,20 @@ impl ScriptEngine {
         self.eval_boolean(script)
     }
 
+    /// Parse a script without evaluating it
+    pub fn check_syntax(&mut self, script: &str) -> Result<()> {
+        Script::parse(Source::from_bytes(script), None, &mut self.context)
+            .map_err(|e| eyre::eyre!("JS syntax error: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Parse a scene script without evaluating it
+    pub fn check_scene_script_syntax(&mut self, script: &str) -> Result<()> {
+        // Scene scripts are evaluated as expressions, see `eval_json`
+        self.check_syntax(&format!("JSON.stringify({})", script))
+    }
+
     /// Register a global variable in the script context
     pub fn register_global(&mut self, name: &str, value: serde_json::Value) {
         let json_str = serde_json::to_string(&value).unwrap_or_else(|_| "null".to_string());
@@ -210,6 +224,19 @@ mod tests {
         assert_eq!(result["demo/device"]["brightness"], serde_json::json!(0.5));
     }
 
+    #[test]
+    fn test_check_syntax() {
+        let mut engine = ScriptEngine::new();
+
+        assert!(engine.check_syntax("devices['a/b'].power === true").is_ok());
+        assert!(engine.check_syntax("devices['a/b'].power ===").is_err());
+        assert!(engine
+            .check_scene_script_syntax("defineSceneScript(() => ({}))")
+            .is_ok());
+        // Statements aren't valid scene scripts
+        assert!(engine.check_scene_script_syntax("var x = 1;").is_err());
+    }
+
     #[test]
     fn test_global_var_access() {
         let mut engine = ScriptEngine::new();
@@ -0,0 +1,493 @@
+//! Runner for [Action::Sequence]. Pending sequences are owned by the state
+//! actor separately from the routines config, so they keep running across
+//! config reloads.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::types::{
+    action::{Action, Actions},
+    event::{Event, TxEventChannel},
+    rule::IfDescriptor,
+    sequence::{PendingSequence, PendingSequenceState, SequenceDescriptor, WaitUntilDescriptor},
+};
+
+use super::{devices::Devices, groups::Groups, routines::Routines};
+
+enum SequenceWait {
+    Delay {
+        until: DateTime<Utc>,
+    },
+    Rule {
+        descriptor: WaitUntilDescriptor,
+        timeout_at: Option<DateTime<Utc>>,
+    },
+}
+
+struct RunningSequence {
+    key: Option<String>,
+    started_at: DateTime<Utc>,
+    actions: Actions,
+    /// Index of the next action to run, or of the action being waited on.
+    step: usize,
+    wait: Option<SequenceWait>,
+}
+
+pub struct Sequences {
+    event_tx: TxEventChannel,
+    next_id: u64,
+    running: BTreeMap<u64, RunningSequence>,
+}
+
+impl Sequences {
+    pub fn new(event_tx: TxEventChannel) -> Self {
+        Sequences {
+            event_tx,
+            next_id: 1,
+            running: BTreeMap::new(),
+        }
+    }
+
+    /// Starts running a sequence, cancelling pending sequences with the same
+    /// key. Returns the id of the new sequence.
+    pub fn start(
+        &mut self,
+        descriptor: &SequenceDescriptor,
+        routines: &mut Routines,
+        devices: &Devices,
+        groups: &Groups,
+    ) -> u64 {
+        if let Some(key) = &descriptor.key {
+            self.cancel_key(key);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.running.insert(
+            id,
+            RunningSequence {
+                key: descriptor.key.clone(),
+                started_at: Utc::now(),
+                actions: descriptor.actions.clone(),
+                step: 0,
+                wait: None,
+            },
+        );
+        self.advance(id, routines, devices, groups);
+
+        id
+    }
+
+    /// Cancels the sequence with the given id, returns whether it was
+    /// pending.
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let cancelled = self.running.remove(&id).is_some();
+        if cancelled {
+            info!("Sequence {id} cancelled");
+        }
+        cancelled
+    }
+
+    /// Cancels all pending sequences with the given key, returns how many
+    /// were cancelled.
+    pub fn cancel_key(&mut self, key: &str) -> usize {
+        let ids: Vec<u64> = self
+            .running
+            .iter()
+            .filter(|(_, sequence)| sequence.key.as_deref() == Some(key))
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in &ids {
+            self.cancel(*id);
+        }
+
+        ids.len()
+    }
+
+    pub fn pending(&self) -> Vec<PendingSequence> {
+        self.running
+            .iter()
+            .filter_map(|(id, sequence)| {
+                let (state, resumes_at) = match sequence.wait.as_ref()? {
+                    SequenceWait::Delay { until } => (PendingSequenceState::Delaying, Some(until)),
+                    SequenceWait::Rule { timeout_at, .. } => {
+                        (PendingSequenceState::WaitingForRule, timeout_at.as_ref())
+                    }
+                };
+
+                Some(PendingSequence {
+                    id: *id,
+                    key: sequence.key.clone(),
+                    started_at: sequence.started_at.to_rfc3339(),
+                    state,
+                    step: sequence.step,
+                    step_count: sequence.actions.len(),
+                    resumes_at: resumes_at.map(DateTime::to_rfc3339),
+                })
+            })
+            .collect()
+    }
+
+    /// The delay or wait timeout of `step` of a sequence has elapsed. Timers
+    /// of cancelled sequences and earlier steps are ignored.
+    ///
+    /// The timer is trusted rather than compared to the wall clock, which may
+    /// be stepped back while the timer runs.
+    pub fn handle_step(
+        &mut self,
+        id: u64,
+        step: usize,
+        routines: &mut Routines,
+        devices: &Devices,
+        groups: &Groups,
+    ) {
+        let Some(sequence) = self.running.get_mut(&id) else {
+            return;
+        };
+        if sequence.step != step {
+            return;
+        }
+
+        match &sequence.wait {
+            Some(SequenceWait::Delay { .. }) => {}
+            Some(SequenceWait::Rule {
+                descriptor,
+                timeout_at: Some(_),
+            }) => {
+                if descriptor.cancel_on_timeout {
+                    info!("Sequence {id} cancelled after wait timed out");
+                    self.running.remove(&id);
+                    return;
+                }
+
+                debug!("Sequence {id} wait timed out, continuing");
+            }
+            _ => return,
+        }
+
+        sequence.wait = None;
+        sequence.step += 1;
+        self.advance(id, routines, devices, groups);
+    }
+
+    /// Re-evaluates rules of waiting sequences after state has changed.
+    /// Returns whether any sequence resumed.
+    pub fn check_waits(
+        &mut self,
+        routines: &mut Routines,
+        devices: &Devices,
+        groups: &Groups,
+    ) -> bool {
+        let resumed: Vec<u64> = self
+            .running
+            .iter()
+            .filter(|(_, sequence)| match &sequence.wait {
+                Some(SequenceWait::Rule { descriptor, .. }) => {
+                    routines.rule_condition_matches(&descriptor.rule, devices, groups)
+                }
+                _ => false,
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in &resumed {
+            if let Some(sequence) = self.running.get_mut(id) {
+                sequence.wait = None;
+                sequence.step += 1;
+            }
+            self.advance(*id, routines, devices, groups);
+        }
+
+        !resumed.is_empty()
+    }
+
+    /// Runs actions of the sequence until it either has to wait or runs out
+    /// of actions.
+    fn advance(&mut self, id: u64, routines: &mut Routines, devices: &Devices, groups: &Groups) {
+        let Some(sequence) = self.running.get_mut(&id) else {
+            return;
+        };
+
+        while let Some(action) = sequence.actions.get(sequence.step) {
+            match action {
+                Action::Delay { ms } => {
+                    sequence.wait = Some(SequenceWait::Delay {
+                        until: after_ms(Utc::now(), *ms),
+                    });
+                    schedule_step(&self.event_tx, id, sequence.step, *ms);
+                    return;
+                }
+                Action::WaitUntil(descriptor) => {
+                    if !routines.rule_condition_matches(&descriptor.rule, devices, groups) {
+                        if let Some(timeout_ms) = descriptor.timeout_ms {
+                            schedule_step(&self.event_tx, id, sequence.step, timeout_ms);
+                        }
+                        sequence.wait = Some(SequenceWait::Rule {
+                            descriptor: descriptor.clone(),
+                            timeout_at: descriptor.timeout_ms.map(|ms| after_ms(Utc::now(), ms)),
+                        });
+                        return;
+                    }
+                }
+                Action::If(descriptor) => {
+                    // Run the chosen branch as part of this sequence, so
+                    // that it may pause as well
+                    let condition_matches =
+                        routines.conditions_match(&descriptor.condition, devices, groups);
+                    let branch = descriptor.branch(condition_matches).clone();
+                    let step = sequence.step;
+                    sequence.actions.splice(step..=step, branch);
+                    continue;
+                }
+                action => self.event_tx.send(Event::Action(action.clone())),
+            }
+
+            sequence.step += 1;
+        }
+
+        debug!("Sequence {id} completed");
+        self.running.remove(&id);
+    }
+}
+
+/// Whether `actions` pause at some point, and so have to run as a sequence.
+pub fn actions_pause(actions: &[Action]) -> bool {
+    actions.iter().any(|action| match action {
+        Action::Delay { .. } | Action::WaitUntil(_) => true,
+        Action::If(IfDescriptor {
+            then, else_actions, ..
+        }) => actions_pause(then) || actions_pause(else_actions),
+        _ => false,
+    })
+}
+
+fn after_ms(now: DateTime<Utc>, ms: u64) -> DateTime<Utc> {
+    i64::try_from(ms)
+        .ok()
+        .and_then(TimeDelta::try_milliseconds)
+        .and_then(|delta| now.checked_add_signed(delta))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+fn schedule_step(event_tx: &TxEventChannel, sequence_id: u64, step: usize, delay_ms: u64) {
+    let event_tx = event_tx.clone();
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        event_tx.send(Event::SequenceStep { sequence_id, step });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sequences;
+    use crate::core::{devices::Devices, groups::Groups, routines::Routines};
+    use crate::types::{
+        action::Action,
+        event::{mk_event_channel, Event, RxEventChannel},
+        rule::{AnyRule, IfDescriptor, Rule, TimeWindow, TimeWindowRule, TriggerMode},
+        sequence::{
+            CancelSequenceDescriptor, PendingSequenceState, SequenceDescriptor, WaitUntilDescriptor,
+        },
+    };
+    use crate::utils::cli::dry_run_cli;
+    use chrono::NaiveTime;
+
+    struct TestRunner {
+        sequences: Sequences,
+        routines: Routines,
+        devices: Devices,
+        groups: Groups,
+        event_rx: RxEventChannel,
+    }
+
+    fn test_runner() -> TestRunner {
+        let (event_tx, event_rx) = mk_event_channel();
+        let cli = dry_run_cli();
+
+        TestRunner {
+            sequences: Sequences::new(event_tx.clone()),
+            routines: Routines::new(Default::default(), event_tx.clone()),
+            devices: Devices::new(event_tx, &cli),
+            groups: Groups::new(Default::default()),
+            event_rx,
+        }
+    }
+
+    impl TestRunner {
+        fn start(&mut self, actions: Vec<Action>, key: Option<&str>) -> u64 {
+            self.sequences.start(
+                &SequenceDescriptor {
+                    actions,
+                    key: key.map(str::to_string),
+                },
+                &mut self.routines,
+                &self.devices,
+                &self.groups,
+            )
+        }
+
+        /// Fires the timer of the current step of the sequence.
+        fn step(&mut self, id: u64) {
+            let step = self.sequences.running.get(&id).map_or(0, |s| s.step);
+            self.step_at(id, step);
+        }
+
+        fn step_at(&mut self, id: u64, step: usize) {
+            self.sequences
+                .handle_step(id, step, &mut self.routines, &self.devices, &self.groups);
+        }
+
+        /// Keys of marker actions dispatched so far, ignoring timer events.
+        fn dispatched(&mut self) -> Vec<String> {
+            let mut keys = Vec::new();
+            while let Ok(event) = self.event_rx.try_recv() {
+                if let Event::Action(Action::CancelSequence(CancelSequenceDescriptor { key })) =
+                    event
+                {
+                    keys.push(key);
+                }
+            }
+            keys
+        }
+    }
+
+    fn marker(key: &str) -> Action {
+        Action::CancelSequence(CancelSequenceDescriptor {
+            key: key.to_string(),
+        })
+    }
+
+    fn never_matching_rule() -> Box<Rule> {
+        Box::new(Rule::Any(AnyRule { any: Vec::new() }))
+    }
+
+    fn always_matching_rule() -> Rule {
+        let midnight = NaiveTime::MIN;
+        Rule::TimeWindow(TimeWindowRule {
+            time_window: TimeWindow {
+                start: midnight,
+                end: midnight,
+                weekdays: None,
+            },
+            trigger_mode: TriggerMode::Level,
+        })
+    }
+
+    #[tokio::test]
+    async fn delays_pause_the_sequence_until_elapsed() {
+        let mut runner = test_runner();
+        let id = runner.start(
+            vec![marker("first"), Action::Delay { ms: 0 }, marker("second")],
+            None,
+        );
+
+        assert_eq!(runner.dispatched(), vec!["first"]);
+        let pending = runner.sequences.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].state, PendingSequenceState::Delaying);
+        assert_eq!(pending[0].step, 1);
+
+        runner.step(id);
+
+        assert_eq!(runner.dispatched(), vec!["second"]);
+        assert!(runner.sequences.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn timers_of_earlier_steps_are_ignored() {
+        let mut runner = test_runner();
+        let id = runner.start(
+            vec![
+                Action::Delay { ms: 60_000 },
+                Action::Delay { ms: 60_000 },
+                marker("done"),
+            ],
+            None,
+        );
+
+        runner.step_at(id, 0);
+        runner.step_at(id, 0);
+        assert!(runner.dispatched().is_empty());
+        assert_eq!(runner.sequences.pending()[0].step, 1);
+
+        // Delays end when their timer fires, even if the clock says otherwise
+        runner.step_at(id, 1);
+        assert_eq!(runner.dispatched(), vec!["done"]);
+    }
+
+    #[tokio::test]
+    async fn starting_a_keyed_sequence_cancels_the_previous_one() {
+        let mut runner = test_runner();
+        let actions = vec![Action::Delay { ms: 60_000 }, marker("off")];
+        let first = runner.start(actions.clone(), Some("hallway"));
+        let second = runner.start(actions, Some("hallway"));
+
+        let pending = runner.sequences.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, second);
+
+        // Timers of the cancelled sequence are ignored
+        runner.step(first);
+        assert!(runner.dispatched().is_empty());
+        assert_eq!(runner.sequences.pending().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn wait_timeouts_continue_or_cancel() {
+        let mut runner = test_runner();
+        let wait = |cancel_on_timeout| {
+            Action::WaitUntil(WaitUntilDescriptor {
+                rule: never_matching_rule(),
+                timeout_ms: Some(0),
+                cancel_on_timeout,
+            })
+        };
+
+        let continued = runner.start(vec![wait(false), marker("continued")], None);
+        let cancelled = runner.start(vec![wait(true), marker("cancelled")], None);
+        assert_eq!(
+            runner.sequences.pending()[0].state,
+            PendingSequenceState::WaitingForRule
+        );
+
+        runner.step(continued);
+        runner.step(cancelled);
+
+        assert_eq!(runner.dispatched(), vec!["continued"]);
+        assert!(runner.sequences.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn conditional_branches_run_within_the_sequence() {
+        let mut runner = test_runner();
+        let conditional = |condition: Rule| {
+            Action::If(IfDescriptor {
+                condition: vec![condition],
+                then: vec![marker("then"), Action::Delay { ms: 0 }],
+                else_actions: vec![marker("else")],
+            })
+        };
+
+        let id = runner.start(
+            vec![conditional(always_matching_rule()), marker("after")],
+            None,
+        );
+        assert_eq!(runner.dispatched(), vec!["then"]);
+        assert_eq!(runner.sequences.pending()[0].step_count, 3);
+
+        runner.step(id);
+        assert_eq!(runner.dispatched(), vec!["after"]);
+
+        runner.start(
+            vec![conditional(*never_matching_rule()), marker("after")],
+            None,
+        );
+        assert_eq!(runner.dispatched(), vec!["else", "after"]);
+        assert!(runner.sequences.pending().is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 //! Simulation mode: mirrors production config into an in-memory runtime snapshot
-//! and replaces MQTT integrations with dummy equivalents.
+//! and replaces integrations that control real devices with dummy equivalents.
 
 use crate::api::config::{parse_config_backup, ParsedConfigBackup};
 use crate::db::config_queries::{
@@ -137,6 +137,7 @@ async fn export_from_legacy_sqlite_source_db<C: ConnectionTrait>(db: &C) -> Resu
     .await?
     .map(|row| config_queries::CoreConfigRow {
         warmup_time_seconds: get_i32_or_default(&row, "warmup_time_seconds", 1),
+        ..Default::default()
     })
     .unwrap_or_default();
 
@@ -157,6 +158,7 @@ async fn export_from_legacy_sqlite_source_db<C: ConnectionTrait>(db: &C) -> Resu
             hidden: get_bool_or_default(&row, "hidden", false),
             devices: legacy_group_devices(db, &id).await?,
             linked_groups: legacy_group_links(db, &id).await?,
+            occupancy: None,
             id,
         });
     }
@@ -628,6 +630,8 @@ fn routine_from_row(row: QueryResult) -> Result<RoutineRow> {
         enabled: get_bool_or_default(&row, "enabled", true),
         rules: parse_json_or_default(&rules),
         actions: parse_json_or_default(&actions),
+        cooldown_ms: None,
+        debounce_ms: None,
     })
 }
 
@@ -718,34 +722,57 @@ fn get_i32_or_default(row: &QueryResult, column: &str, default: i32) -> i32 {
         .unwrap_or(default)
 }
 
-/// Rewrite all MQTT integrations in the simulation snapshot as dummy equivalents.
-/// Discovers devices by scanning groups, scenes, and routines for references
-/// to each MQTT integration.
-pub fn convert_mqtt_to_dummy(config: &mut ConfigExport) -> Result<()> {
-    let mqtt_ids: Vec<String> = config
+/// Plugins that control real devices. Simulation replaces them with dummy
+/// integrations exposing the same devices.
+const LIVE_DEVICE_PLUGINS: [&str; 5] = ["mqtt", "zigbee2mqtt", "http", "hue", "wled"];
+
+/// Plugins that send homectl state to outside systems. Simulation disables them.
+const OUTBOUND_PLUGINS: [&str; 2] = ["homeassistant", "webhook"];
+
+/// Rewrite all integrations that control real devices in the simulation
+/// snapshot as dummy equivalents. Discovers devices by scanning groups,
+/// scenes, and routines for references to each integration. Integrations that
+/// talk to outside systems, e.g. Home Assistant exporters and webhooks, are
+/// disabled.
+pub fn convert_live_integrations_to_dummy(config: &mut ConfigExport) -> Result<()> {
+    // Exporting the simulation would let a real Home Assistant instance see
+    // and control simulated devices, and webhooks would fire for them.
+    for integration in config
+        .integrations
+        .iter_mut()
+        .filter(|integration| OUTBOUND_PLUGINS.contains(&integration.plugin.as_str()))
+    {
+        info!(
+            "Disabling {} integration '{}' in simulation",
+            integration.plugin, integration.id
+        );
+        integration.enabled = false;
+    }
+
+    let live_ids: Vec<String> = config
         .integrations
         .iter()
-        .filter(|i| i.plugin == "mqtt")
+        .filter(|i| LIVE_DEVICE_PLUGINS.contains(&i.plugin.as_str()))
         .map(|i| i.id.clone())
         .collect();
 
-    if mqtt_ids.is_empty() {
-        info!("No MQTT integrations found, nothing to convert");
+    if live_ids.is_empty() {
+        info!("No device integrations found, nothing to convert");
         return Ok(());
     }
 
     // Collect sensor device keys from routine rules
     let sensor_keys = collect_sensor_device_keys(&config.routines);
 
-    for mqtt_id in mqtt_ids {
+    for integration_id in live_ids {
         let mut devices: HashMap<String, serde_json::Value> = HashMap::new();
 
         // 1. Scan groups for device references
         for group in &config.groups {
             for gd in &group.devices {
-                if gd.integration_id == mqtt_id {
+                if gd.integration_id == integration_id {
                     let device_id = gd.device_id.clone();
-                    let key = format!("{mqtt_id}/{device_id}");
+                    let key = format!("{integration_id}/{device_id}");
                     let is_sensor = sensor_keys.contains(&key);
                     devices
                         .entry(device_id.clone())
@@ -758,7 +785,7 @@ pub fn convert_mqtt_to_dummy(config: &mut ConfigExport) -> Result<()> {
         for scene in &config.scenes {
             for device_key_str in scene.device_states.keys() {
                 if let Some((iid, did)) = device_key_str.split_once('/') {
-                    if iid == mqtt_id {
+                    if iid == integration_id {
                         let is_sensor = sensor_keys.contains(device_key_str);
                         devices
                             .entry(did.to_string())
@@ -770,17 +797,22 @@ pub fn convert_mqtt_to_dummy(config: &mut ConfigExport) -> Result<()> {
 
         // 3. Scan routine rules for direct device references under this integration
         for routine in &config.routines {
-            collect_device_refs_from_rules(&routine.rules, &mqtt_id, &sensor_keys, &mut devices);
+            collect_device_refs_from_rules(
+                &routine.rules,
+                &integration_id,
+                &sensor_keys,
+                &mut devices,
+            );
         }
 
         if devices.is_empty() {
             warn!(
-                "MQTT integration '{mqtt_id}' has no discoverable devices — \
+                "Integration '{integration_id}' has no discoverable devices — \
                  creating empty dummy integration"
             );
         } else {
             info!(
-                "Converting MQTT integration '{mqtt_id}' to dummy with {} devices",
+                "Converting integration '{integration_id}' to dummy with {} devices",
                 devices.len()
             );
         }
@@ -788,7 +820,7 @@ pub fn convert_mqtt_to_dummy(config: &mut ConfigExport) -> Result<()> {
         // Build DummyConfig JSON and rewrite the integration row
         let dummy_config = json!({ "devices": devices });
         let row = IntegrationRow {
-            id: mqtt_id.to_string(),
+            id: integration_id.to_string(),
             plugin: "dummy".to_string(),
             config: dummy_config,
             enabled: true,
@@ -797,7 +829,7 @@ pub fn convert_mqtt_to_dummy(config: &mut ConfigExport) -> Result<()> {
         if let Some(existing) = config
             .integrations
             .iter_mut()
-            .find(|integration| integration.id == mqtt_id)
+            .find(|integration| integration.id == integration_id)
         {
             *existing = row;
         }
@@ -934,7 +966,10 @@ fn collect_device_refs_from_rules(
 
 #[cfg(test)]
 mod tests {
-    use super::export_from_config_file;
+    use super::{convert_live_integrations_to_dummy, export_from_config_file};
+    use crate::core::integrations::BUILT_IN_PLUGIN_NAMES;
+    use crate::db::config_queries::{ConfigExport, CoreConfigRow, IntegrationRow};
+    use serde_json::json;
     use std::fs;
     use std::path::PathBuf;
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -1018,4 +1053,46 @@ actions = []
 
         fs::remove_file(path).expect("temp config file should be removed");
     }
+
+    #[test]
+    fn simulation_leaves_no_live_integrations() {
+        let mut config = ConfigExport {
+            version: 1,
+            core: CoreConfigRow::default(),
+            integrations: BUILT_IN_PLUGIN_NAMES
+                .iter()
+                .map(|plugin| IntegrationRow {
+                    id: format!("{plugin}_integration"),
+                    plugin: plugin.to_string(),
+                    config: json!({}),
+                    enabled: true,
+                })
+                .collect(),
+            groups: Vec::new(),
+            scenes: Vec::new(),
+            routines: Vec::new(),
+            floorplan: None,
+            floorplans: Vec::new(),
+            group_positions: Vec::new(),
+            device_display_overrides: Vec::new(),
+            device_sensor_configs: Vec::new(),
+            widget_settings: Vec::new(),
+            dashboard_layouts: Vec::new(),
+            dashboard_widgets: Vec::new(),
+        };
+
+        convert_live_integrations_to_dummy(&mut config).expect("conversion should succeed");
+
+        // Only plugins that never reach outside homectl may stay enabled.
+        let offline_plugins = ["circadian", "cron", "timer", "dummy", "random"];
+        for integration in &config.integrations {
+            assert!(
+                !integration.enabled || offline_plugins.contains(&integration.plugin.as_str()),
+                "integration '{}' stays live with plugin {}",
+                integration.id,
+                integration.plugin
+            );
+        }
+        assert_eq!(config.integrations.len(), BUILT_IN_PLUGIN_NAMES.len());
+    }
 }
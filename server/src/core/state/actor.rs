@@ -298,5 +298,10 @@ fn event_kind(event: &Event) -> &'static str {
         Event::DbEditScene { .. } => "DbEditScene",
         Event::DbDeleteScene { .. } => "DbDeleteScene",
         Event::Action(_) => "Action",
+        Event::RoutineClockTick => "RoutineClockTick",
+        Event::RoutineRuleHoldElapsed => "RoutineRuleHoldElapsed",
+        Event::SequenceStep { .. } => "SequenceStep",
+        Event::OccupancyTimeoutElapsed => "OccupancyTimeoutElapsed",
+        Event::FadeStep { .. } => "FadeStep",
     }
 }
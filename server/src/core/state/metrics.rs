@@ -29,10 +29,15 @@ pub const KIND_LABELS: &[&str] = &[
     "HandleEvent:DbEditScene",
     "HandleEvent:DbDeleteScene",
     "HandleEvent:Action",
+    "HandleEvent:RoutineClockTick",
+    "HandleEvent:RoutineRuleHoldElapsed",
+    "HandleEvent:SequenceStep",
+    "HandleEvent:OccupancyTimeoutElapsed",
+    "HandleEvent:FadeStep",
     "Mutate",
 ];
 
-pub const KIND_MUTATE: usize = 10;
+pub const KIND_MUTATE: usize = 15;
 
 /// Aggregated counters for the state actor. One entry per
 /// [`KIND_LABELS`] slot.
@@ -169,5 +174,10 @@ fn event_kind_index(event: &crate::types::event::Event) -> usize {
         Event::DbEditScene { .. } => 7,
         Event::DbDeleteScene { .. } => 8,
         Event::Action(_) => 9,
+        Event::RoutineClockTick => 10,
+        Event::RoutineRuleHoldElapsed => 11,
+        Event::SequenceStep { .. } => 12,
+        Event::OccupancyTimeoutElapsed => 13,
+        Event::FadeStep { .. } => 14,
     }
 }
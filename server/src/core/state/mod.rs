@@ -6,12 +6,13 @@ use crate::db::config_queries::{
 };
 use crate::types::{
     color::ColorMode,
-    device::{DeviceKey, DevicesState},
+    device::{Device, DeviceKey, DevicesState},
     event::TxEventChannel,
     group::FlattenedGroupsConfig,
     integration::IntegrationId,
     routine_status::RoutineStatuses,
     scene::FlattenedScenesConfig,
+    websockets::{WebSocketSubscription, WebSocketTopic},
 };
 
 pub mod actor;
@@ -22,14 +23,20 @@ pub use actor::{spawn_state_actor, StateHandle};
 pub use command::StateCommand;
 
 use super::{
+    config_versions::notify_config_changed,
     devices::Devices,
+    fades::Fades,
     groups::Groups,
     integrations::Integrations,
+    occupancy::Occupancy,
     routines::Routines,
     scenes::Scenes,
+    sequences::Sequences,
     snapshot::{RuntimeSnapshot, SnapshotChanges, SnapshotHandle},
     ui::Ui,
-    websockets::WebSockets,
+    websockets::{
+        serialize_message, subscribed_device_keys, subscribed_groups, subscribed_scenes, WebSockets,
+    },
 };
 
 use color_eyre::Result;
@@ -113,6 +120,9 @@ pub struct AppState {
     pub scenes: Scenes,
     pub devices: Devices,
     pub rules: Routines,
+    pub sequences: Sequences,
+    pub occupancy: Occupancy,
+    pub fades: Fades,
     pub event_tx: TxEventChannel,
     pub ws: WebSockets,
     pub ui: Ui,
@@ -169,9 +179,16 @@ impl AppState {
             warming_up: self.warming_up,
         };
         self.snapshot.store(Arc::new(snapshot));
+
+        if changes.runtime_config {
+            notify_config_changed();
+        }
     }
 
     pub fn update_core_config(&mut self, config: CoreConfigRow) {
+        self.devices
+            .set_history_enabled(config.device_history_retention_days > 0);
+        self.rules.set_location(config.location());
         self.runtime_config.core = config;
     }
 
@@ -520,6 +537,7 @@ impl AppState {
 
     pub fn apply_runtime_groups(&mut self) {
         self.groups.load_config_rows(&self.runtime_config.groups);
+        self.apply_runtime_occupancy();
         self.groups.force_invalidate(&self.devices);
         self.scenes.force_invalidate(&self.devices, &self.groups);
         self.refresh_routine_statuses();
@@ -531,6 +549,26 @@ impl AppState {
         });
     }
 
+    /// Reloads occupancy zones from group config, removing sensor devices of
+    /// zones that no longer exist.
+    fn apply_runtime_occupancy(&mut self) {
+        let removed_device_keys = self
+            .occupancy
+            .load_config_rows(&self.runtime_config.groups, &self.devices);
+
+        if removed_device_keys.is_empty() {
+            return;
+        }
+
+        for device_key in &removed_device_keys {
+            self.devices.remove_device(device_key);
+        }
+        self.schedule_ws_broadcast(PendingWsUpdate::device_removals(
+            removed_device_keys,
+            SnapshotChanges::devices(),
+        ));
+    }
+
     pub fn upsert_integration(&mut self, integration: IntegrationRow) {
         if let Some(existing) = self
             .runtime_config
@@ -592,6 +630,9 @@ impl AppState {
         integrations: Integrations,
         removed_ids: Vec<IntegrationId>,
     ) {
+        self.devices
+            .set_history_enabled(runtime_config.core.device_history_retention_days > 0);
+        self.rules.set_location(runtime_config.core.location());
         self.runtime_config = runtime_config;
         self.integrations = integrations;
         let removed_device_keys = self.remove_devices_for_integrations(&removed_ids);
@@ -804,6 +845,7 @@ impl AppState {
             warn!("Failed to refresh runtime config snapshot: {e}");
         }
 
+        self.apply_runtime_occupancy();
         self.groups.force_invalidate(&self.devices);
         self.scenes.force_invalidate(&self.devices, &self.groups);
 
@@ -886,14 +928,101 @@ struct StatePatchRef<'a> {
     ui_state: Option<&'a HashMap<String, serde_json::Value>>,
 }
 
+impl StatePatchRef<'_> {
+    fn is_empty(&self) -> bool {
+        self.devices.is_none()
+            && self.scenes.is_none()
+            && self.groups.is_none()
+            && self.routine_statuses.is_none()
+            && self.ui_state.is_none()
+    }
+}
+
 #[derive(Serialize)]
 enum WebSocketPatchResponseRef<'a> {
     Patch(StatePatchRef<'a>),
 }
 
+/// Converts the devices included by a subscription to the color mode used by
+/// the UI.
+fn ws_devices<'a>(
+    devices: impl Iterator<Item = &'a Device>,
+    subscribed: Option<&BTreeSet<DeviceKey>>,
+) -> DevicesState {
+    DevicesState(
+        devices
+            .filter(|device| {
+                subscribed.is_none_or(|subscribed| subscribed.contains(&device.get_device_key()))
+            })
+            .map(|device| {
+                (
+                    device.get_device_key(),
+                    device.color_to_mode(ColorMode::Hs, true),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Serializes the `StateUpdate` that a client with the given subscription
+/// receives.
+pub fn state_ws_message(
+    snap: &RuntimeSnapshot,
+    subscription: Option<&WebSocketSubscription>,
+) -> Option<String> {
+    let empty_scenes = FlattenedScenesConfig::default();
+    let empty_groups = FlattenedGroupsConfig::default();
+    let empty_routine_statuses = RoutineStatuses::default();
+    let empty_ui_state = HashMap::new();
+
+    let includes = |topic| subscription.is_none_or(|sub| sub.includes_topic(topic));
+    let subscribed = subscription.and_then(|subscription| {
+        subscribed_device_keys(subscription, &snap.devices, &snap.flattened_groups)
+    });
+
+    let devices = if includes(WebSocketTopic::Devices) {
+        ws_devices(snap.devices.0.values(), subscribed.as_ref())
+    } else {
+        DevicesState::default()
+    };
+    let scenes = includes(WebSocketTopic::Scenes)
+        .then(|| subscribed_scenes(&snap.flattened_scenes, subscribed.as_ref()));
+    let groups = includes(WebSocketTopic::Groups).then(|| {
+        subscription
+            .and_then(|subscription| subscribed_groups(subscription, &snap.flattened_groups))
+    });
+
+    let message = WebSocketResponseRef::State(StateUpdateRef {
+        devices,
+        scenes: match &scenes {
+            Some(Some(scenes)) => scenes,
+            Some(None) => snap.flattened_scenes.as_ref(),
+            None => &empty_scenes,
+        },
+        groups: match &groups {
+            Some(Some(groups)) => groups,
+            Some(None) => snap.flattened_groups.as_ref(),
+            None => &empty_groups,
+        },
+        routine_statuses: if includes(WebSocketTopic::RoutineStatuses) {
+            snap.routine_statuses.as_ref()
+        } else {
+            &empty_routine_statuses
+        },
+        ui_state: if includes(WebSocketTopic::UiState) {
+            snap.ui_state.as_ref()
+        } else {
+            &empty_ui_state
+        },
+    });
+
+    serialize_message(&message)
+}
+
 /// Build a `StateUpdate` from the currently published runtime snapshot and
 /// broadcast it to the given WebSocket peers. If `user_id` is omitted, the
-/// message is broadcast to all connected peers.
+/// message is broadcast to all connected peers. Each peer only receives the
+/// state that matches its subscription.
 pub async fn send_state_ws_from_snapshot(
     snapshot: &SnapshotHandle,
     ws: &WebSockets,
@@ -905,31 +1034,19 @@ pub async fn send_state_ws_from_snapshot(
     }
 
     let snap = snapshot.load();
-    let devices_converted = snap
-        .devices
-        .0
-        .values()
-        .map(|device| {
-            (
-                device.get_device_key(),
-                device.color_to_mode(ColorMode::Hs, true),
-            )
-        })
-        .collect();
 
-    let message = WebSocketResponseRef::State(StateUpdateRef {
-        devices: DevicesState(devices_converted),
-        scenes: snap.flattened_scenes.as_ref(),
-        groups: snap.flattened_groups.as_ref(),
-        routine_statuses: snap.routine_statuses.as_ref(),
-        ui_state: snap.ui_state.as_ref(),
-    });
+    for recipients in ws.recipients(user_id).await {
+        let Some(message) = state_ws_message(&snap, recipients.subscription.as_deref()) else {
+            continue;
+        };
 
-    ws.send(user_id, &message).await;
+        ws.send_serialized_to(&recipients.user_ids, message).await;
+    }
 }
 
 /// Build a targeted `StatePatch` from the currently published runtime snapshot
-/// and broadcast it to the given WebSocket peers.
+/// and broadcast it to the given WebSocket peers. Peers with a subscription
+/// only receive the parts of the patch that match it.
 pub async fn send_state_ws_patch_from_snapshot(
     snapshot: &SnapshotHandle,
     ws: &WebSockets,
@@ -952,55 +1069,65 @@ pub async fn send_state_ws_patch_from_snapshot(
 
     let snap = snapshot.load();
     let removed_devices = update.device_removals.into_iter().collect::<Vec<_>>();
-    let devices = if update.changes.devices {
-        let device_upserts = if update.device_upserts.is_empty() && removed_devices.is_empty() {
-            snap.devices
-                .0
-                .values()
-                .map(|device| {
-                    (
-                        device.get_device_key(),
-                        device.color_to_mode(ColorMode::Hs, true),
-                    )
-                })
-                .collect()
+    let changed_devices = if update.device_upserts.is_empty() && removed_devices.is_empty() {
+        snap.devices.0.values().collect::<Vec<_>>()
+    } else {
+        update
+            .device_upserts
+            .iter()
+            .filter_map(|device_key| snap.devices.0.get(device_key))
+            .collect()
+    };
+
+    for recipients in ws.recipients(user_id).await {
+        let subscription = recipients.subscription.as_deref();
+        let includes = |topic| subscription.is_none_or(|sub| sub.includes_topic(topic));
+        let subscribed = subscription.and_then(|subscription| {
+            subscribed_device_keys(subscription, &snap.devices, &snap.flattened_groups)
+        });
+
+        let devices = if update.changes.devices && includes(WebSocketTopic::Devices) {
+            let upserted = ws_devices(changed_devices.iter().copied(), subscribed.as_ref());
+
+            // Removed devices can't be matched against groups anymore, so
+            // these are always sent
+            (!upserted.0.is_empty() || !removed_devices.is_empty()).then_some(DevicesPatchRef {
+                upserted,
+                removed: &removed_devices,
+            })
         } else {
-            update
-                .device_upserts
-                .into_iter()
-                .filter_map(|device_key| {
-                    snap.devices
-                        .0
-                        .get(&device_key)
-                        .map(|device| (device_key, device.color_to_mode(ColorMode::Hs, true)))
-                })
-                .collect()
+            None
         };
 
-        Some(DevicesPatchRef {
-            upserted: DevicesState(device_upserts),
-            removed: &removed_devices,
-        })
-    } else {
-        None
-    };
+        let scenes = (update.changes.flattened_scenes && includes(WebSocketTopic::Scenes))
+            .then(|| subscribed_scenes(&snap.flattened_scenes, subscribed.as_ref()));
+        let groups =
+            (update.changes.flattened_groups && includes(WebSocketTopic::Groups)).then(|| {
+                subscription.and_then(|subscription| {
+                    subscribed_groups(subscription, &snap.flattened_groups)
+                })
+            });
 
-    let message = WebSocketPatchResponseRef::Patch(StatePatchRef {
-        devices,
-        scenes: update
-            .changes
-            .flattened_scenes
-            .then_some(snap.flattened_scenes.as_ref()),
-        groups: update
-            .changes
-            .flattened_groups
-            .then_some(snap.flattened_groups.as_ref()),
-        routine_statuses: update
-            .changes
-            .routine_statuses
+        let patch = StatePatchRef {
+            devices,
+            scenes: scenes
+                .as_ref()
+                .map(|scenes| scenes.as_ref().unwrap_or(&snap.flattened_scenes)),
+            groups: groups
+                .as_ref()
+                .map(|groups| groups.as_ref().unwrap_or(&snap.flattened_groups)),
+            routine_statuses: (update.changes.routine_statuses
+                && includes(WebSocketTopic::RoutineStatuses))
             .then_some(snap.routine_statuses.as_ref()),
-        ui_state: update.changes.ui_state.then_some(snap.ui_state.as_ref()),
-    });
+            ui_state: (update.changes.ui_state && includes(WebSocketTopic::UiState))
+                .then_some(snap.ui_state.as_ref()),
+        };
 
-    ws.send(user_id, &message).await;
+        if patch.is_empty() {
+            continue;
+        }
+
+        let message = WebSocketPatchResponseRef::Patch(patch);
+        ws.send_to(&recipients.user_ids, &message).await;
+    }
 }
@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+
+use crate::types::{integration::IntegrationId, webhook::WebhookDelivery};
+
+const MAX_WEBHOOK_DELIVERIES: usize = 500;
+
+static WEBHOOK_DELIVERY_BUFFER: Lazy<RwLock<VecDeque<WebhookDelivery>>> =
+    Lazy::new(|| RwLock::new(VecDeque::with_capacity(MAX_WEBHOOK_DELIVERIES)));
+static NEXT_WEBHOOK_DELIVERY_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Outcome of the last attempt of a webhook delivery.
+pub struct DeliveryOutcome {
+    pub attempts: u32,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+pub fn recent_webhook_deliveries() -> Vec<WebhookDelivery> {
+    read_delivery_buffer().iter().cloned().collect()
+}
+
+pub fn record_webhook_delivery(
+    integration_id: &IntegrationId,
+    webhook_id: &str,
+    url: &str,
+    event_kind: &str,
+    outcome: DeliveryOutcome,
+) {
+    let delivery = WebhookDelivery {
+        id: NEXT_WEBHOOK_DELIVERY_ID
+            .fetch_add(1, Ordering::Relaxed)
+            .to_string(),
+        timestamp: Utc::now().to_rfc3339(),
+        integration_id: integration_id.clone(),
+        webhook_id: webhook_id.to_string(),
+        url: url.to_string(),
+        event_kind: event_kind.to_string(),
+        attempts: outcome.attempts,
+        success: outcome.error.is_none(),
+        status: outcome.status,
+        error: outcome.error,
+    };
+
+    let mut buffer = write_delivery_buffer();
+    if buffer.len() == MAX_WEBHOOK_DELIVERIES {
+        buffer.pop_front();
+    }
+    buffer.push_back(delivery);
+}
+
+fn read_delivery_buffer() -> RwLockReadGuard<'static, VecDeque<WebhookDelivery>> {
+    match WEBHOOK_DELIVERY_BUFFER.read() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn write_delivery_buffer() -> RwLockWriteGuard<'static, VecDeque<WebhookDelivery>> {
+    match WEBHOOK_DELIVERY_BUFFER.write() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
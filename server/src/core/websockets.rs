@@ -1,9 +1,58 @@
-use std::{collections::HashMap, sync::Arc};
+//! Clients of the state stream, connected over WebSocket or Server-Sent
+//! Events.
 
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use once_cell::sync::Lazy;
 use serde::Serialize;
-use tokio::sync::{mpsc::Sender, RwLock};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc::Sender,
+    RwLock,
+};
+
+use crate::core::routine_history::subscribe_routine_history;
+use crate::types::{
+    device::{DeviceKey, DevicesState},
+    group::FlattenedGroupsConfig,
+    scene::FlattenedScenesConfig,
+    websockets::{FeedEvent, WebSocketResponse, WebSocketSubscription},
+};
+
+/// Our global unique user id counter.
+static NEXT_USER_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub fn next_user_id() -> usize {
+    NEXT_USER_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+static FEED_TX: Lazy<broadcast::Sender<FeedEvent>> = Lazy::new(|| broadcast::channel(256).0);
 
-type Users = Arc<RwLock<HashMap<usize, Sender<warp::ws::Message>>>>;
+/// Receives every event of the event feed, regardless of client
+/// subscriptions.
+pub fn subscribe_feed() -> broadcast::Receiver<FeedEvent> {
+    FEED_TX.subscribe()
+}
+
+/// A connected client. Messages are sent as serialized JSON.
+struct WebSocketUser {
+    sender: Sender<String>,
+    subscription: Option<Arc<WebSocketSubscription>>,
+}
+
+type Users = Arc<RwLock<HashMap<usize, WebSocketUser>>>;
+
+/// Users that receive the same messages because they share a subscription.
+pub struct Recipients {
+    pub subscription: Option<Arc<WebSocketSubscription>>,
+    pub user_ids: Vec<usize>,
+}
 
 #[derive(Clone, Default)]
 pub struct WebSockets {
@@ -11,8 +60,14 @@ pub struct WebSockets {
 }
 
 impl WebSockets {
-    pub async fn user_connected(&self, user_id: usize, sender: Sender<warp::ws::Message>) {
-        self.users.write().await.insert(user_id, sender);
+    pub async fn user_connected(&self, user_id: usize, sender: Sender<String>) {
+        self.users.write().await.insert(
+            user_id,
+            WebSocketUser {
+                sender,
+                subscription: None,
+            },
+        );
     }
 
     pub async fn user_disconnected(&self, user_id: usize) {
@@ -23,59 +78,366 @@ impl WebSockets {
         self.users.read().await.len()
     }
 
+    /// Replaces the subscription of a user. An empty subscription is
+    /// the same as not having one.
+    pub async fn set_subscription(&self, user_id: usize, subscription: WebSocketSubscription) {
+        let subscription =
+            (subscription != WebSocketSubscription::default()).then(|| Arc::new(subscription));
+
+        if let Some(user) = self.users.write().await.get_mut(&user_id) {
+            user.subscription = subscription;
+        }
+    }
+
+    /// Groups the given user, or all users if `user_id` is omitted, by their
+    /// subscriptions so that each distinct message is only built once.
+    pub async fn recipients(&self, user_id: Option<usize>) -> Vec<Recipients> {
+        let users = self.users.read().await;
+        let mut recipients: Vec<Recipients> = Vec::new();
+
+        let users = users
+            .iter()
+            .filter(|(id, _)| user_id.is_none_or(|user_id| **id == user_id));
+
+        for (id, user) in users {
+            match recipients
+                .iter_mut()
+                .find(|recipients| recipients.subscription == user.subscription)
+            {
+                Some(recipients) => recipients.user_ids.push(*id),
+                None => recipients.push(Recipients {
+                    subscription: user.subscription.clone(),
+                    user_ids: vec![*id],
+                }),
+            }
+        }
+
+        recipients
+    }
+
     pub async fn send<T>(&self, user_id: Option<usize>, message: &T) -> Option<()>
     where
         T: Serialize + ?Sized,
     {
-        let s = match serde_json::to_string(message) {
-            Ok(s) => s,
-            Err(error) => {
-                warn!("Failed to serialize WebSocket message: {error}");
-                return None;
+        match user_id {
+            Some(user_id) => self.send_to(&[user_id], message).await,
+            None => {
+                let user_ids = { self.users.read().await.keys().copied().collect::<Vec<_>>() };
+                self.send_to(&user_ids, message).await
             }
+        }
+    }
+
+    /// Sends the same message to each of the given users.
+    pub async fn send_to<T>(&self, user_ids: &[usize], message: &T) -> Option<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let s = serialize_message(message)?;
+        self.send_serialized_to(user_ids, s).await
+    }
+
+    /// Sends an already serialized message to each of the given users.
+    pub async fn send_serialized_to(&self, user_ids: &[usize], s: String) -> Option<()> {
+        let senders = {
+            let users = self.users.read().await;
+            user_ids
+                .iter()
+                .filter_map(|id| users.get(id).map(|user| (*id, user.sender.clone())))
+                .collect::<Vec<_>>()
         };
-        let msg = warp::ws::Message::text(s);
+        let mut dead_users = Vec::new();
 
-        match user_id {
-            Some(user_id) => {
-                let user = { self.users.read().await.get(&user_id).cloned() };
-                if let Some(user) = user {
-                    // try_send fails immediately if channel is full (client is slow)
-                    if user.try_send(msg).is_err() {
-                        warn!("Removing slow WebSocket client {user_id}");
-                        self.user_disconnected(user_id).await;
-                        return None;
-                    }
-                }
-                Some(())
+        for (id, sender) in senders {
+            // try_send fails immediately if channel is full (client is slow)
+            if sender.try_send(s.clone()).is_err() {
+                dead_users.push(id);
             }
-            None => {
-                let users = {
-                    self.users
-                        .read()
-                        .await
-                        .iter()
-                        .map(|(id, sender)| (*id, sender.clone()))
-                        .collect::<Vec<_>>()
-                };
-                let mut dead_users = Vec::new();
-
-                for (id, user) in users {
-                    if user.try_send(msg.clone()).is_err() {
-                        dead_users.push(id);
-                    }
-                }
+        }
 
-                if !dead_users.is_empty() {
-                    let mut users = self.users.write().await;
-                    for id in dead_users {
-                        warn!("Removing dead/slow WebSocket client {id}");
-                        users.remove(&id);
-                    }
-                }
+        if !dead_users.is_empty() {
+            let mut users = self.users.write().await;
+            for id in dead_users {
+                warn!("Removing dead/slow WebSocket client {id}");
+                users.remove(&id);
+            }
+        }
+
+        Some(())
+    }
 
-                Some(())
+    /// Sends an event to clients that subscribed to the event feed.
+    pub async fn publish_feed(&self, event: FeedEvent) {
+        // Fails only when nobody is subscribed
+        let _ = FEED_TX.send(event.clone());
+
+        let user_ids = {
+            self.users
+                .read()
+                .await
+                .iter()
+                .filter(|(_, user)| {
+                    user.subscription
+                        .as_ref()
+                        .is_some_and(|subscription| subscription.feed)
+                })
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>()
+        };
+
+        if !user_ids.is_empty() {
+            self.send_to(&user_ids, &WebSocketResponse::Event(event))
+                .await;
+        }
+    }
+}
+
+/// Forwards routine history entries to the event feed.
+pub fn start_routine_history_feed(ws: WebSockets) {
+    let mut history_rx = subscribe_routine_history();
+
+    tokio::spawn(async move {
+        loop {
+            match history_rx.recv().await {
+                Ok(entry) => ws.publish_feed(FeedEvent::RoutineTriggered(entry)).await,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Event feed skipped {skipped} routine history entries");
+                }
+                Err(RecvError::Closed) => break,
             }
         }
+    });
+}
+
+/// Device keys matched by the device filters of a subscription, or `None`
+/// if it doesn't filter devices.
+pub fn subscribed_device_keys(
+    subscription: &WebSocketSubscription,
+    devices: &DevicesState,
+    groups: &FlattenedGroupsConfig,
+) -> Option<BTreeSet<DeviceKey>> {
+    if !subscription.filters_devices() {
+        return None;
+    }
+
+    let mut device_keys: BTreeSet<DeviceKey> =
+        subscription.device_keys.iter().flatten().cloned().collect();
+
+    for group_id in subscription.groups.iter().flatten() {
+        if let Some(group) = groups.0.get(group_id) {
+            device_keys.extend(group.device_keys.iter().cloned());
+        }
+    }
+
+    if let Some(integration_ids) = &subscription.integration_ids {
+        device_keys.extend(
+            devices
+                .0
+                .keys()
+                .filter(|device_key| integration_ids.contains(&device_key.integration_id))
+                .cloned(),
+        );
+    }
+
+    Some(device_keys)
+}
+
+/// Serializes a message for WebSocket clients, logging failures.
+pub fn serialize_message<T>(message: &T) -> Option<String>
+where
+    T: Serialize + ?Sized,
+{
+    serde_json::to_string(message)
+        .inspect_err(|error| warn!("Failed to serialize WebSocket message: {error}"))
+        .ok()
+}
+
+/// Groups included by a subscription, or `None` if it doesn't filter groups.
+pub fn subscribed_groups(
+    subscription: &WebSocketSubscription,
+    groups: &FlattenedGroupsConfig,
+) -> Option<FlattenedGroupsConfig> {
+    let group_ids = subscription.groups.as_ref()?;
+
+    Some(FlattenedGroupsConfig(
+        groups
+            .0
+            .iter()
+            .filter(|(group_id, _)| group_ids.contains(group_id))
+            .map(|(group_id, group)| (group_id.clone(), group.clone()))
+            .collect(),
+    ))
+}
+
+/// Scenes that set any of the subscribed devices, or `None` if the
+/// subscription doesn't filter devices.
+pub fn subscribed_scenes(
+    scenes: &FlattenedScenesConfig,
+    subscribed: Option<&BTreeSet<DeviceKey>>,
+) -> Option<FlattenedScenesConfig> {
+    let subscribed = subscribed?;
+
+    Some(FlattenedScenesConfig(
+        scenes
+            .0
+            .iter()
+            .filter(|(_, scene)| {
+                scene
+                    .devices
+                    .0
+                    .keys()
+                    .any(|device_key| subscribed.contains(device_key))
+            })
+            .map(|(scene_id, scene)| (scene_id.clone(), scene.clone()))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{subscribed_device_keys, subscribed_groups, subscribed_scenes};
+    use crate::types::{
+        device::{
+            ControllableState, Device, DeviceData, DeviceId, DeviceKey, DevicesState, SensorDevice,
+        },
+        group::{FlattenedGroupConfig, FlattenedGroupsConfig, GroupId},
+        integration::IntegrationId,
+        scene::{FlattenedSceneConfig, FlattenedScenesConfig, SceneDeviceStates, SceneId},
+        websockets::WebSocketSubscription,
+    };
+
+    fn device_key(integration_id: &str, device_id: &str) -> DeviceKey {
+        DeviceKey::new(
+            IntegrationId::from(integration_id.to_string()),
+            DeviceId::new(device_id),
+        )
+    }
+
+    #[test]
+    fn subscription_matches_devices_of_any_filter() {
+        let devices = DevicesState(
+            [
+                device_key("hue", "kitchen"),
+                device_key("hue", "bedroom"),
+                device_key("mqtt", "hallway"),
+                device_key("mqtt", "office"),
+            ]
+            .into_iter()
+            .map(|key| {
+                let device = Device::new(
+                    key.integration_id.clone(),
+                    key.device_id.clone(),
+                    "Test device".to_string(),
+                    DeviceData::Sensor(SensorDevice::Boolean { value: true }),
+                    None,
+                );
+                (key, device)
+            })
+            .collect(),
+        );
+        let groups = FlattenedGroupsConfig(BTreeMap::from([(
+            GroupId("upstairs".to_string()),
+            FlattenedGroupConfig {
+                name: "Upstairs".to_string(),
+                device_keys: vec![device_key("hue", "bedroom")],
+                hidden: None,
+            },
+        )]));
+
+        assert_eq!(
+            subscribed_device_keys(&WebSocketSubscription::default(), &devices, &groups),
+            None
+        );
+
+        let subscription = WebSocketSubscription {
+            device_keys: Some(vec![device_key("mqtt", "office")]),
+            groups: Some(vec![GroupId("upstairs".to_string())]),
+            integration_ids: Some(vec![IntegrationId::from("unknown".to_string())]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            subscribed_device_keys(&subscription, &devices, &groups),
+            Some(
+                [device_key("hue", "bedroom"), device_key("mqtt", "office")]
+                    .into_iter()
+                    .collect()
+            )
+        );
+    }
+
+    #[test]
+    fn subscription_limits_groups_and_scenes() {
+        let group = |name: &str, device_keys| FlattenedGroupConfig {
+            name: name.to_string(),
+            device_keys,
+            hidden: None,
+        };
+        let groups = FlattenedGroupsConfig(BTreeMap::from([
+            (
+                GroupId("upstairs".to_string()),
+                group("Upstairs", vec![device_key("hue", "bedroom")]),
+            ),
+            (
+                GroupId("downstairs".to_string()),
+                group("Downstairs", vec![device_key("hue", "kitchen")]),
+            ),
+        ]));
+
+        let scene = |device_key: DeviceKey| FlattenedSceneConfig {
+            name: "Scene".to_string(),
+            devices: SceneDeviceStates(BTreeMap::from([(
+                device_key,
+                ControllableState {
+                    power: true,
+                    brightness: None,
+                    color: None,
+                    transition: None,
+                },
+            )])),
+            active_overrides: vec![],
+            hidden: None,
+        };
+        let scenes = FlattenedScenesConfig(BTreeMap::from([
+            (
+                SceneId::new("sleep".to_string()),
+                scene(device_key("hue", "bedroom")),
+            ),
+            (
+                SceneId::new("cooking".to_string()),
+                scene(device_key("hue", "kitchen")),
+            ),
+        ]));
+
+        assert_eq!(
+            subscribed_groups(&WebSocketSubscription::default(), &groups),
+            None
+        );
+        assert_eq!(subscribed_scenes(&scenes, None), None);
+
+        let subscription = WebSocketSubscription {
+            groups: Some(vec![GroupId("upstairs".to_string())]),
+            ..Default::default()
+        };
+        let subscribed = [device_key("hue", "bedroom")].into_iter().collect();
+
+        assert_eq!(
+            subscribed_groups(&subscription, &groups)
+                .unwrap()
+                .0
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec![GroupId("upstairs".to_string())]
+        );
+        assert_eq!(
+            subscribed_scenes(&scenes, Some(&subscribed))
+                .unwrap()
+                .0
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec![SceneId::new("sleep".to_string())]
+        );
     }
 }
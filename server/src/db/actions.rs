@@ -2,7 +2,8 @@ use std::collections::{BTreeMap, HashMap};
 
 use super::get_db_connection;
 use super::schema::{
-    Devices, SceneDeviceStates, SceneGroupStates, SceneOverrides, Scenes, UiState,
+    DeviceStateHistory, Devices, RoutineRuleHolds, SceneDeviceStates, SceneGroupStates,
+    SceneOverrides, Scenes, UiState,
 };
 use crate::types::device::{Device, DeviceData, DeviceKey};
 use crate::types::group::GroupId;
@@ -14,6 +15,27 @@ use crate::types::scene::{
 use color_eyre::Result;
 use sea_orm::sea_query::{Expr, OnConflict, Order, Query};
 use sea_orm::{ConnectionTrait, QueryResult, Statement, StatementBuilder};
+use serde::Serialize;
+
+/// Upper bound on how many raw history rows a single query returns.
+const DEVICE_HISTORY_QUERY_LIMIT: u64 = 50_000;
+/// Rows per history insert, keeping each statement well below the bound
+/// parameter limit of SQLite.
+const DEVICE_HISTORY_INSERT_CHUNK_SIZE: usize = 500;
+
+/// A device state captured at a point in time, queued for the history table.
+#[derive(Clone, Debug)]
+pub struct DeviceHistoryEntry {
+    pub recorded_at_ms: i64,
+    pub device: Device,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DeviceStateHistoryRow {
+    pub recorded_at_ms: i64,
+    pub state: DeviceData,
+    pub raw: Option<serde_json::Value>,
+}
 
 pub async fn db_update_device(device: &Device) -> Result<Device> {
     let db = get_db_connection()?;
@@ -118,6 +140,193 @@ pub async fn db_delete_device(device_key: &DeviceKey) -> Result<bool> {
     Ok(result.rows_affected() > 0)
 }
 
+pub async fn db_append_device_history(entries: &[DeviceHistoryEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let db = get_db_connection()?;
+
+    for chunk in entries.chunks(DEVICE_HISTORY_INSERT_CHUNK_SIZE) {
+        let mut query = Query::insert();
+        query.into_table(DeviceStateHistory::Table).columns([
+            DeviceStateHistory::IntegrationId,
+            DeviceStateHistory::DeviceId,
+            DeviceStateHistory::RecordedAtMs,
+            DeviceStateHistory::State,
+            DeviceStateHistory::Raw,
+        ]);
+
+        for entry in chunk {
+            let state = serde_json::to_string(&entry.device.data)?;
+            let raw = entry
+                .device
+                .raw
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?;
+
+            query.values_panic([
+                entry.device.integration_id.to_string().into(),
+                entry.device.id.to_string().into(),
+                entry.recorded_at_ms.into(),
+                state.into(),
+                raw.into(),
+            ]);
+        }
+
+        db.execute(statement(db, query)).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns history rows for a device within `[from_ms, to_ms]`, oldest first.
+/// Long ranges are limited to the newest [DEVICE_HISTORY_QUERY_LIMIT] rows.
+pub async fn db_get_device_history(
+    key: &DeviceKey,
+    from_ms: i64,
+    to_ms: i64,
+) -> Result<Vec<DeviceStateHistoryRow>> {
+    let db = get_db_connection()?;
+
+    let rows = db
+        .query_all(statement(
+            db,
+            Query::select()
+                .columns([
+                    DeviceStateHistory::RecordedAtMs,
+                    DeviceStateHistory::State,
+                    DeviceStateHistory::Raw,
+                ])
+                .from(DeviceStateHistory::Table)
+                .and_where(
+                    Expr::col(DeviceStateHistory::IntegrationId).eq(key.integration_id.to_string()),
+                )
+                .and_where(Expr::col(DeviceStateHistory::DeviceId).eq(key.device_id.to_string()))
+                .and_where(Expr::col(DeviceStateHistory::RecordedAtMs).gte(from_ms))
+                .and_where(Expr::col(DeviceStateHistory::RecordedAtMs).lte(to_ms))
+                // Newest first, so that the limit drops the oldest rows
+                .order_by(DeviceStateHistory::RecordedAtMs, Order::Desc)
+                .order_by(DeviceStateHistory::Id, Order::Desc)
+                .limit(DEVICE_HISTORY_QUERY_LIMIT)
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .rev()
+        .filter_map(history_from_row)
+        .collect())
+}
+
+/// Deletes history rows recorded before `cutoff_ms`, returning the number of
+/// removed rows.
+pub async fn db_prune_device_history(cutoff_ms: i64) -> Result<u64> {
+    let db = get_db_connection()?;
+
+    let result = db
+        .execute(statement(
+            db,
+            Query::delete()
+                .from_table(DeviceStateHistory::Table)
+                .and_where(Expr::col(DeviceStateHistory::RecordedAtMs).lt(cutoff_ms))
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Persisted start time of a duration-qualified routine rule whose condition
+/// currently holds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoutineRuleHoldRow {
+    pub routine_id: String,
+    pub rule_key: String,
+    pub since_ms: i64,
+    pub fired: bool,
+}
+
+pub async fn db_get_routine_rule_holds() -> Result<Vec<RoutineRuleHoldRow>> {
+    let db = get_db_connection()?;
+
+    let rows = db
+        .query_all(statement(
+            db,
+            Query::select()
+                .columns([
+                    RoutineRuleHolds::RoutineId,
+                    RoutineRuleHolds::RuleKey,
+                    RoutineRuleHolds::SinceMs,
+                    RoutineRuleHolds::Fired,
+                ])
+                .from(RoutineRuleHolds::Table)
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(RoutineRuleHoldRow {
+                routine_id: row.try_get("", "routine_id").ok()?,
+                rule_key: row.try_get("", "rule_key").ok()?,
+                since_ms: row.try_get("", "since_ms").ok()?,
+                fired: row.try_get("", "fired").ok()?,
+            })
+        })
+        .collect())
+}
+
+pub async fn db_upsert_routine_rule_hold(hold: &RoutineRuleHoldRow) -> Result<()> {
+    let db = get_db_connection()?;
+
+    db.execute(statement(
+        db,
+        Query::insert()
+            .into_table(RoutineRuleHolds::Table)
+            .columns([
+                RoutineRuleHolds::RoutineId,
+                RoutineRuleHolds::RuleKey,
+                RoutineRuleHolds::SinceMs,
+                RoutineRuleHolds::Fired,
+            ])
+            .values_panic([
+                hold.routine_id.clone().into(),
+                hold.rule_key.clone().into(),
+                hold.since_ms.into(),
+                hold.fired.into(),
+            ])
+            .on_conflict(
+                OnConflict::columns([RoutineRuleHolds::RoutineId, RoutineRuleHolds::RuleKey])
+                    .update_columns([RoutineRuleHolds::SinceMs, RoutineRuleHolds::Fired])
+                    .to_owned(),
+            )
+            .to_owned(),
+    ))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn db_delete_routine_rule_hold(routine_id: &str, rule_key: &str) -> Result<()> {
+    let db = get_db_connection()?;
+
+    db.execute(statement(
+        db,
+        Query::delete()
+            .from_table(RoutineRuleHolds::Table)
+            .and_where(Expr::col(RoutineRuleHolds::RoutineId).eq(routine_id))
+            .and_where(Expr::col(RoutineRuleHolds::RuleKey).eq(rule_key))
+            .to_owned(),
+    ))
+    .await?;
+
+    Ok(())
+}
+
 pub async fn db_get_scenes() -> Result<ScenesConfig> {
     let db = get_db_connection()?;
 
@@ -412,6 +621,26 @@ fn device_from_row(row: QueryResult) -> Option<Device> {
     })
 }
 
+fn history_from_row(row: QueryResult) -> Option<DeviceStateHistoryRow> {
+    let recorded_at_ms: i64 = row.try_get("", "recorded_at_ms").ok()?;
+    let state: String = row.try_get("", "state").ok()?;
+    let raw: Option<String> = row.try_get("", "raw").ok()?;
+
+    let state = match serde_json::from_str(&state) {
+        Ok(state) => state,
+        Err(error) => {
+            warn!("Failed to parse device history state recorded at {recorded_at_ms}: {error}");
+            return None;
+        }
+    };
+
+    Some(DeviceStateHistoryRow {
+        recorded_at_ms,
+        state,
+        raw: raw.and_then(|raw| serde_json::from_str(&raw).ok()),
+    })
+}
+
 async fn scene_device_state_rows<C: ConnectionTrait>(
     db: &C,
     scene_id: &str,
@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use super::get_db_connection;
+use super::schema::{ApiTokens, Users};
+use crate::types::auth::Role;
+use color_eyre::Result;
+use sea_orm::sea_query::{Expr, OnConflict, Order, Query};
+use sea_orm::{ConnectionTrait, QueryResult, Statement, StatementBuilder};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserRow {
+    pub username: String,
+    pub password_hash: String,
+    pub role: Role,
+    pub created_at_ms: i64,
+}
+
+/// An API token or a login session. Only a hash of the token is stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiTokenRow {
+    pub id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub role: Role,
+    /// Set for login sessions, which have the role of their user.
+    pub username: Option<String>,
+    pub expires_at_ms: Option<i64>,
+    pub created_at_ms: i64,
+}
+
+pub async fn db_get_users() -> Result<Vec<UserRow>> {
+    let db = get_db_connection()?;
+
+    let rows = db
+        .query_all(statement(
+            db,
+            Query::select()
+                .columns([
+                    Users::Username,
+                    Users::PasswordHash,
+                    Users::Role,
+                    Users::CreatedAtMs,
+                ])
+                .from(Users::Table)
+                .order_by(Users::Username, Order::Asc)
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(rows.into_iter().filter_map(user_from_row).collect())
+}
+
+pub async fn db_upsert_user(user: &UserRow) -> Result<()> {
+    let db = get_db_connection()?;
+
+    db.execute(statement(
+        db,
+        Query::insert()
+            .into_table(Users::Table)
+            .columns([
+                Users::Username,
+                Users::PasswordHash,
+                Users::Role,
+                Users::CreatedAtMs,
+            ])
+            .values_panic([
+                user.username.clone().into(),
+                user.password_hash.clone().into(),
+                user.role.as_str().into(),
+                user.created_at_ms.into(),
+            ])
+            .on_conflict(
+                OnConflict::column(Users::Username)
+                    .update_columns([Users::PasswordHash, Users::Role])
+                    .to_owned(),
+            )
+            .to_owned(),
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes a user along with its login sessions.
+pub async fn db_delete_user(username: &str) -> Result<bool> {
+    let db = get_db_connection()?;
+
+    db.execute(statement(
+        db,
+        Query::delete()
+            .from_table(ApiTokens::Table)
+            .and_where(Expr::col(ApiTokens::Username).eq(username))
+            .to_owned(),
+    ))
+    .await?;
+
+    let result = db
+        .execute(statement(
+            db,
+            Query::delete()
+                .from_table(Users::Table)
+                .and_where(Expr::col(Users::Username).eq(username))
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn db_get_api_tokens() -> Result<Vec<ApiTokenRow>> {
+    let db = get_db_connection()?;
+
+    let rows = db
+        .query_all(statement(
+            db,
+            Query::select()
+                .columns([
+                    ApiTokens::Id,
+                    ApiTokens::Name,
+                    ApiTokens::TokenHash,
+                    ApiTokens::Role,
+                    ApiTokens::Username,
+                    ApiTokens::ExpiresAtMs,
+                    ApiTokens::CreatedAtMs,
+                ])
+                .from(ApiTokens::Table)
+                .order_by(ApiTokens::CreatedAtMs, Order::Asc)
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(rows.into_iter().filter_map(api_token_from_row).collect())
+}
+
+pub async fn db_insert_api_token(token: &ApiTokenRow) -> Result<()> {
+    let db = get_db_connection()?;
+
+    db.execute(statement(
+        db,
+        Query::insert()
+            .into_table(ApiTokens::Table)
+            .columns([
+                ApiTokens::Id,
+                ApiTokens::Name,
+                ApiTokens::TokenHash,
+                ApiTokens::Role,
+                ApiTokens::Username,
+                ApiTokens::ExpiresAtMs,
+                ApiTokens::CreatedAtMs,
+            ])
+            .values_panic([
+                token.id.clone().into(),
+                token.name.clone().into(),
+                token.token_hash.clone().into(),
+                token.role.as_str().into(),
+                token.username.clone().into(),
+                token.expires_at_ms.into(),
+                token.created_at_ms.into(),
+            ])
+            .to_owned(),
+    ))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn db_delete_api_token(id: &str) -> Result<bool> {
+    let db = get_db_connection()?;
+
+    let result = db
+        .execute(statement(
+            db,
+            Query::delete()
+                .from_table(ApiTokens::Table)
+                .and_where(Expr::col(ApiTokens::Id).eq(id))
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes login sessions that expired before the given time.
+pub async fn db_delete_expired_sessions(now_ms: i64) -> Result<u64> {
+    let db = get_db_connection()?;
+
+    let result = db
+        .execute(statement(
+            db,
+            Query::delete()
+                .from_table(ApiTokens::Table)
+                .and_where(Expr::col(ApiTokens::ExpiresAtMs).lte(now_ms))
+                .to_owned(),
+        ))
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+fn statement<C, S>(db: &C, builder: S) -> Statement
+where
+    C: ConnectionTrait,
+    S: StatementBuilder,
+{
+    db.get_database_backend().build(&builder)
+}
+
+fn role_from_row(row: &QueryResult) -> Option<Role> {
+    let role: String = row.try_get("", "role").ok()?;
+
+    match Role::from_str(&role) {
+        Ok(role) => Some(role),
+        Err(e) => {
+            warn!("Ignoring credentials with invalid role: {e}");
+            None
+        }
+    }
+}
+
+fn user_from_row(row: QueryResult) -> Option<UserRow> {
+    Some(UserRow {
+        username: row.try_get("", "username").ok()?,
+        password_hash: row.try_get("", "password_hash").ok()?,
+        role: role_from_row(&row)?,
+        created_at_ms: row.try_get("", "created_at_ms").ok()?,
+    })
+}
+
+fn api_token_from_row(row: QueryResult) -> Option<ApiTokenRow> {
+    Some(ApiTokenRow {
+        id: row.try_get("", "id").ok()?,
+        name: row.try_get("", "name").ok()?,
+        token_hash: row.try_get("", "token_hash").ok()?,
+        role: role_from_row(&row)?,
+        username: row.try_get("", "username").ok()?,
+        expires_at_ms: row.try_get("", "expires_at_ms").ok()?,
+        created_at_ms: row.try_get("", "created_at_ms").ok()?,
+    })
+}
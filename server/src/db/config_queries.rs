@@ -11,7 +11,10 @@ use super::schema::{
     Integrations, Routines, SceneDeviceStates, SceneGroupStates, SceneOverrides, Scenes,
     WidgetSettings,
 };
+use crate::types::occupancy::OccupancyConfig;
+use crate::utils::sun::Coordinates;
 use color_eyre::Result;
+use eyre::eyre;
 use sea_orm::sea_query::{Expr, OnConflict, Order, Query};
 use sea_orm::{ConnectionTrait, QueryResult, Statement, StatementBuilder, TransactionTrait};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -36,6 +39,8 @@ pub struct GroupRow {
     pub hidden: bool,
     pub devices: Vec<GroupDeviceRow>,
     pub linked_groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occupancy: Option<OccupancyConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,6 +66,10 @@ pub struct RoutineRow {
     pub enabled: bool,
     pub rules: serde_json::Value,
     pub actions: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown_ms: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debounce_ms: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -143,16 +152,43 @@ fn default_warmup_time_seconds() -> i32 {
     1
 }
 
+pub const DEFAULT_DEVICE_HISTORY_RETENTION_DAYS: i32 = 7;
+
+fn default_device_history_retention_days() -> i32 {
+    DEFAULT_DEVICE_HISTORY_RETENTION_DAYS
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CoreConfigRow {
     #[serde(default = "default_warmup_time_seconds")]
     pub warmup_time_seconds: i32,
+    /// How many days of device state history to keep. Older rows are pruned
+    /// periodically, `0` keeps no history at all.
+    #[serde(default = "default_device_history_retention_days")]
+    pub device_history_retention_days: i32,
+    /// Location used for sun position rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+impl CoreConfigRow {
+    pub fn location(&self) -> Option<Coordinates> {
+        Some(Coordinates {
+            latitude: self.latitude?,
+            longitude: self.longitude?,
+        })
+    }
 }
 
 impl Default for CoreConfigRow {
     fn default() -> Self {
         Self {
             warmup_time_seconds: default_warmup_time_seconds(),
+            device_history_retention_days: default_device_history_retention_days(),
+            latitude: None,
+            longitude: None,
         }
     }
 }
@@ -171,6 +207,22 @@ pub struct DeviceSensorConfigRow {
     pub config: serde_json::Value,
 }
 
+/// Saved config version, without the config itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigVersionRow {
+    pub id: i32,
+    pub version: i32,
+    pub description: Option<String>,
+    pub exported_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigVersionExport {
+    #[serde(flatten)]
+    pub version: ConfigVersionRow,
+    pub config: ConfigExport,
+}
+
 /// Full config export structure.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigExport {
@@ -357,6 +409,57 @@ pub fn rewrite_floorplan_device_references_in_grid(
     }
 }
 
+/// Moves the outline of a group drawn on the floorplan grid to another group
+/// id. An outline the replacement group already has is kept.
+pub fn rewrite_floorplan_group_references_in_grid(
+    floorplan: &mut FloorplanExportRow,
+    source_group_id: &str,
+    replacement_group_id: &str,
+) -> bool {
+    let Some(grid_json) = floorplan.grid_data.as_deref() else {
+        return false;
+    };
+
+    let mut grid_data: serde_json::Value = match serde_json::from_str(grid_json) {
+        Ok(grid_data) => grid_data,
+        Err(error) => {
+            warn!(
+                "Failed to parse grid_data for floorplan '{}': {error}",
+                floorplan.id
+            );
+            return false;
+        }
+    };
+
+    let Some(groups) = grid_data
+        .get_mut("groups")
+        .and_then(serde_json::Value::as_object_mut)
+    else {
+        return false;
+    };
+
+    let Some(points) = groups.remove(source_group_id) else {
+        return false;
+    };
+    groups
+        .entry(replacement_group_id.to_string())
+        .or_insert(points);
+
+    match serde_json::to_string(&grid_data) {
+        Ok(serialized) => {
+            floorplan.grid_data = Some(serialized);
+            true
+        }
+        Err(error) => {
+            warn!(
+                "Failed to serialize rewritten grid_data for floorplan '{}': {error}",
+                floorplan.id
+            );
+            false
+        }
+    }
+}
+
 // ============================================================================
 // Core Config
 // ============================================================================
@@ -368,7 +471,12 @@ pub async fn db_get_core_config() -> Result<Option<CoreConfigRow>> {
         .query_one(statement(
             db,
             Query::select()
-                .column(CoreConfig::WarmupTimeSeconds)
+                .columns([
+                    CoreConfig::WarmupTimeSeconds,
+                    CoreConfig::DeviceHistoryRetentionDays,
+                    CoreConfig::Latitude,
+                    CoreConfig::Longitude,
+                ])
                 .from(CoreConfig::Table)
                 .and_where(Expr::col(CoreConfig::Id).eq(1))
                 .to_owned(),
@@ -377,12 +485,21 @@ pub async fn db_get_core_config() -> Result<Option<CoreConfigRow>> {
 
     Ok(row.map(|row| CoreConfigRow {
         warmup_time_seconds: get_i32_or_default(&row, "warmup_time_seconds", 1),
+        device_history_retention_days: get_i32_or_default(
+            &row,
+            "device_history_retention_days",
+            DEFAULT_DEVICE_HISTORY_RETENTION_DAYS,
+        ),
+        latitude: get_optional_f64(&row, "latitude"),
+        longitude: get_optional_f64(&row, "longitude"),
     }))
 }
 
 pub async fn db_update_core_config(config: &CoreConfigRow) -> Result<()> {
-    let db = get_db_connection()?;
+    update_core_config_on(get_db_connection()?, config).await
+}
 
+async fn update_core_config_on<C: ConnectionTrait>(db: &C, config: &CoreConfigRow) -> Result<()> {
     execute(
         db,
         Query::update()
@@ -391,6 +508,12 @@ pub async fn db_update_core_config(config: &CoreConfigRow) -> Result<()> {
                 CoreConfig::WarmupTimeSeconds,
                 Expr::value(config.warmup_time_seconds),
             )
+            .value(
+                CoreConfig::DeviceHistoryRetentionDays,
+                Expr::value(config.device_history_retention_days),
+            )
+            .value(CoreConfig::Latitude, Expr::value(config.latitude))
+            .value(CoreConfig::Longitude, Expr::value(config.longitude))
             .value(CoreConfig::UpdatedAt, Expr::current_timestamp())
             .and_where(Expr::col(CoreConfig::Id).eq(1))
             .to_owned(),
@@ -419,8 +542,13 @@ pub async fn db_get_device_display_overrides() -> Result<Vec<DeviceDisplayNameRo
 }
 
 pub async fn db_upsert_device_display_override(row: &DeviceDisplayNameRow) -> Result<()> {
-    let db = get_db_connection()?;
+    upsert_device_display_override_on(get_db_connection()?, row).await
+}
 
+async fn upsert_device_display_override_on<C: ConnectionTrait>(
+    db: &C,
+    row: &DeviceDisplayNameRow,
+) -> Result<()> {
     execute(
         db,
         Query::insert()
@@ -479,7 +607,13 @@ pub async fn db_get_device_sensor_configs() -> Result<Vec<DeviceSensorConfigRow>
 }
 
 pub async fn db_upsert_device_sensor_config(row: &DeviceSensorConfigRow) -> Result<()> {
-    let db = get_db_connection()?;
+    upsert_device_sensor_config_on(get_db_connection()?, row).await
+}
+
+async fn upsert_device_sensor_config_on<C: ConnectionTrait>(
+    db: &C,
+    row: &DeviceSensorConfigRow,
+) -> Result<()> {
     let config_json = serde_json::to_string(&row.config)?;
 
     execute(
@@ -568,7 +702,13 @@ pub async fn db_get_integration(id: &str) -> Result<Option<IntegrationRow>> {
 }
 
 pub async fn db_upsert_integration(integration: &IntegrationRow) -> Result<()> {
-    let db = get_db_connection()?;
+    upsert_integration_on(get_db_connection()?, integration).await
+}
+
+async fn upsert_integration_on<C: ConnectionTrait>(
+    db: &C,
+    integration: &IntegrationRow,
+) -> Result<()> {
     let config = serde_json::to_string(&integration.config)?;
 
     execute(
@@ -702,6 +842,8 @@ pub async fn db_get_routines() -> Result<Vec<RoutineRow>> {
                 Routines::Enabled,
                 Routines::Rules,
                 Routines::Actions,
+                Routines::CooldownMs,
+                Routines::DebounceMs,
             ])
             .from(Routines::Table)
             .order_by(Routines::Name, Order::Asc)
@@ -723,6 +865,8 @@ pub async fn db_get_routine(id: &str) -> Result<Option<RoutineRow>> {
                 Routines::Enabled,
                 Routines::Rules,
                 Routines::Actions,
+                Routines::CooldownMs,
+                Routines::DebounceMs,
             ])
             .from(Routines::Table)
             .and_where(Expr::col(Routines::Id).eq(id))
@@ -734,7 +878,10 @@ pub async fn db_get_routine(id: &str) -> Result<Option<RoutineRow>> {
 }
 
 pub async fn db_upsert_routine(routine: &RoutineRow) -> Result<()> {
-    let db = get_db_connection()?;
+    upsert_routine_on(get_db_connection()?, routine).await
+}
+
+async fn upsert_routine_on<C: ConnectionTrait>(db: &C, routine: &RoutineRow) -> Result<()> {
     let rules = serde_json::to_string(&routine.rules)?;
     let actions = serde_json::to_string(&routine.actions)?;
 
@@ -748,6 +895,8 @@ pub async fn db_upsert_routine(routine: &RoutineRow) -> Result<()> {
                 Routines::Enabled,
                 Routines::Rules,
                 Routines::Actions,
+                Routines::CooldownMs,
+                Routines::DebounceMs,
             ])
             .values_panic([
                 Expr::value(routine.id.clone()),
@@ -755,6 +904,8 @@ pub async fn db_upsert_routine(routine: &RoutineRow) -> Result<()> {
                 Expr::value(routine.enabled),
                 Expr::value(rules),
                 Expr::value(actions),
+                Expr::value(routine.cooldown_ms),
+                Expr::value(routine.debounce_ms),
             ])
             .on_conflict(
                 OnConflict::column(Routines::Id)
@@ -763,6 +914,8 @@ pub async fn db_upsert_routine(routine: &RoutineRow) -> Result<()> {
                         Routines::Enabled,
                         Routines::Rules,
                         Routines::Actions,
+                        Routines::CooldownMs,
+                        Routines::DebounceMs,
                     ])
                     .value(Routines::UpdatedAt, Expr::current_timestamp())
                     .to_owned(),
@@ -812,7 +965,14 @@ pub async fn db_upsert_floorplan(floorplan: &FloorplanRow) -> Result<()> {
 }
 
 pub async fn db_upsert_floorplan_by_id(id: &str, floorplan: &FloorplanRow) -> Result<()> {
-    let db = get_db_connection()?;
+    upsert_floorplan_by_id_on(get_db_connection()?, id, floorplan).await
+}
+
+async fn upsert_floorplan_by_id_on<C: ConnectionTrait>(
+    db: &C,
+    id: &str,
+    floorplan: &FloorplanRow,
+) -> Result<()> {
     let default_name = default_floorplan_name(id);
 
     execute(
@@ -975,8 +1135,14 @@ pub async fn db_upsert_floorplan_export(
     floorplan: &FloorplanExportRow,
     sort_order: i32,
 ) -> Result<()> {
-    let db = get_db_connection()?;
+    upsert_floorplan_export_on(get_db_connection()?, floorplan, sort_order).await
+}
 
+async fn upsert_floorplan_export_on<C: ConnectionTrait>(
+    db: &C,
+    floorplan: &FloorplanExportRow,
+    sort_order: i32,
+) -> Result<()> {
     execute(
         db,
         Query::insert()
@@ -1091,8 +1257,13 @@ pub async fn db_get_group_positions() -> Result<Vec<GroupPositionRow>> {
 }
 
 pub async fn db_upsert_group_position(pos: &GroupPositionRow) -> Result<()> {
-    let db = get_db_connection()?;
+    upsert_group_position_on(get_db_connection()?, pos).await
+}
 
+async fn upsert_group_position_on<C: ConnectionTrait>(
+    db: &C,
+    pos: &GroupPositionRow,
+) -> Result<()> {
     execute(
         db,
         Query::insert()
@@ -1162,10 +1333,18 @@ pub async fn db_get_dashboard_layouts() -> Result<Vec<DashboardLayoutRow>> {
 pub async fn db_upsert_dashboard_layout(layout: &DashboardLayoutRow) -> Result<i32> {
     let db = get_db_connection()?;
     let txn = db.begin().await?;
+    let id = upsert_dashboard_layout_on(&txn, layout).await?;
+    txn.commit().await?;
+    Ok(id)
+}
 
+async fn upsert_dashboard_layout_on<C: ConnectionTrait>(
+    db: &C,
+    layout: &DashboardLayoutRow,
+) -> Result<i32> {
     if layout.is_default {
         execute(
-            &txn,
+            db,
             Query::update()
                 .table(DashboardLayouts::Table)
                 .value(DashboardLayouts::IsDefault, Expr::value(false))
@@ -1177,7 +1356,7 @@ pub async fn db_upsert_dashboard_layout(layout: &DashboardLayoutRow) -> Result<i
 
     let id = if layout.id > 0 {
         execute(
-            &txn,
+            db,
             Query::update()
                 .table(DashboardLayouts::Table)
                 .value(DashboardLayouts::Name, Expr::value(layout.name.clone()))
@@ -1189,9 +1368,9 @@ pub async fn db_upsert_dashboard_layout(layout: &DashboardLayoutRow) -> Result<i
         .await?;
         layout.id
     } else {
-        let id = next_i32_id(&txn, DashboardLayouts::Table, DashboardLayouts::Id).await?;
+        let id = next_i32_id(db, DashboardLayouts::Table, DashboardLayouts::Id).await?;
         execute(
-            &txn,
+            db,
             Query::insert()
                 .into_table(DashboardLayouts::Table)
                 .columns([
@@ -1210,7 +1389,6 @@ pub async fn db_upsert_dashboard_layout(layout: &DashboardLayoutRow) -> Result<i
         id
     };
 
-    txn.commit().await?;
     Ok(id)
 }
 
@@ -1229,7 +1407,13 @@ pub async fn db_get_dashboard_widgets(layout_id: i32) -> Result<Vec<DashboardWid
 }
 
 pub async fn db_upsert_dashboard_widget(widget: &DashboardWidgetRow) -> Result<i32> {
-    let db = get_db_connection()?;
+    upsert_dashboard_widget_on(get_db_connection()?, widget).await
+}
+
+async fn upsert_dashboard_widget_on<C: ConnectionTrait>(
+    db: &C,
+    widget: &DashboardWidgetRow,
+) -> Result<i32> {
     let config = serde_json::to_string(&widget.config)?;
 
     if widget.id > 0 {
@@ -1316,18 +1500,25 @@ pub async fn db_upsert_widget_setting(setting: &WidgetSettingRow) -> Result<()>
 pub async fn db_replace_widget_settings(settings: &[WidgetSettingRow]) -> Result<()> {
     let db = get_db_connection()?;
     let txn = db.begin().await?;
+    replace_widget_settings_on(&txn, settings).await?;
+    txn.commit().await?;
+    Ok(())
+}
 
+async fn replace_widget_settings_on<C: ConnectionTrait>(
+    db: &C,
+    settings: &[WidgetSettingRow],
+) -> Result<()> {
     execute(
-        &txn,
+        db,
         Query::delete().from_table(WidgetSettings::Table).to_owned(),
     )
     .await?;
 
     for setting in settings {
-        insert_widget_setting_on(&txn, setting).await?;
+        insert_widget_setting_on(db, setting).await?;
     }
 
-    txn.commit().await?;
     Ok(())
 }
 
@@ -1343,7 +1534,12 @@ pub async fn db_export_config_from_connection<C: ConnectionTrait>(db: &C) -> Res
     let core = one(
         db,
         Query::select()
-            .column(CoreConfig::WarmupTimeSeconds)
+            .columns([
+                CoreConfig::WarmupTimeSeconds,
+                CoreConfig::DeviceHistoryRetentionDays,
+                CoreConfig::Latitude,
+                CoreConfig::Longitude,
+            ])
             .from(CoreConfig::Table)
             .and_where(Expr::col(CoreConfig::Id).eq(1))
             .to_owned(),
@@ -1351,6 +1547,13 @@ pub async fn db_export_config_from_connection<C: ConnectionTrait>(db: &C) -> Res
     .await?
     .map(|row| CoreConfigRow {
         warmup_time_seconds: get_i32_or_default(&row, "warmup_time_seconds", 1),
+        device_history_retention_days: get_i32_or_default(
+            &row,
+            "device_history_retention_days",
+            DEFAULT_DEVICE_HISTORY_RETENTION_DAYS,
+        ),
+        latitude: get_optional_f64(&row, "latitude"),
+        longitude: get_optional_f64(&row, "longitude"),
     })
     .unwrap_or_default();
 
@@ -1391,6 +1594,8 @@ pub async fn db_export_config_from_connection<C: ConnectionTrait>(db: &C) -> Res
                 Routines::Enabled,
                 Routines::Rules,
                 Routines::Actions,
+                Routines::CooldownMs,
+                Routines::DebounceMs,
             ])
             .from(Routines::Table)
             .order_by(Routines::Name, Order::Asc)
@@ -1553,11 +1758,19 @@ pub async fn db_export_config_from_connection<C: ConnectionTrait>(db: &C) -> Res
 }
 
 pub async fn db_import_config(config: &ConfigExport) -> Result<()> {
-    db_update_core_config(&config.core).await?;
-    db_replace_widget_settings(&config.widget_settings).await?;
+    let db = get_db_connection()?;
+    let txn = db.begin().await?;
+    import_config_on(&txn, config).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+async fn import_config_on<C: ConnectionTrait>(db: &C, config: &ConfigExport) -> Result<()> {
+    update_core_config_on(db, &config.core).await?;
+    replace_widget_settings_on(db, &config.widget_settings).await?;
 
     for integration in &config.integrations {
-        db_upsert_integration(integration).await?;
+        upsert_integration_on(db, integration).await?;
     }
 
     let valid_group_ids: HashSet<&str> = config.groups.iter().map(|g| g.id.as_str()).collect();
@@ -1567,7 +1780,7 @@ pub async fn db_import_config(config: &ConfigExport) -> Result<()> {
             linked_groups: Vec::new(),
             ..group.clone()
         };
-        db_upsert_group(&group_without_links).await?;
+        upsert_group_on(db, &group_without_links).await?;
     }
 
     for group in &config.groups {
@@ -1597,39 +1810,199 @@ pub async fn db_import_config(config: &ConfigExport) -> Result<()> {
                 linked_groups: valid_links,
                 ..group.clone()
             };
-            db_upsert_group(&filtered_group).await?;
+            upsert_group_on(db, &filtered_group).await?;
         }
     }
 
     for scene in &config.scenes {
-        db_upsert_config_scene(scene).await?;
+        upsert_scene_on(db, scene).await?;
     }
     for routine in &config.routines {
-        db_upsert_routine(routine).await?;
+        upsert_routine_on(db, routine).await?;
     }
     if !config.floorplans.is_empty() {
         for (sort_order, floorplan) in config.floorplans.iter().enumerate() {
-            db_upsert_floorplan_export(floorplan, sort_order as i32).await?;
+            upsert_floorplan_export_on(db, floorplan, sort_order as i32).await?;
         }
     } else if let Some(floorplan) = &config.floorplan {
-        db_upsert_floorplan(floorplan).await?;
+        upsert_floorplan_by_id_on(db, "default", floorplan).await?;
     }
     for pos in &config.group_positions {
-        db_upsert_group_position(pos).await?;
+        upsert_group_position_on(db, pos).await?;
     }
     for device_display_override in &config.device_display_overrides {
-        db_upsert_device_display_override(device_display_override).await?;
+        upsert_device_display_override_on(db, device_display_override).await?;
     }
     for device_sensor_config in &config.device_sensor_configs {
-        db_upsert_device_sensor_config(device_sensor_config).await?;
+        upsert_device_sensor_config_on(db, device_sensor_config).await?;
     }
     for layout in &config.dashboard_layouts {
-        db_upsert_dashboard_layout(layout).await?;
+        upsert_dashboard_layout_on(db, layout).await?;
     }
     for widget in &config.dashboard_widgets {
-        db_upsert_dashboard_widget(widget).await?;
+        upsert_dashboard_widget_on(db, widget).await?;
+    }
+
+    Ok(())
+}
+
+/// Imports `config` over `previous`, deleting the entities that `config`
+/// doesn't contain. Nothing is changed if any of it fails.
+pub async fn db_replace_config(previous: &ConfigExport, config: &ConfigExport) -> Result<()> {
+    let db = get_db_connection()?;
+    let txn = db.begin().await?;
+
+    for id in removed_keys(
+        &previous.dashboard_widgets,
+        &config.dashboard_widgets,
+        |w| w.id,
+    ) {
+        delete_by_i32_key(&txn, DashboardWidgets::Table, DashboardWidgets::Id, id).await?;
+    }
+    for id in removed_keys(
+        &previous.dashboard_layouts,
+        &config.dashboard_layouts,
+        |l| l.id,
+    ) {
+        delete_by_i32_key(&txn, DashboardLayouts::Table, DashboardLayouts::Id, id).await?;
+    }
+    for group_id in removed_keys(&previous.group_positions, &config.group_positions, |p| {
+        p.group_id.as_str()
+    }) {
+        delete_by_string_key(
+            &txn,
+            GroupPositions::Table,
+            GroupPositions::GroupId,
+            group_id,
+        )
+        .await?;
+    }
+    for device_key in removed_keys(
+        &previous.device_display_overrides,
+        &config.device_display_overrides,
+        |o| o.device_key.as_str(),
+    ) {
+        delete_by_string_key(
+            &txn,
+            DeviceDisplayOverrides::Table,
+            DeviceDisplayOverrides::DeviceKey,
+            device_key,
+        )
+        .await?;
+    }
+    for device_ref in removed_keys(
+        &previous.device_sensor_configs,
+        &config.device_sensor_configs,
+        |c| c.device_ref.as_str(),
+    ) {
+        delete_by_string_key(
+            &txn,
+            DeviceSensorConfigs::Table,
+            DeviceSensorConfigs::DeviceRef,
+            device_ref,
+        )
+        .await?;
+    }
+    for id in removed_keys(&previous.routines, &config.routines, |r| r.id.as_str()) {
+        delete_by_string_key(&txn, Routines::Table, Routines::Id, id).await?;
+    }
+    for id in removed_keys(&previous.scenes, &config.scenes, |s| s.id.as_str()) {
+        delete_by_string_key(&txn, Scenes::Table, Scenes::Id, id).await?;
+    }
+    for id in removed_keys(&previous.groups, &config.groups, |g| g.id.as_str()) {
+        delete_by_string_key(&txn, Groups::Table, Groups::Id, id).await?;
+    }
+    for id in removed_keys(&previous.integrations, &config.integrations, |i| {
+        i.id.as_str()
+    }) {
+        delete_by_string_key(&txn, Integrations::Table, Integrations::Id, id).await?;
+    }
+    // Legacy exports only contain the default floorplan
+    if !config.floorplans.is_empty() {
+        for id in removed_keys(&previous.floorplans, &config.floorplans, |f| f.id.as_str()) {
+            delete_by_string_key(&txn, Floorplans::Table, Floorplans::Id, id).await?;
+        }
+    }
+
+    import_config_on(&txn, config).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+fn removed_keys<'a, T, K>(previous: &'a [T], current: &'a [T], key: impl Fn(&'a T) -> K) -> Vec<K>
+where
+    K: Eq + std::hash::Hash,
+{
+    let kept: HashSet<K> = current.iter().map(&key).collect();
+    previous
+        .iter()
+        .map(key)
+        .filter(|key| !kept.contains(key))
+        .collect()
+}
+
+/// Config rows that are written and deleted together, e.g. the rows whose
+/// references were rewritten when deleting or renaming an entity.
+#[derive(Debug, Default)]
+pub struct ConfigRowChanges {
+    pub groups: Vec<GroupRow>,
+    pub scenes: Vec<SceneRow>,
+    pub routines: Vec<RoutineRow>,
+    /// Floorplans and their sort orders.
+    pub floorplans: Vec<(FloorplanExportRow, i32)>,
+    pub dashboard_widgets: Vec<DashboardWidgetRow>,
+    pub group_positions: Vec<GroupPositionRow>,
+    pub deleted_group_positions: Vec<String>,
+    pub deleted_routines: Vec<String>,
+    pub deleted_scenes: Vec<String>,
+    pub deleted_groups: Vec<String>,
+}
+
+/// Writes and then deletes the rows of `changes`. Nothing is changed if any
+/// of it fails.
+pub async fn db_apply_config_row_changes(changes: &ConfigRowChanges) -> Result<()> {
+    let db = get_db_connection()?;
+    let txn = db.begin().await?;
+
+    for group in &changes.groups {
+        upsert_group_on(&txn, group).await?;
+    }
+    for scene in &changes.scenes {
+        upsert_scene_on(&txn, scene).await?;
+    }
+    for routine in &changes.routines {
+        upsert_routine_on(&txn, routine).await?;
+    }
+    for (floorplan, sort_order) in &changes.floorplans {
+        upsert_floorplan_export_on(&txn, floorplan, *sort_order).await?;
+    }
+    for widget in &changes.dashboard_widgets {
+        upsert_dashboard_widget_on(&txn, widget).await?;
+    }
+    for position in &changes.group_positions {
+        upsert_group_position_on(&txn, position).await?;
+    }
+
+    for group_id in &changes.deleted_group_positions {
+        delete_by_string_key(
+            &txn,
+            GroupPositions::Table,
+            GroupPositions::GroupId,
+            group_id,
+        )
+        .await?;
+    }
+    for id in &changes.deleted_routines {
+        delete_by_string_key(&txn, Routines::Table, Routines::Id, id).await?;
+    }
+    for id in &changes.deleted_scenes {
+        delete_by_string_key(&txn, Scenes::Table, Scenes::Id, id).await?;
+    }
+    for id in &changes.deleted_groups {
+        delete_by_string_key(&txn, Groups::Table, Groups::Id, id).await?;
     }
 
+    txn.commit().await?;
     Ok(())
 }
 
@@ -1662,6 +2035,81 @@ pub async fn db_save_config_version(
     Ok(version)
 }
 
+/// Saved config versions, newest first.
+pub async fn db_get_config_versions() -> Result<Vec<ConfigVersionRow>> {
+    let db = get_db_connection()?;
+    let rows = all(
+        db,
+        Query::select()
+            .columns([
+                ConfigVersions::Id,
+                ConfigVersions::Version,
+                ConfigVersions::Description,
+                ConfigVersions::ExportedAt,
+            ])
+            .from(ConfigVersions::Table)
+            .order_by(ConfigVersions::Version, Order::Desc)
+            .to_owned(),
+    )
+    .await?;
+
+    rows.into_iter().map(config_version_from_row).collect()
+}
+
+pub async fn db_get_config_version(version: i32) -> Result<Option<ConfigVersionExport>> {
+    let db = get_db_connection()?;
+    let mut query = config_version_query();
+    query.and_where(Expr::col(ConfigVersions::Version).eq(version));
+
+    one(db, query.to_owned())
+        .await?
+        .map(config_version_export_from_row)
+        .transpose()
+}
+
+pub async fn db_get_latest_config_version() -> Result<Option<ConfigVersionExport>> {
+    let db = get_db_connection()?;
+    let mut query = config_version_query();
+    query
+        .order_by(ConfigVersions::Version, Order::Desc)
+        .limit(1);
+
+    one(db, query.to_owned())
+        .await?
+        .map(config_version_export_from_row)
+        .transpose()
+}
+
+/// Deletes all but the `keep` newest config versions.
+pub async fn db_prune_config_versions(keep: u64) -> Result<u64> {
+    let db = get_db_connection()?;
+    let oldest_kept = one(
+        db,
+        Query::select()
+            .column(ConfigVersions::Version)
+            .from(ConfigVersions::Table)
+            .order_by(ConfigVersions::Version, Order::Desc)
+            .limit(1)
+            .offset(keep.saturating_sub(1))
+            .to_owned(),
+    )
+    .await?;
+
+    let Some(oldest_kept) = oldest_kept else {
+        return Ok(0);
+    };
+    let oldest_kept: i32 = oldest_kept.try_get("", "version")?;
+
+    execute(
+        db,
+        Query::delete()
+            .from_table(ConfigVersions::Table)
+            .and_where(Expr::col(ConfigVersions::Version).lt(oldest_kept))
+            .to_owned(),
+    )
+    .await
+}
+
 /// Check whether the database contains any user-managed configuration.
 pub async fn db_has_config() -> Result<bool> {
     if !db_get_integrations().await?.is_empty()
@@ -1794,7 +2242,7 @@ where
 async fn group_rows<C: ConnectionTrait>(db: &C, id: Option<&str>) -> Result<Vec<QueryResult>> {
     let mut query = Query::select();
     query
-        .columns([Groups::Id, Groups::Name, Groups::Hidden])
+        .columns([Groups::Id, Groups::Name, Groups::Hidden, Groups::Occupancy])
         .from(Groups::Table)
         .order_by(Groups::Name, Order::Asc);
 
@@ -1809,6 +2257,11 @@ async fn group_from_row<C: ConnectionTrait>(db: &C, row: QueryResult) -> Result<
     let id: String = row.try_get("", "id")?;
     let name: String = row.try_get("", "name")?;
     let hidden = get_bool_or_default(&row, "hidden", false);
+    let occupancy = row
+        .try_get::<Option<String>>("", "occupancy")
+        .ok()
+        .flatten()
+        .and_then(|json| parse_json_or_default(&json, "group occupancy config"));
 
     let devices = all(
         db,
@@ -1844,23 +2297,31 @@ async fn group_from_row<C: ConnectionTrait>(db: &C, row: QueryResult) -> Result<
         hidden,
         devices,
         linked_groups,
+        occupancy,
     })
 }
 
 async fn upsert_group_on<C: ConnectionTrait>(db: &C, group: &GroupRow) -> Result<()> {
+    let occupancy = group
+        .occupancy
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
     execute(
         db,
         Query::insert()
             .into_table(Groups::Table)
-            .columns([Groups::Id, Groups::Name, Groups::Hidden])
+            .columns([Groups::Id, Groups::Name, Groups::Hidden, Groups::Occupancy])
             .values_panic([
                 Expr::value(group.id.clone()),
                 Expr::value(group.name.clone()),
                 Expr::value(group.hidden),
+                Expr::value(occupancy),
             ])
             .on_conflict(
                 OnConflict::column(Groups::Id)
-                    .update_columns([Groups::Name, Groups::Hidden])
+                    .update_columns([Groups::Name, Groups::Hidden, Groups::Occupancy])
                     .value(Groups::UpdatedAt, Expr::current_timestamp())
                     .to_owned(),
             )
@@ -2224,9 +2685,48 @@ fn routine_from_row(row: QueryResult) -> Result<RoutineRow> {
         enabled: get_bool_or_default(&row, "enabled", true),
         rules: parse_json_or_default(&rules, "routine rules"),
         actions: parse_json_or_default(&actions, "routine actions"),
+        cooldown_ms: row.try_get("", "cooldown_ms").ok().flatten(),
+        debounce_ms: row.try_get("", "debounce_ms").ok().flatten(),
     })
 }
 
+fn config_version_query() -> sea_orm::sea_query::SelectStatement {
+    Query::select()
+        .columns([
+            ConfigVersions::Id,
+            ConfigVersions::Version,
+            ConfigVersions::Description,
+            ConfigVersions::ExportedAt,
+            ConfigVersions::ConfigJson,
+        ])
+        .from(ConfigVersions::Table)
+        .to_owned()
+}
+
+fn config_version_from_row(row: QueryResult) -> Result<ConfigVersionRow> {
+    let exported_at = row
+        .try_get::<Option<chrono::NaiveDateTime>>("", "exported_at")
+        .ok()
+        .flatten()
+        .map(|exported_at| exported_at.and_utc().to_rfc3339());
+
+    Ok(ConfigVersionRow {
+        id: row.try_get("", "id")?,
+        version: row.try_get("", "version")?,
+        description: row.try_get("", "description")?,
+        exported_at,
+    })
+}
+
+fn config_version_export_from_row(row: QueryResult) -> Result<ConfigVersionExport> {
+    let config_json: String = row.try_get("", "config_json")?;
+    let version = config_version_from_row(row)?;
+    let config = serde_json::from_str(&config_json)
+        .map_err(|e| eyre!("Failed to parse config of version {}: {e}", version.version))?;
+
+    Ok(ConfigVersionExport { version, config })
+}
+
 fn floorplan_from_row(row: QueryResult) -> Result<FloorplanRow> {
     Ok(FloorplanRow {
         image_data: row.try_get("", "image_data")?,
@@ -2340,6 +2840,10 @@ fn get_i32_or_default(row: &QueryResult, column: &str, default: i32) -> i32 {
         .unwrap_or(default)
 }
 
+fn get_optional_f64(row: &QueryResult, column: &str) -> Option<f64> {
+    row.try_get::<Option<f64>>("", column).ok().flatten()
+}
+
 fn get_f32(row: &QueryResult, column: &str) -> Result<f32> {
     match row.try_get::<f32>("", column) {
         Ok(value) => Ok(value),
@@ -1,8 +1,8 @@
 use crate::db::schema::{
-    ConfigVersions, CoreConfig, DashboardLayouts, DashboardWidgets, DeviceDisplayOverrides,
-    DeviceSensorConfigs, Devices, Floorplans, GroupDevices, GroupLinks, GroupPositions, Groups,
-    Integrations, Routines, SceneDeviceStates, SceneGroupStates, SceneOverrides, Scenes, UiState,
-    WidgetSettings,
+    ApiTokens, ConfigVersions, CoreConfig, DashboardLayouts, DashboardWidgets,
+    DeviceDisplayOverrides, DeviceSensorConfigs, DeviceStateHistory, Devices, Floorplans,
+    GroupDevices, GroupLinks, GroupPositions, Groups, Integrations, RoutineRuleHolds, Routines,
+    SceneDeviceStates, SceneGroupStates, SceneOverrides, Scenes, UiState, Users, WidgetSettings,
 };
 use sea_orm::sea_query::{Expr, OnConflict};
 use sea_orm_migration::prelude::*;
@@ -15,6 +15,12 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(M20260227000000Init),
             Box::new(M20260420000000DashboardWidgetSources),
+            Box::new(M20261001000000DeviceStateHistory),
+            Box::new(M20261005000000CoreLocation),
+            Box::new(M20261008000000RoutineRuleHolds),
+            Box::new(M20261010000000RoutineRateLimits),
+            Box::new(M20261012000000GroupOccupancy),
+            Box::new(M20261014000000Auth),
         ]
     }
 }
@@ -113,6 +119,319 @@ impl MigrationTrait for M20260420000000DashboardWidgetSources {
     }
 }
 
+struct M20261001000000DeviceStateHistory;
+
+impl MigrationName for M20261001000000DeviceStateHistory {
+    fn name(&self) -> &str {
+        "m20261001000000_device_state_history"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for M20261001000000DeviceStateHistory {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        create_device_state_history(manager).await?;
+
+        if !manager
+            .has_column("core_config", "device_history_retention_days")
+            .await?
+        {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(CoreConfig::Table)
+                        .add_column(
+                            ColumnDef::new(CoreConfig::DeviceHistoryRetentionDays)
+                                .integer()
+                                .default(7),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(DeviceStateHistory::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CoreConfig::Table)
+                    .drop_column(CoreConfig::DeviceHistoryRetentionDays)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+struct M20261005000000CoreLocation;
+
+impl MigrationName for M20261005000000CoreLocation {
+    fn name(&self) -> &str {
+        "m20261005000000_core_location"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for M20261005000000CoreLocation {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (name, column) in [
+            ("latitude", CoreConfig::Latitude),
+            ("longitude", CoreConfig::Longitude),
+        ] {
+            if manager.has_column("core_config", name).await? {
+                continue;
+            }
+
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(CoreConfig::Table)
+                        .add_column(ColumnDef::new(column).double().null())
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [CoreConfig::Latitude, CoreConfig::Longitude] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(CoreConfig::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+struct M20261008000000RoutineRuleHolds;
+
+impl MigrationName for M20261008000000RoutineRuleHolds {
+    fn name(&self) -> &str {
+        "m20261008000000_routine_rule_holds"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for M20261008000000RoutineRuleHolds {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RoutineRuleHolds::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RoutineRuleHolds::RoutineId)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RoutineRuleHolds::RuleKey).text().not_null())
+                    .col(
+                        ColumnDef::new(RoutineRuleHolds::SinceMs)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RoutineRuleHolds::Fired)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(RoutineRuleHolds::RoutineId)
+                            .col(RoutineRuleHolds::RuleKey),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(RoutineRuleHolds::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+struct M20261010000000RoutineRateLimits;
+
+impl MigrationName for M20261010000000RoutineRateLimits {
+    fn name(&self) -> &str {
+        "m20261010000000_routine_rate_limits"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for M20261010000000RoutineRateLimits {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (name, column) in [
+            ("cooldown_ms", Routines::CooldownMs),
+            ("debounce_ms", Routines::DebounceMs),
+        ] {
+            if manager.has_column("routines", name).await? {
+                continue;
+            }
+
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Routines::Table)
+                        .add_column(ColumnDef::new(column).big_integer().null())
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Routines::CooldownMs, Routines::DebounceMs] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Routines::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+struct M20261012000000GroupOccupancy;
+
+impl MigrationName for M20261012000000GroupOccupancy {
+    fn name(&self) -> &str {
+        "m20261012000000_group_occupancy"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for M20261012000000GroupOccupancy {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.has_column("groups", "occupancy").await? {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Groups::Table)
+                    .add_column(ColumnDef::new(Groups::Occupancy).text().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Groups::Table)
+                    .drop_column(Groups::Occupancy)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+struct M20261014000000Auth;
+
+impl MigrationName for M20261014000000Auth {
+    fn name(&self) -> &str {
+        "m20261014000000_auth"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for M20261014000000Auth {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Users::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Users::Username)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Users::PasswordHash).text().not_null())
+                    .col(ColumnDef::new(Users::Role).text().not_null())
+                    .col(ColumnDef::new(Users::CreatedAtMs).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiTokens::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApiTokens::Id)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ApiTokens::Name).text().not_null())
+                    .col(
+                        ColumnDef::new(ApiTokens::TokenHash)
+                            .text()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(ApiTokens::Role).text().not_null())
+                    .col(ColumnDef::new(ApiTokens::Username).text().null())
+                    .col(ColumnDef::new(ApiTokens::ExpiresAtMs).big_integer().null())
+                    .col(
+                        ColumnDef::new(ApiTokens::CreatedAtMs)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiTokens::Table).if_exists().to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Users::Table).if_exists().to_owned())
+            .await
+    }
+}
+
 async fn create_devices(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
     manager
         .create_table(
@@ -741,6 +1060,62 @@ async fn create_widget_settings(manager: &SchemaManager<'_>) -> Result<(), DbErr
         .await
 }
 
+async fn create_device_state_history(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    manager
+        .create_table(
+            Table::create()
+                .table(DeviceStateHistory::Table)
+                .if_not_exists()
+                .col(
+                    ColumnDef::new(DeviceStateHistory::Id)
+                        .big_integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(
+                    ColumnDef::new(DeviceStateHistory::IntegrationId)
+                        .text()
+                        .not_null(),
+                )
+                .col(
+                    ColumnDef::new(DeviceStateHistory::DeviceId)
+                        .text()
+                        .not_null(),
+                )
+                .col(
+                    ColumnDef::new(DeviceStateHistory::RecordedAtMs)
+                        .big_integer()
+                        .not_null(),
+                )
+                .col(ColumnDef::new(DeviceStateHistory::State).text().not_null())
+                .col(ColumnDef::new(DeviceStateHistory::Raw).text())
+                .to_owned(),
+        )
+        .await?;
+
+    for index in [
+        Index::create()
+            .name("idx_device_state_history_device_time")
+            .table(DeviceStateHistory::Table)
+            .col(DeviceStateHistory::IntegrationId)
+            .col(DeviceStateHistory::DeviceId)
+            .col(DeviceStateHistory::RecordedAtMs)
+            .if_not_exists()
+            .to_owned(),
+        Index::create()
+            .name("idx_device_state_history_recorded_at")
+            .table(DeviceStateHistory::Table)
+            .col(DeviceStateHistory::RecordedAtMs)
+            .if_not_exists()
+            .to_owned(),
+    ] {
+        manager.create_index(index).await?;
+    }
+
+    Ok(())
+}
+
 async fn create_indexes(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
     for index in [
         Index::create()
@@ -8,6 +8,7 @@ use sqlx::Postgres;
 use std::{fs, path::PathBuf, time::Duration};
 
 pub mod actions;
+pub mod auth_queries;
 pub mod config_queries;
 pub mod migrations;
 pub mod schema;
@@ -11,11 +11,25 @@ pub enum Devices {
     State,
 }
 
+#[derive(Clone, Copy, Iden)]
+pub enum DeviceStateHistory {
+    Table,
+    Id,
+    IntegrationId,
+    DeviceId,
+    RecordedAtMs,
+    State,
+    Raw,
+}
+
 #[derive(Clone, Copy, Iden)]
 pub enum CoreConfig {
     Table,
     Id,
     WarmupTimeSeconds,
+    DeviceHistoryRetentionDays,
+    Latitude,
+    Longitude,
     UpdatedAt,
 }
 
@@ -36,6 +50,7 @@ pub enum Groups {
     Id,
     Name,
     Hidden,
+    Occupancy,
     CreatedAt,
     UpdatedAt,
 }
@@ -99,6 +114,8 @@ pub enum Routines {
     Enabled,
     Rules,
     Actions,
+    CooldownMs,
+    DebounceMs,
     CreatedAt,
     UpdatedAt,
 }
@@ -179,6 +196,15 @@ pub enum ConfigVersions {
     ConfigJson,
 }
 
+#[derive(Clone, Copy, Iden)]
+pub enum RoutineRuleHolds {
+    Table,
+    RoutineId,
+    RuleKey,
+    SinceMs,
+    Fired,
+}
+
 #[derive(Clone, Copy, Iden)]
 pub enum UiState {
     Table,
@@ -193,3 +219,24 @@ pub enum WidgetSettings {
     Config,
     UpdatedAt,
 }
+
+#[derive(Clone, Copy, Iden)]
+pub enum Users {
+    Table,
+    Username,
+    PasswordHash,
+    Role,
+    CreatedAtMs,
+}
+
+#[derive(Clone, Copy, Iden)]
+pub enum ApiTokens {
+    Table,
+    Id,
+    Name,
+    TokenHash,
+    Role,
+    Username,
+    ExpiresAtMs,
+    CreatedAtMs,
+}
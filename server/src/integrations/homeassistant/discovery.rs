@@ -0,0 +1,705 @@
+//! Translation between homectl runtime state and Home Assistant MQTT
+//! discovery.
+//!
+//! Every controllable device and group is exported as a JSON schema `light`,
+//! sensors as `sensor` / `binary_sensor` and scenes as `scene`. Commands
+//! published by Home Assistant are translated back into homectl [Action]s.
+
+use std::collections::{BTreeMap, HashMap};
+
+use color_eyre::Result;
+use eyre::eyre;
+use ordered_float::OrderedFloat;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::types::{
+    action::Action,
+    color::{Capabilities, DeviceColor},
+    device::{Device, DeviceData, DeviceKey, DevicesState, SensorDevice},
+    group::{FlattenedGroupsConfig, GroupId},
+    scene::{ActivateSceneActionDescriptor, FlattenedScenesConfig, SceneId},
+};
+
+const ON: &str = "ON";
+const OFF: &str = "OFF";
+
+/// Topic layout shared by discovery configs, state and command topics.
+#[derive(Clone, Debug)]
+pub struct Topics {
+    pub discovery_prefix: String,
+    pub base_topic: String,
+}
+
+impl Topics {
+    /// Availability topic, also used as the MQTT last will.
+    pub fn availability(&self) -> String {
+        format!("{}/status", self.base_topic)
+    }
+
+    /// Home Assistant publishes `online` here when it (re)starts, which means
+    /// discovery configs need to be published again.
+    pub fn ha_status(&self) -> String {
+        format!("{}/status", self.discovery_prefix)
+    }
+
+    /// Wildcard matching every command topic exported by [build_export].
+    pub fn command_subscription(&self) -> String {
+        format!("{}/+/+/set", self.base_topic)
+    }
+
+    fn entity(&self, kind: &str, object_id: &str, suffix: &str) -> String {
+        format!("{}/{kind}/{object_id}/{suffix}", self.base_topic)
+    }
+
+    fn config(&self, component: &str, object_id: &str) -> String {
+        format!(
+            "{}/{component}/{}/{object_id}/config",
+            self.discovery_prefix,
+            sanitize(&self.base_topic)
+        )
+    }
+}
+
+/// homectl object controlled through a command topic.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandTarget {
+    Device(DeviceKey),
+    Group(GroupId),
+    Scene(SceneId),
+}
+
+/// Everything that should currently be retained on the broker.
+#[derive(Debug, Default)]
+pub struct Export {
+    /// Retained messages keyed by topic.
+    pub messages: BTreeMap<String, String>,
+
+    /// Command topics and the homectl object each of them controls.
+    pub command_targets: HashMap<String, CommandTarget>,
+}
+
+impl Export {
+    /// Reserves an object id that is unique among the exported entities of
+    /// `kind`, since sanitizing may map distinct homectl ids onto the same
+    /// string.
+    fn reserve_object_id(&mut self, topics: &Topics, kind: &str, id: &str) -> String {
+        let base = sanitize(id);
+        let mut object_id = base.clone();
+        let mut suffix = 2;
+
+        while self
+            .command_targets
+            .contains_key(&topics.entity(kind, &object_id, "set"))
+            || self
+                .messages
+                .contains_key(&topics.entity(kind, &object_id, "state"))
+        {
+            object_id = format!("{base}_{suffix}");
+            suffix += 1;
+        }
+
+        object_id
+    }
+}
+
+/// Replaces everything except ASCII alphanumerics with underscores so that
+/// ids are valid both as MQTT topic levels and Home Assistant object ids.
+fn sanitize(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn supported_color_modes(capabilities: &Capabilities) -> Vec<&'static str> {
+    let mut modes = Vec::new();
+
+    if capabilities.xy {
+        modes.push("xy");
+    }
+    if capabilities.hs {
+        modes.push("hs");
+    }
+    if capabilities.rgb {
+        modes.push("rgb");
+    }
+    if capabilities.ct.is_some() {
+        modes.push("color_temp");
+    }
+    if modes.is_empty() {
+        modes.push("brightness");
+    }
+
+    modes
+}
+
+fn device_info(identifier: &str, name: &str, model: &str) -> Value {
+    json!({
+        "identifiers": [identifier],
+        "name": name,
+        "manufacturer": "homectl",
+        "model": model,
+    })
+}
+
+fn light_config(
+    topics: &Topics,
+    kind: &str,
+    object_id: &str,
+    name: &str,
+    model: &str,
+    capabilities: &Capabilities,
+) -> Value {
+    let unique_id = format!(
+        "homectl_{}_{kind}_{object_id}",
+        sanitize(&topics.base_topic)
+    );
+
+    let mut config = json!({
+        "name": null,
+        "unique_id": unique_id,
+        "schema": "json",
+        "state_topic": topics.entity(kind, object_id, "state"),
+        "command_topic": topics.entity(kind, object_id, "set"),
+        "availability_topic": topics.availability(),
+        "brightness": true,
+        "supported_color_modes": supported_color_modes(capabilities),
+        "device": device_info(&unique_id, name, model),
+    });
+
+    if let Some(ct) = &capabilities.ct {
+        config["color_temp_kelvin"] = json!(true);
+        config["min_kelvin"] = json!(ct.start);
+        config["max_kelvin"] = json!(ct.end);
+    }
+
+    config
+}
+
+fn brightness_to_ha(brightness: Option<OrderedFloat<f32>>) -> u8 {
+    (brightness.map(|b| *b).unwrap_or(1.0) * 255.0)
+        .round()
+        .clamp(0.0, 255.0) as u8
+}
+
+fn color_to_ha(color: &DeviceColor) -> (&'static str, Value) {
+    match color {
+        DeviceColor::Xy(xy) => ("xy", json!({ "color": { "x": *xy.x, "y": *xy.y } })),
+        DeviceColor::Hs(hs) => (
+            "hs",
+            json!({ "color": { "h": hs.h, "s": (*hs.s * 100.0).round() } }),
+        ),
+        DeviceColor::Rgb(rgb) => (
+            "rgb",
+            json!({ "color": { "r": rgb.r, "g": rgb.g, "b": rgb.b } }),
+        ),
+        DeviceColor::Ct(ct) => ("color_temp", json!({ "color_temp": ct.ct })),
+    }
+}
+
+/// Home Assistant JSON schema light state for a controllable device.
+fn light_state(device: &Device) -> Option<Value> {
+    let DeviceData::Controllable(data) = &device.data else {
+        return None;
+    };
+
+    let mut state = json!({
+        "state": if data.state.power { ON } else { OFF },
+        "brightness": brightness_to_ha(data.state.brightness),
+    });
+
+    // Home Assistant rejects colors in modes the light didn't declare
+    let color = data
+        .state
+        .color
+        .as_ref()
+        .and_then(|color| color.to_device_preferred_mode(&data.capabilities));
+
+    match color {
+        Some(color) => {
+            let (mode, fields) = color_to_ha(&color);
+            state["color_mode"] = json!(mode);
+            if let (Some(state), Some(fields)) = (state.as_object_mut(), fields.as_object()) {
+                state.extend(fields.clone());
+            }
+        }
+        None if supported_color_modes(&data.capabilities) == ["brightness"] => {
+            state["color_mode"] = json!("brightness");
+        }
+        None => {}
+    }
+
+    Some(state)
+}
+
+/// Aggregated group state: on when any member is on, with the brightest
+/// powered member's brightness.
+fn group_state(devices: &[&Device]) -> Value {
+    let powered = devices
+        .iter()
+        .filter_map(|device| device.get_controllable_state())
+        .filter(|state| state.power)
+        .collect::<Vec<_>>();
+
+    let brightness = powered
+        .iter()
+        .map(|state| state.brightness.unwrap_or(OrderedFloat(1.0)))
+        .max();
+
+    json!({
+        "state": if powered.is_empty() { OFF } else { ON },
+        "brightness": brightness_to_ha(brightness),
+        "color_mode": "brightness",
+    })
+}
+
+fn export_device(export: &mut Export, topics: &Topics, device: &Device) {
+    let key = device.get_device_key();
+    let id = format!("{}/{}", key.integration_id, key.device_id);
+
+    match &device.data {
+        DeviceData::Controllable(data) => {
+            let object_id = export.reserve_object_id(topics, "device", &id);
+            let config = light_config(
+                topics,
+                "device",
+                &object_id,
+                &device.name,
+                &device.integration_id.to_string(),
+                &data.capabilities,
+            );
+
+            export
+                .messages
+                .insert(topics.config("light", &object_id), config.to_string());
+            if let Some(state) = light_state(device) {
+                export.messages.insert(
+                    topics.entity("device", &object_id, "state"),
+                    state.to_string(),
+                );
+            }
+            export.command_targets.insert(
+                topics.entity("device", &object_id, "set"),
+                CommandTarget::Device(key),
+            );
+        }
+        DeviceData::Sensor(sensor) => {
+            let (component, value) = match sensor {
+                _ if sensor.is_unknown_placeholder() => return,
+                SensorDevice::Boolean { value } => {
+                    ("binary_sensor", if *value { ON } else { OFF }.to_string())
+                }
+                SensorDevice::Number { value } => ("sensor", value.to_string()),
+                SensorDevice::Text { value } => ("sensor", value.clone()),
+                SensorDevice::Color(_) => return,
+            };
+
+            let object_id = export.reserve_object_id(topics, "device", &id);
+            let unique_id = format!(
+                "homectl_{}_device_{object_id}",
+                sanitize(&topics.base_topic)
+            );
+            let config = json!({
+                "name": null,
+                "unique_id": unique_id,
+                "state_topic": topics.entity("device", &object_id, "state"),
+                "availability_topic": topics.availability(),
+                "device": device_info(&unique_id, &device.name, &device.integration_id.to_string()),
+            });
+
+            export
+                .messages
+                .insert(topics.config(component, &object_id), config.to_string());
+            export
+                .messages
+                .insert(topics.entity("device", &object_id, "state"), value);
+        }
+    }
+}
+
+/// Builds the full set of retained discovery and state messages for the
+/// given runtime state.
+pub fn build_export(
+    topics: &Topics,
+    devices: &DevicesState,
+    groups: &FlattenedGroupsConfig,
+    scenes: &FlattenedScenesConfig,
+) -> Export {
+    let mut export = Export::default();
+
+    for device in devices.0.values() {
+        export_device(&mut export, topics, device);
+    }
+
+    for (group_id, group) in &groups.0 {
+        if group.hidden.unwrap_or(false) {
+            continue;
+        }
+
+        let members = group
+            .device_keys
+            .iter()
+            .filter_map(|key| devices.0.get(key))
+            .filter(|device| device.get_controllable_state().is_some())
+            .collect::<Vec<_>>();
+
+        let object_id = export.reserve_object_id(topics, "group", &group_id.to_string());
+        let config = light_config(
+            topics,
+            "group",
+            &object_id,
+            &group.name,
+            "group",
+            &Capabilities::default(),
+        );
+
+        export
+            .messages
+            .insert(topics.config("light", &object_id), config.to_string());
+        export.messages.insert(
+            topics.entity("group", &object_id, "state"),
+            group_state(&members).to_string(),
+        );
+        export.command_targets.insert(
+            topics.entity("group", &object_id, "set"),
+            CommandTarget::Group(group_id.clone()),
+        );
+    }
+
+    for (scene_id, scene) in &scenes.0 {
+        if scene.hidden.unwrap_or(false) {
+            continue;
+        }
+
+        let object_id = export.reserve_object_id(topics, "scene", &scene_id.to_string());
+        let unique_id = format!("homectl_{}_scene_{object_id}", sanitize(&topics.base_topic));
+        let config = json!({
+            "name": scene.name,
+            "unique_id": unique_id,
+            "command_topic": topics.entity("scene", &object_id, "set"),
+            "availability_topic": topics.availability(),
+            "payload_on": ON,
+        });
+
+        export
+            .messages
+            .insert(topics.config("scene", &object_id), config.to_string());
+        export.command_targets.insert(
+            topics.entity("scene", &object_id, "set"),
+            CommandTarget::Scene(scene_id.clone()),
+        );
+    }
+
+    export
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CommandColor {
+    x: Option<f32>,
+    y: Option<f32>,
+    h: Option<f32>,
+    s: Option<f32>,
+    r: Option<u8>,
+    g: Option<u8>,
+    b: Option<u8>,
+}
+
+/// Home Assistant JSON schema light command.
+#[derive(Debug, Default, Deserialize)]
+struct LightCommand {
+    state: Option<String>,
+    brightness: Option<f32>,
+    color: Option<CommandColor>,
+    color_temp: Option<u16>,
+    transition: Option<f32>,
+}
+
+impl LightCommand {
+    fn color(&self) -> Option<DeviceColor> {
+        if let Some(ct) = self.color_temp {
+            return Some(DeviceColor::new_from_ct(ct));
+        }
+
+        let color = self.color.as_ref()?;
+        match color {
+            CommandColor {
+                x: Some(x),
+                y: Some(y),
+                ..
+            } => Some(DeviceColor::new_from_xy(*x, *y)),
+            CommandColor {
+                h: Some(h),
+                s: Some(s),
+                ..
+            } => Some(DeviceColor::new_from_hs(h.round() as u16, s / 100.0)),
+            CommandColor {
+                r: Some(r),
+                g: Some(g),
+                b: Some(b),
+                ..
+            } => Some(DeviceColor::new_from_rgb(*r, *g, *b)),
+            _ => None,
+        }
+    }
+
+    /// Applies the command on top of the device's current state, returning
+    /// `None` for devices that can't be controlled.
+    fn apply(&self, device: &Device) -> Option<Device> {
+        let mut state = device.get_controllable_state()?.clone();
+
+        state.power = match self.state.as_deref() {
+            Some(OFF) => false,
+            Some(_) => true,
+            None => state.power,
+        };
+        if let Some(brightness) = self.brightness {
+            state.brightness = Some(OrderedFloat((brightness / 255.0).clamp(0.0, 1.0)));
+        }
+        if let Some(color) = self.color() {
+            state.color = Some(color);
+        }
+        state.transition = self.transition.map(OrderedFloat);
+
+        Some(device.set_controllable_state(state))
+    }
+}
+
+/// Translates a Home Assistant command into the homectl actions it stands
+/// for.
+pub fn command_to_actions(
+    target: &CommandTarget,
+    payload: &[u8],
+    devices: &DevicesState,
+    groups: &FlattenedGroupsConfig,
+) -> Result<Vec<Action>> {
+    let device_command = |key: &DeviceKey, command: &LightCommand| {
+        devices
+            .0
+            .get(key)
+            .and_then(|device| command.apply(device))
+            .map(Action::SetDeviceState)
+    };
+
+    match target {
+        CommandTarget::Device(key) => {
+            let command: LightCommand = serde_json::from_slice(payload)?;
+            let action = device_command(key, &command)
+                .ok_or_else(|| eyre!("Device {key} is not a controllable device"))?;
+
+            Ok(vec![action])
+        }
+        CommandTarget::Group(group_id) => {
+            let command: LightCommand = serde_json::from_slice(payload)?;
+            let group = groups
+                .0
+                .get(group_id)
+                .ok_or_else(|| eyre!("Unknown group {group_id}"))?;
+
+            Ok(group
+                .device_keys
+                .iter()
+                .filter_map(|key| device_command(key, &command))
+                .collect())
+        }
+        CommandTarget::Scene(scene_id) => {
+            Ok(vec![Action::ActivateScene(ActivateSceneActionDescriptor {
+                scene_id: scene_id.clone(),
+                mirror_from_group: None,
+                device_keys: None,
+                group_keys: None,
+                include_source_groups: false,
+                use_scene_transition: false,
+                transition: None,
+                rollout: None,
+                rollout_source_device_key: None,
+                rollout_duration_ms: None,
+                fade: None,
+            })])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        device::{ControllableDevice, DeviceId, ManageKind},
+        group::FlattenedGroupConfig,
+        integration::IntegrationId,
+        scene::{FlattenedSceneConfig, SceneDeviceStates},
+    };
+    use std::str::FromStr;
+
+    fn topics() -> Topics {
+        Topics {
+            discovery_prefix: "homeassistant".to_string(),
+            base_topic: "homectl".to_string(),
+        }
+    }
+
+    fn light(id: &str, power: bool) -> Device {
+        Device::new(
+            IntegrationId::from_str("hue").unwrap(),
+            DeviceId::new(id),
+            format!("Light {id}"),
+            DeviceData::Controllable(ControllableDevice::new(
+                None,
+                power,
+                Some(0.5),
+                Some(DeviceColor::new_from_xy(0.3, 0.3)),
+                None,
+                Capabilities {
+                    xy: true,
+                    ct: Some(2000..6500),
+                    ..Default::default()
+                },
+                ManageKind::Full,
+            )),
+            None,
+        )
+    }
+
+    fn devices_state(devices: Vec<Device>) -> DevicesState {
+        DevicesState(
+            devices
+                .into_iter()
+                .map(|device| (device.get_device_key(), device))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn exports_lights_with_discovery_config_and_state() {
+        let devices = devices_state(vec![light("Living Room/1", true)]);
+        let export = build_export(
+            &topics(),
+            &devices,
+            &Default::default(),
+            &Default::default(),
+        );
+
+        let config: Value = serde_json::from_str(
+            &export.messages["homeassistant/light/homectl/hue_living_room_1/config"],
+        )
+        .unwrap();
+        assert_eq!(
+            config["command_topic"],
+            "homectl/device/hue_living_room_1/set"
+        );
+        assert_eq!(config["supported_color_modes"], json!(["xy", "color_temp"]));
+        assert_eq!(config["min_kelvin"], 2000);
+
+        let state: Value =
+            serde_json::from_str(&export.messages["homectl/device/hue_living_room_1/state"])
+                .unwrap();
+        assert_eq!(state["state"], "ON");
+        assert_eq!(state["brightness"], 128);
+        assert_eq!(state["color_mode"], "xy");
+
+        assert_eq!(
+            export.command_targets["homectl/device/hue_living_room_1/set"],
+            CommandTarget::Device(devices.0.keys().next().unwrap().clone())
+        );
+    }
+
+    #[test]
+    fn colliding_object_ids_are_disambiguated() {
+        let devices = devices_state(vec![light("a-b", false), light("a_b", false)]);
+        let export = build_export(
+            &topics(),
+            &devices,
+            &Default::default(),
+            &Default::default(),
+        );
+
+        assert_eq!(export.command_targets.len(), 2);
+        assert!(export
+            .command_targets
+            .contains_key("homectl/device/hue_a_b_2/set"));
+    }
+
+    #[test]
+    fn device_commands_map_to_set_device_state() {
+        let device = light("1", false);
+        let key = device.get_device_key();
+        let devices = devices_state(vec![device]);
+
+        let actions = command_to_actions(
+            &CommandTarget::Device(key),
+            br#"{"state":"ON","brightness":255,"color_temp":2700}"#,
+            &devices,
+            &Default::default(),
+        )
+        .unwrap();
+
+        let [Action::SetDeviceState(device)] = actions.as_slice() else {
+            panic!("expected a single SetDeviceState action, got {actions:?}");
+        };
+        let state = device.get_controllable_state().unwrap();
+        assert!(state.power);
+        assert_eq!(state.brightness, Some(OrderedFloat(1.0)));
+        assert_eq!(state.color, Some(DeviceColor::new_from_ct(2700)));
+    }
+
+    #[test]
+    fn group_and_scene_commands_map_to_actions() {
+        let devices = devices_state(vec![light("1", true), light("2", true)]);
+        let group_id = GroupId::from_str("downstairs").unwrap();
+        let groups = FlattenedGroupsConfig(BTreeMap::from([(
+            group_id.clone(),
+            FlattenedGroupConfig {
+                name: "Downstairs".to_string(),
+                device_keys: devices.0.keys().cloned().collect(),
+                hidden: None,
+            },
+        )]));
+
+        let actions = command_to_actions(
+            &CommandTarget::Group(group_id),
+            br#"{"state":"OFF"}"#,
+            &devices,
+            &groups,
+        )
+        .unwrap();
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|action| matches!(
+            action,
+            Action::SetDeviceState(device) if device.is_powered_on() == Some(false)
+        )));
+
+        let scene_id = SceneId::from_str("evening").unwrap();
+        let actions = command_to_actions(
+            &CommandTarget::Scene(scene_id.clone()),
+            b"ON",
+            &devices,
+            &groups,
+        )
+        .unwrap();
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::ActivateScene(descriptor)] if descriptor.scene_id == scene_id
+        ));
+    }
+
+    #[test]
+    fn hidden_scenes_are_not_exported() {
+        let scenes = FlattenedScenesConfig(BTreeMap::from([(
+            SceneId::from_str("secret").unwrap(),
+            FlattenedSceneConfig {
+                name: "Secret".to_string(),
+                devices: SceneDeviceStates(BTreeMap::new()),
+                active_overrides: Vec::new(),
+                hidden: Some(true),
+            },
+        )]));
+
+        let export = build_export(&topics(), &Default::default(), &Default::default(), &scenes);
+        assert!(export.messages.is_empty());
+    }
+}
@@ -0,0 +1,439 @@
+mod discovery;
+
+use crate::{
+    core::{
+        integrations::status,
+        snapshot::{RuntimeSnapshot, SnapshotHandle},
+    },
+    types::{
+        device::DevicesState,
+        event::{Event, TxEventChannel},
+        group::FlattenedGroupsConfig,
+        integration::{Integration, IntegrationId},
+        scene::FlattenedScenesConfig,
+    },
+    utils::cli::Cli,
+};
+use async_trait::async_trait;
+use color_eyre::Result;
+use eyre::{eyre, Context};
+use rand::{distributions::Alphanumeric, Rng};
+use rumqttc::{AsyncClient, LastWill, MqttOptions, QoS};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
+use std::time::Duration;
+use tokio::task::{self, JoinHandle};
+
+use self::discovery::{build_export, command_to_actions, CommandTarget, Topics};
+
+const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+const DEFAULT_BASE_TOPIC: &str = "homectl";
+const DEFAULT_PUBLISH_INTERVAL_MS: u64 = 500;
+
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct HomeAssistantConfig {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+
+    /// Home Assistant MQTT discovery prefix, defaults to `homeassistant`.
+    discovery_prefix: Option<String>,
+
+    /// Prefix for homectl's own state and command topics, defaults to
+    /// `homectl`.
+    base_topic: Option<String>,
+
+    /// How often to check the runtime state for changes that need to be
+    /// published.
+    publish_interval_ms: Option<u64>,
+}
+
+impl HomeAssistantConfig {
+    fn topics(&self) -> Topics {
+        let trimmed = |topic: &Option<String>, default: &str| {
+            topic
+                .as_deref()
+                .map(|topic| topic.trim_end_matches('/'))
+                .filter(|topic| !topic.is_empty())
+                .unwrap_or(default)
+                .to_string()
+        };
+
+        Topics {
+            discovery_prefix: trimmed(&self.discovery_prefix, DEFAULT_DISCOVERY_PREFIX),
+            base_topic: trimmed(&self.base_topic, DEFAULT_BASE_TOPIC),
+        }
+    }
+}
+
+type CommandTargets = Arc<RwLock<HashMap<String, CommandTarget>>>;
+
+/// Exports homectl devices, groups and scenes to Home Assistant through MQTT
+/// discovery, and maps Home Assistant commands back onto homectl actions.
+pub struct HomeAssistant {
+    id: IntegrationId,
+    event_tx: TxEventChannel,
+    config: HomeAssistantConfig,
+    snapshot: Option<SnapshotHandle>,
+    client: Option<AsyncClient>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl HomeAssistant {
+    /// The exporter mirrors runtime state, so it needs read access to the
+    /// runtime snapshot in addition to what other integrations receive.
+    pub fn with_snapshot(mut self, snapshot: Option<SnapshotHandle>) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+}
+
+/// The parts of the runtime snapshot that are exported to Home Assistant.
+#[derive(Clone)]
+struct ExportedState {
+    devices: Arc<DevicesState>,
+    groups: Arc<FlattenedGroupsConfig>,
+    scenes: Arc<FlattenedScenesConfig>,
+}
+
+impl ExportedState {
+    fn from_snapshot(snapshot: &RuntimeSnapshot) -> Self {
+        ExportedState {
+            devices: Arc::clone(&snapshot.devices),
+            groups: Arc::clone(&snapshot.flattened_groups),
+            scenes: Arc::clone(&snapshot.flattened_scenes),
+        }
+    }
+
+    /// Snapshots share unchanged fields by pointer, so this is a cheap way to
+    /// tell whether anything exported may have changed.
+    fn ptr_eq(&self, other: &ExportedState) -> bool {
+        Arc::ptr_eq(&self.devices, &other.devices)
+            && Arc::ptr_eq(&self.groups, &other.groups)
+            && Arc::ptr_eq(&self.scenes, &other.scenes)
+    }
+}
+
+/// Tracks which retained messages have been published, so that each publish
+/// pass only sends what changed.
+#[derive(Default)]
+struct Publisher {
+    published: BTreeMap<String, String>,
+    last_state: Option<ExportedState>,
+}
+
+impl Publisher {
+    /// Returns the messages that need to be published to bring the broker up
+    /// to date with `state`. Entities that no longer exist get an empty
+    /// retained payload, which removes them from Home Assistant.
+    fn pending_messages(
+        &mut self,
+        topics: &Topics,
+        state: ExportedState,
+        command_targets: &CommandTargets,
+    ) -> Vec<(String, String)> {
+        if self
+            .last_state
+            .as_ref()
+            .is_some_and(|last| last.ptr_eq(&state))
+        {
+            return Vec::new();
+        }
+
+        let export = build_export(topics, &state.devices, &state.groups, &state.scenes);
+        self.last_state = Some(state);
+
+        let mut pending = self
+            .published
+            .keys()
+            .filter(|topic| !export.messages.contains_key(*topic))
+            .map(|topic| (topic.clone(), String::new()))
+            .collect::<Vec<_>>();
+
+        pending.extend(
+            export
+                .messages
+                .iter()
+                .filter(|(topic, payload)| self.published.get(*topic) != Some(payload))
+                .map(|(topic, payload)| (topic.clone(), payload.clone())),
+        );
+
+        self.published = export.messages;
+        *command_targets
+            .write()
+            .expect("homeassistant command targets lock poisoned") = export.command_targets;
+
+        pending
+    }
+
+    /// Forgets everything published so far, e.g. after reconnecting or when
+    /// Home Assistant has restarted.
+    fn reset(&mut self) {
+        self.last_state = None;
+        self.published.clear();
+    }
+}
+
+#[async_trait]
+impl Integration for HomeAssistant {
+    fn new(
+        id: &IntegrationId,
+        config: &serde_json::Value,
+        _cli: &Cli,
+        event_tx: TxEventChannel,
+    ) -> Result<Self> {
+        let config: HomeAssistantConfig = serde_json::from_value(config.clone())
+            .wrap_err("Failed to deserialize config of HomeAssistant integration")?;
+
+        Ok(HomeAssistant {
+            id: id.clone(),
+            event_tx,
+            config,
+            snapshot: None,
+            client: None,
+            handles: Vec::new(),
+        })
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let snapshot = self
+            .snapshot
+            .clone()
+            .ok_or_else(|| eyre!("homeassistant integration requires the runtime snapshot"))?;
+
+        let random_string: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+
+        let topics = self.config.topics();
+
+        let mut options = MqttOptions::new(
+            format!("{}-{}", self.id, random_string),
+            self.config.host.clone(),
+            self.config.port,
+        );
+        options.set_keep_alive(Duration::from_secs(5));
+        options.set_last_will(LastWill::new(
+            topics.availability(),
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+
+        if let Some(username) = &self.config.username {
+            options.set_credentials(username, self.config.password.as_deref().unwrap_or(""));
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, 100);
+
+        self.client = Some(client.clone());
+
+        let command_targets = CommandTargets::default();
+        let republish = Arc::new(AtomicBool::new(false));
+
+        // Incoming packets: (re)subscribe on connect and translate commands
+        // into actions.
+        let eventloop_handle = {
+            let id = self.id.clone();
+            let event_tx = self.event_tx.clone();
+            let topics = topics.clone();
+            let snapshot = snapshot.clone();
+            let client = client.clone();
+            let command_targets = Arc::clone(&command_targets);
+            let republish = Arc::clone(&republish);
+
+            task::spawn(async move {
+                loop {
+                    let notification = eventloop.poll().await;
+                    status::record_mqtt_notification(&id, &notification);
+
+                    let res = async {
+                        match notification? {
+                            rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) => {
+                                client
+                                    .subscribe(topics.command_subscription(), QoS::AtMostOnce)
+                                    .await?;
+                                client
+                                    .subscribe(topics.ha_status(), QoS::AtMostOnce)
+                                    .await?;
+                                republish.store(true, Ordering::SeqCst);
+                            }
+
+                            rumqttc::Event::Incoming(rumqttc::Packet::Publish(msg)) => {
+                                if msg.topic == topics.ha_status() {
+                                    if msg.payload.as_ref() == b"online" {
+                                        republish.store(true, Ordering::SeqCst);
+                                    }
+                                    return Ok(());
+                                }
+
+                                let target = command_targets
+                                    .read()
+                                    .expect("homeassistant command targets lock poisoned")
+                                    .get(&msg.topic)
+                                    .cloned();
+
+                                let Some(target) = target else {
+                                    debug!("Ignoring command on unknown topic {}", msg.topic);
+                                    return Ok(());
+                                };
+
+                                let snapshot = snapshot.load_full();
+                                match command_to_actions(
+                                    &target,
+                                    &msg.payload,
+                                    &snapshot.devices,
+                                    &snapshot.flattened_groups,
+                                ) {
+                                    Ok(actions) => {
+                                        for action in actions {
+                                            event_tx.send(Event::Action(action));
+                                        }
+                                    }
+                                    Err(e) => {
+                                        warn!("Ignoring invalid command on {}: {e}", msg.topic);
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+
+                        Ok::<(), Box<dyn std::error::Error + Sync + Send>>(())
+                    }
+                    .await;
+
+                    if let Err(e) = res {
+                        error!(
+                            target: &format!("homectl_server::integrations::homeassistant::{id}"),
+                            "homeassistant error: {e:?}"
+                        );
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            })
+        };
+
+        // Outgoing packets: periodically diff the runtime snapshot against
+        // what has been published so far.
+        let publisher_handle = {
+            let id = self.id.clone();
+            let publish_interval = Duration::from_millis(
+                self.config
+                    .publish_interval_ms
+                    .unwrap_or(DEFAULT_PUBLISH_INTERVAL_MS),
+            );
+
+            task::spawn(async move {
+                let mut publisher = Publisher::default();
+                let mut interval = tokio::time::interval(publish_interval);
+
+                loop {
+                    interval.tick().await;
+
+                    if republish.swap(false, Ordering::SeqCst) {
+                        publisher.reset();
+
+                        if let Err(e) = client
+                            .publish(topics.availability(), QoS::AtLeastOnce, true, "online")
+                            .await
+                        {
+                            warn!("homeassistant integration {id} failed to publish availability: {e}");
+                        }
+                    }
+
+                    let state = ExportedState::from_snapshot(&snapshot.load());
+                    for (topic, payload) in
+                        publisher.pending_messages(&topics, state, &command_targets)
+                    {
+                        if let Err(e) = client
+                            .publish(topic.clone(), QoS::AtLeastOnce, true, payload)
+                            .await
+                        {
+                            warn!("homeassistant integration {id} failed to publish {topic}: {e}");
+                        }
+                    }
+                }
+            })
+        };
+
+        self.handles = vec![eventloop_handle, publisher_handle];
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        for handle in self.handles.drain(..) {
+            handle.abort();
+        }
+
+        if let Some(client) = self.client.take() {
+            let topics = self.config.topics();
+            let _ = client
+                .publish(topics.availability(), QoS::AtLeastOnce, true, "offline")
+                .await;
+            let _ = client.disconnect().await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exported_state(scenes: serde_json::Value) -> ExportedState {
+        ExportedState {
+            devices: Default::default(),
+            groups: Default::default(),
+            scenes: Arc::new(serde_json::from_value(scenes).unwrap()),
+        }
+    }
+
+    #[test]
+    fn publisher_only_sends_changes() {
+        let topics = HomeAssistantConfig::default().topics();
+        let command_targets = CommandTargets::default();
+        let mut publisher = Publisher::default();
+
+        let state = exported_state(serde_json::json!({
+            "evening": {
+                "name": "Evening",
+                "devices": {},
+                "active_overrides": [],
+                "hidden": null
+            }
+        }));
+
+        let first = publisher.pending_messages(&topics, state.clone(), &command_targets);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].0, "homeassistant/scene/homectl/evening/config");
+        assert_eq!(command_targets.read().unwrap().len(), 1);
+
+        let unchanged = publisher.pending_messages(&topics, state, &command_targets);
+        assert!(unchanged.is_empty());
+
+        let empty = exported_state(serde_json::json!({}));
+        let removed = publisher.pending_messages(&topics, empty.clone(), &command_targets);
+        assert_eq!(
+            removed,
+            vec![(
+                "homeassistant/scene/homectl/evening/config".to_string(),
+                String::new()
+            )]
+        );
+        assert!(command_targets.read().unwrap().is_empty());
+
+        publisher.reset();
+        assert!(publisher
+            .pending_messages(&topics, empty, &command_targets)
+            .is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+//! Polls devices from REST endpoints and sends state changes back as HTTP
+//! requests.
+//!
+//! Payloads are mapped to devices with the same JSON pointer fields as the
+//! `mqtt` integration.
+
+use crate::{
+    core::integrations::status,
+    integrations::json_mapping::{device_to_json, json_to_device, JsonDeviceMapping},
+    types::{
+        device::Device,
+        event::{Event, TxEventChannel},
+        integration::{Integration, IntegrationId},
+    },
+    utils::cli::Cli,
+};
+use async_trait::async_trait;
+use color_eyre::Result;
+use eyre::{eyre, Context};
+use jsonptr::{Assign, PointerBuf};
+use serde::Deserialize;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::time;
+
+const DEFAULT_POLL_INTERVAL_MS: u64 = 5000;
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct HttpPollEndpoint {
+    url: String,
+    /// JSON pointer to an array of devices in the response. Without it, an
+    /// array response is read as a list of devices and anything else as a
+    /// single device.
+    devices_field: Option<PointerBuf>,
+    /// Device id for endpoints that describe a single device without
+    /// including its id.
+    device_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpSetMethod {
+    Put,
+    #[default]
+    Post,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct HttpSetRequest {
+    #[serde(default)]
+    method: HttpSetMethod,
+    /// Supports `{id}` and `{name}` placeholders.
+    url: String,
+    /// JSON the mapped device state is written into. `{id}` and `{name}`
+    /// placeholders in string values are replaced.
+    body_template: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct HttpConfig {
+    poll: Vec<HttpPollEndpoint>,
+    poll_interval_ms: Option<u64>,
+    timeout_ms: Option<u64>,
+    /// Sent with every request, e.g. for authentication.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Devices are read-only when this is not set.
+    set_request: Option<HttpSetRequest>,
+
+    #[serde(flatten)]
+    mapping: JsonDeviceMapping,
+}
+
+pub struct Http {
+    id: IntegrationId,
+    event_tx: TxEventChannel,
+    config: Arc<HttpConfig>,
+    cli: Cli,
+    client: reqwest::Client,
+    handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[async_trait]
+impl Integration for Http {
+    fn new(
+        id: &IntegrationId,
+        config: &serde_json::Value,
+        cli: &Cli,
+        event_tx: TxEventChannel,
+    ) -> Result<Self> {
+        let config: HttpConfig = serde_json::from_value(config.clone())
+            .wrap_err("Failed to deserialize config of Http integration")?;
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(
+                config.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+            ))
+            .build()
+            .wrap_err("Failed to build HTTP client of Http integration")?;
+
+        Ok(Http {
+            id: id.clone(),
+            event_tx,
+            config: Arc::new(config),
+            cli: cli.clone(),
+            client,
+            handle: None,
+        })
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let id = self.id.clone();
+        let event_tx = self.event_tx.clone();
+        let config = self.config.clone();
+        let client = self.client.clone();
+
+        let poll_rate = Duration::from_millis(
+            config
+                .poll_interval_ms
+                .unwrap_or(DEFAULT_POLL_INTERVAL_MS)
+                .max(100),
+        );
+
+        self.handle = Some(tokio::spawn(async move {
+            let mut interval = time::interval(poll_rate);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                let mut failed = Vec::new();
+
+                for endpoint in &config.poll {
+                    match poll_endpoint(&client, &id, &config, endpoint).await {
+                        Ok(devices) => {
+                            status::record_integration_message(&id);
+                            for device in devices {
+                                event_tx.send(Event::ExternalStateUpdate { device });
+                            }
+                        }
+                        Err(e) => {
+                            error!(
+                                target: &format!("homectl_server::integrations::http::{id}"),
+                                "Failed to poll {url}: {e:?}",
+                                url = endpoint.url
+                            );
+                            failed.push(format!("{}: {e}", endpoint.url));
+                        }
+                    }
+                }
+
+                // One status per round, so that a working endpoint doesn't
+                // hide a failing one
+                if failed.is_empty() {
+                    status::mark_integration_connected(&id);
+                } else {
+                    status::mark_integration_disconnected(
+                        &id,
+                        format!(
+                            "Failed to poll {} of {} endpoints: {}",
+                            failed.len(),
+                            config.poll.len(),
+                            failed.join("; ")
+                        ),
+                    );
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn set_integration_device_state(&mut self, device: &Device) -> Result<()> {
+        let Some(set_request) = &self.config.set_request else {
+            debug!(
+                "Http integration {} has no set_request, ignoring {device}",
+                self.id
+            );
+            return Ok(());
+        };
+
+        let url = fill_placeholders(&set_request.url, device);
+        let body = set_request_body(device, set_request, &self.config.mapping)?;
+
+        if self.cli.dry_run {
+            debug!("(dry run) would send device state to {url}: {device}");
+            return Ok(());
+        }
+
+        let request = match set_request.method {
+            HttpSetMethod::Put => self.client.put(&url),
+            HttpSetMethod::Post => self.client.post(&url),
+        };
+
+        with_headers(request, &self.config.headers)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+async fn poll_endpoint(
+    client: &reqwest::Client,
+    integration_id: &IntegrationId,
+    config: &HttpConfig,
+    endpoint: &HttpPollEndpoint,
+) -> Result<Vec<Device>> {
+    let response: serde_json::Value = with_headers(client.get(&endpoint.url), &config.headers)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(response_devices(response, endpoint, &config.mapping)?
+        .iter()
+        .filter_map(|value| {
+            json_to_device(
+                value,
+                &endpoint.url,
+                integration_id.clone(),
+                &config.mapping,
+            )
+        })
+        .collect())
+}
+
+/// Splits a poll response into one JSON value per device.
+fn response_devices(
+    response: serde_json::Value,
+    endpoint: &HttpPollEndpoint,
+    mapping: &JsonDeviceMapping,
+) -> Result<Vec<serde_json::Value>> {
+    let mut devices = match &endpoint.devices_field {
+        Some(devices_field) => match devices_field.resolve(&response) {
+            Ok(serde_json::Value::Array(devices)) => devices.clone(),
+            _ => {
+                return Err(eyre!(
+                    "Expected an array of devices at '{devices_field}' in response"
+                ))
+            }
+        },
+        None => match response {
+            serde_json::Value::Array(devices) => devices,
+            device => vec![device],
+        },
+    };
+
+    if let Some(device_id) = &endpoint.device_id {
+        for device in &mut devices {
+            device.assign(
+                mapping.id_field(),
+                serde_json::Value::String(device_id.clone()),
+            )?;
+        }
+    }
+
+    Ok(devices)
+}
+
+fn set_request_body(
+    device: &Device,
+    set_request: &HttpSetRequest,
+    mapping: &JsonDeviceMapping,
+) -> Result<serde_json::Value> {
+    let mut body = set_request.body_template.clone().unwrap_or_default();
+    fill_template(&mut body, device);
+
+    device_to_json(device.clone(), mapping, body)
+}
+
+fn fill_placeholders(template: &str, device: &Device) -> String {
+    template
+        .replace("{id}", &device.id.to_string())
+        .replace("{name}", &device.name)
+}
+
+fn fill_template(value: &mut serde_json::Value, device: &Device) {
+    match value {
+        serde_json::Value::String(s) => *s = fill_placeholders(s, device),
+        serde_json::Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| fill_template(value, device)),
+        serde_json::Value::Object(values) => values
+            .values_mut()
+            .for_each(|value| fill_template(value, device)),
+        _ => {}
+    }
+}
+
+fn with_headers(
+    mut request: reqwest::RequestBuilder,
+    headers: &BTreeMap<String, String>,
+) -> reqwest::RequestBuilder {
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        color::Capabilities,
+        device::{ControllableDevice, DeviceData, DeviceId, ManageKind, SensorDevice},
+    };
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn endpoint(devices_field: Option<&str>, device_id: Option<&str>) -> HttpPollEndpoint {
+        HttpPollEndpoint {
+            url: "http://localhost/status".to_string(),
+            devices_field: devices_field.map(|field| PointerBuf::parse(field).unwrap()),
+            device_id: device_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn maps_poll_responses_to_devices() {
+        let integration_id = IntegrationId::from_str("http").unwrap();
+        let mapping = JsonDeviceMapping {
+            sensor_value_fields: Some(vec![PointerBuf::parse("/power_w").unwrap()]),
+            ..Default::default()
+        };
+
+        let response = json!({ "inverter": [{ "id": "roof", "power_w": 1250.0 }] });
+        let devices =
+            response_devices(response, &endpoint(Some("/inverter"), None), &mapping).unwrap();
+        let device = json_to_device(&devices[0], "test", integration_id.clone(), &mapping);
+        assert_eq!(
+            device.map(|device| (device.id, device.data)),
+            Some((
+                DeviceId::new("roof"),
+                DeviceData::Sensor(SensorDevice::Number { value: 1250.0 })
+            ))
+        );
+
+        // Single device endpoint without an id in its payload
+        let response = json!({ "power_w": 3.5 });
+        let devices = response_devices(response, &endpoint(None, Some("plug")), &mapping).unwrap();
+        assert_eq!(devices, vec![json!({ "id": "plug", "power_w": 3.5 })]);
+
+        let response = json!({ "inverter": null });
+        assert!(response_devices(response, &endpoint(Some("/inverter"), None), &mapping).is_err());
+    }
+
+    #[test]
+    fn fills_set_request_template() {
+        let device = Device {
+            id: DeviceId::new("plug"),
+            name: "Desk plug".to_string(),
+            integration_id: IntegrationId::from_str("http").unwrap(),
+            data: DeviceData::Controllable(ControllableDevice::new(
+                None,
+                false,
+                None,
+                None,
+                None,
+                Capabilities::default(),
+                ManageKind::Full,
+            )),
+            raw: None,
+        };
+        let set_request: HttpSetRequest = serde_json::from_value(json!({
+            "method": "PUT",
+            "url": "http://plugs.local/{id}/state",
+            "body_template": { "label": "{name}", "relay": {} }
+        }))
+        .unwrap();
+        let mapping = JsonDeviceMapping {
+            power_field: Some(PointerBuf::parse("/relay/on").unwrap()),
+            power_off_value: Some(json!(0)),
+            ..Default::default()
+        };
+
+        assert_eq!(set_request.method, HttpSetMethod::Put);
+        assert_eq!(
+            fill_placeholders(&set_request.url, &device),
+            "http://plugs.local/plug/state"
+        );
+        assert_eq!(
+            set_request_body(&device, &set_request, &mapping).unwrap(),
+            json!({ "label": "Desk plug", "relay": { "on": 0 } })
+        );
+    }
+}
@@ -0,0 +1,550 @@
+//! Hue CLIP v2 resources and their mapping to homectl devices.
+//!
+//! The bridge sends partial resources in its event stream, so resources are
+//! kept as JSON and updates are merged into them. Devices are derived from
+//! the merged resources.
+
+use crate::types::{
+    color::{Capabilities, DeviceColor},
+    device::{ControllableDevice, Device, DeviceData, DeviceId, ManageKind, SensorDevice},
+    integration::IntegrationId,
+};
+use color_eyre::Result;
+use eyre::eyre;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Hue lights support 153 - 500 mirek.
+const DEFAULT_CT_RANGE: std::ops::Range<u16> = 2000..6535;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ResourceRef {
+    rid: String,
+    rtype: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct Metadata {
+    name: Option<String>,
+    control_id: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct On {
+    on: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Dimming {
+    brightness: f32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ClipXy {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Color {
+    xy: ClipXy,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct MirekSchema {
+    mirek_minimum: u16,
+    mirek_maximum: u16,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct ColorTemperature {
+    mirek: Option<u16>,
+    mirek_valid: bool,
+    mirek_schema: Option<MirekSchema>,
+}
+
+/// Sensor readings moved into `*_report` objects in newer bridge firmware,
+/// both are accepted.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct Motion {
+    motion: Option<bool>,
+    motion_report: Option<MotionReport>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct MotionReport {
+    motion: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct Temperature {
+    temperature: Option<f64>,
+    temperature_report: Option<TemperatureReport>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct TemperatureReport {
+    temperature: f64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct LightLevel {
+    light_level: Option<f64>,
+    light_level_report: Option<LightLevelReport>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct LightLevelReport {
+    light_level: f64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct Button {
+    last_event: Option<String>,
+    button_report: Option<ButtonReport>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ButtonReport {
+    event: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ContactReport {
+    state: String,
+}
+
+/// The fields of a resource that we map, for any resource type.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct Resource {
+    #[serde(rename = "type")]
+    kind: String,
+    owner: Option<ResourceRef>,
+    metadata: Option<Metadata>,
+    on: Option<On>,
+    dimming: Option<Dimming>,
+    color: Option<Color>,
+    color_temperature: Option<ColorTemperature>,
+    motion: Option<Motion>,
+    temperature: Option<Temperature>,
+    light: Option<LightLevel>,
+    button: Option<Button>,
+    contact_report: Option<ContactReport>,
+}
+
+/// One message of the bridge event stream.
+#[derive(Debug, Deserialize)]
+pub struct EventContainer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: Vec<Value>,
+}
+
+#[derive(Debug, Default)]
+pub struct Resources(BTreeMap<String, Value>);
+
+impl Resources {
+    /// Reads the response of `GET /clip/v2/resource`.
+    pub fn from_response(response: Value) -> Result<Resources> {
+        let Some(Value::Array(data)) = response.get("data") else {
+            return Err(eyre!("Missing data in Hue bridge response"));
+        };
+
+        Ok(Resources(
+            data.iter()
+                .filter_map(|resource| Some((resource_id(resource)?, resource.clone())))
+                .collect(),
+        ))
+    }
+
+    /// Resource type of a resource, e.g. `light`.
+    pub fn kind(&self, id: &str) -> Option<&str> {
+        self.0.get(id)?.get("type")?.as_str()
+    }
+
+    /// Applies event stream messages, returning ids of resources whose
+    /// devices may have changed.
+    pub fn apply_events(&mut self, containers: Vec<EventContainer>) -> BTreeSet<String> {
+        let mut changed = BTreeSet::new();
+        let mut renamed = false;
+
+        for container in containers {
+            for resource in container.data {
+                let Some(id) = resource_id(&resource) else {
+                    continue;
+                };
+
+                renamed |= matches!(
+                    resource.get("type").and_then(Value::as_str),
+                    Some("device" | "room" | "zone")
+                );
+
+                match container.kind.as_str() {
+                    "update" => match self.0.get_mut(&id) {
+                        Some(existing) => merge(existing, &resource),
+                        None => continue,
+                    },
+                    "add" => {
+                        self.0.insert(id.clone(), resource);
+                    }
+                    "delete" => {
+                        self.0.remove(&id);
+                        continue;
+                    }
+                    _ => continue,
+                }
+
+                changed.insert(id);
+            }
+        }
+
+        // Sensors and grouped lights are named after the resources owning them
+        if renamed {
+            changed.extend(self.0.keys().cloned());
+        }
+
+        changed
+    }
+
+    pub fn devices(&self, integration_id: &IntegrationId, managed: &ManageKind) -> Vec<Device> {
+        self.0
+            .keys()
+            .filter_map(|id| self.device(id, integration_id, managed))
+            .collect()
+    }
+
+    pub fn device(
+        &self,
+        id: &str,
+        integration_id: &IntegrationId,
+        managed: &ManageKind,
+    ) -> Option<Device> {
+        let value = self.0.get(id)?;
+        let resource: Resource = match serde_json::from_value(value.clone()) {
+            Ok(resource) => resource,
+            Err(e) => {
+                warn!("Failed to read Hue resource {id}: {e}");
+                return None;
+            }
+        };
+
+        let (name, data) = match resource.kind.as_str() {
+            "light" => (
+                resource_name(&resource)?,
+                DeviceData::Controllable(controllable(&resource, managed.clone())),
+            ),
+            "grouped_light" => {
+                let owner = resource.owner.as_ref()?;
+                if owner.rtype != "room" && owner.rtype != "zone" {
+                    return None;
+                }
+
+                // The bridge doesn't report the color of grouped lights, so
+                // drift can't be corrected
+                (
+                    self.owner_name(&resource)?,
+                    DeviceData::Controllable(controllable(&resource, ManageKind::Unmanaged)),
+                )
+            }
+            "motion" => (
+                self.owner_name(&resource)?,
+                DeviceData::Sensor(
+                    resource
+                        .motion
+                        .and_then(|motion| {
+                            motion
+                                .motion_report
+                                .map(|report| report.motion)
+                                .or(motion.motion)
+                        })
+                        .map(|value| SensorDevice::Boolean { value })
+                        .unwrap_or_else(SensorDevice::unknown_placeholder),
+                ),
+            ),
+            "temperature" => (
+                format!("{} temperature", self.owner_name(&resource)?),
+                DeviceData::Sensor(
+                    resource
+                        .temperature
+                        .and_then(|temperature| {
+                            temperature
+                                .temperature_report
+                                .map(|report| report.temperature)
+                                .or(temperature.temperature)
+                        })
+                        .map(|value| SensorDevice::Number { value })
+                        .unwrap_or_else(SensorDevice::unknown_placeholder),
+                ),
+            ),
+            "light_level" => (
+                format!("{} light level", self.owner_name(&resource)?),
+                DeviceData::Sensor(
+                    resource
+                        .light
+                        .and_then(|light| {
+                            light
+                                .light_level_report
+                                .map(|report| report.light_level)
+                                .or(light.light_level)
+                        })
+                        // Hue reports 10000 * log10(lux) + 1
+                        .map(|level| SensorDevice::Number {
+                            value: (10f64.powf((level - 1.0) / 10000.0) * 10.0).round() / 10.0,
+                        })
+                        .unwrap_or_else(SensorDevice::unknown_placeholder),
+                ),
+            ),
+            "button" => {
+                let control_id = resource
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.control_id)
+                    .unwrap_or_default();
+
+                (
+                    format!("{} button {control_id}", self.owner_name(&resource)?),
+                    DeviceData::Sensor(
+                        resource
+                            .button
+                            .and_then(|button| {
+                                button
+                                    .button_report
+                                    .map(|report| report.event)
+                                    .or(button.last_event)
+                            })
+                            .map(|value| SensorDevice::Text { value })
+                            .unwrap_or_else(SensorDevice::unknown_placeholder),
+                    ),
+                )
+            }
+            "contact" => (
+                self.owner_name(&resource)?,
+                DeviceData::Sensor(
+                    resource
+                        .contact_report
+                        .map(|report| SensorDevice::Boolean {
+                            value: report.state == "contact",
+                        })
+                        .unwrap_or_else(SensorDevice::unknown_placeholder),
+                ),
+            ),
+            _ => return None,
+        };
+
+        Some(Device::new(
+            integration_id.clone(),
+            DeviceId::new(id),
+            name,
+            data,
+            Some(value.clone()),
+        ))
+    }
+
+    fn owner_name(&self, resource: &Resource) -> Option<String> {
+        let owner = resource.owner.as_ref()?;
+        let owner: Resource = serde_json::from_value(self.0.get(&owner.rid)?.clone()).ok()?;
+
+        resource_name(&owner)
+    }
+}
+
+fn resource_id(resource: &Value) -> Option<String> {
+    resource.get("id")?.as_str().map(str::to_string)
+}
+
+fn resource_name(resource: &Resource) -> Option<String> {
+    resource.metadata.as_ref()?.name.clone()
+}
+
+/// Recursively merges a partial resource into a resource.
+fn merge(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+fn controllable(resource: &Resource, managed: ManageKind) -> ControllableDevice {
+    let is_group = resource.kind == "grouped_light";
+    let color_temperature = resource.color_temperature.as_ref();
+
+    let capabilities = Capabilities {
+        xy: is_group || resource.color.is_some(),
+        ct: color_temperature
+            .and_then(|ct| ct.mirek_schema.as_ref())
+            .map(|schema| {
+                mirek_to_kelvin(schema.mirek_maximum)..mirek_to_kelvin(schema.mirek_minimum)
+            })
+            .or_else(|| (is_group || color_temperature.is_some()).then_some(DEFAULT_CT_RANGE)),
+        ..Default::default()
+    };
+
+    let color = match (color_temperature, &resource.color) {
+        (
+            Some(ColorTemperature {
+                mirek: Some(mirek),
+                mirek_valid: true,
+                ..
+            }),
+            _,
+        ) => Some(DeviceColor::new_from_ct(mirek_to_kelvin(*mirek))),
+        (_, Some(color)) => Some(DeviceColor::new_from_xy(color.xy.x, color.xy.y)),
+        _ => None,
+    };
+
+    ControllableDevice::new(
+        None,
+        resource.on.as_ref().map(|on| on.on).unwrap_or_default(),
+        resource
+            .dimming
+            .as_ref()
+            .map(|dimming| dimming.brightness / 100.0),
+        color,
+        None,
+        capabilities,
+        managed,
+    )
+}
+
+fn mirek_to_kelvin(mirek: u16) -> u16 {
+    (1_000_000 / u32::from(mirek.max(1))).min(u32::from(u16::MAX)) as u16
+}
+
+fn kelvin_to_mirek(kelvin: u64) -> u64 {
+    1_000_000 / kelvin.max(1)
+}
+
+/// Body of a `light` or `grouped_light` PUT request.
+pub fn state_body(device: &ControllableDevice, default_transition: Option<f32>) -> Value {
+    let state = &device.state;
+    let mut body = json!({ "on": { "on": state.power } });
+
+    if state.power {
+        if let Some(brightness) = state.brightness {
+            body["dimming"] = json!({ "brightness": (*brightness * 100.0).clamp(0.0, 100.0) });
+        }
+
+        let color = state
+            .color
+            .as_ref()
+            .and_then(|color| color.to_device_preferred_mode(&device.capabilities));
+
+        match color {
+            Some(DeviceColor::Xy(xy)) => {
+                body["color"] = json!({ "xy": { "x": *xy.x, "y": *xy.y } });
+            }
+            Some(DeviceColor::Ct(ct)) => {
+                let range = device.capabilities.ct.clone().unwrap_or(DEFAULT_CT_RANGE);
+                let kelvin = ct.ct.clamp(u64::from(range.start), u64::from(range.end));
+                body["color_temperature"] = json!({ "mirek": kelvin_to_mirek(kelvin) });
+            }
+            _ => {}
+        }
+    }
+
+    let transition = state.transition.map(|t| *t).or(default_transition);
+    if let Some(transition) = transition {
+        body["dynamics"] = json!({ "duration": (transition.max(0.0) * 1000.0).round() as u64 });
+    }
+
+    body
+}
+
+/// Splits the bridge event stream into the data of its messages.
+#[derive(Debug, Default)]
+pub struct EventStreamParser {
+    buffer: Vec<u8>,
+}
+
+impl EventStreamParser {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut messages = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+            let message: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let message = String::from_utf8_lossy(&message);
+
+            let data = message
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim_start)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if !data.is_empty() {
+                messages.push(data);
+            }
+        }
+
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_messages_split_across_chunks() {
+        let mut parser = EventStreamParser::default();
+
+        assert!(parser.push(b": hi\n\nid: 1:0\ndata: [{\"ty").is_empty());
+        assert_eq!(
+            parser.push(b"pe\":\"update\"}]\n\nid: 2:0\n"),
+            vec!["[{\"type\":\"update\"}]".to_string()]
+        );
+    }
+
+    #[test]
+    fn maps_controllable_state_to_request_body() {
+        let device = ControllableDevice::new(
+            None,
+            true,
+            Some(0.5),
+            Some(DeviceColor::new_from_ct(10000)),
+            Some(0.4),
+            Capabilities {
+                ct: Some(2000..6500),
+                ..Default::default()
+            },
+            ManageKind::Full,
+        );
+
+        assert_eq!(
+            state_body(&device, None),
+            json!({
+                "on": { "on": true },
+                "dimming": { "brightness": 50.0 },
+                "color_temperature": { "mirek": 153 },
+                "dynamics": { "duration": 400 }
+            })
+        );
+
+        let mut device = device;
+        device.state.power = false;
+        device.state.transition = None;
+        assert_eq!(
+            state_body(&device, Some(1.0)),
+            json!({ "on": { "on": false }, "dynamics": { "duration": 1000 } })
+        );
+    }
+}
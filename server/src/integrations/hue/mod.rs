@@ -0,0 +1,471 @@
+//! Philips Hue bridge integration using the CLIP v2 API.
+//!
+//! Lights, rooms, zones and sensors are discovered from the bridge and kept
+//! up to date from its event stream. Devices are keyed by their CLIP v2
+//! resource id.
+
+mod clip;
+mod tls;
+
+use crate::{
+    core::integrations::status,
+    types::{
+        device::{Device, DeviceData, ManageKind},
+        event::{Event, TxEventChannel},
+        integration::{Integration, IntegrationId},
+    },
+    utils::cli::Cli,
+};
+use async_trait::async_trait;
+use color_eyre::Result;
+use eyre::{eyre, Context};
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use self::clip::{state_body, EventContainer, EventStreamParser, Resources};
+
+const APPLICATION_KEY_HEADER: &str = "hue-application-key";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct HueConfig {
+    /// Address of the bridge. HTTPS is used unless a scheme is included.
+    addr: String,
+
+    /// Id of the bridge, e.g. `001788fffe123456`, which its certificate is
+    /// checked against. Shown in the Hue app, and as `bridgeid` at
+    /// `https://<addr>/api/config`. Required for HTTPS.
+    bridge_id: Option<String>,
+
+    /// Application key created by pressing the link button of the bridge.
+    application_key: String,
+
+    /// Can be used to control whether the lights published by this integration
+    /// are "managed" or not, i.e.  whether homectl should keep track of the
+    /// devices' expected states or not.
+    managed: Option<ManageKind>,
+
+    /// Transition time in seconds used when homectl doesn't provide one.
+    default_transition: Option<f32>,
+}
+
+impl HueConfig {
+    fn base_url(&self) -> String {
+        let addr = self.addr.trim_end_matches('/');
+
+        if addr.contains("://") {
+            addr.to_string()
+        } else {
+            format!("https://{addr}")
+        }
+    }
+}
+
+type KnownResources = Arc<RwLock<Resources>>;
+
+pub struct Hue {
+    id: IntegrationId,
+    event_tx: TxEventChannel,
+    config: Arc<HueConfig>,
+    cli: Cli,
+    client: reqwest::Client,
+    resources: KnownResources,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[async_trait]
+impl Integration for Hue {
+    fn new(
+        id: &IntegrationId,
+        config: &serde_json::Value,
+        cli: &Cli,
+        event_tx: TxEventChannel,
+    ) -> Result<Self> {
+        let config: HueConfig = serde_json::from_value(config.clone())
+            .wrap_err("Failed to deserialize config of Hue integration")?;
+
+        let mut client = reqwest::Client::builder();
+        if config.base_url().starts_with("https://") {
+            let bridge_id = config
+                .bridge_id
+                .as_deref()
+                .ok_or_else(|| eyre!("bridge_id is required to verify the Hue bridge"))?;
+            client = client.use_preconfigured_tls(tls::bridge_tls_config(bridge_id)?);
+        }
+        let client = client
+            .build()
+            .wrap_err("Failed to build HTTP client of Hue integration")?;
+
+        Ok(Hue {
+            id: id.clone(),
+            event_tx,
+            config: Arc::new(config),
+            cli: cli.clone(),
+            client,
+            resources: Default::default(),
+            handle: None,
+        })
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let id = self.id.clone();
+        let event_tx = self.event_tx.clone();
+        let config = Arc::clone(&self.config);
+        let client = self.client.clone();
+        let resources = Arc::clone(&self.resources);
+
+        self.handle = Some(tokio::spawn(async move {
+            loop {
+                if let Err(e) = run_event_stream(&client, &config, &id, &event_tx, &resources).await
+                {
+                    status::mark_integration_disconnected(&id, &e);
+                    error!(
+                        target: &format!("homectl_server::integrations::hue::{id}"),
+                        "Hue bridge error: {e:?}"
+                    );
+                }
+
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn set_integration_device_state(&mut self, device: &Device) -> Result<()> {
+        let DeviceData::Controllable(controllable) = &device.data else {
+            return Ok(());
+        };
+
+        let id = device.id.to_string();
+        let kind = self
+            .resources
+            .read()
+            .expect("Hue resources lock poisoned")
+            .kind(&id)
+            .map(str::to_string)
+            .ok_or_else(|| eyre!("Unknown Hue resource {id}"))?;
+
+        if kind != "light" && kind != "grouped_light" {
+            return Err(eyre!(
+                "Hue resource {id} of type {kind} can't be controlled"
+            ));
+        }
+
+        let body = state_body(controllable, self.config.default_transition);
+
+        if self.cli.dry_run {
+            debug!("(dry run) would send device state: {device}");
+            return Ok(());
+        }
+
+        let response = self
+            .client
+            .put(format!(
+                "{}/clip/v2/resource/{kind}/{id}",
+                self.config.base_url()
+            ))
+            .header(APPLICATION_KEY_HEADER, &self.config.application_key)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&body)
+            .send()
+            .await?;
+
+        check_response(response).await?;
+
+        Ok(())
+    }
+}
+
+/// Reads the resources of the bridge and follows its event stream until it
+/// disconnects.
+async fn run_event_stream(
+    client: &reqwest::Client,
+    config: &HueConfig,
+    id: &IntegrationId,
+    event_tx: &TxEventChannel,
+    resources: &KnownResources,
+) -> Result<()> {
+    let base_url = config.base_url();
+    let managed = config.managed.clone().unwrap_or_default();
+
+    // Connect before reading the resources so that no changes are missed in
+    // between
+    let mut stream = client
+        .get(format!("{base_url}/eventstream/clip/v2"))
+        .header(APPLICATION_KEY_HEADER, &config.application_key)
+        .header(reqwest::header::ACCEPT, "text/event-stream")
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let response = client
+        .get(format!("{base_url}/clip/v2/resource"))
+        .header(APPLICATION_KEY_HEADER, &config.application_key)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?;
+    let discovered = Resources::from_response(check_response(response).await?)?;
+    let devices = discovered.devices(id, &managed);
+
+    info!("Hue integration {id} discovered {} devices", devices.len());
+    status::mark_integration_connected(id);
+    *resources.write().expect("Hue resources lock poisoned") = discovered;
+
+    for device in devices {
+        event_tx.send(Event::ExternalStateUpdate { device });
+    }
+
+    let mut parser = EventStreamParser::default();
+    while let Some(chunk) = stream.chunk().await? {
+        for data in parser.push(&chunk) {
+            status::record_integration_message(id);
+            let containers: Vec<EventContainer> = match serde_json::from_str(&data) {
+                Ok(containers) => containers,
+                Err(e) => {
+                    warn!("Failed to parse Hue event: {e}");
+                    continue;
+                }
+            };
+
+            let devices = {
+                let mut resources = resources.write().expect("Hue resources lock poisoned");
+                let changed = resources.apply_events(containers);
+
+                changed
+                    .iter()
+                    .filter_map(|resource_id| resources.device(resource_id, id, &managed))
+                    .collect::<Vec<_>>()
+            };
+
+            for device in devices {
+                event_tx.send(Event::ExternalStateUpdate { device });
+            }
+        }
+    }
+
+    Err(eyre!("Event stream was closed by the bridge"))
+}
+
+/// Returns the body of a bridge response, or the errors it reported.
+async fn check_response(response: reqwest::Response) -> Result<serde_json::Value> {
+    let status = response.status();
+    let body: serde_json::Value = response.json().await?;
+
+    let errors = body
+        .get("errors")
+        .and_then(serde_json::Value::as_array)
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|error| error.get("description")?.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+
+    if !status.is_success() || !errors.is_empty() {
+        return Err(eyre!("Hue bridge responded with {status}: {errors}"));
+    }
+
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        color::DeviceColor,
+        device::{ControllableDevice, ControllableState, SensorDevice},
+        event::{mk_event_channel, RxEventChannel},
+    };
+    use crate::utils::cli::dry_run_cli;
+    use ordered_float::OrderedFloat;
+    use serde_json::json;
+    use std::str::FromStr;
+    use tokio::sync::mpsc;
+    use warp::Filter;
+
+    const LIGHT_ID: &str = "light-1";
+
+    fn bridge_resources() -> serde_json::Value {
+        json!({
+            "errors": [],
+            "data": [
+                {
+                    "id": "device-1",
+                    "type": "device",
+                    "metadata": { "name": "Hallway sensor" }
+                },
+                {
+                    "id": "room-1",
+                    "type": "room",
+                    "metadata": { "name": "Kitchen" }
+                },
+                {
+                    "id": LIGHT_ID,
+                    "type": "light",
+                    "metadata": { "name": "Kitchen ceiling" },
+                    "on": { "on": true },
+                    "dimming": { "brightness": 80.0 },
+                    "color": { "xy": { "x": 0.3, "y": 0.3 } },
+                    "color_temperature": {
+                        "mirek": null,
+                        "mirek_valid": false,
+                        "mirek_schema": { "mirek_minimum": 153, "mirek_maximum": 500 }
+                    }
+                },
+                {
+                    "id": "grouped-1",
+                    "type": "grouped_light",
+                    "owner": { "rid": "room-1", "rtype": "room" },
+                    "on": { "on": false }
+                },
+                {
+                    "id": "motion-1",
+                    "type": "motion",
+                    "owner": { "rid": "device-1", "rtype": "device" },
+                    "motion": { "motion": false, "motion_valid": true }
+                }
+            ]
+        })
+    }
+
+    /// Stands in for a bridge, forwarding the bodies of PUT requests.
+    fn mock_bridge(
+        put_tx: mpsc::UnboundedSender<(String, serde_json::Value)>,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let key = warp::header::exact(APPLICATION_KEY_HEADER, "test-key");
+
+        let resources = warp::get()
+            .and(warp::path!("clip" / "v2" / "resource"))
+            .map(|| warp::reply::json(&bridge_resources()));
+
+        let events = warp::get()
+            .and(warp::path!("eventstream" / "clip" / "v2"))
+            .map(|| {
+                let update = json!([{
+                    "type": "update",
+                    "data": [
+                        { "id": LIGHT_ID, "type": "light", "on": { "on": false } },
+                        { "id": "motion-1", "type": "motion", "motion": { "motion": true } }
+                    ]
+                }]);
+
+                warp::reply::with_header(
+                    format!(": hi\n\nid: 1:0\ndata: {update}\n\n"),
+                    "content-type",
+                    "text/event-stream",
+                )
+            });
+
+        let put = warp::put()
+            .and(warp::path!("clip" / "v2" / "resource" / String / String))
+            .and(warp::body::json())
+            .map(move |kind: String, id: String, body: serde_json::Value| {
+                let _ = put_tx.send((format!("{kind}/{id}"), body));
+                warp::reply::json(&json!({ "errors": [], "data": [{ "rid": id, "rtype": kind }] }))
+            });
+
+        key.and(resources.or(events).or(put))
+    }
+
+    async fn next_device(rx: &mut RxEventChannel, id: &str) -> Device {
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for device")
+                .expect("event channel closed");
+
+            if let Event::ExternalStateUpdate { device } = event {
+                if device.id.to_string() == id {
+                    return device;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn syncs_with_mocked_bridge() {
+        let (put_tx, mut put_rx) = mpsc::unbounded_channel();
+        let (addr, server) = warp::serve(mock_bridge(put_tx)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let (event_tx, mut event_rx) = mk_event_channel();
+        let cli = Cli {
+            dry_run: false,
+            ..dry_run_cli()
+        };
+        let mut hue = Hue::new(
+            &IntegrationId::from_str("hue").unwrap(),
+            &json!({ "addr": format!("http://{addr}"), "application_key": "test-key" }),
+            &cli,
+            event_tx,
+        )
+        .unwrap();
+        hue.start().await.unwrap();
+
+        // Initial state from the resources, in order of resource id
+        let group = next_device(&mut event_rx, "grouped-1").await;
+        assert_eq!(group.name, "Kitchen");
+
+        let light = next_device(&mut event_rx, LIGHT_ID).await;
+        assert_eq!(light.name, "Kitchen ceiling");
+        let DeviceData::Controllable(controllable) = &light.data else {
+            panic!("expected a controllable light");
+        };
+        assert!(controllable.state.power);
+        assert_eq!(controllable.capabilities.ct, Some(2000..6535));
+        assert_eq!(
+            controllable.state.color,
+            Some(DeviceColor::new_from_xy(0.3, 0.3))
+        );
+
+        let motion = next_device(&mut event_rx, "motion-1").await;
+        assert_eq!(motion.name, "Hallway sensor");
+
+        // Updates from the event stream
+        let light = next_device(&mut event_rx, LIGHT_ID).await;
+        assert_eq!(
+            light.get_controllable_state().map(|state| state.power),
+            Some(false)
+        );
+        let motion = next_device(&mut event_rx, "motion-1").await;
+        assert_eq!(
+            motion.data,
+            DeviceData::Sensor(SensorDevice::Boolean { value: true })
+        );
+
+        let mut light = light;
+        light.data = DeviceData::Controllable(ControllableDevice {
+            state: ControllableState {
+                power: true,
+                brightness: Some(OrderedFloat(0.25)),
+                color: None,
+                transition: None,
+            },
+            ..controllable.clone()
+        });
+        hue.set_integration_device_state(&light).await.unwrap();
+
+        let (resource, body) = put_rx.recv().await.unwrap();
+        assert_eq!(resource, format!("light/{LIGHT_ID}"));
+        assert_eq!(
+            body,
+            json!({ "on": { "on": true }, "dimming": { "brightness": 25.0 } })
+        );
+
+        hue.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+//! Certificate verification for Hue bridges.
+//!
+//! Bridges don't have certificates for their address. They are signed by the
+//! Hue root CA instead, and name the bridge id as their common name.
+
+use std::sync::Arc;
+
+use color_eyre::Result;
+use eyre::Context;
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        verify_server_cert_signed_by_trust_anchor,
+    },
+    crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms},
+    pki_types::{pem::PemObject, CertificateDer, ServerName, UnixTime},
+    server::ParsedCertificate,
+    CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use x509_cert::der::{oid::db::rfc4519::COMMON_NAME, Decode};
+
+/// Signify's root CA of Hue bridges.
+const HUE_ROOT_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIICMjCCAdigAwIBAgIUO7FSLbaxikuXAljzVaurLXWmFw4wCgYIKoZIzj0EAwIw
+OTELMAkGA1UEBhMCTkwxFDASBgNVBAoMC1BoaWxpcHMgSHVlMRQwEgYDVQQDDAty
+b290LWJyaWRnZTAiGA8yMDE3MDEwMTAwMDAwMFoYDzIwMzgwMTE5MDMxNDA3WjA5
+MQswCQYDVQQGEwJOTDEUMBIGA1UECgwLUGhpbGlwcyBIdWUxFDASBgNVBAMMC3Jv
+b3QtYnJpZGdlMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEjNw2tx2AplOf9x86
+aTdvEcL1FU65QDxziKvBpW9XXSIcibAeQiKxegpq8Exbr9v6LBnYbna2VcaK0G22
+jOKkTqOBuTCBtjAPBgNVHRMBAf8EBTADAQH/MA4GA1UdDwEB/wQEAwIBhjAdBgNV
+HQ4EFgQUZ2ONTFrDT6o8ItRnKfqWKnHFGmQwdAYDVR0jBG0wa4AUZ2ONTFrDT6o8
+ItRnKfqWKnHFGmShPaQ7MDkxCzAJBgNVBAYTAk5MMRQwEgYDVQQKDAtQaGlsaXBz
+IEh1ZTEUMBIGA1UEAwwLcm9vdC1icmlkZ2WCFDuxUi22sYpLlwJY81Wrqy11phcO
+MAoGCCqGSM49BAMCA0gAMEUCIEBYYEOsa07TH7E5MJnGw557lVkORgit2Rm1h3B2
+sFgDAiEA1Fj/C3AN5psFMjo0//mrQebo0eKd3aWRx+pQY08mk48=
+-----END CERTIFICATE-----
+";
+
+#[derive(Debug)]
+struct BridgeCertVerifier {
+    roots: RootCertStore,
+    bridge_id: String,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl BridgeCertVerifier {
+    fn new(root_ca: &str, bridge_id: &str, algorithms: WebPkiSupportedAlgorithms) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        let root_ca = CertificateDer::from_pem_slice(root_ca.as_bytes())
+            .wrap_err("Failed to parse Hue root CA")?;
+        roots.add(root_ca).wrap_err("Failed to add Hue root CA")?;
+
+        Ok(BridgeCertVerifier {
+            roots,
+            bridge_id: bridge_id.to_string(),
+            algorithms,
+        })
+    }
+}
+
+impl ServerCertVerifier for BridgeCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let cert = ParsedCertificate::try_from(end_entity)?;
+        verify_server_cert_signed_by_trust_anchor(
+            &cert,
+            &self.roots,
+            intermediates,
+            now,
+            self.algorithms.all,
+        )?;
+
+        let common_name = common_name(end_entity).ok_or(rustls::Error::InvalidCertificate(
+            CertificateError::BadEncoding,
+        ))?;
+
+        if !common_name.eq_ignore_ascii_case(&self.bridge_id) {
+            return Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName,
+            ));
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let cert = x509_cert::Certificate::from_der(cert.as_ref()).ok()?;
+
+    cert.tbs_certificate
+        .subject
+        .0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .find(|attribute| attribute.oid == COMMON_NAME)
+        .and_then(|attribute| String::from_utf8(attribute.value.value().to_vec()).ok())
+}
+
+/// TLS config that only accepts the certificate of the given bridge.
+pub fn bridge_tls_config(bridge_id: &str) -> Result<rustls::ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = BridgeCertVerifier::new(
+        HUE_ROOT_CA,
+        bridge_id,
+        provider.signature_verification_algorithms,
+    )?;
+
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .wrap_err("Failed to configure TLS of Hue integration")?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stand-in for the Hue root CA, and a bridge certificate signed by it
+    const TEST_ROOT_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIB1DCCAXmgAwIBAgIUNx3K/UHZL4h4VXqm5lG0Y4s7j3UwCgYIKoZIzj0EAwIw
+NjELMAkGA1UEBhMCTkwxETAPBgNVBAoMCFRlc3QgSHVlMRQwEgYDVQQDDAtyb290
+LWJyaWRnZTAgFw0yNjEwMTgwOTM3NDFaGA8yMTI2MDkyNDA5Mzc0MVowNjELMAkG
+A1UEBhMCTkwxETAPBgNVBAoMCFRlc3QgSHVlMRQwEgYDVQQDDAtyb290LWJyaWRn
+ZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABMUMNuuRENVEmQ23gGe82A0D0R8I
+XwpfusPSMbpLGm/LsBiFJUjNXeOhp6W5nn0j69kXtMG+UoG2Ri3HlI3OgaGjYzBh
+MB0GA1UdDgQWBBSpIvTHVQrTmbbCxVE3NIqT6uZg+DAfBgNVHSMEGDAWgBSpIvTH
+VQrTmbbCxVE3NIqT6uZg+DAPBgNVHRMBAf8EBTADAQH/MA4GA1UdDwEB/wQEAwIB
+BjAKBggqhkjOPQQDAgNJADBGAiEAgUbUNiWhIkBrfP0/VFmKvswil2KiES+mJH8X
+x06YmSgCIQC3tqTZgIqm29MRjXUCnHc1lCtecSayZTCWHh1Tj6omLA==
+-----END CERTIFICATE-----
+";
+    const TEST_BRIDGE_CERT: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIB5jCCAY2gAwIBAgIUNn+Mgf9wasK/weRLewXUin2hFZ8wCgYIKoZIzj0EAwIw
+NjELMAkGA1UEBhMCTkwxETAPBgNVBAoMCFRlc3QgSHVlMRQwEgYDVQQDDAtyb290
+LWJyaWRnZTAgFw0yNjEwMTgwOTM3NDFaGA8yMTI2MDkyNDA5Mzc0MVowOzELMAkG
+A1UEBhMCTkwxETAPBgNVBAoMCFRlc3QgSHVlMRkwFwYDVQQDDBAwMDE3ODhmZmZl
+MTIzNDU2MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE6Q7trhHvGw8R4r/oDgDe
+5LJgKBKdblq/ouMvu0FaxL66VOciB46b/LtkvCpGEeKXVa+YeauM3x48XoicnWnn
+bKNyMHAwCQYDVR0TBAIwADAOBgNVHQ8BAf8EBAMCB4AwEwYDVR0lBAwwCgYIKwYB
+BQUHAwEwHQYDVR0OBBYEFFRtsGbnIvjFhptJL4s5u0gwGQD8MB8GA1UdIwQYMBaA
+FKki9MdVCtOZtsLFUTc0ipPq5mD4MAoGCCqGSM49BAMCA0cAMEQCIDpscGsqpsyi
+bKdK7bLPG0S6YOnAX5nORpfUzBWi8QFhAiAatQYhi2ayRai1kCuZ98Oy9LPAjaEQ
+g0bYVBQzzRvK2g==
+-----END CERTIFICATE-----
+";
+
+    fn verify(root_ca: &str, bridge_id: &str) -> Result<ServerCertVerified, rustls::Error> {
+        let algorithms = rustls::crypto::ring::default_provider().signature_verification_algorithms;
+        let verifier = BridgeCertVerifier::new(root_ca, bridge_id, algorithms).unwrap();
+        let cert = CertificateDer::from_pem_slice(TEST_BRIDGE_CERT.as_bytes()).unwrap();
+
+        verifier.verify_server_cert(
+            &cert,
+            &[],
+            &ServerName::try_from("192.168.1.10").unwrap(),
+            &[],
+            // 2030-01-01, within the validity of the test certificates
+            UnixTime::since_unix_epoch(std::time::Duration::from_secs(1_893_456_000)),
+        )
+    }
+
+    #[test]
+    fn verifies_bridge_id_and_issuer() {
+        assert!(verify(TEST_ROOT_CA, "001788FFFE123456").is_ok());
+        assert_eq!(
+            verify(TEST_ROOT_CA, "001788fffe654321").unwrap_err(),
+            rustls::Error::InvalidCertificate(CertificateError::NotValidForName)
+        );
+        assert_eq!(
+            verify(HUE_ROOT_CA, "001788fffe123456").unwrap_err(),
+            rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer)
+        );
+    }
+
+    #[test]
+    fn builds_config_with_hue_root_ca() {
+        assert!(bridge_tls_config("001788fffe123456").is_ok());
+    }
+}
@@ -0,0 +1,317 @@
+//! Maps between devices and JSON payloads using configurable JSON pointers.
+//!
+//! Shared by integrations that receive device state as arbitrary JSON, such
+//! as `mqtt` and `http`.
+
+use crate::types::color::{Capabilities, DeviceColor};
+use crate::types::{
+    device::{ControllableDevice, Device, DeviceData, DeviceId, ManageKind, SensorDevice},
+    integration::IntegrationId,
+};
+use color_eyre::Result;
+use jsonptr::{Assign, Pointer};
+use ordered_float::OrderedFloat;
+use serde::Deserialize;
+
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct JsonDeviceMapping {
+    /// Can be used to control whether the devices published by this integration
+    /// are "managed" or not, i.e.  whether homectl should keep track of the
+    /// devices' expected states or not.
+    pub managed: Option<ManageKind>,
+
+    pub id_field: Option<jsonptr::PointerBuf>,
+    pub name_field: Option<jsonptr::PointerBuf>,
+    pub color_field: Option<jsonptr::PointerBuf>,
+    pub power_field: Option<jsonptr::PointerBuf>,
+    pub power_on_value: Option<serde_json::Value>,
+    pub power_off_value: Option<serde_json::Value>,
+    pub brightness_field: Option<jsonptr::PointerBuf>,
+    pub brightness_range: Option<(f32, f32)>,
+    pub sensor_value_fields: Option<Vec<jsonptr::PointerBuf>>,
+    pub transition_field: Option<jsonptr::PointerBuf>,
+    pub transition_range: Option<(f32, f32)>,
+    pub default_transition: Option<f32>,
+    pub capabilities_field: Option<jsonptr::PointerBuf>,
+    pub capabilities_override: Option<Capabilities>,
+    pub raw_field: Option<jsonptr::PointerBuf>,
+    pub include_id_name_in_set_payload: Option<bool>,
+}
+
+impl JsonDeviceMapping {
+    pub fn id_field(&self) -> &Pointer {
+        self.id_field
+            .as_deref()
+            .unwrap_or(Pointer::from_static("/id"))
+    }
+}
+
+/// Reads a device from a JSON payload. `source` identifies where the payload
+/// came from in log messages.
+pub fn json_to_device(
+    value: &serde_json::Value,
+    source: &str,
+    integration_id: IntegrationId,
+    mapping: &JsonDeviceMapping,
+) -> Option<Device> {
+    let id_field = mapping.id_field();
+    let name_field = mapping
+        .name_field
+        .as_deref()
+        .unwrap_or(Pointer::from_static("/name"));
+    let color_field = mapping
+        .color_field
+        .as_deref()
+        .unwrap_or(Pointer::from_static("/color"));
+    let power_field = mapping
+        .power_field
+        .as_deref()
+        .unwrap_or(Pointer::from_static("/power"));
+    let brightness_field = mapping
+        .brightness_field
+        .as_deref()
+        .unwrap_or(Pointer::from_static("/brightness"));
+    let sensor_value_fields = mapping
+        .sensor_value_fields
+        .as_ref()
+        .map(|v| v.iter().map(|p| p.as_ref()).collect())
+        .unwrap_or(vec![Pointer::from_static("/sensor_value")]);
+    let has_explicit_sensor_value_fields = mapping
+        .sensor_value_fields
+        .as_ref()
+        .map(|fields| !fields.is_empty())
+        .unwrap_or(false);
+    let transition_field = mapping
+        .transition_field
+        .as_deref()
+        .unwrap_or(Pointer::from_static("/transition"));
+    let capabilities_field = mapping
+        .capabilities_field
+        .as_deref()
+        .unwrap_or(Pointer::from_static("/capabilities"));
+
+    let id = id_field
+        .resolve(value)
+        .ok()
+        .and_then(serde_json::Value::as_str)
+        .map(|id| id.to_string());
+
+    let Some(id) = id else {
+        error!("Missing '{id_field}' field in message from {source}");
+        return None;
+    };
+
+    let name = name_field
+        .resolve(value)
+        .ok()
+        .and_then(serde_json::Value::as_str)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| id.clone());
+
+    let color = color_field
+        .resolve(value)
+        .ok()
+        .and_then(|value| serde_json::from_value::<DeviceColor>(value.clone()).ok());
+
+    let power = power_field.resolve(value).ok().and_then(|value| {
+        if mapping
+            .power_on_value
+            .as_ref()
+            .unwrap_or(&serde_json::Value::Bool(true))
+            == value
+        {
+            Some(true)
+        } else if mapping
+            .power_off_value
+            .as_ref()
+            .unwrap_or(&serde_json::Value::Bool(false))
+            == value
+        {
+            Some(false)
+        } else {
+            None
+        }
+    });
+
+    let brightness = {
+        let range = mapping.brightness_range.unwrap_or((0.0, 1.0));
+
+        brightness_field
+            .resolve(value)
+            .ok()
+            .and_then(serde_json::Value::as_f64)
+            .map(|value| value as f32)
+            // scale value from [range.0, range.1] to [0, 1]
+            .map(|value| (value - range.0) / (range.1 - range.0))
+    };
+
+    let transition = {
+        let range = mapping.transition_range.unwrap_or((0.0, 1.0));
+
+        transition_field
+            .resolve(value)
+            .ok()
+            .and_then(serde_json::Value::as_f64)
+            .map(|value| value as f32)
+            // scale value from [range.0, range.1] to [0, 1]
+            .map(|value| (value - range.0) / (range.1 - range.0))
+    };
+
+    let resolved_sensor_value_field = sensor_value_fields
+        .iter()
+        .find_map(|field| Some((field, field.resolve(value).ok()?)))
+        .filter(|(_, v)| !v.is_null());
+    let has_controllable_state = power.is_some() || brightness.is_some() || color.is_some();
+
+    let device_state = if let Some((field, value)) = resolved_sensor_value_field {
+        DeviceData::Sensor(match value {
+            serde_json::Value::Number(value) => SensorDevice::Number {
+                value: value.as_f64().unwrap(),
+            },
+
+            serde_json::Value::Bool(value) => SensorDevice::Boolean { value: *value },
+
+            // TODO: get rid of this hack and use proper booleans
+            serde_json::Value::String(value) if value == "true" => {
+                SensorDevice::Boolean { value: true }
+            }
+            serde_json::Value::String(value) if value == "false" => {
+                SensorDevice::Boolean { value: false }
+            }
+
+            serde_json::Value::String(value) => SensorDevice::Text {
+                value: value.clone(),
+            },
+            _ => {
+                error!("Unsupported value for sensor field '{field}'");
+                return None;
+            }
+        })
+    } else if !has_controllable_state && has_explicit_sensor_value_fields {
+        DeviceData::Sensor(SensorDevice::unknown_placeholder())
+    } else if !has_controllable_state {
+        warn!("Unable to determine device type for {source}, discarding message");
+        return None;
+    } else {
+        let capabilities: Capabilities = capabilities_field
+            .resolve(value)
+            .ok()
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .or_else(|| mapping.capabilities_override.clone())
+            .unwrap_or_default();
+
+        let controllable_device = ControllableDevice::new(
+            None,
+            power.unwrap_or_default(),
+            brightness,
+            color,
+            transition,
+            capabilities,
+            mapping.managed.clone().unwrap_or_default(),
+        );
+
+        DeviceData::Controllable(controllable_device)
+    };
+
+    let raw = match mapping.raw_field.as_deref() {
+        Some(pointer) => pointer.resolve(value).ok().cloned(),
+        None => Some(value.clone()),
+    };
+
+    Some(Device {
+        id: DeviceId::new(&id),
+        name,
+        integration_id,
+        data: device_state,
+        raw,
+    })
+}
+
+/// Writes the state of a device into `payload`, which is usually an empty
+/// JSON value.
+pub fn device_to_json(
+    device: Device,
+    mapping: &JsonDeviceMapping,
+    mut payload: serde_json::Value,
+) -> Result<serde_json::Value> {
+    let id_field = mapping
+        .id_field
+        .clone()
+        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["id"]));
+    let name_field = mapping
+        .name_field
+        .clone()
+        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["name"]));
+    let color_field = mapping
+        .color_field
+        .clone()
+        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["color"]));
+    let power_field = mapping
+        .power_field
+        .clone()
+        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["power"]));
+    let brightness_field = mapping
+        .brightness_field
+        .clone()
+        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["brightness"]));
+    let transition_field = mapping
+        .transition_field
+        .clone()
+        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["transition"]));
+
+    if mapping.include_id_name_in_set_payload.unwrap_or_default() {
+        payload.assign(&id_field, serde_json::Value::String(device.id.to_string()))?;
+        payload.assign(&name_field, serde_json::Value::String(device.name))?;
+    }
+
+    if let DeviceData::Controllable(device) = device.data {
+        let power_value = if device.state.power {
+            mapping
+                .power_on_value
+                .clone()
+                .unwrap_or(serde_json::Value::Bool(true))
+        } else {
+            mapping
+                .power_off_value
+                .clone()
+                .unwrap_or(serde_json::Value::Bool(false))
+        };
+        payload.assign(&power_field, power_value)?;
+
+        if let Some(brightness) = device.state.brightness {
+            let range = mapping.brightness_range.unwrap_or((0.0, 1.0));
+            // scale value from [0, 1] to [range.0, range.1]
+            let value = brightness * (range.1 - range.0) + range.0;
+            payload.assign(
+                &brightness_field,
+                serde_json::Number::from_f64((*value).into())
+                    .map(serde_json::Value::Number)
+                    .unwrap(),
+            )?;
+        }
+
+        if let Some(color) = &device.state.color {
+            payload.assign(&color_field, serde_json::to_value(color)?)?;
+        }
+
+        let transition = device
+            .state
+            .transition
+            .or(mapping.default_transition.map(OrderedFloat));
+        if let Some(transition) = transition {
+            let range = mapping.transition_range.unwrap_or((0.0, 1.0));
+            // scale value from [0, 1] to [range.0, range.1]
+            let value = transition * (range.1 - range.0) + range.0;
+            payload.assign(
+                &transition_field,
+                serde_json::Number::from_f64((*value).into())
+                    .map(serde_json::Value::Number)
+                    .unwrap(),
+            )?;
+        }
+    };
+
+    Ok(payload)
+}
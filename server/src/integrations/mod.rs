@@ -1,6 +1,13 @@
 pub mod circadian;
 pub mod cron;
 pub mod dummy;
+pub mod homeassistant;
+pub mod http;
+pub mod hue;
+pub mod json_mapping;
 pub mod mqtt;
 pub mod random;
 pub mod timer;
+pub mod webhook;
+pub mod wled;
+pub mod zigbee2mqtt;
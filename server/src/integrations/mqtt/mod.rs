@@ -3,9 +3,10 @@
 mod utils;
 
 use crate::{
+    core::integrations::status,
+    integrations::json_mapping::JsonDeviceMapping,
     types::{
-        color::Capabilities,
-        device::{Device, ManageKind},
+        device::Device,
         event::{Event, TxEventChannel},
         integration::{Integration, IntegrationActionPayload, IntegrationId},
     },
@@ -34,27 +35,8 @@ pub struct MqttConfig {
     topic: String,
     topic_set: String,
 
-    /// Can be used to control whether the devices published by this integration
-    /// are "managed" or not, i.e.  whether homectl should keep track of the
-    /// devices' expected states or not.
-    managed: Option<ManageKind>,
-
-    id_field: Option<jsonptr::PointerBuf>,
-    name_field: Option<jsonptr::PointerBuf>,
-    color_field: Option<jsonptr::PointerBuf>,
-    power_field: Option<jsonptr::PointerBuf>,
-    power_on_value: Option<serde_json::Value>,
-    power_off_value: Option<serde_json::Value>,
-    brightness_field: Option<jsonptr::PointerBuf>,
-    brightness_range: Option<(f32, f32)>,
-    sensor_value_fields: Option<Vec<jsonptr::PointerBuf>>,
-    transition_field: Option<jsonptr::PointerBuf>,
-    transition_range: Option<(f32, f32)>,
-    default_transition: Option<f32>,
-    capabilities_field: Option<jsonptr::PointerBuf>,
-    capabilities_override: Option<Capabilities>,
-    raw_field: Option<jsonptr::PointerBuf>,
-    include_id_name_in_set_payload: Option<bool>,
+    #[serde(flatten)]
+    mapping: JsonDeviceMapping,
 }
 
 pub struct Mqtt {
@@ -67,8 +49,20 @@ pub struct Mqtt {
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct CustomMqttAction {
-    topic: String,
-    json: String,
+    /// Zigbee2MQTT integrations publish relative to their base topic, e.g. to
+    /// `bridge/request/permit_join`.
+    pub topic: String,
+    /// Published as is when given as a string, otherwise serialized.
+    json: serde_json::Value,
+}
+
+impl CustomMqttAction {
+    pub fn payload(&self) -> String {
+        match &self.json {
+            serde_json::Value::String(json) => json.clone(),
+            json => json.to_string(),
+        }
+    }
 }
 
 #[async_trait]
@@ -121,6 +115,7 @@ impl Integration for Mqtt {
         task::spawn(async move {
             loop {
                 let notification = eventloop.poll().await;
+                status::record_mqtt_notification(&id, &notification);
 
                 let id = id.clone();
                 let event_tx = event_tx.clone();
@@ -187,14 +182,14 @@ impl Integration for Mqtt {
 
     /// Can be used for pushing arbitrary values to the MQTT broker
     async fn run_integration_action(&mut self, payload: &IntegrationActionPayload) -> Result<()> {
-        let action: CustomMqttAction = serde_json::from_str(&payload.to_string())?;
+        let action: CustomMqttAction = payload.parse()?;
 
         let client = self.client.as_ref().ok_or_else(|| {
             eyre!("MQTT client is not initialized; start phase has not completed")
         })?;
 
         client
-            .publish(action.topic, QoS::AtLeastOnce, true, action.json)
+            .publish(&action.topic, QoS::AtLeastOnce, true, action.payload())
             .await?;
 
         Ok(())
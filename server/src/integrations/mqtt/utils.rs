@@ -1,12 +1,7 @@
+use crate::integrations::json_mapping::{device_to_json, json_to_device};
 use crate::integrations::mqtt::MqttConfig;
-use crate::types::color::{Capabilities, DeviceColor};
-use crate::types::{
-    device::{ControllableDevice, Device, DeviceData, DeviceId, SensorDevice},
-    integration::IntegrationId,
-};
+use crate::types::{device::Device, integration::IntegrationId};
 use color_eyre::Result;
-use jsonptr::{Assign, Pointer};
-use ordered_float::OrderedFloat;
 
 pub fn mqtt_to_homectl(
     payload: &[u8],
@@ -24,275 +19,21 @@ pub fn mqtt_to_homectl(
         }
     };
 
-    let id_field = config
-        .id_field
-        .as_deref()
-        .unwrap_or(Pointer::from_static("/id"));
-    let name_field = config
-        .name_field
-        .as_deref()
-        .unwrap_or(Pointer::from_static("/name"));
-    let color_field = config
-        .color_field
-        .as_deref()
-        .unwrap_or(Pointer::from_static("/color"));
-    let power_field = config
-        .power_field
-        .as_deref()
-        .unwrap_or(Pointer::from_static("/power"));
-    let brightness_field = config
-        .brightness_field
-        .as_deref()
-        .unwrap_or(Pointer::from_static("/brightness"));
-    let sensor_value_fields = config
-        .sensor_value_fields
-        .as_ref()
-        .map(|v| v.iter().map(|p| p.as_ref()).collect())
-        .unwrap_or(vec![Pointer::from_static("/sensor_value")]);
-    let has_explicit_sensor_value_fields = config
-        .sensor_value_fields
-        .as_ref()
-        .map(|fields| !fields.is_empty())
-        .unwrap_or(false);
-    let transition_field = config
-        .transition_field
-        .as_deref()
-        .unwrap_or(Pointer::from_static("/transition"));
-    let capabilities_field = config
-        .capabilities_field
-        .as_deref()
-        .unwrap_or(Pointer::from_static("/capabilities"));
-
-    let id = id_field
-        .resolve(&value)
-        .ok()
-        .and_then(serde_json::Value::as_str)
-        .map(|id| id.to_string());
-
-    let Some(id) = id else {
-        error!("Missing '{id_field}' field in MQTT message");
-        return None;
-    };
-
-    let name = name_field
-        .resolve(&value)
-        .ok()
-        .and_then(serde_json::Value::as_str)
-        .map(str::trim)
-        .filter(|name| !name.is_empty())
-        .map(str::to_string)
-        .unwrap_or_else(|| id.clone());
-
-    let color = color_field
-        .resolve(&value)
-        .ok()
-        .and_then(|value| serde_json::from_value::<DeviceColor>(value.clone()).ok());
-
-    let power = power_field.resolve(&value).ok().and_then(|value| {
-        if config
-            .power_on_value
-            .as_ref()
-            .unwrap_or(&serde_json::Value::Bool(true))
-            == value
-        {
-            Some(true)
-        } else if config
-            .power_off_value
-            .as_ref()
-            .unwrap_or(&serde_json::Value::Bool(false))
-            == value
-        {
-            Some(false)
-        } else {
-            None
-        }
-    });
-
-    let brightness = {
-        let range = config.brightness_range.unwrap_or((0.0, 1.0));
-
-        brightness_field
-            .resolve(&value)
-            .ok()
-            .and_then(serde_json::Value::as_f64)
-            .map(|value| value as f32)
-            // scale value from [range.0, range.1] to [0, 1]
-            .map(|value| (value - range.0) / (range.1 - range.0))
-    };
-
-    let transition = {
-        let range = config.transition_range.unwrap_or((0.0, 1.0));
-
-        transition_field
-            .resolve(&value)
-            .ok()
-            .and_then(serde_json::Value::as_f64)
-            .map(|value| value as f32)
-            // scale value from [range.0, range.1] to [0, 1]
-            .map(|value| (value - range.0) / (range.1 - range.0))
-    };
-
-    let resolved_sensor_value_field = sensor_value_fields
-        .iter()
-        .find_map(|field| Some((field, field.resolve(&value).ok()?)))
-        .filter(|(_, v)| !v.is_null());
-    let has_controllable_state = power.is_some() || brightness.is_some() || color.is_some();
-
-    let device_state = if let Some((field, value)) = resolved_sensor_value_field {
-        DeviceData::Sensor(match value {
-            serde_json::Value::Number(value) => SensorDevice::Number {
-                value: value.as_f64().unwrap(),
-            },
-
-            serde_json::Value::Bool(value) => SensorDevice::Boolean { value: *value },
-
-            // TODO: get rid of this hack and use proper booleans
-            serde_json::Value::String(value) if value == "true" => {
-                SensorDevice::Boolean { value: true }
-            }
-            serde_json::Value::String(value) if value == "false" => {
-                SensorDevice::Boolean { value: false }
-            }
-
-            serde_json::Value::String(value) => SensorDevice::Text {
-                value: value.clone(),
-            },
-            _ => {
-                error!("Unsupported value for sensor field '{field}'");
-                return None;
-            }
-        })
-    } else if !has_controllable_state && has_explicit_sensor_value_fields {
-        DeviceData::Sensor(SensorDevice::unknown_placeholder())
-    } else if !has_controllable_state {
-        warn!("Unable to determine device type for {topic}, discarding MQTT message");
-        return None;
-    } else {
-        let capabilities: Capabilities = capabilities_field
-            .resolve(&value)
-            .ok()
-            .and_then(|value| serde_json::from_value(value.clone()).ok())
-            .or_else(|| config.capabilities_override.clone())
-            .unwrap_or_default();
-
-        let controllable_device = ControllableDevice::new(
-            None,
-            power.unwrap_or_default(),
-            brightness,
-            color,
-            transition,
-            capabilities,
-            config.managed.clone().unwrap_or_default(),
-        );
-
-        DeviceData::Controllable(controllable_device)
-    };
-
-    let raw = match config.raw_field.as_deref() {
-        Some(pointer) => pointer.resolve(&value).ok().cloned(),
-        None => Some(value.clone()),
-    };
-
-    Some(Device {
-        id: DeviceId::new(&id),
-        name,
-        integration_id,
-        data: device_state,
-        raw,
-    })
+    json_to_device(&value, topic, integration_id, &config.mapping)
 }
 
 pub fn homectl_to_mqtt(device: Device, config: &MqttConfig) -> Result<serde_json::Value> {
-    let mut payload = serde_json::Value::default();
-
-    let id_field = config
-        .id_field
-        .clone()
-        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["id"]));
-    let name_field = config
-        .name_field
-        .clone()
-        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["name"]));
-    let color_field = config
-        .color_field
-        .clone()
-        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["color"]));
-    let power_field = config
-        .power_field
-        .clone()
-        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["power"]));
-    let brightness_field = config
-        .brightness_field
-        .clone()
-        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["brightness"]));
-    let transition_field = config
-        .transition_field
-        .clone()
-        .unwrap_or_else(|| jsonptr::PointerBuf::from_tokens(["transition"]));
-
-    if config.include_id_name_in_set_payload.unwrap_or_default() {
-        payload.assign(&id_field, serde_json::Value::String(device.id.to_string()))?;
-        payload.assign(&name_field, serde_json::Value::String(device.name))?;
-    }
-
-    if let DeviceData::Controllable(device) = device.data {
-        let power_value = if device.state.power {
-            config
-                .power_on_value
-                .clone()
-                .unwrap_or(serde_json::Value::Bool(true))
-        } else {
-            config
-                .power_off_value
-                .clone()
-                .unwrap_or(serde_json::Value::Bool(false))
-        };
-        payload.assign(&power_field, power_value)?;
-
-        if let Some(brightness) = device.state.brightness {
-            let range = config.brightness_range.unwrap_or((0.0, 1.0));
-            // scale value from [0, 1] to [range.0, range.1]
-            let value = brightness * (range.1 - range.0) + range.0;
-            payload.assign(
-                &brightness_field,
-                serde_json::Number::from_f64((*value).into())
-                    .map(serde_json::Value::Number)
-                    .unwrap(),
-            )?;
-        }
-
-        if let Some(color) = &device.state.color {
-            payload.assign(&color_field, serde_json::to_value(color)?)?;
-        }
-
-        let transition = device
-            .state
-            .transition
-            .or(config.default_transition.map(OrderedFloat));
-        if let Some(transition) = transition {
-            let range = config.transition_range.unwrap_or((0.0, 1.0));
-            // scale value from [0, 1] to [range.0, range.1]
-            let value = transition * (range.1 - range.0) + range.0;
-            payload.assign(
-                &transition_field,
-                serde_json::Number::from_f64((*value).into())
-                    .map(serde_json::Value::Number)
-                    .unwrap(),
-            )?;
-        }
-    };
-
-    Ok(payload)
+    device_to_json(device, &config.mapping, serde_json::Value::default())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::integrations::json_mapping::JsonDeviceMapping;
     use crate::types::{
-        color::{Capabilities, ColorMode, Hs},
-        device::ManageKind,
+        color::{Capabilities, ColorMode, DeviceColor, Hs},
+        device::{ControllableDevice, DeviceData, DeviceId, ManageKind, SensorDevice},
     };
-
-    use super::*;
     use jsonptr::PointerBuf;
     use ordered_float::OrderedFloat;
     use serde_json::json;
@@ -325,7 +66,10 @@ mod tests {
             port: 1883,
             topic: "homectl/devices/{id}".to_string(),
             topic_set: "homectl/set/{id}".to_string(),
-            include_id_name_in_set_payload: Some(true),
+            mapping: JsonDeviceMapping {
+                include_id_name_in_set_payload: Some(true),
+                ..Default::default()
+            },
             ..Default::default()
         };
 
@@ -359,7 +103,10 @@ mod tests {
             port: 1883,
             topic: "homectl/devices/{id}".to_string(),
             topic_set: "homectl/set/{id}".to_string(),
-            managed: Some(ManageKind::Unmanaged),
+            mapping: JsonDeviceMapping {
+                managed: Some(ManageKind::Unmanaged),
+                ..Default::default()
+            },
             ..Default::default()
         };
 
@@ -406,7 +153,10 @@ mod tests {
             port: 1883,
             topic: "homectl/devices/{id}".to_string(),
             topic_set: "homectl/set/{id}".to_string(),
-            name_field: Some(PointerBuf::from_tokens(["missing_name"])),
+            mapping: JsonDeviceMapping {
+                name_field: Some(PointerBuf::from_tokens(["missing_name"])),
+                ..Default::default()
+            },
             ..Default::default()
         };
 
@@ -434,7 +184,10 @@ mod tests {
             port: 1883,
             topic: "zigbee2mqtt/{id}".to_string(),
             topic_set: "zigbee2mqtt/{id}/set".to_string(),
-            sensor_value_fields: Some(vec![PointerBuf::from_tokens(["action"])]),
+            mapping: JsonDeviceMapping {
+                sensor_value_fields: Some(vec![PointerBuf::from_tokens(["action"])]),
+                ..Default::default()
+            },
             ..Default::default()
         };
 
@@ -469,8 +222,11 @@ mod tests {
             port: 1883,
             topic: "homectl/devices/{id}".to_string(),
             topic_set: "homectl/set/{id}".to_string(),
-            managed: Some(ManageKind::Unmanaged),
-            include_id_name_in_set_payload: Some(true),
+            mapping: JsonDeviceMapping {
+                managed: Some(ManageKind::Unmanaged),
+                include_id_name_in_set_payload: Some(true),
+                ..Default::default()
+            },
             ..Default::default()
         };
 
@@ -20,6 +20,22 @@ pub struct TimerConfig {
     device_name: String,
 }
 
+/// Starts the timer. Also accepted as a plain number of milliseconds.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum TimerAction {
+    TimeoutMs(u64),
+    Start { timeout_ms: u64 },
+}
+
+impl TimerAction {
+    fn timeout_ms(&self) -> u64 {
+        match self {
+            TimerAction::TimeoutMs(timeout_ms) | TimerAction::Start { timeout_ms } => *timeout_ms,
+        }
+    }
+}
+
 pub struct Timer {
     id: IntegrationId,
     config: TimerConfig,
@@ -55,8 +71,7 @@ impl Integration for Timer {
     }
 
     async fn run_integration_action(&mut self, action: &IntegrationActionPayload) -> Result<()> {
-        let payload = action.to_string();
-        let timeout_ms: u64 = payload.parse()?;
+        let timeout_ms = action.parse::<TimerAction>()?.timeout_ms();
         let started_at = SystemTime::now().duration_since(UNIX_EPOCH)?;
 
         let device = mk_timer_device(
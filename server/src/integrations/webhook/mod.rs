@@ -0,0 +1,477 @@
+//! POSTs events of the event feed as JSON to configured URLs.
+//!
+//! Each webhook lists triggers it should be called for. A delivery that
+//! fails is retried with exponential backoff, and the outcome of every
+//! delivery is kept in the webhook delivery log.
+
+use crate::{
+    core::{
+        webhook_deliveries::{record_webhook_delivery, DeliveryOutcome},
+        websockets::subscribe_feed,
+    },
+    types::{
+        device::DeviceKey,
+        event::TxEventChannel,
+        integration::{Integration, IntegrationId},
+        routine_history::RoutineHistoryTriggerKind,
+        rule::RoutineId,
+        scene::SceneId,
+        websockets::FeedEvent,
+    },
+    utils::cli::Cli,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::Result;
+use eyre::Context;
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::{
+    sync::{broadcast::error::RecvError, OwnedSemaphorePermit, Semaphore},
+    task::JoinSet,
+};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 1000;
+const DEFAULT_TIMEOUT_MS: u64 = 10000;
+const DEFAULT_MAX_PENDING_DELIVERIES: usize = 100;
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "on", rename_all = "snake_case")]
+pub enum WebhookTrigger {
+    /// Any change to the state of a device, or only of the given devices.
+    DeviceChanged {
+        #[serde(default)]
+        device_keys: Option<Vec<DeviceKey>>,
+    },
+    RoutineTriggered {
+        #[serde(default)]
+        routine_ids: Option<Vec<RoutineId>>,
+        /// Also call the webhook when cooldown or debounce suppressed the
+        /// routine.
+        #[serde(default)]
+        include_suppressed: bool,
+    },
+    SceneActivated {
+        #[serde(default)]
+        scene_ids: Option<Vec<SceneId>>,
+    },
+}
+
+impl WebhookTrigger {
+    fn matches(&self, event: &FeedEvent) -> bool {
+        match (self, event) {
+            (
+                WebhookTrigger::DeviceChanged { device_keys },
+                FeedEvent::DeviceChanged { device },
+            ) => device_keys
+                .as_ref()
+                .is_none_or(|keys| keys.contains(&device.get_device_key())),
+            (
+                WebhookTrigger::RoutineTriggered {
+                    routine_ids,
+                    include_suppressed,
+                },
+                FeedEvent::RoutineTriggered(entry),
+            ) => {
+                (*include_suppressed || entry.trigger_kind != RoutineHistoryTriggerKind::Suppressed)
+                    && routine_ids
+                        .as_ref()
+                        .is_none_or(|ids| ids.contains(&entry.routine_id))
+            }
+            (
+                WebhookTrigger::SceneActivated { scene_ids },
+                FeedEvent::SceneActivated { scene_id, .. },
+            ) => scene_ids.as_ref().is_none_or(|ids| ids.contains(scene_id)),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WebhookTarget {
+    url: String,
+    /// Extra request headers, e.g. for authentication.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    triggers: Vec<WebhookTrigger>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WebhookConfig {
+    webhooks: BTreeMap<String, WebhookTarget>,
+    /// Number of attempts per delivery, including the first one.
+    max_attempts: Option<u32>,
+    /// Delay before the first retry, doubled for each following retry up to
+    /// five minutes.
+    retry_backoff_ms: Option<u64>,
+    timeout_ms: Option<u64>,
+    /// Number of deliveries per webhook that may be in flight at once,
+    /// including ones waiting for a retry. Further deliveries are dropped.
+    max_pending_deliveries: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+pub struct Webhook {
+    id: IntegrationId,
+    config: Arc<WebhookConfig>,
+    cli: Cli,
+    handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[async_trait]
+impl Integration for Webhook {
+    fn new(
+        id: &IntegrationId,
+        config: &serde_json::Value,
+        cli: &Cli,
+        _event_tx: TxEventChannel,
+    ) -> Result<Self> {
+        let config: WebhookConfig = serde_json::from_value(config.clone())
+            .wrap_err("Failed to deserialize config of Webhook integration")?;
+
+        Ok(Webhook {
+            id: id.clone(),
+            config: Arc::new(config),
+            cli: cli.clone(),
+            handle: None,
+        })
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(
+                self.config.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+            ))
+            .build()
+            .wrap_err("Failed to build HTTP client of Webhook integration")?;
+        let retry = RetryPolicy {
+            max_attempts: self
+                .config
+                .max_attempts
+                .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+                .max(1),
+            backoff: Duration::from_millis(
+                self.config
+                    .retry_backoff_ms
+                    .unwrap_or(DEFAULT_RETRY_BACKOFF_MS),
+            ),
+        };
+
+        let id = self.id.clone();
+        let config = self.config.clone();
+        let dry_run = self.cli.dry_run;
+        let max_pending = self
+            .config
+            .max_pending_deliveries
+            .unwrap_or(DEFAULT_MAX_PENDING_DELIVERIES)
+            .max(1);
+        let pending: BTreeMap<String, Arc<Semaphore>> = self
+            .config
+            .webhooks
+            .keys()
+            .map(|webhook_id| (webhook_id.clone(), Arc::new(Semaphore::new(max_pending))))
+            .collect();
+        let mut feed_rx = subscribe_feed();
+
+        self.handle = Some(tokio::spawn(async move {
+            // Owning the deliveries here aborts their retries along with this
+            // task when the integration is stopped
+            let mut deliveries = JoinSet::new();
+
+            loop {
+                while deliveries.try_join_next().is_some() {}
+
+                let event = match feed_rx.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Webhook integration {id} skipped {skipped} events");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                for (webhook_id, target) in &config.webhooks {
+                    if !target
+                        .triggers
+                        .iter()
+                        .any(|trigger| trigger.matches(&event))
+                    {
+                        continue;
+                    }
+
+                    if dry_run {
+                        debug!(
+                            "(dry run) would deliver {} event to {}",
+                            event.kind(),
+                            target.url
+                        );
+                        continue;
+                    }
+
+                    let Some(permit) =
+                        reserve_delivery(&pending[webhook_id], &id, webhook_id, target, &event)
+                    else {
+                        continue;
+                    };
+
+                    // Retries of one webhook shouldn't hold back the others
+                    let delivery = deliver(
+                        client.clone(),
+                        id.clone(),
+                        webhook_id.clone(),
+                        target.clone(),
+                        event.clone(),
+                        retry,
+                    );
+                    deliveries.spawn(async move {
+                        let _permit = permit;
+                        delivery.await
+                    });
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+
+        Ok(())
+    }
+}
+
+/// Takes a slot for another delivery to a webhook, or records the delivery as
+/// dropped if all slots are taken by deliveries that are still in flight.
+fn reserve_delivery(
+    pending: &Arc<Semaphore>,
+    integration_id: &IntegrationId,
+    webhook_id: &str,
+    target: &WebhookTarget,
+    event: &FeedEvent,
+) -> Option<OwnedSemaphorePermit> {
+    match pending.clone().try_acquire_owned() {
+        Ok(permit) => Some(permit),
+        Err(_) => {
+            let error = "Too many deliveries are pending".to_string();
+            warn!(
+                "Webhook {integration_id}/{webhook_id} dropped a {} event: {error}",
+                event.kind()
+            );
+
+            record_webhook_delivery(
+                integration_id,
+                webhook_id,
+                &target.url,
+                event.kind(),
+                DeliveryOutcome {
+                    attempts: 0,
+                    status: None,
+                    error: Some(error),
+                },
+            );
+
+            None
+        }
+    }
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    integration_id: IntegrationId,
+    webhook_id: String,
+    target: WebhookTarget,
+    event: FeedEvent,
+    retry: RetryPolicy,
+) -> DeliveryOutcome {
+    let body = json!({
+        "integration_id": integration_id,
+        "webhook_id": webhook_id,
+        "timestamp": Utc::now().to_rfc3339(),
+        "event": event,
+    });
+
+    let mut attempts = 0;
+    let mut backoff = retry.backoff.min(MAX_RETRY_BACKOFF);
+
+    let (status, error) = loop {
+        attempts += 1;
+
+        let mut request = client.post(&target.url).json(&body);
+        for (name, value) in &target.headers {
+            request = request.header(name, value);
+        }
+
+        let (status, error, retryable) = match request.send().await {
+            Ok(response) => {
+                let status = response.status();
+                let error = (!status.is_success()).then(|| format!("Received HTTP {status}"));
+
+                // Other client errors won't go away by sending the same request again
+                let retryable = !status.is_client_error()
+                    || status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+
+                (Some(status.as_u16()), error, retryable)
+            }
+            Err(e) => (None, Some(e.to_string()), true),
+        };
+
+        if error.is_none() || !retryable || attempts >= retry.max_attempts {
+            break (status, error);
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = backoff.saturating_mul(2).min(MAX_RETRY_BACKOFF);
+    };
+
+    if let Some(error) = &error {
+        warn!("Webhook {integration_id}/{webhook_id} failed after {attempts} attempts: {error}");
+    }
+
+    record_webhook_delivery(
+        &integration_id,
+        &webhook_id,
+        &target.url,
+        event.kind(),
+        DeliveryOutcome {
+            attempts,
+            status,
+            error: error.clone(),
+        },
+    );
+
+    DeliveryOutcome {
+        attempts,
+        status,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use tokio::sync::{mpsc, Semaphore};
+    use warp::{http::StatusCode, Filter};
+
+    use super::{deliver, reserve_delivery, RetryPolicy, WebhookTarget, WebhookTrigger};
+    use crate::core::webhook_deliveries::recent_webhook_deliveries;
+    use crate::types::{integration::IntegrationId, scene::SceneId, websockets::FeedEvent};
+
+    fn scene_activated(scene_id: &str) -> FeedEvent {
+        FeedEvent::SceneActivated {
+            scene_id: SceneId::from(scene_id.to_string()),
+            device_keys: None,
+            group_keys: None,
+        }
+    }
+
+    #[test]
+    fn triggers_match_by_event_kind_and_filter() {
+        let triggers: Vec<WebhookTrigger> = serde_json::from_value(serde_json::json!([
+            { "on": "scene_activated", "scene_ids": ["evening"] },
+            { "on": "routine_triggered" }
+        ]))
+        .unwrap();
+
+        assert!(triggers[0].matches(&scene_activated("evening")));
+        assert!(!triggers[0].matches(&scene_activated("morning")));
+        assert!(!triggers[1].matches(&scene_activated("evening")));
+    }
+
+    #[tokio::test]
+    async fn retries_failed_delivery() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (body_tx, mut body_rx) = mpsc::unbounded_channel::<serde_json::Value>();
+
+        // Local stand-in for the receiving end that fails the first request
+        let route = warp::post()
+            .and(warp::path("hook"))
+            .and(warp::header::<String>("x-secret"))
+            .and(warp::body::json())
+            .map(move |secret: String, body: serde_json::Value| {
+                assert_eq!(secret, "hunter2");
+                let _ = body_tx.send(body);
+
+                if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                    StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    StatusCode::NO_CONTENT
+                }
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let target = WebhookTarget {
+            url: format!("http://{addr}/hook"),
+            headers: BTreeMap::from([("x-secret".to_string(), "hunter2".to_string())]),
+            triggers: Vec::new(),
+        };
+        let outcome = deliver(
+            reqwest::Client::new(),
+            IntegrationId::from("webhook".to_string()),
+            "scenes".to_string(),
+            target,
+            scene_activated("evening"),
+            RetryPolicy {
+                max_attempts: 3,
+                backoff: Duration::from_millis(10),
+            },
+        )
+        .await;
+
+        assert_eq!(outcome.attempts, 2);
+        assert_eq!(outcome.status, Some(204));
+        assert_eq!(outcome.error, None);
+
+        let body = body_rx.recv().await.unwrap();
+        assert_eq!(body["webhook_id"], "scenes");
+        assert_eq!(body["event"]["SceneActivated"]["scene_id"], "evening");
+    }
+
+    #[test]
+    fn drops_deliveries_beyond_the_pending_limit() {
+        let integration_id = IntegrationId::from("webhook_pending_limit".to_string());
+        let target = WebhookTarget {
+            url: "http://127.0.0.1:9/hook".to_string(),
+            headers: BTreeMap::new(),
+            triggers: Vec::new(),
+        };
+        let pending = Arc::new(Semaphore::new(1));
+        let event = scene_activated("evening");
+
+        let first = reserve_delivery(&pending, &integration_id, "scenes", &target, &event);
+        assert!(first.is_some());
+        assert!(reserve_delivery(&pending, &integration_id, "scenes", &target, &event).is_none());
+
+        let dropped: Vec<_> = recent_webhook_deliveries()
+            .into_iter()
+            .filter(|delivery| delivery.integration_id == integration_id)
+            .collect();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].attempts, 0);
+        assert!(!dropped[0].success);
+
+        // Finished deliveries free their slot
+        drop(first);
+        assert!(reserve_delivery(&pending, &integration_id, "scenes", &target, &event).is_some());
+    }
+}
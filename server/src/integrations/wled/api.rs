@@ -0,0 +1,341 @@
+//! WLED JSON API state and its mapping to homectl devices.
+//!
+//! Each segment of a controller becomes a device keyed by its segment id.
+
+use crate::types::{
+    color::{Capabilities, ColorMode, DeviceColor},
+    device::{ControllableDevice, Device, DeviceData, DeviceId, ManageKind},
+    integration::IntegrationId,
+};
+use color_eyre::Result;
+use eyre::eyre;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WledSegment {
+    pub id: u8,
+    #[serde(default)]
+    on: bool,
+    #[serde(default)]
+    bri: u8,
+    /// Primary, secondary and tertiary colors, as `[r, g, b]` or
+    /// `[r, g, b, w]` arrays.
+    #[serde(default)]
+    col: Vec<Value>,
+    /// Segment name, if one has been set.
+    n: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WledState {
+    #[serde(default)]
+    on: bool,
+    #[serde(default)]
+    bri: u8,
+    #[serde(default)]
+    pub seg: Vec<WledSegment>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WledInfo {
+    pub name: String,
+}
+
+/// Message pushed over the WebSocket, or the response of `GET /json`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WledMessage {
+    pub state: Option<WledState>,
+    pub info: Option<WledInfo>,
+    /// Names of effects, indexed by effect id. Only included in `GET /json`.
+    pub effects: Option<Vec<String>>,
+    /// Names of palettes, indexed by palette id. Only included in
+    /// `GET /json`.
+    pub palettes: Option<Vec<String>>,
+}
+
+/// Effect or palette, by id or by name.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum WledRef {
+    Id(u16),
+    Name(String),
+}
+
+impl WledRef {
+    fn resolve(&self, names: &[String], kind: &str) -> Result<u16> {
+        match self {
+            WledRef::Id(id) if usize::from(*id) < names.len() || names.is_empty() => Ok(*id),
+            WledRef::Id(id) => Err(eyre!("Unknown WLED {kind} {id}")),
+            WledRef::Name(name) => names
+                .iter()
+                .position(|candidate| candidate.eq_ignore_ascii_case(name))
+                .map(|id| id as u16)
+                .ok_or_else(|| eyre!("Unknown WLED {kind} '{name}'")),
+        }
+    }
+}
+
+/// Payload of WLED integration actions, e.g.
+/// `{ "action": "effect", "segment": 0, "effect": "Rainbow", "speed": 200 }`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum WledAction {
+    /// Starts an effect on a segment, or on all segments if omitted.
+    Effect {
+        segment: Option<u8>,
+        effect: WledRef,
+        speed: Option<u8>,
+        intensity: Option<u8>,
+        palette: Option<WledRef>,
+    },
+    Palette {
+        segment: Option<u8>,
+        palette: WledRef,
+    },
+    /// Applies a preset stored on the controller.
+    Preset { preset: u16 },
+}
+
+/// Effect and palette names of a controller.
+#[derive(Clone, Debug, Default)]
+pub struct WledCatalog {
+    pub effects: Vec<String>,
+    pub palettes: Vec<String>,
+}
+
+pub fn segment_devices(
+    state: &WledState,
+    controller_name: &str,
+    integration_id: &IntegrationId,
+    managed: &ManageKind,
+) -> Vec<Device> {
+    state
+        .seg
+        .iter()
+        .map(|segment| {
+            let color = segment
+                .col
+                .first()
+                .and_then(Value::as_array)
+                .and_then(|rgb| {
+                    let channel = |i: usize| rgb.get(i)?.as_u64().map(|c| c.min(255) as u8);
+                    Some(DeviceColor::new_from_rgb(
+                        channel(0)?,
+                        channel(1)?,
+                        channel(2)?,
+                    ))
+                });
+
+            // Segment brightness is relative to the brightness of the
+            // controller
+            let brightness = f32::from(segment.bri) * f32::from(state.bri) / (255.0 * 255.0);
+
+            let name = segment
+                .n
+                .clone()
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| format!("{controller_name} segment {}", segment.id));
+
+            Device::new(
+                integration_id.clone(),
+                DeviceId::new(&segment.id.to_string()),
+                name,
+                DeviceData::Controllable(ControllableDevice::new(
+                    None,
+                    state.on && segment.on,
+                    Some(brightness),
+                    color,
+                    None,
+                    Capabilities::singleton(ColorMode::Rgb),
+                    managed.clone(),
+                )),
+                None,
+            )
+        })
+        .collect()
+}
+
+/// Body of a `POST /json/state` request setting the state of one segment.
+pub fn state_request(
+    device: &ControllableDevice,
+    segment_id: u8,
+    default_transition: Option<f32>,
+) -> Value {
+    let state = &device.state;
+    let mut segment = json!({ "id": segment_id, "on": state.power });
+
+    if let Some(brightness) = state.brightness {
+        segment["bri"] = json!((*brightness * 255.0).round().clamp(0.0, 255.0) as u8);
+    }
+
+    let color = state
+        .color
+        .as_ref()
+        .and_then(|color| color.to_device_preferred_mode(&device.capabilities));
+    if let Some(DeviceColor::Rgb(rgb)) = color {
+        segment["col"] = json!([[rgb.r, rgb.g, rgb.b]]);
+    }
+
+    let mut body = json!({ "seg": [segment] });
+
+    // Segments can't light up while the controller is off. Brightness is
+    // controlled per segment, so the controller is kept at full brightness.
+    if state.power {
+        body["on"] = json!(true);
+        body["bri"] = json!(255);
+    }
+
+    let transition = state.transition.map(|t| *t).or(default_transition);
+    if let Some(transition) = transition {
+        // In units of 100 ms, for this request only
+        body["tt"] = json!((transition.max(0.0) * 10.0).round() as u64);
+    }
+
+    body
+}
+
+/// Body of a `POST /json/state` request running an action. Actions without
+/// a segment apply to all `segment_ids`.
+pub fn action_request(
+    action: &WledAction,
+    catalog: &WledCatalog,
+    segment_ids: &[u8],
+) -> Result<Value> {
+    let (segment, mut fields) = match action {
+        WledAction::Preset { preset } => return Ok(json!({ "ps": preset })),
+        WledAction::Effect {
+            segment,
+            effect,
+            speed,
+            intensity,
+            palette,
+        } => {
+            let mut fields = json!({ "fx": effect.resolve(&catalog.effects, "effect")? });
+            if let Some(speed) = speed {
+                fields["sx"] = json!(speed);
+            }
+            if let Some(intensity) = intensity {
+                fields["ix"] = json!(intensity);
+            }
+            if let Some(palette) = palette {
+                fields["pal"] = json!(palette.resolve(&catalog.palettes, "palette")?);
+            }
+
+            (segment, fields)
+        }
+        WledAction::Palette { segment, palette } => (
+            segment,
+            json!({ "pal": palette.resolve(&catalog.palettes, "palette")? }),
+        ),
+    };
+
+    let segments = match segment {
+        Some(segment) => vec![*segment],
+        None => segment_ids.to_vec(),
+    };
+
+    let segments = segments
+        .into_iter()
+        .map(|id| {
+            fields["id"] = json!(id);
+            fields.clone()
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!({ "seg": segments }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::color::Rgb;
+    use ordered_float::OrderedFloat;
+    use std::str::FromStr;
+
+    #[test]
+    fn maps_segments_to_devices() {
+        let message: WledMessage = serde_json::from_value(json!({
+            "state": {
+                "on": true,
+                "bri": 255,
+                "seg": [
+                    { "id": 0, "on": true, "bri": 51, "col": [[255, 0, 0, 0], [0, 0, 0]], "n": "Shelf" },
+                    { "id": 1, "on": false, "bri": 255, "col": [] }
+                ]
+            },
+            "info": { "name": "Living room" }
+        }))
+        .unwrap();
+
+        let devices = segment_devices(
+            message.state.as_ref().unwrap(),
+            &message.info.unwrap().name,
+            &IntegrationId::from_str("wled").unwrap(),
+            &ManageKind::Full,
+        );
+
+        assert_eq!(devices[0].name, "Shelf");
+        assert_eq!(
+            devices[0].get_controllable_state().unwrap().color,
+            Some(DeviceColor::new_from_rgb(255, 0, 0))
+        );
+        assert_eq!(
+            devices[0].get_controllable_state().unwrap().brightness,
+            Some(OrderedFloat(0.2))
+        );
+        assert_eq!(devices[1].name, "Living room segment 1");
+        assert!(!devices[1].get_controllable_state().unwrap().power);
+    }
+
+    #[test]
+    fn maps_state_and_actions_to_requests() {
+        let device = ControllableDevice::new(
+            None,
+            true,
+            Some(0.5),
+            Some(DeviceColor::Rgb(Rgb {
+                r: 0,
+                g: 128,
+                b: 255,
+            })),
+            Some(0.5),
+            Capabilities::singleton(ColorMode::Rgb),
+            ManageKind::Full,
+        );
+
+        assert_eq!(
+            state_request(&device, 1, None),
+            json!({
+                "on": true,
+                "bri": 255,
+                "tt": 5,
+                "seg": [{ "id": 1, "on": true, "bri": 128, "col": [[0, 128, 255]] }]
+            })
+        );
+
+        let catalog = WledCatalog {
+            effects: vec!["Solid".to_string(), "Rainbow".to_string()],
+            palettes: vec!["Default".to_string(), "Ocean".to_string()],
+        };
+        let action: WledAction = serde_json::from_value(json!({
+            "action": "effect",
+            "effect": "rainbow",
+            "speed": 200,
+            "palette": 1
+        }))
+        .unwrap();
+
+        assert_eq!(
+            action_request(&action, &catalog, &[0, 1]).unwrap(),
+            json!({ "seg": [
+                { "id": 0, "fx": 1, "sx": 200, "pal": 1 },
+                { "id": 1, "fx": 1, "sx": 200, "pal": 1 }
+            ] })
+        );
+
+        let typo: WledAction =
+            serde_json::from_value(json!({ "action": "palette", "palette": "Oecan" })).unwrap();
+        assert!(action_request(&typo, &catalog, &[0]).is_err());
+    }
+}
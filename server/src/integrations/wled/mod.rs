@@ -0,0 +1,386 @@
+//! WLED LED controller integration.
+//!
+//! State is pushed by the controller over its WebSocket, and changes are sent
+//! with its JSON API. Effects, palettes and presets are applied with
+//! integration actions, see `WledAction`.
+
+mod api;
+
+use crate::{
+    core::integrations::status,
+    types::{
+        device::{Device, DeviceData, ManageKind},
+        event::{Event, TxEventChannel},
+        integration::{Integration, IntegrationActionPayload, IntegrationId},
+    },
+    utils::cli::Cli,
+};
+use async_trait::async_trait;
+use color_eyre::Result;
+use eyre::{eyre, Context};
+use futures::StreamExt;
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+pub use self::api::WledAction;
+use self::api::{action_request, segment_devices, state_request, WledCatalog, WledMessage};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct WledConfig {
+    /// Address of the controller, e.g. `192.168.1.20`.
+    host: String,
+
+    /// Can be used to control whether the segments published by this
+    /// integration are "managed" or not, i.e.  whether homectl should keep
+    /// track of the devices' expected states or not.
+    managed: Option<ManageKind>,
+
+    /// Transition time in seconds used when homectl doesn't provide one.
+    default_transition: Option<f32>,
+}
+
+impl WledConfig {
+    fn http_url(&self, path: &str) -> String {
+        let host = self.host.trim_end_matches('/');
+
+        if host.contains("://") {
+            format!("{host}{path}")
+        } else {
+            format!("http://{host}{path}")
+        }
+    }
+
+    fn ws_url(&self) -> String {
+        let url = self.http_url("/ws");
+
+        match url.strip_prefix("http") {
+            Some(rest) => format!("ws{rest}"),
+            None => url,
+        }
+    }
+}
+
+/// What we know about the controller from its latest messages.
+#[derive(Debug, Default)]
+struct Controller {
+    name: Option<String>,
+    segment_ids: Vec<u8>,
+    catalog: WledCatalog,
+}
+
+pub struct Wled {
+    id: IntegrationId,
+    event_tx: TxEventChannel,
+    config: Arc<WledConfig>,
+    cli: Cli,
+    client: reqwest::Client,
+    controller: Arc<RwLock<Controller>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[async_trait]
+impl Integration for Wled {
+    fn new(
+        id: &IntegrationId,
+        config: &serde_json::Value,
+        cli: &Cli,
+        event_tx: TxEventChannel,
+    ) -> Result<Self> {
+        let config: WledConfig = serde_json::from_value(config.clone())
+            .wrap_err("Failed to deserialize config of Wled integration")?;
+
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .wrap_err("Failed to build HTTP client of Wled integration")?;
+
+        Ok(Wled {
+            id: id.clone(),
+            event_tx,
+            config: Arc::new(config),
+            cli: cli.clone(),
+            client,
+            controller: Default::default(),
+            handle: None,
+        })
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let id = self.id.clone();
+        let event_tx = self.event_tx.clone();
+        let config = Arc::clone(&self.config);
+        let client = self.client.clone();
+        let controller = Arc::clone(&self.controller);
+
+        self.handle = Some(tokio::spawn(async move {
+            loop {
+                if let Err(e) = run_websocket(&client, &config, &id, &event_tx, &controller).await {
+                    status::mark_integration_disconnected(&id, &e);
+                    error!(
+                        target: &format!("homectl_server::integrations::wled::{id}"),
+                        "WLED error: {e:?}"
+                    );
+                }
+
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }));
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn set_integration_device_state(&mut self, device: &Device) -> Result<()> {
+        let DeviceData::Controllable(controllable) = &device.data else {
+            return Ok(());
+        };
+
+        let segment_id: u8 = device
+            .id
+            .to_string()
+            .parse()
+            .wrap_err_with(|| format!("Invalid WLED segment id {}", device.id))?;
+        let body = state_request(controllable, segment_id, self.config.default_transition);
+
+        if self.cli.dry_run {
+            debug!("(dry run) would send device state: {device}");
+            return Ok(());
+        }
+
+        self.post_state(&body).await
+    }
+
+    async fn run_integration_action(&mut self, payload: &IntegrationActionPayload) -> Result<()> {
+        let action: WledAction = payload.parse().wrap_err("Failed to parse WLED action")?;
+
+        let body = {
+            let controller = self
+                .controller
+                .read()
+                .expect("WLED controller lock poisoned");
+            action_request(&action, &controller.catalog, &controller.segment_ids)?
+        };
+
+        self.post_state(&body).await
+    }
+}
+
+impl Wled {
+    async fn post_state(&self, body: &serde_json::Value) -> Result<()> {
+        self.client
+            .post(self.config.http_url("/json/state"))
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Reads effect and palette names, then follows state pushed over the
+/// WebSocket until it disconnects.
+async fn run_websocket(
+    client: &reqwest::Client,
+    config: &WledConfig,
+    id: &IntegrationId,
+    event_tx: &TxEventChannel,
+    controller: &Arc<RwLock<Controller>>,
+) -> Result<()> {
+    let message: WledMessage = client
+        .get(config.http_url("/json"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    {
+        let mut controller = controller.write().expect("WLED controller lock poisoned");
+        controller.catalog = WledCatalog {
+            effects: message.effects.unwrap_or_default(),
+            palettes: message.palettes.unwrap_or_default(),
+        };
+    }
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(config.ws_url())
+        .await
+        .wrap_err("Failed to connect to WLED WebSocket")?;
+
+    info!("WLED integration {id} connected to {}", config.host);
+    status::mark_integration_connected(id);
+
+    let managed = config.managed.clone().unwrap_or_default();
+
+    while let Some(message) = ws.next().await {
+        let text = match message? {
+            Message::Text(text) => {
+                status::record_integration_message(id);
+                text
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let message: WledMessage = match serde_json::from_str(&text) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Failed to parse WLED message: {e}");
+                continue;
+            }
+        };
+
+        let Some(state) = message.state else {
+            continue;
+        };
+
+        let devices = {
+            let mut controller = controller.write().expect("WLED controller lock poisoned");
+            if let Some(info) = message.info {
+                controller.name = Some(info.name);
+            }
+            controller.segment_ids = state.seg.iter().map(|segment| segment.id).collect();
+
+            let name = controller.name.as_deref().unwrap_or("WLED");
+            segment_devices(&state, name, id, &managed)
+        };
+
+        for device in devices {
+            event_tx.send(Event::ExternalStateUpdate { device });
+        }
+    }
+
+    Err(eyre!("WLED WebSocket was closed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::event::{mk_event_channel, RxEventChannel};
+    use crate::utils::cli::dry_run_cli;
+    use futures::SinkExt;
+    use serde_json::json;
+    use std::str::FromStr;
+    use tokio::sync::mpsc;
+    use warp::Filter;
+
+    /// Stands in for a controller with two segments, forwarding the bodies
+    /// of state requests.
+    fn mock_controller(
+        state_tx: mpsc::UnboundedSender<serde_json::Value>,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let json_api = warp::get().and(warp::path!("json")).map(|| {
+            warp::reply::json(&json!({
+                "effects": ["Solid", "Blink", "Rainbow"],
+                "palettes": ["Default", "Party"]
+            }))
+        });
+
+        let ws = warp::path!("ws").and(warp::ws()).map(|ws: warp::ws::Ws| {
+            ws.on_upgrade(|mut socket| async move {
+                let message = json!({
+                    "state": {
+                        "on": true,
+                        "bri": 255,
+                        "seg": [
+                            { "id": 0, "on": true, "bri": 255, "col": [[255, 160, 0]] },
+                            { "id": 1, "on": true, "bri": 128, "col": [[0, 0, 255]] }
+                        ]
+                    },
+                    "info": { "name": "Desk" }
+                });
+                let _ = socket
+                    .send(warp::ws::Message::text(message.to_string()))
+                    .await;
+
+                // Keep the connection open
+                while socket.next().await.is_some() {}
+            })
+        });
+
+        let post_state = warp::post()
+            .and(warp::path!("json" / "state"))
+            .and(warp::body::json())
+            .map(move |body: serde_json::Value| {
+                let _ = state_tx.send(body);
+                warp::reply::json(&json!({ "success": true }))
+            });
+
+        json_api.or(ws).or(post_state)
+    }
+
+    async fn next_device(rx: &mut RxEventChannel) -> Device {
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for device")
+                .expect("event channel closed");
+
+            if let Event::ExternalStateUpdate { device } = event {
+                return device;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn syncs_with_mocked_controller() {
+        let (state_tx, mut state_rx) = mpsc::unbounded_channel();
+        let (addr, server) =
+            warp::serve(mock_controller(state_tx)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let (event_tx, mut event_rx) = mk_event_channel();
+        let cli = Cli {
+            dry_run: false,
+            ..dry_run_cli()
+        };
+        let mut wled = Wled::new(
+            &IntegrationId::from_str("wled").unwrap(),
+            &json!({ "host": addr.to_string() }),
+            &cli,
+            event_tx,
+        )
+        .unwrap();
+        wled.start().await.unwrap();
+
+        let first = next_device(&mut event_rx).await;
+        assert_eq!(first.name, "Desk segment 0");
+        let second = next_device(&mut event_rx).await;
+        assert_eq!(
+            second
+                .get_controllable_state()
+                .and_then(|state| state.brightness),
+            Some(ordered_float::OrderedFloat(128.0 / 255.0))
+        );
+
+        wled.set_integration_device_state(&second).await.unwrap();
+        assert_eq!(
+            state_rx.recv().await.unwrap()["seg"],
+            json!([{ "id": 1, "on": true, "bri": 128, "col": [[0, 0, 255]] }])
+        );
+
+        let action = IntegrationActionPayload::from(
+            json!({ "action": "effect", "segment": 1, "effect": "Rainbow" }),
+        );
+        wled.run_integration_action(&action).await.unwrap();
+        assert_eq!(
+            state_rx.recv().await.unwrap(),
+            json!({ "seg": [{ "id": 1, "fx": 2 }] })
+        );
+
+        wled.stop().await.unwrap();
+    }
+}
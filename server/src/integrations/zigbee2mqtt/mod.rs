@@ -0,0 +1,450 @@
+#![allow(clippy::redundant_closure_call)]
+
+mod utils;
+
+use crate::{
+    core::integrations::status,
+    integrations::mqtt::CustomMqttAction,
+    types::{
+        device::{Device, ManageKind},
+        event::{Event, TxEventChannel},
+        integration::{Integration, IntegrationActionPayload, IntegrationId},
+    },
+    utils::cli::Cli,
+};
+use async_trait::async_trait;
+use color_eyre::Result;
+use eyre::{eyre, Context};
+use rand::{distributions::Alphanumeric, Rng};
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::{self, JoinHandle};
+
+use self::utils::{
+    homectl_to_z2m, parse_bridge_devices, z2m_to_homectl, Z2mDeviceInfo, Z2mDeviceKind,
+};
+
+const DEFAULT_BASE_TOPIC: &str = "zigbee2mqtt";
+
+/// Topic suffixes published by (or to) zigbee2mqtt that never carry device
+/// state.
+const IGNORED_TOPIC_SUFFIXES: [&str; 3] = ["/set", "/get", "/availability"];
+
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct Zigbee2MqttConfig {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+
+    /// zigbee2mqtt `mqtt.base_topic`, defaults to `zigbee2mqtt`.
+    base_topic: Option<String>,
+
+    /// Can be used to control whether the devices published by this integration
+    /// are "managed" or not, i.e.  whether homectl should keep track of the
+    /// devices' expected states or not.
+    managed: Option<ManageKind>,
+
+    /// Transition time in seconds used when homectl doesn't provide one.
+    default_transition: Option<f32>,
+}
+
+impl Zigbee2MqttConfig {
+    fn base_topic(&self) -> &str {
+        self.base_topic
+            .as_deref()
+            .map(|topic| topic.trim_end_matches('/'))
+            .filter(|topic| !topic.is_empty())
+            .unwrap_or(DEFAULT_BASE_TOPIC)
+    }
+}
+
+/// Devices known from the latest `bridge/devices` message, keyed by friendly
+/// name.
+type KnownDevices = Arc<RwLock<HashMap<String, Z2mDeviceInfo>>>;
+
+pub struct Zigbee2Mqtt {
+    id: IntegrationId,
+    event_tx: TxEventChannel,
+    config: Zigbee2MqttConfig,
+    cli: Cli,
+    client: Option<AsyncClient>,
+    devices: KnownDevices,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Outcome of processing one incoming MQTT message.
+#[derive(Debug, PartialEq)]
+enum IncomingMessage {
+    /// The device list changed, these friendly names should be polled for
+    /// their current state.
+    Devices(Vec<String>),
+    State(Box<Device>),
+    Ignored,
+}
+
+fn handle_incoming_message(
+    topic: &str,
+    payload: &[u8],
+    integration_id: &IntegrationId,
+    config: &Zigbee2MqttConfig,
+    devices: &KnownDevices,
+) -> Result<IncomingMessage> {
+    let base_topic = config.base_topic();
+    let Some(relative_topic) = topic
+        .strip_prefix(base_topic)
+        .and_then(|topic| topic.strip_prefix('/'))
+    else {
+        return Ok(IncomingMessage::Ignored);
+    };
+
+    if relative_topic == "bridge/devices" {
+        let parsed = parse_bridge_devices(payload)
+            .wrap_err("Failed to parse zigbee2mqtt bridge/devices message")?;
+
+        let mut poll = parsed
+            .values()
+            .filter(|info| matches!(info.kind, Z2mDeviceKind::Controllable { .. }))
+            .map(|info| info.friendly_name.clone())
+            .collect::<Vec<_>>();
+        poll.sort();
+
+        *devices.write().expect("zigbee2mqtt devices lock poisoned") = parsed;
+
+        return Ok(IncomingMessage::Devices(poll));
+    }
+
+    if relative_topic.starts_with("bridge/")
+        || IGNORED_TOPIC_SUFFIXES
+            .iter()
+            .any(|suffix| relative_topic.ends_with(suffix))
+    {
+        return Ok(IncomingMessage::Ignored);
+    }
+
+    let devices = devices.read().expect("zigbee2mqtt devices lock poisoned");
+    let Some(info) = devices.get(relative_topic) else {
+        debug!("Ignoring zigbee2mqtt message for unknown device {relative_topic}");
+        return Ok(IncomingMessage::Ignored);
+    };
+
+    let managed = config.managed.clone().unwrap_or_default();
+    Ok(
+        z2m_to_homectl(payload, info, integration_id.clone(), &managed)
+            .map(|device| IncomingMessage::State(Box::new(device)))
+            .unwrap_or(IncomingMessage::Ignored),
+    )
+}
+
+#[async_trait]
+impl Integration for Zigbee2Mqtt {
+    fn new(
+        id: &IntegrationId,
+        config: &serde_json::Value,
+        cli: &Cli,
+        event_tx: TxEventChannel,
+    ) -> Result<Self> {
+        let config: Zigbee2MqttConfig = serde_json::from_value(config.clone())
+            .wrap_err("Failed to deserialize config of Zigbee2Mqtt integration")?;
+
+        Ok(Zigbee2Mqtt {
+            id: id.clone(),
+            config,
+            cli: cli.clone(),
+            event_tx,
+            client: None,
+            devices: Default::default(),
+            handle: None,
+        })
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let random_string: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+
+        let mut options = MqttOptions::new(
+            format!("{}-{}", self.id, random_string),
+            self.config.host.clone(),
+            self.config.port,
+        );
+        options.set_keep_alive(Duration::from_secs(5));
+        // bridge/devices can be large on big networks
+        options.set_max_packet_size(1024 * 1024, 1024 * 1024);
+
+        if let Some(username) = &self.config.username {
+            options.set_credentials(username, self.config.password.as_deref().unwrap_or(""));
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, 10);
+
+        self.client = Some(client.clone());
+
+        let id = self.id.clone();
+        let event_tx = self.event_tx.clone();
+        let config = Arc::new(self.config.clone());
+        let devices = Arc::clone(&self.devices);
+
+        let handle = task::spawn(async move {
+            loop {
+                let notification = eventloop.poll().await;
+                status::record_mqtt_notification(&id, &notification);
+
+                let res = (|| async {
+                    match notification? {
+                        rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) => {
+                            client
+                                .subscribe(format!("{}/#", config.base_topic()), QoS::AtMostOnce)
+                                .await?;
+                        }
+
+                        rumqttc::Event::Incoming(rumqttc::Packet::Publish(msg)) => {
+                            match handle_incoming_message(
+                                &msg.topic,
+                                &msg.payload,
+                                &id,
+                                &config,
+                                &devices,
+                            )? {
+                                IncomingMessage::Devices(poll) => {
+                                    info!(
+                                        "zigbee2mqtt integration {id} discovered {} devices",
+                                        devices
+                                            .read()
+                                            .expect("zigbee2mqtt devices lock poisoned")
+                                            .len()
+                                    );
+
+                                    for friendly_name in poll {
+                                        client
+                                            .publish(
+                                                format!(
+                                                    "{}/{friendly_name}/get",
+                                                    config.base_topic()
+                                                ),
+                                                QoS::AtMostOnce,
+                                                false,
+                                                r#"{"state":""}"#,
+                                            )
+                                            .await?;
+                                    }
+                                }
+                                IncomingMessage::State(device) => {
+                                    event_tx.send(Event::ExternalStateUpdate { device: *device });
+                                }
+                                IncomingMessage::Ignored => {}
+                            }
+                        }
+                        _ => {}
+                    }
+
+                    Ok::<(), Box<dyn std::error::Error + Sync + Send>>(())
+                })()
+                .await;
+
+                if let Err(e) = res {
+                    error!(
+                        target: &format!("homectl_server::integrations::zigbee2mqtt::{id}"),
+                        "zigbee2mqtt error: {e:?}"
+                    );
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        });
+
+        self.handle = Some(handle);
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+
+        if let Some(client) = self.client.take() {
+            let _ = client.disconnect().await;
+        }
+
+        Ok(())
+    }
+
+    async fn set_integration_device_state(&mut self, device: &Device) -> Result<()> {
+        let client = self.client.as_ref().ok_or_else(|| {
+            eyre!("zigbee2mqtt client is not initialized; start phase has not completed")
+        })?;
+
+        let info = self
+            .devices
+            .read()
+            .expect("zigbee2mqtt devices lock poisoned")
+            .values()
+            .find(|info| info.ieee_address == device.id.to_string())
+            .cloned()
+            .ok_or_else(|| eyre!("Unknown zigbee2mqtt device {}", device.id))?;
+
+        let payload = homectl_to_z2m(device, &info, self.config.default_transition)?;
+        let topic = format!("{}/{}/set", self.config.base_topic(), info.friendly_name);
+
+        if !self.cli.dry_run {
+            client
+                .publish(topic, QoS::AtLeastOnce, false, payload.to_string())
+                .await?;
+        } else {
+            debug!("(dry run) would publish device state: {device}");
+        }
+
+        Ok(())
+    }
+
+    /// Publishes an arbitrary payload below the zigbee2mqtt base topic, for
+    /// example bridge requests.
+    async fn run_integration_action(&mut self, payload: &IntegrationActionPayload) -> Result<()> {
+        let action: CustomMqttAction = payload.parse()?;
+
+        let client = self.client.as_ref().ok_or_else(|| {
+            eyre!("zigbee2mqtt client is not initialized; start phase has not completed")
+        })?;
+
+        client
+            .publish(
+                format!("{}/{}", self.config.base_topic(), action.topic),
+                QoS::AtLeastOnce,
+                false,
+                action.payload(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn test_config() -> Zigbee2MqttConfig {
+        Zigbee2MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            base_topic: Some("z2m/".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn bridge_devices() -> Vec<u8> {
+        json!([
+            {
+                "ieee_address": "0x01",
+                "friendly_name": "kitchen/lamp",
+                "type": "Router",
+                "definition": {
+                    "exposes": [{
+                        "type": "switch",
+                        "features": [
+                            { "type": "binary", "name": "state", "property": "state", "access": 7, "value_on": "ON", "value_off": "OFF" }
+                        ]
+                    }]
+                }
+            },
+            {
+                "ieee_address": "0x02",
+                "friendly_name": "kitchen/button",
+                "type": "EndDevice",
+                "definition": {
+                    "exposes": [
+                        { "type": "enum", "name": "action", "property": "action", "access": 1 }
+                    ]
+                }
+            }
+        ])
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn bridge_devices_message_updates_known_devices() {
+        let devices = KnownDevices::default();
+        let id = IntegrationId::from_str("z2m").unwrap();
+
+        let outcome = handle_incoming_message(
+            "z2m/bridge/devices",
+            &bridge_devices(),
+            &id,
+            &test_config(),
+            &devices,
+        )
+        .unwrap();
+
+        assert_eq!(
+            outcome,
+            IncomingMessage::Devices(vec!["kitchen/lamp".to_string()])
+        );
+        assert_eq!(devices.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn state_messages_resolve_nested_friendly_names() {
+        let devices = KnownDevices::default();
+        let id = IntegrationId::from_str("z2m").unwrap();
+        let config = test_config();
+        handle_incoming_message(
+            "z2m/bridge/devices",
+            &bridge_devices(),
+            &id,
+            &config,
+            &devices,
+        )
+        .unwrap();
+
+        let outcome = handle_incoming_message(
+            "z2m/kitchen/button",
+            json!({ "action": "single" }).to_string().as_bytes(),
+            &id,
+            &config,
+            &devices,
+        )
+        .unwrap();
+
+        let IncomingMessage::State(device) = outcome else {
+            panic!("expected a state update");
+        };
+        assert_eq!(device.id.to_string(), "0x02");
+        assert_eq!(device.name, "kitchen/button");
+    }
+
+    #[test]
+    fn command_and_unknown_topics_are_ignored() {
+        let devices = KnownDevices::default();
+        let id = IntegrationId::from_str("z2m").unwrap();
+        let config = test_config();
+        handle_incoming_message(
+            "z2m/bridge/devices",
+            &bridge_devices(),
+            &id,
+            &config,
+            &devices,
+        )
+        .unwrap();
+
+        for topic in [
+            "z2m/kitchen/lamp/set",
+            "z2m/kitchen/lamp/availability",
+            "z2m/bridge/state",
+            "z2m/unknown",
+            "other/kitchen/lamp",
+        ] {
+            let outcome =
+                handle_incoming_message(topic, br#"{"state":"ON"}"#, &id, &config, &devices)
+                    .unwrap();
+            assert_eq!(outcome, IncomingMessage::Ignored, "topic {topic}");
+        }
+    }
+}
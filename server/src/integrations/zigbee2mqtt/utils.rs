@@ -0,0 +1,613 @@
+use crate::types::color::{Capabilities, DeviceColor};
+use crate::types::{
+    device::{ControllableDevice, Device, DeviceData, DeviceId, ManageKind, SensorDevice},
+    integration::IntegrationId,
+};
+use color_eyre::Result;
+use ordered_float::OrderedFloat;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Sensor properties in the order we prefer them when a device exposes more
+/// than one readable value. Anything else falls back to the first exposed
+/// readable property.
+const PREFERRED_SENSOR_PROPERTIES: [&str; 10] = [
+    "action",
+    "occupancy",
+    "presence",
+    "contact",
+    "water_leak",
+    "smoke",
+    "vibration",
+    "temperature",
+    "humidity",
+    "illuminance_lux",
+];
+
+/// Expose access bit signalling that the property is published in state
+/// messages.
+const ACCESS_STATE: u64 = 0b001;
+
+const DEFAULT_BRIGHTNESS_MAX: f32 = 254.0;
+
+/// Device entry as published on `bridge/devices`. Only the fields we use are
+/// deserialized.
+#[derive(Debug, Deserialize)]
+struct BridgeDevice {
+    ieee_address: String,
+    friendly_name: String,
+    #[serde(rename = "type")]
+    device_type: Option<String>,
+    #[serde(default)]
+    disabled: bool,
+    definition: Option<BridgeDeviceDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BridgeDeviceDefinition {
+    #[serde(default)]
+    exposes: Vec<Expose>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Expose {
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    property: Option<String>,
+    access: Option<u64>,
+    value_min: Option<f64>,
+    value_max: Option<f64>,
+    value_on: Option<Value>,
+    value_off: Option<Value>,
+    #[serde(default)]
+    features: Vec<Expose>,
+}
+
+impl Expose {
+    fn is_published(&self) -> bool {
+        self.access
+            .map(|access| access & ACCESS_STATE != 0)
+            .unwrap_or(true)
+    }
+}
+
+/// What we know about how to read and write a binary property.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinaryProperty {
+    pub property: String,
+    pub value_on: Value,
+    pub value_off: Value,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Z2mDeviceKind {
+    /// A light or switch, i.e. something exposing a writable `state`.
+    Controllable {
+        power: BinaryProperty,
+        brightness_max: Option<f32>,
+        capabilities: Capabilities,
+    },
+    /// A read-only device, we track a single property as its sensor value.
+    Sensor {
+        property: String,
+        binary: Option<BinaryProperty>,
+    },
+}
+
+/// Capabilities derived from a device's `exposes` metadata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Z2mDeviceInfo {
+    pub ieee_address: String,
+    pub friendly_name: String,
+    pub kind: Z2mDeviceKind,
+}
+
+/// Parses a `bridge/devices` payload into device descriptions keyed by
+/// friendly name. Coordinators, disabled devices and devices that haven't
+/// finished interviewing (no definition) are skipped.
+pub fn parse_bridge_devices(payload: &[u8]) -> Result<HashMap<String, Z2mDeviceInfo>> {
+    let devices: Vec<BridgeDevice> = serde_json::from_slice(payload)?;
+
+    Ok(devices
+        .into_iter()
+        .filter(|device| device.device_type.as_deref() != Some("Coordinator"))
+        .filter(|device| !device.disabled)
+        .filter_map(|device| {
+            let exposes = device.definition?.exposes;
+            let kind = device_kind_from_exposes(&exposes)?;
+
+            Some((
+                device.friendly_name.clone(),
+                Z2mDeviceInfo {
+                    ieee_address: device.ieee_address,
+                    friendly_name: device.friendly_name,
+                    kind,
+                },
+            ))
+        })
+        .collect())
+}
+
+fn device_kind_from_exposes(exposes: &[Expose]) -> Option<Z2mDeviceKind> {
+    if let Some(expose) = exposes
+        .iter()
+        .find(|expose| expose.kind == "light" || expose.kind == "switch")
+    {
+        if let Some(kind) = controllable_from_features(&expose.features) {
+            return Some(kind);
+        }
+    }
+
+    let readable = exposes
+        .iter()
+        .filter(|expose| expose.property.is_some() && expose.is_published())
+        .filter(|expose| matches!(expose.kind.as_str(), "binary" | "numeric" | "enum" | "text"))
+        .filter(|expose| expose.property.as_deref() != Some("linkquality"))
+        .collect::<Vec<_>>();
+
+    let sensor = PREFERRED_SENSOR_PROPERTIES
+        .iter()
+        .find_map(|preferred| {
+            readable
+                .iter()
+                .find(|expose| expose.property.as_deref() == Some(preferred))
+        })
+        .or_else(|| readable.first())?;
+
+    Some(Z2mDeviceKind::Sensor {
+        property: sensor.property.clone()?,
+        binary: binary_property(sensor),
+    })
+}
+
+fn binary_property(expose: &Expose) -> Option<BinaryProperty> {
+    if expose.kind != "binary" {
+        return None;
+    }
+
+    Some(BinaryProperty {
+        property: expose.property.clone()?,
+        value_on: expose.value_on.clone().unwrap_or(Value::Bool(true)),
+        value_off: expose.value_off.clone().unwrap_or(Value::Bool(false)),
+    })
+}
+
+fn controllable_from_features(features: &[Expose]) -> Option<Z2mDeviceKind> {
+    let find = |name: &str| {
+        features
+            .iter()
+            .find(|feature| feature.name.as_deref() == Some(name))
+    };
+
+    let power = find("state").and_then(binary_property)?;
+    let brightness_max = find("brightness")
+        .map(|feature| feature.value_max.unwrap_or(DEFAULT_BRIGHTNESS_MAX as f64) as f32);
+
+    // z2m reports color temperature in mireds, homectl uses kelvin. The
+    // range flips since a higher mired value means a warmer color.
+    let ct = find("color_temp").and_then(|feature| {
+        let min_mired = feature.value_min.filter(|value| *value > 0.0)?;
+        let max_mired = feature.value_max.filter(|value| *value > 0.0)?;
+        Some(mired_to_kelvin(max_mired)..mired_to_kelvin(min_mired))
+    });
+
+    let capabilities = Capabilities {
+        xy: find("color_xy").is_some(),
+        hs: find("color_hs").is_some(),
+        rgb: false,
+        ct,
+    };
+
+    Some(Z2mDeviceKind::Controllable {
+        power,
+        brightness_max,
+        capabilities,
+    })
+}
+
+fn mired_to_kelvin(mired: f64) -> u16 {
+    (1_000_000.0 / mired).round().clamp(0.0, u16::MAX as f64) as u16
+}
+
+fn kelvin_to_mired(kelvin: u64) -> u64 {
+    if kelvin == 0 {
+        return 0;
+    }
+
+    (1_000_000.0 / kelvin as f64).round() as u64
+}
+
+/// Converts a z2m device state message into a homectl device.
+pub fn z2m_to_homectl(
+    payload: &[u8],
+    info: &Z2mDeviceInfo,
+    integration_id: IntegrationId,
+    managed: &ManageKind,
+) -> Option<Device> {
+    let value: Value = match serde_json::from_slice(payload) {
+        Ok(value) => value,
+        Err(err) => {
+            error!(
+                "Failed to parse zigbee2mqtt message for {}: {err}",
+                info.friendly_name
+            );
+            return None;
+        }
+    };
+
+    let data = match &info.kind {
+        Z2mDeviceKind::Controllable {
+            power,
+            brightness_max,
+            capabilities,
+        } => {
+            let power_value = value.get(&power.property)?;
+            let is_on = power_value == &power.value_on;
+
+            let brightness = brightness_max.and_then(|max| {
+                value
+                    .get("brightness")
+                    .and_then(Value::as_f64)
+                    .map(|brightness| (brightness as f32 / max).clamp(0.0, 1.0))
+            });
+
+            let color = color_from_payload(&value, capabilities);
+
+            DeviceData::Controllable(ControllableDevice::new(
+                None,
+                is_on,
+                brightness,
+                color,
+                None,
+                capabilities.clone(),
+                managed.clone(),
+            ))
+        }
+        Z2mDeviceKind::Sensor { property, binary } => {
+            let sensor_value = value.get(property).filter(|value| !value.is_null())?;
+
+            DeviceData::Sensor(match (binary, sensor_value) {
+                (Some(binary), value) if value == &binary.value_on => {
+                    SensorDevice::Boolean { value: true }
+                }
+                (Some(binary), value) if value == &binary.value_off => {
+                    SensorDevice::Boolean { value: false }
+                }
+                (_, Value::Bool(value)) => SensorDevice::Boolean { value: *value },
+                (_, Value::Number(value)) => SensorDevice::Number {
+                    value: value.as_f64()?,
+                },
+                (_, Value::String(value)) => SensorDevice::Text {
+                    value: value.clone(),
+                },
+                _ => {
+                    warn!(
+                        "Unsupported value for zigbee2mqtt sensor property '{property}' on {}",
+                        info.friendly_name
+                    );
+                    return None;
+                }
+            })
+        }
+    };
+
+    Some(Device {
+        id: DeviceId::new(&info.ieee_address),
+        name: info.friendly_name.clone(),
+        integration_id,
+        data,
+        raw: Some(value),
+    })
+}
+
+fn color_from_payload(value: &Value, capabilities: &Capabilities) -> Option<DeviceColor> {
+    let color_mode = value.get("color_mode").and_then(Value::as_str);
+    let color = value.get("color");
+
+    let ct = || {
+        let mired = value.get("color_temp").and_then(Value::as_f64)?;
+        capabilities
+            .ct
+            .as_ref()
+            .map(|_| DeviceColor::new_from_ct(mired_to_kelvin(mired)))
+    };
+    let xy = || {
+        let color = color?;
+        let x = color.get("x").and_then(Value::as_f64)?;
+        let y = color.get("y").and_then(Value::as_f64)?;
+        Some(DeviceColor::new_from_xy(x as f32, y as f32))
+    };
+    let hs = || {
+        let color = color?;
+        let hue = color.get("hue").and_then(Value::as_f64)?;
+        let saturation = color.get("saturation").and_then(Value::as_f64)?;
+        Some(DeviceColor::new_from_hs(
+            hue.round() as u16,
+            (saturation / 100.0) as f32,
+        ))
+    };
+
+    match color_mode {
+        Some("color_temp") => ct().or_else(xy).or_else(hs),
+        Some("hs") => hs().or_else(xy).or_else(ct),
+        _ => xy().or_else(hs).or_else(ct),
+    }
+}
+
+/// Converts homectl device state into a z2m `/set` payload.
+pub fn homectl_to_z2m(
+    device: &Device,
+    info: &Z2mDeviceInfo,
+    default_transition: Option<f32>,
+) -> Result<Value> {
+    let Z2mDeviceKind::Controllable {
+        power,
+        brightness_max,
+        ..
+    } = &info.kind
+    else {
+        return Err(eyre!(
+            "zigbee2mqtt device {} is not controllable",
+            info.friendly_name
+        ));
+    };
+
+    let DeviceData::Controllable(controllable) = &device.data else {
+        return Err(eyre!("Expected controllable device state for {device}"));
+    };
+    let state = &controllable.state;
+
+    let mut payload = serde_json::Map::new();
+    payload.insert(
+        power.property.clone(),
+        if state.power {
+            power.value_on.clone()
+        } else {
+            power.value_off.clone()
+        },
+    );
+
+    if let (Some(brightness), Some(max)) = (state.brightness, brightness_max) {
+        payload.insert(
+            "brightness".to_string(),
+            json!((brightness.0.clamp(0.0, 1.0) * max).round() as u64),
+        );
+    }
+
+    match &state.color {
+        Some(DeviceColor::Xy(xy)) => {
+            payload.insert("color".to_string(), json!({ "x": xy.x.0, "y": xy.y.0 }));
+        }
+        Some(DeviceColor::Hs(hs)) => {
+            payload.insert(
+                "color".to_string(),
+                json!({ "hue": hs.h, "saturation": (hs.s.0 * 100.0).round() }),
+            );
+        }
+        Some(DeviceColor::Rgb(rgb)) => {
+            payload.insert(
+                "color".to_string(),
+                json!({ "r": rgb.r, "g": rgb.g, "b": rgb.b }),
+            );
+        }
+        Some(DeviceColor::Ct(ct)) => {
+            payload.insert("color_temp".to_string(), json!(kelvin_to_mired(ct.ct)));
+        }
+        None => {}
+    }
+
+    if let Some(transition) = state.transition.or(default_transition.map(OrderedFloat)) {
+        payload.insert("transition".to_string(), json!(transition.0));
+    }
+
+    Ok(Value::Object(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::color::Hs;
+    use std::str::FromStr;
+
+    fn bridge_devices_payload() -> Value {
+        json!([
+            {
+                "ieee_address": "0x0000000000000000",
+                "friendly_name": "Coordinator",
+                "type": "Coordinator",
+                "definition": null
+            },
+            {
+                "ieee_address": "0x001788010b1c2d3e",
+                "friendly_name": "living_room/ceiling",
+                "type": "Router",
+                "definition": {
+                    "exposes": [
+                        {
+                            "type": "light",
+                            "features": [
+                                { "type": "binary", "name": "state", "property": "state", "access": 7, "value_on": "ON", "value_off": "OFF" },
+                                { "type": "numeric", "name": "brightness", "property": "brightness", "access": 7, "value_min": 0, "value_max": 254 },
+                                { "type": "numeric", "name": "color_temp", "property": "color_temp", "access": 7, "value_min": 153, "value_max": 500 },
+                                { "type": "composite", "name": "color_xy", "property": "color", "access": 7, "features": [] },
+                                { "type": "composite", "name": "color_hs", "property": "color", "access": 7, "features": [] }
+                            ]
+                        },
+                        { "type": "numeric", "name": "linkquality", "property": "linkquality", "access": 1 }
+                    ]
+                }
+            },
+            {
+                "ieee_address": "0x00158d0001a2b3c4",
+                "friendly_name": "hallway/motion",
+                "type": "EndDevice",
+                "definition": {
+                    "exposes": [
+                        { "type": "numeric", "name": "battery", "property": "battery", "access": 1 },
+                        { "type": "binary", "name": "occupancy", "property": "occupancy", "access": 1, "value_on": true, "value_off": false },
+                        { "type": "numeric", "name": "illuminance_lux", "property": "illuminance_lux", "access": 1 }
+                    ]
+                }
+            }
+        ])
+    }
+
+    fn parsed_devices() -> HashMap<String, Z2mDeviceInfo> {
+        parse_bridge_devices(bridge_devices_payload().to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn parse_bridge_devices_derives_light_capabilities() {
+        let devices = parsed_devices();
+
+        assert_eq!(devices.len(), 2);
+        let light = &devices["living_room/ceiling"];
+        assert_eq!(light.ieee_address, "0x001788010b1c2d3e");
+        assert_eq!(
+            light.kind,
+            Z2mDeviceKind::Controllable {
+                power: BinaryProperty {
+                    property: "state".to_string(),
+                    value_on: json!("ON"),
+                    value_off: json!("OFF"),
+                },
+                brightness_max: Some(254.0),
+                capabilities: Capabilities {
+                    xy: true,
+                    hs: true,
+                    rgb: false,
+                    ct: Some(2000..6536),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn parse_bridge_devices_prefers_meaningful_sensor_property() {
+        let devices = parsed_devices();
+
+        assert_eq!(
+            devices["hallway/motion"].kind,
+            Z2mDeviceKind::Sensor {
+                property: "occupancy".to_string(),
+                binary: Some(BinaryProperty {
+                    property: "occupancy".to_string(),
+                    value_on: json!(true),
+                    value_off: json!(false),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn z2m_to_homectl_reads_light_state() {
+        let devices = parsed_devices();
+        let payload = json!({
+            "state": "ON",
+            "brightness": 127,
+            "color_mode": "color_temp",
+            "color_temp": 370,
+            "color": { "x": 0.46, "y": 0.41 }
+        });
+
+        let device = z2m_to_homectl(
+            payload.to_string().as_bytes(),
+            &devices["living_room/ceiling"],
+            IntegrationId::from_str("z2m").unwrap(),
+            &ManageKind::Full,
+        )
+        .unwrap();
+
+        assert_eq!(device.id, DeviceId::new("0x001788010b1c2d3e"));
+        assert_eq!(device.name, "living_room/ceiling");
+        let state = device.get_controllable_state().unwrap();
+        assert!(state.power);
+        assert_eq!(state.brightness, Some(OrderedFloat(127.0 / 254.0)));
+        assert_eq!(state.color, Some(DeviceColor::new_from_ct(2703)));
+    }
+
+    #[test]
+    fn z2m_to_homectl_reads_binary_sensor() {
+        let devices = parsed_devices();
+        let payload = json!({ "occupancy": true, "battery": 90 });
+
+        let device = z2m_to_homectl(
+            payload.to_string().as_bytes(),
+            &devices["hallway/motion"],
+            IntegrationId::from_str("z2m").unwrap(),
+            &ManageKind::Full,
+        )
+        .unwrap();
+
+        assert_eq!(
+            device.data,
+            DeviceData::Sensor(SensorDevice::Boolean { value: true })
+        );
+    }
+
+    #[test]
+    fn homectl_to_z2m_writes_light_payload() {
+        let devices = parsed_devices();
+        let info = &devices["living_room/ceiling"];
+        let device = Device::new(
+            IntegrationId::from_str("z2m").unwrap(),
+            DeviceId::new(&info.ieee_address),
+            info.friendly_name.clone(),
+            DeviceData::Controllable(ControllableDevice::new(
+                None,
+                true,
+                Some(0.5),
+                Some(DeviceColor::Hs(Hs {
+                    h: 30,
+                    s: OrderedFloat(0.5),
+                })),
+                Some(1.0),
+                Capabilities::default(),
+                ManageKind::Full,
+            )),
+            None,
+        );
+
+        let payload = homectl_to_z2m(&device, info, None).unwrap();
+
+        assert_eq!(
+            payload,
+            json!({
+                "state": "ON",
+                "brightness": 127,
+                "color": { "hue": 30, "saturation": 50.0 },
+                "transition": 1.0
+            })
+        );
+    }
+
+    #[test]
+    fn homectl_to_z2m_converts_ct_to_mireds() {
+        let devices = parsed_devices();
+        let info = &devices["living_room/ceiling"];
+        let device = Device::new(
+            IntegrationId::from_str("z2m").unwrap(),
+            DeviceId::new(&info.ieee_address),
+            info.friendly_name.clone(),
+            DeviceData::Controllable(ControllableDevice::new(
+                None,
+                false,
+                None,
+                Some(DeviceColor::new_from_ct(4000)),
+                None,
+                Capabilities::default(),
+                ManageKind::Full,
+            )),
+            None,
+        );
+
+        let payload = homectl_to_z2m(&device, info, Some(0.5)).unwrap();
+
+        assert_eq!(
+            payload,
+            json!({ "state": "OFF", "color_temp": 250, "transition": 0.5 })
+        );
+    }
+}
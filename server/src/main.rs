@@ -5,37 +5,48 @@ use homectl_server::api::config::{parse_config_backup, ParsedConfigBackup};
 use homectl_server::api::init_api;
 use homectl_server::core::simulate;
 use homectl_server::core::{
+    auth::Auth,
+    config_files::start_config_dir_sync,
+    config_versions::{config_settled, config_version_recorder},
     devices::Devices,
     event::DeferredEventWork,
+    fades::Fades,
     groups::Groups,
     integrations::Integrations,
     logs::init_logging,
+    occupancy::Occupancy,
     routines::Routines,
     scenes::Scenes,
-    snapshot::{new_snapshot_handle, RuntimeSnapshot},
+    sequences::Sequences,
+    snapshot::{new_snapshot_handle, RuntimeSnapshot, SnapshotHandle},
     state::{spawn_state_actor, AppState, StateHandle},
     ui::Ui,
+    websockets::start_routine_history_feed,
 };
 use homectl_server::db::{
     actions, config_queries, connect_configured_database, init_db, is_db_connected,
     is_db_reconnect_configured,
 };
-use homectl_server::types::event::{mk_event_channel, Event};
+use homectl_server::types::event::{mk_event_channel, Event, TxEventChannel};
 use homectl_server::types::scene::SceneOverridesConfig;
 use homectl_server::utils::cli::{Cli, Command};
 
+use chrono::Timelike;
 use clap::Parser;
 use color_eyre::Result;
 use eyre::eyre;
 use std::collections::HashMap;
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{atomic::AtomicBool, Arc};
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
 const DATABASE_RECONNECT_INTERVAL_SECS: u64 = 2;
 const SLOW_DEFERRED_WORK_WARN_MS: u64 = 1000;
+const DEVICE_HISTORY_PRUNE_INTERVAL_SECS: u64 = 60 * 60;
+const CONFIG_VERSION_INTERVAL_SECS: u64 = 60;
+const CONFIG_VERSION_DEBOUNCE_SECS: u64 = 5;
 
 fn default_backup_config_path() -> &'static Path {
     Path::new("Settings.json")
@@ -151,7 +162,7 @@ async fn run_simulation(
     let mut config =
         simulate::prepare_simulation_config(args.source_db.as_deref(), args.config.as_deref())
             .await?;
-    simulate::convert_mqtt_to_dummy(&mut config)?;
+    simulate::convert_live_integrations_to_dummy(&mut config)?;
 
     info!("Simulation runtime snapshot ready, starting server...");
     let runtime_config = RuntimeConfigSnapshot::from_config_export(config);
@@ -159,6 +170,8 @@ async fn run_simulation(
     simulation_cli.port = args.port;
     simulation_cli.database_url = None;
     simulation_cli.warmup_time = Some(args.warmup_time);
+    // Simulation may read a config directory, but never writes to it
+    simulation_cli.config_dir_write_back = false;
     simulation_cli.command = None;
 
     run_event_loop(&simulation_cli, args.port, args.warmup_time, runtime_config).await
@@ -191,11 +204,6 @@ async fn run_event_loop(
         }
     });
 
-    let mut integrations = Integrations::new(event_tx.clone(), cli);
-    integrations
-        .load_config_rows(&runtime_config.config.integrations)
-        .await?;
-
     let mut groups = Groups::new(Default::default());
     groups.load_config_rows(&runtime_config.config.groups);
 
@@ -206,16 +214,19 @@ async fn run_event_loop(
     );
 
     let mut devices = Devices::new(event_tx.clone(), cli);
+    devices.set_history_enabled(runtime_config.config.core.device_history_retention_days > 0);
     devices.refresh_db_devices(&scenes).await;
 
+    let mut occupancy = Occupancy::new(event_tx.clone());
+    occupancy.load_config_rows(&runtime_config.config.groups, &devices);
+
     let mut rules = Routines::new(Default::default(), event_tx.clone());
     rules.load_config_rows(&runtime_config.config.routines);
+    rules.set_location(runtime_config.config.core.location());
+    rules.restore_rule_holds().await;
 
     let ui = Ui::with_state(runtime_config.ui_state);
 
-    integrations.run_register_pass().await?;
-    integrations.run_start_pass().await?;
-
     let snapshot = new_snapshot_handle(RuntimeSnapshot {
         runtime_config: Arc::new(runtime_config.config.clone()),
         devices: Arc::new(devices.get_state().clone()),
@@ -226,6 +237,17 @@ async fn run_event_loop(
         warming_up: true,
     });
 
+    // Integrations are loaded after the initial snapshot exists so that
+    // state-mirroring integrations can read it.
+    let mut integrations = Integrations::new(event_tx.clone(), cli);
+    integrations.set_snapshot(snapshot.clone());
+    integrations
+        .load_config_rows(&runtime_config.config.integrations)
+        .await?;
+
+    integrations.run_register_pass().await?;
+    integrations.run_start_pass().await?;
+
     let state = AppState {
         warming_up: true,
         runtime_config: runtime_config.config.clone(),
@@ -234,6 +256,9 @@ async fn run_event_loop(
         scenes,
         devices,
         rules,
+        sequences: Sequences::new(event_tx.clone()),
+        occupancy,
+        fades: Fades::new(event_tx.clone()),
         event_tx: event_tx.clone(),
         ui,
         ws: Default::default(),
@@ -244,6 +269,7 @@ async fn run_event_loop(
     };
 
     let ws_handle = state.ws.clone();
+    start_routine_history_feed(ws_handle.clone());
 
     // Spawn the state actor. The actor owns `AppState` by value and is
     // the sole writer; admin handlers dispatch mutations through
@@ -251,11 +277,14 @@ async fn run_event_loop(
     // onto the actor's command channel.
     let state_handle = spawn_state_actor(state, snapshot.clone(), deferred_work_tx.clone());
 
+    let auth = Auth::load().await;
+
     init_api(
         snapshot.clone(),
         state_handle.clone(),
         ws_handle,
         event_tx.clone(),
+        auth,
         port,
     )?;
 
@@ -263,6 +292,18 @@ async fn run_event_loop(
         start_database_reconnect_loop(state_handle.clone());
     }
 
+    if let Some(config_dir) = cli.config_dir.as_deref() {
+        start_config_dir_sync(
+            PathBuf::from(config_dir),
+            cli.config_dir_write_back,
+            state_handle.clone(),
+        );
+    }
+
+    start_device_history_pruning(snapshot.clone());
+    start_config_version_recording(snapshot.clone());
+    start_routine_clock(event_tx.clone());
+
     {
         let state_handle = state_handle.clone();
         let event_tx = event_tx.clone();
@@ -289,6 +330,90 @@ async fn run_event_loop(
     }
 }
 
+fn start_device_history_pruning(snapshot: SnapshotHandle) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(DEVICE_HISTORY_PRUNE_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            if !is_db_connected() {
+                continue;
+            }
+
+            let retention_days = snapshot
+                .load()
+                .runtime_config
+                .core
+                .device_history_retention_days
+                .max(0);
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(retention_days.into());
+
+            match actions::db_prune_device_history(cutoff.timestamp_millis()).await {
+                Ok(0) => {}
+                Ok(removed) => debug!("Pruned {removed} device history rows"),
+                Err(error) => warn!("Failed to prune device history: {error}"),
+            }
+        }
+    });
+}
+
+/// Records a config version once a changed runtime config has stayed
+/// unchanged for a moment, so that a burst of edits becomes one version. The
+/// interval catches changes that couldn't be recorded, e.g. while the
+/// database was unavailable.
+fn start_config_version_recording(snapshot: SnapshotHandle) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(CONFIG_VERSION_INTERVAL_SECS));
+        let mut recorded: Option<Arc<config_queries::ConfigExport>> = None;
+
+        loop {
+            tokio::select! {
+                _ = config_settled(Duration::from_secs(CONFIG_VERSION_DEBOUNCE_SECS)) => {}
+                _ = interval.tick() => {}
+            }
+
+            if !is_db_connected() {
+                continue;
+            }
+
+            let config = Arc::clone(&snapshot.load().runtime_config);
+            if recorded
+                .as_ref()
+                .is_some_and(|recorded| Arc::ptr_eq(recorded, &config))
+            {
+                continue;
+            }
+
+            let mut recorder = config_version_recorder().await;
+            match recorder.record(&config, "Automatic snapshot").await {
+                Ok(Some(version)) => debug!("Recorded config version {version}"),
+                Ok(None) => {}
+                Err(error) => {
+                    warn!("Failed to record config version: {error}");
+                    continue;
+                }
+            }
+
+            recorded = Some(config);
+        }
+    });
+}
+
+/// Sends [Event::RoutineClockTick] at the start of every minute.
+fn start_routine_clock(event_tx: TxEventChannel) {
+    tokio::spawn(async move {
+        loop {
+            let seconds_into_minute = chrono::Local::now().second();
+            let until_next_minute = 60 - u64::from(seconds_into_minute.min(59));
+            tokio::time::sleep(Duration::from_secs(until_next_minute)).await;
+
+            event_tx.send(Event::RoutineClockTick);
+        }
+    });
+}
+
 fn start_database_reconnect_loop(state_handle: StateHandle) {
     tokio::spawn(async move {
         if !is_db_reconnect_configured() || is_db_connected() {
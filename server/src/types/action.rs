@@ -5,8 +5,9 @@ use super::{
     device::{Device, DeviceKey},
     dim::DimDescriptor,
     integration::CustomActionDescriptor,
-    rule::ForceTriggerRoutineDescriptor,
+    rule::{ForceTriggerRoutineDescriptor, IfDescriptor},
     scene::{ActivateSceneActionDescriptor, CycleScenesDescriptor},
+    sequence::{CancelSequenceDescriptor, SequenceDescriptor, WaitUntilDescriptor},
     ui::UiActionDescriptor,
 };
 
@@ -17,18 +18,31 @@ pub enum Action {
     /// Request to activate given scene.
     ActivateScene(ActivateSceneActionDescriptor),
 
+    /// Cancels pending sequences with the given key.
+    CancelSequence(CancelSequenceDescriptor),
+
     /// Request to cycle between given scenes.
     CycleScenes(CycleScenesDescriptor),
 
     /// Runs a custom integration action.
     Custom(CustomActionDescriptor),
 
+    /// Pauses a sequence for the given number of milliseconds.
+    Delay { ms: u64 },
+
     /// Dims the given groups and devices.
     Dim(DimDescriptor),
 
     /// Forcibly triggers a routine, ignoring any possible rules.
     ForceTriggerRoutine(ForceTriggerRoutineDescriptor),
 
+    /// Runs one of two lists of actions depending on whether the given rules
+    /// currently match.
+    If(IfDescriptor),
+
+    /// Runs actions one after another, see [SequenceDescriptor].
+    Sequence(SequenceDescriptor),
+
     /// Sets device state to given state.
     SetDeviceState(Device),
 
@@ -41,6 +55,9 @@ pub enum Action {
     /// Special category of actions that are only used by UI.
     Ui(UiActionDescriptor),
 
+    /// Pauses a sequence until the given rule matches.
+    WaitUntil(WaitUntilDescriptor),
+
     /// Legacy evalexpr action payload. No longer executed.
     #[serde(untagged, skip_serializing)]
     #[ts(skip)]
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Access level of an API user or token. Each role includes the access of
+/// the roles before it.
+#[derive(TS, Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Role {
+    /// Can read device state and config.
+    Viewer,
+
+    /// Can also run actions and activate or edit scenes.
+    Operator,
+
+    /// Can also change config, users and API tokens.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(eyre!("Unknown role {s}")),
+        }
+    }
+}
+
+#[derive(TS, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[ts(export)]
+pub struct UserInfo {
+    pub username: String,
+    pub role: Role,
+}
+
+/// An API token, without the token itself which is only shown once when the
+/// token is created.
+#[derive(TS, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[ts(export)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub role: Role,
+    #[ts(type = "number")]
+    pub created_at_ms: i64,
+}
+
+#[derive(TS, Clone, Debug, Deserialize, Serialize)]
+#[ts(export)]
+pub struct CreatedApiToken {
+    pub token: String,
+    pub info: ApiTokenInfo,
+}
+
+#[derive(TS, Clone, Debug, Deserialize, Serialize)]
+#[ts(export)]
+pub struct LoginResponse {
+    /// Session token, sent as `Authorization: Bearer <token>` or as the
+    /// `token` query parameter of WebSocket connections.
+    pub token: String,
+    pub username: String,
+    pub role: Role,
+    #[ts(type = "number")]
+    pub expires_at_ms: i64,
+}
@@ -66,6 +66,22 @@ pub enum Event {
 
     /// Various actions that can be triggered by rules.
     Action(Action),
+
+    /// Sent once a minute to evaluate time based routine rules.
+    RoutineClockTick,
+
+    /// Sent when a duration-qualified routine rule may have held long enough.
+    RoutineRuleHoldElapsed,
+
+    /// Sent when the delay or wait timeout of the given step of a sequence
+    /// has elapsed.
+    SequenceStep { sequence_id: u64, step: usize },
+
+    /// Sent when the timeout of an occupancy zone may have passed.
+    OccupancyTimeoutElapsed,
+
+    /// Sent when the next step of a fade is due.
+    FadeStep { fade_id: u64 },
 }
 
 #[derive(Clone)]
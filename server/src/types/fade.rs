@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(TS, Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Easing {
+    #[default]
+    Linear,
+
+    /// Starts and ends slowly.
+    EaseInOut,
+
+    /// Brightness changes evenly in perceived lightness, rather than in
+    /// light output.
+    Perceptual,
+}
+
+impl Easing {
+    /// Maps fade progress (0.0 - 1.0) to eased progress.
+    pub fn apply(&self, progress: f32) -> f32 {
+        let t = progress.clamp(0.0, 1.0);
+
+        match self {
+            Easing::Linear | Easing::Perceptual => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Fades devices to their target state in steps sent by homectl, for
+/// transitions longer than devices support on their own.
+#[derive(TS, Clone, Debug, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[ts(export)]
+pub struct FadeDescriptor {
+    #[ts(type = "number")]
+    pub duration_ms: u64,
+
+    #[serde(default)]
+    pub easing: Easing,
+
+    /// Time between two steps, defaults to one second. Never shorter than
+    /// the outbound update interval of the device integration.
+    #[serde(default)]
+    #[ts(type = "number | null")]
+    pub step_ms: Option<u64>,
+}
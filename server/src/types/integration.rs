@@ -4,7 +4,7 @@ use super::{device::Device, event::TxEventChannel};
 use async_trait::async_trait;
 use color_eyre::Result;
 use eyre::Context;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{collections::HashMap, convert::Infallible, str::FromStr, time::Duration};
 use ts_rs::TS;
 
@@ -39,6 +39,23 @@ pub struct IntegrationConfigSchema {
     pub name: String,
     pub description: String,
     pub fields: Vec<IntegrationConfigFieldSchema>,
+    /// Payloads accepted by `run_integration_action`, empty if the plugin
+    /// has no actions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<IntegrationActionSchema>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct IntegrationActionSchema {
+    /// Value of the payload's `action` field selecting this action, for
+    /// plugins with several actions.
+    pub action: Option<String>,
+    pub label: String,
+    pub description: Option<String>,
+    /// Fields of the payload object, using the same dot-separated keys as
+    /// config fields.
+    pub fields: Vec<IntegrationConfigFieldSchema>,
+    pub example: serde_json::Value,
 }
 
 #[derive(Clone, Debug, Serialize, PartialEq)]
@@ -116,9 +133,25 @@ impl OutboundDeviceUpdatePolicy {
 }
 
 macro_attr! {
-    #[derive(TS, Clone, Debug, Deserialize, Serialize, Eq, PartialEq, Hash, NewtypeDisplay!, NewtypeFrom!)]
+    #[derive(TS, Clone, Debug, Deserialize, Serialize, Eq, PartialEq, NewtypeDisplay!, NewtypeFrom!)]
     #[ts(export)]
-    pub struct IntegrationActionPayload(String);
+    pub struct IntegrationActionPayload(serde_json::Value);
+}
+
+impl IntegrationActionPayload {
+    /// Deserializes the payload into the action type of an integration.
+    ///
+    /// Payloads used to be opaque strings, so JSON encoded in a string
+    /// payload is parsed before falling back to the string itself.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T> {
+        if let serde_json::Value::String(s) = &self.0 {
+            if let Ok(action) = serde_json::from_str(s) {
+                return Ok(action);
+            }
+        }
+
+        serde_json::from_value(self.0.clone()).wrap_err("Invalid integration action payload")
+    }
 }
 
 #[derive(TS, Clone, Debug, Deserialize, Serialize)]
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::integration::IntegrationId;
+
+#[derive(TS, Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum IntegrationHealth {
+    /// Loaded, but `start()` has not completed yet.
+    Starting,
+    Connected,
+    /// Running, but disconnected or failing to send updates.
+    Degraded,
+    /// Failed to load or start, and won't recover until it is reloaded.
+    Failed,
+}
+
+#[derive(TS, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[ts(export)]
+pub struct IntegrationStatus {
+    pub integration_id: IntegrationId,
+    pub plugin: String,
+    pub status: IntegrationHealth,
+    /// When `status` last changed.
+    pub since: String,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+    /// How many times the integration has reconnected after losing its
+    /// connection.
+    pub reconnect_count: u32,
+    /// When the integration last received a message from its devices or
+    /// service.
+    pub last_message_at: Option<String>,
+}
@@ -1,14 +1,20 @@
 pub mod action;
+pub mod auth;
 pub mod color;
 pub mod device;
 pub mod dim;
 pub mod event;
+pub mod fade;
 pub mod group;
 pub mod integration;
+pub mod integration_status;
 pub mod logs;
+pub mod occupancy;
 pub mod routine_history;
 pub mod routine_status;
 pub mod rule;
 pub mod scene;
+pub mod sequence;
 pub mod ui;
+pub mod webhook;
 pub mod websockets;
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::device::DeviceKey;
+
+/// Combines motion sensors and door contacts of a room into a virtual
+/// occupancy sensor.
+///
+/// The room becomes occupied on motion or when a door opens, and vacant once
+/// `timeout_ms` has passed without either. Motion while all doors are closed
+/// keeps the room occupied until a door opens again.
+#[derive(TS, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[ts(export)]
+pub struct OccupancyConfig {
+    /// Boolean sensors reporting `true` while they detect motion.
+    pub motion_sensors: Vec<DeviceKey>,
+
+    /// Boolean contact sensors on the doors of the room.
+    #[serde(default)]
+    pub door_sensors: Vec<DeviceKey>,
+
+    /// Sensor value that means a door is open. Defaults to `false`, matching
+    /// `contact` sensors that report `true` while closed.
+    #[serde(default)]
+    pub door_open_value: bool,
+
+    /// How long the room stays occupied after the last activity.
+    pub timeout_ms: u64,
+}
@@ -9,6 +9,9 @@ use super::{device::DeviceKey, routine_status::RoutineRuntimeStatus, rule::Routi
 pub enum RoutineHistoryTriggerKind {
     RuleMatch,
     ForceTrigger,
+    /// The rules matched but the routine's cooldown or debounce kept it from
+    /// firing.
+    Suppressed,
 }
 
 #[derive(TS, Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
@@ -22,4 +25,7 @@ pub struct RoutineHistoryEntry {
     pub event_source_device_key: Option<DeviceKey>,
     pub action_count: usize,
     pub status: Option<RoutineRuntimeStatus>,
+    /// Why the trigger was suppressed, only set for suppressed triggers.
+    #[serde(default)]
+    pub suppressed_reason: Option<String>,
 }
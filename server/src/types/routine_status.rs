@@ -12,6 +12,11 @@ pub struct RuleRuntimeStatus {
     pub trigger_match: bool,
     pub error: Option<String>,
     pub children: Option<Vec<RuleRuntimeStatus>>,
+
+    /// For rules with `for_ms`, how long the condition still needs to hold
+    /// before the rule matches.
+    #[serde(default)]
+    pub hold_remaining_ms: Option<u64>,
 }
 
 #[derive(TS, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
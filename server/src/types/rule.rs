@@ -2,6 +2,8 @@ use super::device::{DeviceRef, SensorDevice};
 use super::{group::GroupId, scene::SceneId};
 
 use super::action::Actions;
+use crate::utils::{from_hh_mm, sun::SunEvent, to_hh_mm};
+use chrono::NaiveTime;
 use jsonptr::PointerBuf;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -15,6 +17,11 @@ macro_attr! {
 }
 
 /// Determines how a rule triggers in response to state changes.
+///
+/// Rules with a `for_ms` hold duration only match once their condition has
+/// held continuously for that many milliseconds. The routine then triggers
+/// once when the duration elapses, and with `level` also on later events
+/// while the condition holds.
 #[derive(TS, Clone, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 #[ts(export)]
@@ -43,6 +50,11 @@ pub struct SensorRule {
     #[serde(default)]
     pub trigger_mode: TriggerMode,
 
+    /// How long the condition must hold before the rule matches, see
+    /// [TriggerMode].
+    #[serde(default)]
+    pub for_ms: Option<u64>,
+
     #[serde(flatten)]
     #[ts(skip)]
     pub device_ref: DeviceRef,
@@ -79,6 +91,11 @@ pub struct RawRule {
     #[serde(default)]
     pub trigger_mode: TriggerMode,
 
+    /// How long the condition must hold before the rule matches, see
+    /// [TriggerMode].
+    #[serde(default)]
+    pub for_ms: Option<u64>,
+
     #[serde(flatten)]
     #[ts(skip)]
     pub device_ref: DeviceRef,
@@ -94,6 +111,11 @@ pub struct DeviceRule {
     #[serde(default = "default_level_trigger_mode")]
     pub trigger_mode: TriggerMode,
 
+    /// How long the condition must hold before the rule matches, see
+    /// [TriggerMode].
+    #[serde(default)]
+    pub for_ms: Option<u64>,
+
     #[serde(flatten)]
     #[ts(skip)]
     pub device_ref: DeviceRef,
@@ -113,6 +135,11 @@ pub struct GroupRule {
     /// How this rule should trigger. Defaults to `level` for group rules.
     #[serde(default = "default_level_trigger_mode")]
     pub trigger_mode: TriggerMode,
+
+    /// How long the condition must hold before the rule matches, see
+    /// [TriggerMode].
+    #[serde(default)]
+    pub for_ms: Option<u64>,
 }
 
 #[derive(TS, Clone, Deserialize, Serialize, Debug)]
@@ -122,6 +149,98 @@ pub struct AnyRule {
     pub any: Rules,
 }
 
+#[derive(TS, Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(weekday: chrono::Weekday) -> Self {
+        match weekday {
+            chrono::Weekday::Mon => Weekday::Mon,
+            chrono::Weekday::Tue => Weekday::Tue,
+            chrono::Weekday::Wed => Weekday::Wed,
+            chrono::Weekday::Thu => Weekday::Thu,
+            chrono::Weekday::Fri => Weekday::Fri,
+            chrono::Weekday::Sat => Weekday::Sat,
+            chrono::Weekday::Sun => Weekday::Sun,
+        }
+    }
+}
+
+#[derive(TS, Clone, Deserialize, Serialize, Debug)]
+#[ts(export)]
+pub struct TimeWindow {
+    /// Local time (`HH:MM`) at which the window opens.
+    #[serde(deserialize_with = "from_hh_mm", serialize_with = "to_hh_mm")]
+    #[ts(type = "string")]
+    pub start: NaiveTime,
+
+    /// Local time (`HH:MM`) at which the window closes. Windows where `end` is
+    /// before `start` span midnight, equal times span the whole day.
+    #[serde(deserialize_with = "from_hh_mm", serialize_with = "to_hh_mm")]
+    #[ts(type = "string")]
+    pub end: NaiveTime,
+
+    /// Days on which the window opens. Windows spanning midnight belong to the
+    /// day they opened on. Defaults to every day.
+    pub weekdays: Option<Vec<Weekday>>,
+}
+
+/// Matches while the local time is within a daily time window.
+#[derive(TS, Clone, Deserialize, Serialize, Debug)]
+#[ts(export)]
+pub struct TimeWindowRule {
+    pub time_window: TimeWindow,
+
+    /// How this rule should trigger. Defaults to `level`, i.e. the rule acts
+    /// as a condition. With `edge` or `pulse` the routine triggers when the
+    /// window opens.
+    #[serde(default = "default_level_trigger_mode")]
+    pub trigger_mode: TriggerMode,
+}
+
+#[derive(TS, Clone, Copy, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum SunRelation {
+    /// From the (offset) sun event until local midnight.
+    #[default]
+    After,
+
+    /// From local midnight until the (offset) sun event.
+    Before,
+}
+
+/// Matches relative to today's sunrise or sunset, computed from the location
+/// configured in core config.
+#[derive(TS, Clone, Deserialize, Serialize, Debug)]
+#[ts(export)]
+pub struct SunRule {
+    pub sun: SunEvent,
+
+    #[serde(default)]
+    pub relation: SunRelation,
+
+    /// Minutes added to the sun event, may be negative.
+    #[serde(default)]
+    pub offset_minutes: i32,
+
+    /// How this rule should trigger. Defaults to `level`, i.e. the rule acts
+    /// as a condition. With `edge` or `pulse` the routine triggers when the
+    /// rule starts matching.
+    #[serde(default = "default_level_trigger_mode")]
+    pub trigger_mode: TriggerMode,
+}
+
 /// A JavaScript-based rule that evaluates a script returning boolean
 #[derive(TS, Clone, Deserialize, Serialize, Debug)]
 #[ts(export)]
@@ -152,6 +271,12 @@ pub enum Rule {
     /// one of the contained rules need to match.
     Any(AnyRule),
 
+    /// Match the local time of day.
+    TimeWindow(TimeWindowRule),
+
+    /// Match the local time relative to sunrise or sunset.
+    Sun(SunRule),
+
     /// Legacy evalexpr rule payload. No longer executed.
     #[ts(skip)]
     EvalExpr(String),
@@ -170,10 +295,45 @@ pub struct Routine {
     #[ts(skip)]
     pub rules: Rules,
     pub actions: Actions,
+
+    /// Minimum time between two firings of the routine. Triggers during the
+    /// cooldown are suppressed.
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+
+    /// Suppresses triggers arriving within this long of the previous
+    /// trigger, whether or not that one fired. A routine that keeps
+    /// triggering stays suppressed until it has been quiet for this long.
+    #[serde(default)]
+    pub debounce_ms: Option<u64>,
 }
 
 pub type RoutinesConfig = HashMap<RoutineId, Routine>;
 
+/// Runs `then` if all rules of `condition` currently match, and `else`
+/// otherwise. Trigger modes and `for_ms` durations are ignored.
+#[derive(TS, Clone, Deserialize, Debug, Serialize)]
+#[ts(export)]
+pub struct IfDescriptor {
+    pub condition: Rules,
+
+    #[serde(default)]
+    pub then: Actions,
+
+    #[serde(default, rename = "else")]
+    pub else_actions: Actions,
+}
+
+impl IfDescriptor {
+    pub fn branch(&self, condition_matches: bool) -> &Actions {
+        if condition_matches {
+            &self.then
+        } else {
+            &self.else_actions
+        }
+    }
+}
+
 #[derive(TS, Clone, Deserialize, Debug, Serialize)]
 #[ts(export)]
 pub struct ForceTriggerRoutineDescriptor {
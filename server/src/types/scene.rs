@@ -1,7 +1,7 @@
 use super::color::DeviceColor;
 use super::device::{ControllableState, DeviceKey, DeviceRef};
 
-use super::{group::GroupId, integration::IntegrationId};
+use super::{fade::FadeDescriptor, group::GroupId, integration::IntegrationId};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
@@ -119,6 +119,11 @@ pub struct ActivateSceneActionDescriptor {
     /// Total rollout duration in milliseconds.
     #[ts(type = "number | null")]
     pub rollout_duration_ms: Option<u64>,
+
+    /// Fade devices to the scene in steps driven by homectl instead of
+    /// relying on device transitions. Rollouts are ignored for fades.
+    #[serde(default)]
+    pub fade: Option<FadeDescriptor>,
 }
 
 #[derive(TS, Clone, Deserialize, Serialize, Debug, Eq, PartialEq, Hash)]
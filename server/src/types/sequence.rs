@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::{action::Actions, rule::Rule};
+
+/// Runs actions one after another. `Delay` and `WaitUntil` actions pause the
+/// sequence until they complete.
+#[derive(TS, Clone, Deserialize, Debug, Serialize)]
+#[ts(export)]
+pub struct SequenceDescriptor {
+    pub actions: Actions,
+
+    /// Starting a sequence cancels any pending sequence with the same key.
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+#[derive(TS, Clone, Deserialize, Debug, Serialize)]
+#[ts(export)]
+pub struct WaitUntilDescriptor {
+    /// Rule whose condition the sequence waits for. Trigger modes and
+    /// `for_ms` durations are ignored.
+    pub rule: Box<Rule>,
+
+    /// Stop waiting after this many milliseconds.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+
+    /// Whether the sequence is cancelled when the wait times out, rather
+    /// than continuing with the next action.
+    #[serde(default)]
+    pub cancel_on_timeout: bool,
+}
+
+#[derive(TS, Clone, Deserialize, Debug, Serialize)]
+#[ts(export)]
+pub struct CancelSequenceDescriptor {
+    pub key: String,
+}
+
+#[derive(TS, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum PendingSequenceState {
+    /// Waiting for a `Delay` action to elapse.
+    Delaying,
+    /// Waiting for the rule of a `WaitUntil` action to match.
+    WaitingForRule,
+}
+
+/// A sequence that has actions left to run.
+#[derive(TS, Clone, Debug, Deserialize, Serialize)]
+#[ts(export)]
+pub struct PendingSequence {
+    pub id: u64,
+    pub key: Option<String>,
+    pub started_at: String,
+    pub state: PendingSequenceState,
+
+    /// Index of the action the sequence is currently waiting on.
+    pub step: usize,
+    pub step_count: usize,
+
+    /// When the current delay elapses or the current wait times out.
+    pub resumes_at: Option<String>,
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::integration::IntegrationId;
+
+#[derive(TS, Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[ts(export)]
+pub struct WebhookDelivery {
+    pub id: String,
+    /// When the last attempt finished.
+    pub timestamp: String,
+    pub integration_id: IntegrationId,
+    pub webhook_id: String,
+    pub url: String,
+    /// Kind of the delivered event, e.g. `device_changed`.
+    pub event_kind: String,
+    pub attempts: u32,
+    pub success: bool,
+    /// HTTP status of the last response, if one was received.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
@@ -4,17 +4,145 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 use super::{
-    device::{DeviceKey, DevicesState},
-    event::Event,
-    group::FlattenedGroupsConfig,
+    action::Action,
+    device::{ControllableState, Device, DeviceKey, DevicesState},
+    group::{FlattenedGroupsConfig, GroupId},
+    integration::IntegrationId,
+    routine_history::RoutineHistoryEntry,
     routine_status::RoutineStatuses,
-    scene::FlattenedScenesConfig,
+    scene::{FlattenedScenesConfig, SceneConfig, SceneId},
 };
 
+/// Requests that clients may send over the WebSocket API. These are
+/// validated before being dispatched, and invalid requests are answered with
+/// [WebSocketResponse::Error].
+#[allow(clippy::large_enum_variant)]
 #[derive(TS, Deserialize, Serialize, Debug)]
 #[ts(export)]
 pub enum WebSocketRequest {
-    EventMessage(Event),
+    /// Runs an action, same as `POST /api/v1/actions/trigger`.
+    Action(Action),
+
+    /// Stores UI state that is shared between all clients.
+    StoreUiState {
+        key: String,
+        value: serde_json::Value,
+    },
+
+    /// Sets the state of a controllable device. The device keeps its other
+    /// fields as homectl knows them.
+    SetDeviceState {
+        device_key: DeviceKey,
+        state: ControllableState,
+
+        /// Stores the state as an override of the device's active scene,
+        /// instead of leaving the scene.
+        #[serde(default)]
+        keep_scene: bool,
+    },
+
+    /// Stores a scene from the given device states.
+    StoreScene {
+        scene_id: SceneId,
+        config: SceneConfig,
+    },
+
+    RenameScene {
+        scene_id: SceneId,
+        name: String,
+    },
+
+    DeleteScene {
+        scene_id: SceneId,
+    },
+
+    /// Replaces the subscription of the client and sends it the state that
+    /// matches the new subscription.
+    Subscribe(WebSocketSubscription),
+}
+
+/// Parts of the state that WebSocket clients can subscribe to.
+#[derive(TS, Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum WebSocketTopic {
+    Devices,
+    Scenes,
+    Groups,
+    RoutineStatuses,
+    UiState,
+}
+
+/// Limits which state is sent to a WebSocket client. Filters that are left
+/// out don't limit anything, so an empty subscription receives all state.
+#[derive(TS, Clone, Default, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct WebSocketSubscription {
+    /// Parts of the state to send, defaults to all of them.
+    #[serde(default)]
+    pub topics: Option<Vec<WebSocketTopic>>,
+
+    /// Devices to send. When several device filters are given, devices
+    /// matching any of them are sent.
+    #[serde(default)]
+    pub device_keys: Option<Vec<DeviceKey>>,
+
+    /// Send devices belonging to these groups, and only these groups.
+    /// Scenes are limited to the ones setting any of the subscribed devices.
+    #[serde(default)]
+    pub groups: Option<Vec<GroupId>>,
+
+    /// Send devices of these integrations.
+    #[serde(default)]
+    pub integration_ids: Option<Vec<IntegrationId>>,
+
+    /// Also send individual events as [WebSocketResponse::Event]. These are
+    /// not limited by the other filters.
+    #[serde(default)]
+    pub feed: bool,
+}
+
+impl WebSocketSubscription {
+    pub fn includes_topic(&self, topic: WebSocketTopic) -> bool {
+        self.topics
+            .as_ref()
+            .is_none_or(|topics| topics.contains(&topic))
+    }
+
+    /// Whether any of the device filters are set.
+    pub fn filters_devices(&self) -> bool {
+        self.device_keys.is_some() || self.groups.is_some() || self.integration_ids.is_some()
+    }
+}
+
+#[derive(TS, Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum WebSocketErrorKind {
+    /// The message could not be parsed as a [WebSocketRequest].
+    InvalidMessage,
+
+    /// The role of the client does not allow the request.
+    Forbidden,
+
+    /// The request was well-formed, but refers to unknown or invalid data.
+    InvalidRequest,
+}
+
+#[derive(TS, Clone, Deserialize, Serialize, Debug)]
+#[ts(export)]
+pub struct WebSocketError {
+    pub kind: WebSocketErrorKind,
+    pub message: String,
+}
+
+impl WebSocketError {
+    pub fn new(kind: WebSocketErrorKind, message: impl Into<String>) -> Self {
+        WebSocketError {
+            kind,
+            message: message.into(),
+        }
+    }
 }
 
 #[derive(TS, Deserialize, Serialize, Debug)]
@@ -54,4 +182,40 @@ pub struct StatePatch {
 pub enum WebSocketResponse {
     State(StateUpdate),
     Patch(StatePatch),
+
+    /// Sent to a client whose request was rejected.
+    Error(WebSocketError),
+
+    /// Sent to clients that subscribed to the event feed.
+    Event(FeedEvent),
+}
+
+/// Individual changes sent on the event feed, in addition to the state
+/// patches that only carry the resulting state.
+#[allow(clippy::large_enum_variant)]
+#[derive(TS, Clone, Deserialize, Serialize, Debug)]
+#[ts(export)]
+pub enum FeedEvent {
+    DeviceChanged {
+        device: Device,
+    },
+
+    /// A routine was triggered, or suppressed by its cooldown or debounce.
+    RoutineTriggered(RoutineHistoryEntry),
+
+    SceneActivated {
+        scene_id: SceneId,
+        device_keys: Option<Vec<DeviceKey>>,
+        group_keys: Option<Vec<GroupId>>,
+    },
+}
+
+impl FeedEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FeedEvent::DeviceChanged { .. } => "device_changed",
+            FeedEvent::RoutineTriggered(_) => "routine_triggered",
+            FeedEvent::SceneActivated { .. } => "scene_activated",
+        }
+    }
 }
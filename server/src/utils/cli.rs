@@ -23,6 +23,15 @@ pub struct Cli {
     #[arg(long, env = "WARMUP_TIME")]
     pub warmup_time: Option<u64>,
 
+    /// Directory of TOML files, one per group, scene and routine, to keep the
+    /// runtime config in sync with (optional)
+    #[arg(long, env = "CONFIG_DIR")]
+    pub config_dir: Option<String>,
+
+    /// Write config changes made through homectl back to `--config-dir`
+    #[arg(long, env = "CONFIG_DIR_WRITE_BACK", default_value_t = false)]
+    pub config_dir_write_back: bool,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 }
@@ -30,8 +39,9 @@ pub struct Cli {
 #[derive(Clone, Subcommand)]
 pub enum Command {
     /// Launch a sandboxed simulation server with an in-memory database.
-    /// Copies config from a source database or JSON backup file and replaces
-    /// MQTT integrations with dummy equivalents.
+    /// Copies config from a source database or JSON backup file, replaces
+    /// integrations that control real devices with dummy equivalents and
+    /// disables outgoing exporters and webhooks.
     Simulate(SimulateArgs),
 }
 
@@ -55,3 +65,18 @@ pub struct SimulateArgs {
     #[arg(long, default_value_t = 0)]
     pub warmup_time: u64,
 }
+
+/// Arguments of a server that never writes to real devices, with all optional
+/// settings left unset.
+pub fn dry_run_cli() -> Cli {
+    Cli {
+        dry_run: true,
+        port: 45289,
+        database_url: None,
+        config: None,
+        warmup_time: None,
+        config_dir: None,
+        config_dir_write_back: false,
+        command: None,
+    }
+}
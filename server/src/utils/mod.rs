@@ -1,9 +1,10 @@
 use std::{collections::BTreeMap, hash::Hash};
 
 use color_eyre::Result;
-use serde::{de, Deserialize};
+use serde::{de, Deserialize, Serializer};
 
 pub mod cli;
+pub mod sun;
 
 pub fn from_hh_mm<'de, D>(d: D) -> Result<chrono::NaiveTime, D::Error>
 where
@@ -13,6 +14,13 @@ where
     chrono::NaiveTime::parse_from_str(&str, "%H:%M").map_err(serde::de::Error::custom)
 }
 
+pub fn to_hh_mm<S>(time: &chrono::NaiveTime, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(&time.format("%H:%M").to_string())
+}
+
 pub fn keys_match<T: Eq + Hash + Ord, U, V>(map1: &BTreeMap<T, U>, map2: &BTreeMap<T, V>) -> bool {
     map1.len() == map2.len() && map1.keys().all(|k| map2.contains_key(k))
 }
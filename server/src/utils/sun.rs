@@ -0,0 +1,137 @@
+//! Local sunrise / sunset calculation using the NOAA sunrise equation, which
+//! is accurate to within a couple of minutes at non-polar latitudes.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Julian date of 2000-01-01 12:00 UTC.
+const J2000: f64 = 2451545.0;
+
+/// Julian date of the unix epoch.
+const UNIX_EPOCH_JULIAN_DATE: f64 = 2440587.5;
+
+/// Apparent solar elevation at sunrise and sunset, accounting for refraction
+/// and the solar disc radius.
+const SUN_ALTITUDE_DEG: f64 = -0.833;
+
+const EARTH_AXIAL_TILT_DEG: f64 = 23.4397;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coordinates {
+    /// Degrees north of the equator.
+    pub latitude: f64,
+
+    /// Degrees east of Greenwich.
+    pub longitude: f64,
+}
+
+#[derive(TS, Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+/// Returns the time of `event` on `date` at `location`, or `None` if the sun
+/// doesn't rise or set that day (polar day or night).
+pub fn sun_event_time(
+    event: SunEvent,
+    date: NaiveDate,
+    location: Coordinates,
+) -> Option<DateTime<Utc>> {
+    let j2000_date = NaiveDate::from_ymd_opt(2000, 1, 1)?;
+    let days = (date - j2000_date).num_days() as f64;
+
+    // Mean solar time
+    let mean_solar_noon = days - location.longitude / 360.0;
+
+    let mean_anomaly = (357.5291 + 0.98560028 * mean_solar_noon).rem_euclid(360.0);
+    let m = mean_anomaly.to_radians();
+
+    let equation_of_center = 1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude =
+        (mean_anomaly + equation_of_center + 180.0 + 102.9372).rem_euclid(360.0);
+    let lambda = ecliptic_longitude.to_radians();
+
+    let solar_transit = J2000 + mean_solar_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * lambda).sin();
+
+    let declination = (lambda.sin() * EARTH_AXIAL_TILT_DEG.to_radians().sin()).asin();
+    let latitude = location.latitude.to_radians();
+
+    let cos_hour_angle = (SUN_ALTITUDE_DEG.to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees() / 360.0;
+    let julian_date = match event {
+        SunEvent::Sunrise => solar_transit - hour_angle,
+        SunEvent::Sunset => solar_transit + hour_angle,
+    };
+
+    let unix_ms = ((julian_date - UNIX_EPOCH_JULIAN_DATE) * 86_400_000.0).round() as i64;
+    DateTime::from_timestamp_millis(unix_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELSINKI: Coordinates = Coordinates {
+        latitude: 60.17,
+        longitude: 24.94,
+    };
+
+    fn assert_close(actual: DateTime<Utc>, expected: &str) {
+        let expected = DateTime::parse_from_rfc3339(expected).unwrap();
+        let delta = (actual - expected.with_timezone(&Utc)).num_minutes().abs();
+        assert!(delta <= 5, "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn computes_midsummer_sunrise_and_sunset() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        assert_close(
+            sun_event_time(SunEvent::Sunrise, date, HELSINKI).unwrap(),
+            "2024-06-21T03:54:00+03:00",
+        );
+        assert_close(
+            sun_event_time(SunEvent::Sunset, date, HELSINKI).unwrap(),
+            "2024-06-21T22:50:00+03:00",
+        );
+    }
+
+    #[test]
+    fn handles_western_longitudes() {
+        let new_york = Coordinates {
+            latitude: 40.71,
+            longitude: -74.01,
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+
+        assert_close(
+            sun_event_time(SunEvent::Sunrise, date, new_york).unwrap(),
+            "2024-12-21T07:17:00-05:00",
+        );
+        assert_close(
+            sun_event_time(SunEvent::Sunset, date, new_york).unwrap(),
+            "2024-12-21T16:32:00-05:00",
+        );
+    }
+
+    #[test]
+    fn polar_day_has_no_sunset() {
+        let tromso = Coordinates {
+            latitude: 69.65,
+            longitude: 18.96,
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        assert_eq!(sun_event_time(SunEvent::Sunset, date, tromso), None);
+    }
+}
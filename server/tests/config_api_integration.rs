@@ -344,7 +344,8 @@ fn sample_config_export() -> Value {
     json!({
         "version": 1,
         "core": {
-            "warmup_time_seconds": 7
+            "warmup_time_seconds": 7,
+            "device_history_retention_days": 7
         },
         "integrations": [
             {
@@ -2125,6 +2126,42 @@ fn config_api_hot_reloads_routines_for_sensor_triggers() {
     });
 }
 
+#[test]
+fn config_api_saves_custom_actions_for_dummy_integrations() {
+    let server = start_reload_test_server();
+
+    // Simulation turns MQTT integrations into dummies, so their actions
+    // must still be accepted
+    let create_routine_response = post_json(
+        &server.base_url,
+        "/api/v1/config/routines",
+        &json!({
+            "id": "ring_chime",
+            "name": "Ring Chime",
+            "enabled": true,
+            "rules": [],
+            "actions": [
+                {
+                    "action": "Custom",
+                    "integration_id": "reload_dummy",
+                    "payload": {
+                        "topic": "home/hallway/chime",
+                        "json": { "ring": true }
+                    }
+                }
+            ]
+        }),
+    );
+    assert_eq!(create_routine_response.status(), StatusCode::CREATED);
+
+    let routines = get_json(&server.base_url, "/api/v1/config/routines");
+    assert!(routines["data"]
+        .as_array()
+        .expect("routines data should be an array")
+        .iter()
+        .any(|routine| routine["id"] == "ring_chime"));
+}
+
 #[test]
 fn config_api_updates_routine_id_and_force_trigger_references() {
     let server = TestServer::new().expect("Failed to start test server");
@@ -2194,3 +2231,63 @@ fn config_api_updates_routine_id_and_force_trigger_references() {
         json!("routine_a_renamed"),
     );
 }
+
+#[test]
+fn events_poll_replies_right_away_to_changes_since_its_etag() {
+    let server = start_reload_test_server();
+
+    wait_for("seeded reload fixture devices to appear", || {
+        let devices = get_json(&server.base_url, "/api/v1/devices");
+        device_by_name(&devices, "Reload Sensor").is_some()
+    });
+
+    let etag = |response: &Response| {
+        response
+            .headers()
+            .get("etag")
+            .expect("poll replies should have an ETag")
+            .to_str()
+            .unwrap()
+            .trim_matches('"')
+            .to_string()
+    };
+
+    let initial = get(&server.base_url, "/api/v1/events/poll?topics=devices");
+    assert_eq!(initial.status(), StatusCode::OK);
+    let since = etag(&initial);
+
+    let unchanged = get(
+        &server.base_url,
+        &format!("/api/v1/events/poll?topics=devices&since={since}&wait_ms=0"),
+    );
+    assert_eq!(unchanged.status(), StatusCode::NO_CONTENT);
+    assert_eq!(etag(&unchanged), since);
+
+    // The change lands between two polls
+    let sensor_update_response = put_json(
+        &server.base_url,
+        "/api/v1/devices/sensor1",
+        &json!({
+            "id": "sensor1",
+            "name": "Reload Sensor",
+            "integration_id": "reload_dummy",
+            "data": {
+                "Sensor": {
+                    "value": true
+                }
+            }
+        }),
+    );
+    assert_eq!(sensor_update_response.status(), StatusCode::OK);
+
+    wait_for("the next poll to report the change", || {
+        let started = Instant::now();
+        let changed = get(
+            &server.base_url,
+            &format!("/api/v1/events/poll?topics=devices&since={since}&wait_ms=1000"),
+        );
+        changed.status() == StatusCode::OK
+            && started.elapsed() < Duration::from_millis(1000)
+            && etag(&changed) != since
+    });
+}
@@ -2,6 +2,7 @@ import { useState } from 'react';
 
 import { type Device } from '@/bindings/Device';
 import {
+  confirmRemove,
   type Group,
   useDeviceDisplayNames,
   useGroups,
@@ -145,9 +146,7 @@ export default function GroupsPage() {
               devicesByKey={devicesByKey}
               onEdit={() => setEditingId(group.id)}
               onDelete={async () => {
-                if (confirm(`Delete group "${group.name}"?`)) {
-                  await remove(group.id);
-                }
+                await confirmRemove(remove, group.id, `group "${group.name}"`);
               }}
             />
           ))}
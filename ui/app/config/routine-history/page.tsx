@@ -40,6 +40,7 @@ type TriggerFilter = RoutineHistoryTriggerKind | 'all';
 const triggerLabels: Record<RoutineHistoryTriggerKind, string> = {
   rule_match: 'Rule match',
   force_trigger: 'Force trigger',
+  suppressed: 'Suppressed',
 };
 
 function formatTimestamp(timestamp: string) {
@@ -275,6 +276,7 @@ export default function RoutineHistoryPage() {
                   <SelectItem value="all">All triggers</SelectItem>
                   <SelectItem value="rule_match">Rule matches</SelectItem>
                   <SelectItem value="force_trigger">Manual triggers</SelectItem>
+                  <SelectItem value="suppressed">Suppressed</SelectItem>
                 </SelectContent>
               </Select>
             </div>
@@ -340,6 +342,9 @@ export default function RoutineHistoryPage() {
                         {entry.event_source_device_key
                           ? ` · source ${entry.event_source_device_key}`
                           : ''}
+                        {entry.suppressed_reason
+                          ? ` · ${entry.suppressed_reason}`
+                          : ''}
                       </CardDescription>
                     </div>
                     <CardDescription>
@@ -3,6 +3,7 @@ import {
   useRoutines,
   useScenes,
   Routine,
+  confirmRemove,
 } from '@/hooks/useConfig';
 import type { RoutineRuntimeStatus } from '@/bindings/RoutineRuntimeStatus';
 import { matchesConfigSearch } from '@/lib/configSearch';
@@ -163,8 +164,13 @@ export default function RoutinesPage() {
               }}
               onCancel={() => setEditingId(null)}
               onDelete={async () => {
-                if (confirm(`Delete routine "${routine.name}"?`)) {
-                  await remove(routine.id);
+                if (
+                  await confirmRemove(
+                    remove,
+                    routine.id,
+                    `routine "${routine.name}"`,
+                  )
+                ) {
                   setOpenId((current) =>
                     current === routine.id ? null : current,
                   );
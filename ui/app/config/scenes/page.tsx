@@ -5,6 +5,7 @@ import {
   Scene,
   SceneDeviceConfig,
   getSceneDeviceLinkTargetKey,
+  confirmRemove,
 } from '@/hooks/useConfig';
 import { useAppConfig } from '@/hooks/appConfig';
 import { useMemo, useState } from 'react';
@@ -278,8 +279,13 @@ export default function ScenesPage() {
                 }}
                 onCancel={() => setEditingId(null)}
                 onDelete={async () => {
-                  if (confirm(`Delete scene "${scene.name}"?`)) {
-                    await remove(scene.id);
+                  if (
+                    await confirmRemove(
+                      remove,
+                      scene.id,
+                      `scene "${scene.name}"`,
+                    )
+                  ) {
                     setOpenId((current) =>
                       current === scene.id ? null : current,
                     );
@@ -9,7 +9,6 @@ import {
 import { useDeviceState, useWebsocket } from '@/hooks/websocket';
 import clsx from 'clsx';
 import deepEqual from 'deep-equal';
-import { produce } from 'immer';
 import { Edit, Plus, Settings, Trash } from 'lucide-react';
 import { useCallback, useEffect, useRef, useState } from 'react';
 import { useToggle } from 'usehooks-ts';
@@ -35,21 +34,12 @@ const UnmemoizedCarHeaterModal = () => {
 
   const toggleHeater = useCallback(
     (power: boolean) => {
-      if (carHeaterDevice) {
-        const device = produce(carHeaterDevice, (draft) => {
-          if ('Controllable' in draft.data) {
-            draft.data.Controllable.state.power = power;
-            draft.data.Controllable.scene_id = null;
-          }
-        });
-
+      if (carHeaterDevice && 'Controllable' in carHeaterDevice.data) {
         const msg: WebSocketRequest = {
-          EventMessage: {
-            SetInternalState: {
-              device,
-              skip_external_update: false,
-              skip_db_update: null,
-            },
+          SetDeviceState: {
+            device_key: carHeaterDeviceKey,
+            state: { ...carHeaterDevice.data.Controllable.state, power },
+            keep_scene: false,
           },
         };
 
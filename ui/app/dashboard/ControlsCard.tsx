@@ -1,6 +1,5 @@
 import { WebSocketRequest } from '@/bindings/WebSocketRequest';
 import { useDeviceState, useWebsocket } from '@/hooks/websocket';
-import { produce } from 'immer';
 import { Car, Edit, LampCeiling } from 'lucide-react';
 import { useTimeout, useToggle } from 'usehooks-ts';
 import Viewport from '../map/Viewport';
@@ -43,21 +42,15 @@ export const ControlsCard = () => {
   }
 
   const toggleCarHeater = (power?: boolean) => {
-    if (carHeaterDevice) {
-      const device = produce(carHeaterDevice, (draft) => {
-        if ('Controllable' in draft.data) {
-          draft.data.Controllable.state.power = power ?? !carHeater;
-          draft.data.Controllable.scene_id = null;
-        }
-      });
-
+    if (carHeaterDevice && 'Controllable' in carHeaterDevice.data) {
       const msg: WebSocketRequest = {
-        EventMessage: {
-          SetInternalState: {
-            device,
-            skip_external_update: false,
-            skip_db_update: null,
+        SetDeviceState: {
+          device_key: carHeaterDeviceKey,
+          state: {
+            ...carHeaterDevice.data.Controllable.state,
+            power: power ?? !carHeater,
           },
+          keep_scene: false,
         },
       };
 
@@ -69,11 +62,9 @@ export const ControlsCard = () => {
   /*
   const toggleLights = () => {
     const msg: WebSocketRequest = {
-      EventMessage: {
-        Action: {
-          action: 'ForceTriggerRoutine',
-          routine_id: lightsOn ? 'leave_home' : 'entryway',
-        },
+      Action: {
+        action: 'ForceTriggerRoutine',
+        routine_id: lightsOn ? 'leave_home' : 'entryway',
       },
     };
 
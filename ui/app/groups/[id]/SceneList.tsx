@@ -50,20 +50,18 @@ export const SceneList = (props: Props) => {
 
   const handleSceneClick = (sceneId: SceneId) => () => {
     const msg: WebSocketRequest = {
-      EventMessage: {
-        Action: {
-          action: 'ActivateScene',
-          device_keys: props.deviceKeys,
-          group_keys: null,
-          mirror_from_group: null,
-          include_source_groups: false,
-          use_scene_transition: false,
-          transition: null,
-          rollout: null,
-          rollout_source_device_key: null,
-          rollout_duration_ms: null,
-          scene_id: sceneId,
-        },
+      Action: {
+        action: 'ActivateScene',
+        device_keys: props.deviceKeys,
+        group_keys: null,
+        mirror_from_group: null,
+        include_source_groups: false,
+        use_scene_transition: false,
+        transition: null,
+        rollout: null,
+        rollout_source_device_key: null,
+        rollout_duration_ms: null,
+        scene_id: sceneId,
       },
     };
 
@@ -3,6 +3,7 @@ import { QueryClientProvider } from '@tanstack/react-query';
 import { useProvideWebsocketState } from '@/hooks/websocket';
 import '@/styles/globals.css';
 import { HomectlBottomNavigation } from '@/ui/BottomNavigation';
+import { LoginForm } from '@/ui/LoginForm';
 import { Navbar } from '@/ui/Navbar';
 import { useProvideAppConfig } from '@/hooks/appConfig';
 import { useApplyTheme } from '@/hooks/theme';
@@ -49,8 +50,9 @@ export const Providers = ({ children }: { children: ReactNode }) => {
 };
 
 export const ProvideAppConfig = ({ children }: { children: ReactNode }) => {
-  const appConfigLoaded = useProvideAppConfig();
-  if (!appConfigLoaded) return null;
+  const appConfigStatus = useProvideAppConfig();
+  if (appConfigStatus === 'unauthorized') return <LoginForm />;
+  if (appConfigStatus !== 'loaded') return null;
 
   return children;
 };
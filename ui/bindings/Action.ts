@@ -1,11 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { ActivateSceneActionDescriptor } from "./ActivateSceneActionDescriptor";
+import type { CancelSequenceDescriptor } from "./CancelSequenceDescriptor";
 import type { CustomActionDescriptor } from "./CustomActionDescriptor";
 import type { CycleScenesDescriptor } from "./CycleScenesDescriptor";
 import type { Device } from "./Device";
 import type { DeviceKey } from "./DeviceKey";
 import type { DimDescriptor } from "./DimDescriptor";
 import type { ForceTriggerRoutineDescriptor } from "./ForceTriggerRoutineDescriptor";
+import type { IfDescriptor } from "./IfDescriptor";
+import type { SequenceDescriptor } from "./SequenceDescriptor";
 import type { UiActionDescriptor } from "./UiActionDescriptor";
+import type { WaitUntilDescriptor } from "./WaitUntilDescriptor";
 
-export type Action = { "action": "ActivateScene" } & ActivateSceneActionDescriptor | { "action": "CycleScenes" } & CycleScenesDescriptor | { "action": "Custom" } & CustomActionDescriptor | { "action": "Dim" } & DimDescriptor | { "action": "ForceTriggerRoutine" } & ForceTriggerRoutineDescriptor | { "action": "SetDeviceState" } & Device | { "action": "ToggleDeviceOverride", device_keys: Array<DeviceKey>, override_state: boolean, } | { "action": "Ui" } & UiActionDescriptor;
+export type Action = { "action": "ActivateScene" } & ActivateSceneActionDescriptor | { "action": "CancelSequence" } & CancelSequenceDescriptor | { "action": "CycleScenes" } & CycleScenesDescriptor | { "action": "Custom" } & CustomActionDescriptor | { "action": "Delay", ms: number, } | { "action": "Dim" } & DimDescriptor | { "action": "ForceTriggerRoutine" } & ForceTriggerRoutineDescriptor | { "action": "If" } & IfDescriptor | { "action": "Sequence" } & SequenceDescriptor | { "action": "SetDeviceState" } & Device | { "action": "ToggleDeviceOverride", device_keys: Array<DeviceKey>, override_state: boolean, } | { "action": "Ui" } & UiActionDescriptor | { "action": "WaitUntil" } & WaitUntilDescriptor;
@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { DeviceKey } from "./DeviceKey";
+import type { FadeDescriptor } from "./FadeDescriptor";
 import type { GroupId } from "./GroupId";
 import type { RolloutStyle } from "./RolloutStyle";
 import type { SceneId } from "./SceneId";
@@ -53,4 +54,9 @@ rollout_source_device_key: DeviceKey | null,
 /**
  * Total rollout duration in milliseconds.
  */
-rollout_duration_ms: number | null, };
+rollout_duration_ms: number | null, 
+/**
+ * Fade devices to the scene in steps driven by homectl instead of
+ * relying on device transitions. Rollouts are ignored for fades.
+ */
+fade: FadeDescriptor | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Role } from "./Role";
+
+/**
+ * An API token, without the token itself which is only shown once when the
+ * token is created.
+ */
+export type ApiTokenInfo = { id: string, name: string, role: Role, created_at_ms: number, };
@@ -6,4 +6,9 @@ export type DeviceRule = { power: boolean | null, scene: SceneId | null,
 /**
  * How this rule should trigger. Defaults to `level` for device rules.
  */
-trigger_mode: TriggerMode, };
+trigger_mode: TriggerMode, 
+/**
+ * How long the condition must hold before the rule matches, see
+ * [TriggerMode].
+ */
+for_ms: number | null, };
@@ -21,4 +21,4 @@ skip_external_update: boolean | null,
 /**
  * Whether to skip persisting the device state to DB as a result of this state update.
  */
-skip_db_update: boolean | null, } } | "StartupCompleted" | { "DbStoreScene": { scene_id: SceneId, config: SceneConfig, } } | { "DbEditScene": { scene_id: SceneId, name: string, } } | { "DbDeleteScene": { scene_id: SceneId, } } | { "Action": Action };
+skip_db_update: boolean | null, } } | "StartupCompleted" | { "DbStoreScene": { scene_id: SceneId, config: SceneConfig, } } | { "DbEditScene": { scene_id: SceneId, name: string, } } | { "DbDeleteScene": { scene_id: SceneId, } } | { "Action": Action } | "RoutineClockTick" | "RoutineRuleHoldElapsed" | { "SequenceStep": { sequence_id: number, } } | "OccupancyTimeoutElapsed" | { "FadeStep": { fade_id: number, } };
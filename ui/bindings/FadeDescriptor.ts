@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Easing } from "./Easing";
+
+/**
+ * Fades devices to their target state in steps sent by homectl, for
+ * transitions longer than devices support on their own.
+ */
+export type FadeDescriptor = { duration_ms: number, easing: Easing, 
+/**
+ * Time between two steps, defaults to one second. Never shorter than
+ * the outbound update interval of the device integration.
+ */
+step_ms: number | null, };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Device } from "./Device";
+import type { DeviceKey } from "./DeviceKey";
+import type { GroupId } from "./GroupId";
+import type { RoutineHistoryEntry } from "./RoutineHistoryEntry";
+import type { SceneId } from "./SceneId";
+
+/**
+ * Individual changes sent on the event feed, in addition to the state
+ * patches that only carry the resulting state.
+ */
+export type FeedEvent = { "DeviceChanged": { device: Device, } } | 
+/**
+ * A routine was triggered, or suppressed by its cooldown or debounce.
+ */
+{ "RoutineTriggered": RoutineHistoryEntry } | { "SceneActivated": { scene_id: SceneId, device_keys: Array<DeviceKey> | null, group_keys: Array<GroupId> | null, } };
@@ -7,4 +7,9 @@ export type GroupRule = { group_id: GroupId, power: boolean | null, scene: Scene
 /**
  * How this rule should trigger. Defaults to `level` for group rules.
  */
-trigger_mode: TriggerMode, };
+trigger_mode: TriggerMode, 
+/**
+ * How long the condition must hold before the rule matches, see
+ * [TriggerMode].
+ */
+for_ms: number | null, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Action } from "./Action";
+import type { Rule } from "./Rule";
+
+/**
+ * Runs `then` if all rules of `condition` currently match, and `else`
+ * otherwise. Trigger modes and `for_ms` durations are ignored.
+ */
+export type IfDescriptor = { condition: Array<Rule>, then: Array<Action>, else: Array<Action>, };
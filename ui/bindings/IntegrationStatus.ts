@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IntegrationHealth } from "./IntegrationHealth";
+import type { IntegrationId } from "./IntegrationId";
+
+export type IntegrationStatus = { integration_id: IntegrationId, plugin: string, status: IntegrationHealth, 
+/**
+ * When `status` last changed.
+ */
+since: string, last_error: string | null, last_error_at: string | null, 
+/**
+ * How many times the integration has reconnected after losing its
+ * connection.
+ */
+reconnect_count: number, 
+/**
+ * When the integration last received a message from its devices or
+ * service.
+ */
+last_message_at: string | null, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Role } from "./Role";
+
+export type LoginResponse = { 
+/**
+ * Session token, sent as `Authorization: Bearer <token>` or as the
+ * `token` query parameter of WebSocket connections.
+ */
+token: string, username: string, role: Role, expires_at_ms: number, };
@@ -0,0 +1,29 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceKey } from "./DeviceKey";
+
+/**
+ * Combines motion sensors and door contacts of a room into a virtual
+ * occupancy sensor.
+ *
+ * The room becomes occupied on motion or when a door opens, and vacant once
+ * `timeout_ms` has passed without either. Motion while all doors are closed
+ * keeps the room occupied until a door opens again.
+ */
+export type OccupancyConfig = { 
+/**
+ * Boolean sensors reporting `true` while they detect motion.
+ */
+motion_sensors: Array<DeviceKey>, 
+/**
+ * Boolean contact sensors on the doors of the room.
+ */
+door_sensors: Array<DeviceKey>, 
+/**
+ * Sensor value that means a door is open. Defaults to `false`, matching
+ * `contact` sensors that report `true` while closed.
+ */
+door_open_value: boolean, 
+/**
+ * How long the room stays occupied after the last activity.
+ */
+timeout_ms: number, };
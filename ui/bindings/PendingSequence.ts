@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PendingSequenceState } from "./PendingSequenceState";
+
+/**
+ * A sequence that has actions left to run.
+ */
+export type PendingSequence = { id: number, key: string | null, started_at: string, state: PendingSequenceState, 
+/**
+ * Index of the action the sequence is currently waiting on.
+ */
+step: number, step_count: number, 
+/**
+ * When the current delay elapses or the current wait times out.
+ */
+resumes_at: string | null, };
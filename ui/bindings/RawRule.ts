@@ -7,4 +7,9 @@ export type RawRule = { path: string, operator: RawRuleOperator, value: JsonValu
 /**
  * How this rule should trigger. Defaults to `pulse`, matching sensor rules.
  */
-trigger_mode: TriggerMode, };
+trigger_mode: TriggerMode, 
+/**
+ * How long the condition must hold before the rule matches, see
+ * [TriggerMode].
+ */
+for_ms: number | null, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Access level of an API user or token. Each role includes the access of
+ * the roles before it.
+ */
+export type Role = 
+/**
+ * Can read device state and config.
+ */
+"viewer" | 
+/**
+ * Can also run actions and activate or edit scenes.
+ */
+"operator" | 
+/**
+ * Can also change config, users and API tokens.
+ */
+"admin";
@@ -1,4 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Action } from "./Action";
 
-export type Routine = { name: string, actions: Array<Action>, };
+export type Routine = { name: string, actions: Array<Action>, 
+/**
+ * Minimum time between two firings of the routine. Triggers during the
+ * cooldown are suppressed.
+ */
+cooldown_ms: number | null, 
+/**
+ * Suppresses triggers arriving within this long of the previous
+ * trigger, whether or not that one fired. A routine that keeps
+ * triggering stays suppressed until it has been quiet for this long.
+ */
+debounce_ms: number | null, };
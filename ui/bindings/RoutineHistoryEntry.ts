@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceKey } from "./DeviceKey";
+import type { RoutineHistoryTriggerKind } from "./RoutineHistoryTriggerKind";
+import type { RoutineId } from "./RoutineId";
+import type { RoutineRuntimeStatus } from "./RoutineRuntimeStatus";
+
+export type RoutineHistoryEntry = { id: string, timestamp: string, routine_id: RoutineId, routine_name: string, trigger_kind: RoutineHistoryTriggerKind, event_source_device_key: DeviceKey | null, action_count: number, status: RoutineRuntimeStatus | null, 
+/**
+ * Why the trigger was suppressed, only set for suppressed triggers.
+ */
+suppressed_reason: string | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RoutineHistoryTriggerKind = "rule_match" | "force_trigger" | 
+/**
+ * The rules matched but the routine's cooldown or debounce kept it from
+ * firing.
+ */
+"suppressed";
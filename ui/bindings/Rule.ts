@@ -5,5 +5,7 @@ import type { GroupRule } from "./GroupRule";
 import type { RawRule } from "./RawRule";
 import type { ScriptRule } from "./ScriptRule";
 import type { SensorRule } from "./SensorRule";
+import type { SunRule } from "./SunRule";
+import type { TimeWindowRule } from "./TimeWindowRule";
 
-export type Rule = SensorRule | RawRule | DeviceRule | GroupRule | AnyRule | ScriptRule;
+export type Rule = SensorRule | RawRule | DeviceRule | GroupRule | AnyRule | TimeWindowRule | SunRule | ScriptRule;
@@ -1,3 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type RuleRuntimeStatus = { condition_match: boolean, trigger_match: boolean, error: string | null, children: Array<RuleRuntimeStatus> | null, };
+export type RuleRuntimeStatus = { condition_match: boolean, trigger_match: boolean, error: string | null, children: Array<RuleRuntimeStatus> | null, 
+/**
+ * For rules with `for_ms`, how long the condition still needs to hold
+ * before the rule matches.
+ */
+hold_remaining_ms: number | null, };
@@ -6,4 +6,9 @@ export type SensorRule = { state: SensorDevice,
 /**
  * How this rule should trigger. Defaults to `pulse` for sensors.
  */
-trigger_mode: TriggerMode, };
+trigger_mode: TriggerMode, 
+/**
+ * How long the condition must hold before the rule matches, see
+ * [TriggerMode].
+ */
+for_ms: number | null, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Action } from "./Action";
+
+/**
+ * Runs actions one after another. `Delay` and `WaitUntil` actions pause the
+ * sequence until they complete.
+ */
+export type SequenceDescriptor = { actions: Array<Action>, 
+/**
+ * Starting a sequence cancels any pending sequence with the same key.
+ */
+key: string | null, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SunRelation = 
+/**
+ * From the (offset) sun event until local midnight.
+ */
+"after" | 
+/**
+ * From local midnight until the (offset) sun event.
+ */
+"before";
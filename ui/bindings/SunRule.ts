@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SunEvent } from "./SunEvent";
+import type { SunRelation } from "./SunRelation";
+import type { TriggerMode } from "./TriggerMode";
+
+/**
+ * Matches relative to today's sunrise or sunset, computed from the location
+ * configured in core config.
+ */
+export type SunRule = { sun: SunEvent, relation: SunRelation, 
+/**
+ * Minutes added to the sun event, may be negative.
+ */
+offset_minutes: number, 
+/**
+ * How this rule should trigger. Defaults to `level`, i.e. the rule acts
+ * as a condition. With `edge` or `pulse` the routine triggers when the
+ * rule starts matching.
+ */
+trigger_mode: TriggerMode, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Weekday } from "./Weekday";
+
+export type TimeWindow = { 
+/**
+ * Local time (`HH:MM`) at which the window opens.
+ */
+start: string, 
+/**
+ * Local time (`HH:MM`) at which the window closes. Windows where `end` is
+ * before `start` span midnight, equal times span the whole day.
+ */
+end: string, 
+/**
+ * Days on which the window opens. Windows spanning midnight belong to the
+ * day they opened on. Defaults to every day.
+ */
+weekdays: Array<Weekday> | null, };
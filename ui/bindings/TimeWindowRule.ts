@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TimeWindow } from "./TimeWindow";
+import type { TriggerMode } from "./TriggerMode";
+
+/**
+ * Matches while the local time is within a daily time window.
+ */
+export type TimeWindowRule = { time_window: TimeWindow, 
+/**
+ * How this rule should trigger. Defaults to `level`, i.e. the rule acts
+ * as a condition. With `edge` or `pulse` the routine triggers when the
+ * window opens.
+ */
+trigger_mode: TriggerMode, };
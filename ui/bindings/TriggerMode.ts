@@ -2,5 +2,10 @@
 
 /**
  * Determines how a rule triggers in response to state changes.
+ *
+ * Rules with a `for_ms` hold duration only match once their condition has
+ * held continuously for that many milliseconds. The routine then triggers
+ * once when the duration elapses, and with `level` also on later events
+ * while the condition holds.
  */
 export type TriggerMode = "pulse" | "edge" | "level";
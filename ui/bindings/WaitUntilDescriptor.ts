@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Rule } from "./Rule";
+
+export type WaitUntilDescriptor = { 
+/**
+ * Rule whose condition the sequence waits for. Trigger modes and
+ * `for_ms` durations are ignored.
+ */
+rule: Rule, 
+/**
+ * Stop waiting after this many milliseconds.
+ */
+timeout_ms: number | null, 
+/**
+ * Whether the sequence is cancelled when the wait times out, rather
+ * than continuing with the next action.
+ */
+cancel_on_timeout: boolean, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type WebSocketErrorKind = 
+/**
+ * The message could not be parsed as a [WebSocketRequest].
+ */
+"invalid_message" | 
+/**
+ * The role of the client does not allow the request.
+ */
+"forbidden" | 
+/**
+ * The request was well-formed, but refers to unknown or invalid data.
+ */
+"invalid_request";
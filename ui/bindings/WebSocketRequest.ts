@@ -1,4 +1,20 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
-import type { Event } from "./Event";
+import type { Action } from "./Action";
+import type { ControllableState } from "./ControllableState";
+import type { DeviceKey } from "./DeviceKey";
+import type { SceneConfig } from "./SceneConfig";
+import type { SceneId } from "./SceneId";
+import type { WebSocketSubscription } from "./WebSocketSubscription";
+import type { JsonValue } from "./serde_json/JsonValue";
 
-export type WebSocketRequest = { "EventMessage": Event };
+/**
+ * Requests that clients may send over the WebSocket API. These are
+ * validated before being dispatched, and invalid requests are answered with
+ * [WebSocketResponse::Error].
+ */
+export type WebSocketRequest = { "Action": Action } | { "StoreUiState": { key: string, value: JsonValue, } } | { "SetDeviceState": { device_key: DeviceKey, state: ControllableState, 
+/**
+ * Stores the state as an override of the device's active scene,
+ * instead of leaving the scene.
+ */
+keep_scene: boolean, } } | { "StoreScene": { scene_id: SceneId, config: SceneConfig, } } | { "RenameScene": { scene_id: SceneId, name: string, } } | { "DeleteScene": { scene_id: SceneId, } } | { "Subscribe": WebSocketSubscription };
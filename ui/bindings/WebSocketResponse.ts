@@ -1,5 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FeedEvent } from "./FeedEvent";
 import type { StatePatch } from "./StatePatch";
 import type { StateUpdate } from "./StateUpdate";
+import type { WebSocketError } from "./WebSocketError";
 
-export type WebSocketResponse = { "State": StateUpdate } | { "Patch": StatePatch };
+export type WebSocketResponse = { "State": StateUpdate } | { "Patch": StatePatch } | 
+/**
+ * Sent to a client whose request was rejected.
+ */
+{ "Error": WebSocketError } | 
+/**
+ * Sent to clients that subscribed to the event feed.
+ */
+{ "Event": FeedEvent };
@@ -0,0 +1,34 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceKey } from "./DeviceKey";
+import type { GroupId } from "./GroupId";
+import type { IntegrationId } from "./IntegrationId";
+import type { WebSocketTopic } from "./WebSocketTopic";
+
+/**
+ * Limits which state is sent to a WebSocket client. Filters that are left
+ * out don't limit anything, so an empty subscription receives all state.
+ */
+export type WebSocketSubscription = { 
+/**
+ * Parts of the state to send, defaults to all of them.
+ */
+topics: Array<WebSocketTopic> | null, 
+/**
+ * Devices to send. When several device filters are given, devices
+ * matching any of them are sent.
+ */
+device_keys: Array<DeviceKey> | null, 
+/**
+ * Send devices belonging to these groups, and only these groups.
+ * Scenes are limited to the ones setting any of the subscribed devices.
+ */
+groups: Array<GroupId> | null, 
+/**
+ * Send devices of these integrations.
+ */
+integration_ids: Array<IntegrationId> | null, 
+/**
+ * Also send individual events as [WebSocketResponse::Event]. These are
+ * not limited by the other filters.
+ */
+feed: boolean, };
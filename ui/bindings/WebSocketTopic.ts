@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Parts of the state that WebSocket clients can subscribe to.
+ */
+export type WebSocketTopic = "devices" | "scenes" | "groups" | "routine_statuses" | "ui_state";
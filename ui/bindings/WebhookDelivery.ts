@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IntegrationId } from "./IntegrationId";
+
+export type WebhookDelivery = { id: string, 
+/**
+ * When the last attempt finished.
+ */
+timestamp: string, integration_id: IntegrationId, webhook_id: string, url: string, 
+/**
+ * Kind of the delivered event, e.g. `device_changed`.
+ */
+event_kind: string, attempts: number, success: boolean, 
+/**
+ * HTTP status of the last response, if one was received.
+ */
+status: number | null, error: string | null, };
@@ -11,7 +11,6 @@ type ConfigResponse = Partial<Config> & {
   weather_api_url?: string;
   train_api_url?: string;
   influx_url?: string;
-  influx_token?: string;
   calendar_api_url?: string;
   calendar_ics_url?: string;
 };
@@ -61,8 +60,6 @@ function normalizeConfig(
     trainApiUrl:
       firstNonEmptyString(config.trainApiUrl, config.train_api_url) ?? '',
     influxUrl: firstNonEmptyString(config.influxUrl, config.influx_url) ?? '',
-    influxToken:
-      firstNonEmptyString(config.influxToken, config.influx_token) ?? '',
     calendarApiUrl:
       firstNonEmptyString(config.calendarApiUrl, config.calendar_api_url) ??
       '/api/calendar',
@@ -71,8 +68,10 @@ function normalizeConfig(
   };
 }
 
+export type AppConfigStatus = 'loading' | 'loaded' | 'unauthorized';
+
 export const useProvideAppConfig = () => {
-  const [loaded, setLoaded] = useState(false);
+  const [status, setStatus] = useState<AppConfigStatus>('loading');
   const setConfig = useSetAtom(appConfigAtom);
 
   useEffect(() => {
@@ -82,15 +81,20 @@ export const useProvideAppConfig = () => {
 
     const performFetch = async () => {
       const res = await fetch(resolveApiUrl(apiEndpoint, APP_CONFIG_PATH));
+      if (res.status === 401) {
+        setStatus('unauthorized');
+        return;
+      }
+
       const json = (await res.json()) as ConfigResponse;
       setConfig(normalizeConfig(json, apiEndpoint));
-      setLoaded(true);
+      setStatus('loaded');
     };
 
     performFetch().catch(console.error);
   }, [setConfig]);
 
-  return loaded;
+  return status;
 };
 
 export const useAppConfig = (): Config => {
@@ -98,7 +102,7 @@ export const useAppConfig = (): Config => {
 
   if (config === null) {
     throw new Error(
-      'Calling useAppConfig before config has loaded is a fatal error. Make sure your app waits for useProvideAppConfig to return true before rendering.',
+      "Calling useAppConfig before config has loaded is a fatal error. Make sure your app waits for useProvideAppConfig to return 'loaded' before rendering.",
     );
   }
 
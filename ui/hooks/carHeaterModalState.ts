@@ -49,12 +49,7 @@ export const useCarHeaterModalState = () => {
   const storeState = useCallback(
     (state: CarHeaterModalState) => {
       const msg: WebSocketRequest = {
-        EventMessage: {
-          Action: {
-            action: 'Ui',
-            StoreUIState: { key: uiStateKey, value: state },
-          },
-        },
+        StoreUiState: { key: uiStateKey, value: state },
       };
       ws?.send(JSON.stringify(msg));
     },
@@ -1,4 +1,6 @@
 import { type DeviceSensorConfig } from '@/lib/sensorInteraction';
+import { type IntegrationStatus } from '@/bindings/IntegrationStatus';
+import { type OccupancyConfig } from '@/bindings/OccupancyConfig';
 import { type RoutineRuntimeStatus } from '@/bindings/RoutineRuntimeStatus';
 import { type JsonValue } from '@/bindings/serde_json/JsonValue';
 import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
@@ -11,6 +13,7 @@ export interface Integration {
   plugin: string;
   config: Record<string, unknown>;
   enabled: boolean;
+  status?: IntegrationStatus | null;
 }
 
 export type IntegrationConfigFieldKind =
@@ -43,11 +46,20 @@ export interface IntegrationConfigFieldSchema {
   help_text?: string | null;
 }
 
+export interface IntegrationActionSchema {
+  action?: string | null;
+  label: string;
+  description?: string | null;
+  fields: IntegrationConfigFieldSchema[];
+  example: JsonValue;
+}
+
 export interface IntegrationConfigSchema {
   plugin: string;
   name: string;
   description: string;
   fields: IntegrationConfigFieldSchema[];
+  actions?: IntegrationActionSchema[];
 }
 
 export interface Group {
@@ -56,6 +68,7 @@ export interface Group {
   hidden: boolean;
   devices: { integration_id: string; device_id: string }[];
   linked_groups: string[];
+  occupancy?: OccupancyConfig | null;
   device_keys?: string[];
 }
 
@@ -118,6 +131,8 @@ export interface Routine {
   enabled: boolean;
   rules: unknown[];
   actions: unknown[];
+  cooldown_ms?: number | null;
+  debounce_ms?: number | null;
 }
 
 export interface DeviceDisplayNameOverride {
@@ -139,7 +154,10 @@ export interface UiLogEntry {
   message: string;
 }
 
-export type RoutineHistoryTriggerKind = 'rule_match' | 'force_trigger';
+export type RoutineHistoryTriggerKind =
+  | 'rule_match'
+  | 'force_trigger'
+  | 'suppressed';
 
 export interface RoutineHistoryEntry {
   id: string;
@@ -150,6 +168,7 @@ export interface RoutineHistoryEntry {
   event_source_device_key?: string | null;
   action_count: number;
   status?: RoutineRuntimeStatus | null;
+  suppressed_reason?: string | null;
 }
 
 export interface RuntimeStatus {
@@ -206,6 +225,17 @@ async function readApiResponse<T>(response: Response, fallbackMessage: string) {
   throw new Error(responseBody || fallbackMessage);
 }
 
+/** Thrown when deleting an entry that other config still refers to. */
+export class ConfigDependentsError extends Error {}
+
+export interface RemoveOptions {
+  /**
+   * Also remove references to the entry from other config, deleting routines
+   * whose rules refer to it.
+   */
+  cascade?: boolean;
+}
+
 // Generic fetch hook for config API
 function useConfigApi<T>(endpoint: string) {
   const { apiEndpoint } = useAppConfig();
@@ -257,13 +287,26 @@ function useConfigApi<T>(endpoint: string) {
   });
 
   const removeMutation = useMutation({
-    mutationFn: async (id: string) => {
+    mutationFn: async ({
+      id,
+      options,
+    }: {
+      id: string;
+      options?: RemoveOptions;
+    }) => {
+      const query = options?.cascade ? '?cascade=true' : '';
       const response = await fetch(
-        `${baseUrl}/${endpoint}/${encodeURIComponent(id)}`,
+        `${baseUrl}/${endpoint}/${encodeURIComponent(id)}${query}`,
         {
           method: 'DELETE',
         },
       );
+      if (response.status === 409) {
+        const result = (await response.json()) as ApiResponse<unknown>;
+        throw new ConfigDependentsError(
+          result.error || 'Still referenced by other config',
+        );
+      }
       const result = await readApiResponse<unknown>(
         response,
         'Failed to delete',
@@ -272,8 +315,11 @@ function useConfigApi<T>(endpoint: string) {
         throw new Error(result.error || 'Failed to delete');
       }
     },
-    onSuccess: () => {
-      void queryClient.invalidateQueries({ queryKey });
+    onSuccess: (_, { options }) => {
+      // Cascading also changes other kinds of config
+      void queryClient.invalidateQueries({
+        queryKey: options?.cascade ? ['config', baseUrl] : queryKey,
+      });
     },
   });
 
@@ -284,7 +330,8 @@ function useConfigApi<T>(endpoint: string) {
   const create = (item: Partial<T>) => createMutation.mutateAsync(item);
   const update = (id: string, item: Partial<T>) =>
     updateMutation.mutateAsync({ id, item });
-  const remove = (id: string) => removeMutation.mutateAsync(id);
+  const remove = (id: string, options?: RemoveOptions) =>
+    removeMutation.mutateAsync({ id, options });
   const error = query.error instanceof Error ? query.error.message : null;
 
   return {
@@ -298,6 +345,34 @@ function useConfigApi<T>(endpoint: string) {
   };
 }
 
+/**
+ * Asks before deleting an entry, and again before removing references to it
+ * if other config still refers to it. Returns whether it was deleted.
+ */
+export async function confirmRemove(
+  remove: (id: string, options?: RemoveOptions) => Promise<void>,
+  id: string,
+  description: string,
+) {
+  if (!confirm(`Delete ${description}?`)) {
+    return false;
+  }
+
+  try {
+    await remove(id);
+  } catch (error) {
+    if (!(error instanceof ConfigDependentsError)) {
+      throw error;
+    }
+    if (!confirm(`${error.message}\n\nRemove these references too?`)) {
+      return false;
+    }
+    await remove(id, { cascade: true });
+  }
+
+  return true;
+}
+
 // Specialized hooks for each config type
 export function useIntegrations() {
   return useConfigApi<Integration>('integrations');
@@ -1,6 +1,7 @@
 import { Device } from '@/bindings/Device';
 import { WebSocketRequest } from '@/bindings/WebSocketRequest';
 import { useWebsocket } from '@/hooks/websocket';
+import { getDeviceKey } from '@/lib/device';
 import Color from 'color';
 import { produce } from 'immer';
 import { useCallback } from 'react';
@@ -16,38 +17,35 @@ export const useSetDeviceState = () => {
       brightness?: number,
       transition?: number,
     ) => {
-      const device = produce(clickedDevice, (draft) => {
-        if ('Controllable' in draft.data) {
-          if (!persistEnabled) {
-            draft.data.Controllable.scene_id = null;
-          }
-          draft.data.Controllable.state.power = power;
+      if (!('Controllable' in clickedDevice.data)) {
+        return;
+      }
 
-          if (color !== undefined) {
-            const hsv = color.hsv();
-            draft.data.Controllable.state.color = {
-              h: Math.round(hsv.hue()),
-              s: hsv.saturationv() / 100,
-            };
-          }
+      const state = produce(clickedDevice.data.Controllable.state, (draft) => {
+        draft.power = power;
 
-          if (brightness !== undefined) {
-            draft.data.Controllable.state.brightness = brightness;
-          }
+        if (color !== undefined) {
+          const hsv = color.hsv();
+          draft.color = {
+            h: Math.round(hsv.hue()),
+            s: hsv.saturationv() / 100,
+          };
+        }
+
+        if (brightness !== undefined) {
+          draft.brightness = brightness;
+        }
 
-          if (transition !== undefined) {
-            draft.data.Controllable.state.transition = transition;
-          }
+        if (transition !== undefined) {
+          draft.transition = transition;
         }
       });
 
       const msg: WebSocketRequest = {
-        EventMessage: {
-          SetInternalState: {
-            device,
-            skip_external_update: false,
-            skip_db_update: null,
-          },
+        SetDeviceState: {
+          device_key: getDeviceKey(clickedDevice),
+          state,
+          keep_scene: persistEnabled,
         },
       };
       ws?.send(JSON.stringify(msg));
@@ -10,6 +10,7 @@ import { JsonValue } from '@/bindings/serde_json/JsonValue';
 import { useEffect, useMemo, useRef } from 'react';
 import { atom, useAtomValue, useSetAtom } from 'jotai';
 import { useAppConfig } from './appConfig';
+import { withAuthToken } from '@/lib/auth';
 import { selectAtom } from 'jotai/utils';
 
 type UiState = { [key in string]?: JsonValue };
@@ -114,7 +115,7 @@ export const useProvideWebsocketState = () => {
 
       console.log('Opening ws connection...');
 
-      ws = new WebSocket(wsEndpoint);
+      ws = new WebSocket(withAuthToken(wsEndpoint));
 
       ws.onopen = () => {
         reconnectAttempts.current = 0;
@@ -155,6 +156,8 @@ export const useProvideWebsocketState = () => {
           if (patch.ui_state) {
             setUiState(patch.ui_state);
           }
+        } else if ('Error' in msg) {
+          console.warn('WebSocket request rejected:', msg.Error.message);
         }
       };
 
@@ -0,0 +1,95 @@
+const AUTH_TOKEN_KEY = 'homectl-token';
+const LOGIN_PATH = '/api/v1/auth/login';
+
+type LoginResponse = {
+  token: string;
+};
+
+export function getAuthToken(): string | null {
+  return window.localStorage.getItem(AUTH_TOKEN_KEY);
+}
+
+export function setAuthToken(token: string | null) {
+  if (token) {
+    window.localStorage.setItem(AUTH_TOKEN_KEY, token);
+  } else {
+    window.localStorage.removeItem(AUTH_TOKEN_KEY);
+  }
+}
+
+function apiEndpoint(): string {
+  const endpoint = import.meta.env.API_ENDPOINT as string | undefined;
+  return endpoint?.trim().replace(/\/+$/, '') ?? '';
+}
+
+function apiOrigins(): string[] {
+  const origins = [window.location.origin];
+
+  if (apiEndpoint()) {
+    try {
+      origins.push(new URL(apiEndpoint(), window.location.href).origin);
+    } catch {
+      // Ignore an invalid endpoint, requests to it will fail anyway
+    }
+  }
+
+  return origins;
+}
+
+/**
+ * Makes `fetch` send the stored token to the homectl API. Requests to other
+ * origins, such as widget data sources, are left untouched.
+ */
+export function installAuthFetch() {
+  const baseFetch = window.fetch.bind(window);
+  const origins = apiOrigins();
+
+  window.fetch = (input, init) => {
+    const token = getAuthToken();
+    if (!token) {
+      return baseFetch(input, init);
+    }
+
+    const request = new Request(input, init);
+    const origin = new URL(request.url).origin;
+    if (!origins.includes(origin) || request.headers.has('Authorization')) {
+      return baseFetch(request);
+    }
+
+    request.headers.set('Authorization', `Bearer ${token}`);
+    return baseFetch(request);
+  };
+}
+
+/**
+ * Adds the stored token to a WebSocket URL, since browsers can't set headers
+ * on WebSocket connections.
+ */
+export function withAuthToken(url: string): string {
+  const token = getAuthToken();
+  if (!token) {
+    return url;
+  }
+
+  const separator = url.includes('?') ? '&' : '?';
+  return `${url}${separator}token=${encodeURIComponent(token)}`;
+}
+
+export async function login(username: string, password: string) {
+  const res = await fetch(`${apiEndpoint()}${LOGIN_PATH}`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ username, password }),
+  });
+
+  if (!res.ok) {
+    throw new Error('Invalid username or password');
+  }
+
+  const json = (await res.json()) as { data?: LoginResponse };
+  if (!json.data?.token) {
+    throw new Error('Login failed');
+  }
+
+  setAuthToken(json.data.token);
+}
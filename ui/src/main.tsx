@@ -1,6 +1,8 @@
 import { StrictMode } from 'react';
 import { createRoot } from 'react-dom/client';
 
+import { installAuthFetch } from '@/lib/auth';
+
 import { App } from './app';
 
 const rootElement = document.getElementById('root');
@@ -9,6 +11,8 @@ if (rootElement === null) {
   throw new Error('Failed to find the root element for the Vite app.');
 }
 
+installAuthFetch();
+
 createRoot(rootElement).render(
   <StrictMode>
     <App />
@@ -692,12 +692,10 @@ const ScenesTab = (props: { deviceKeys: string[] }) => {
 
   const togglePersist = () => {
     const msg: WebSocketRequest = {
-      EventMessage: {
-        Action: {
-          action: 'ToggleDeviceOverride',
-          device_keys: props.deviceKeys,
-          override_state: !persistEnabled,
-        },
+      Action: {
+        action: 'ToggleDeviceOverride',
+        device_keys: props.deviceKeys,
+        override_state: !persistEnabled,
       },
     };
 
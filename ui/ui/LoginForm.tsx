@@ -0,0 +1,100 @@
+import { login, setAuthToken } from '@/lib/auth';
+import { Alert, AlertDescription } from '@/ui/primitives/alert';
+import { Button } from '@/ui/primitives/button';
+import {
+  Card,
+  CardContent,
+  CardDescription,
+  CardHeader,
+  CardTitle,
+} from '@/ui/primitives/card';
+import { Input } from '@/ui/primitives/input';
+import { Label } from '@/ui/primitives/label';
+import { useState, type FormEvent } from 'react';
+
+/**
+ * Shown when the server requires authentication. Signs in with a username
+ * and password, or stores an API token as is.
+ */
+export const LoginForm = () => {
+  const [username, setUsername] = useState('');
+  const [password, setPassword] = useState('');
+  const [apiToken, setApiToken] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [submitting, setSubmitting] = useState(false);
+
+  const onSubmit = async (event: FormEvent) => {
+    event.preventDefault();
+    setError(null);
+    setSubmitting(true);
+
+    try {
+      if (apiToken.trim() !== '') {
+        setAuthToken(apiToken.trim());
+      } else {
+        await login(username, password);
+      }
+      window.location.reload();
+    } catch (e) {
+      setError(e instanceof Error ? e.message : 'Login failed');
+      setSubmitting(false);
+    }
+  };
+
+  return (
+    <div className="flex min-h-screen items-center justify-center p-4">
+      <Card className="w-full max-w-sm">
+        <CardHeader>
+          <CardTitle>Sign in to homectl</CardTitle>
+          <CardDescription>
+            Use your account, or paste an API token.
+          </CardDescription>
+        </CardHeader>
+        <CardContent>
+          <form
+            className="flex flex-col gap-4"
+            onSubmit={(event) => void onSubmit(event)}
+          >
+            <div className="flex flex-col gap-2">
+              <Label htmlFor="login-username">Username</Label>
+              <Input
+                id="login-username"
+                autoComplete="username"
+                value={username}
+                onChange={(e) => setUsername(e.target.value)}
+              />
+            </div>
+            <div className="flex flex-col gap-2">
+              <Label htmlFor="login-password">Password</Label>
+              <Input
+                id="login-password"
+                type="password"
+                autoComplete="current-password"
+                value={password}
+                onChange={(e) => setPassword(e.target.value)}
+              />
+            </div>
+            <div className="flex flex-col gap-2">
+              <Label htmlFor="login-token">API token</Label>
+              <Input
+                id="login-token"
+                type="password"
+                autoComplete="off"
+                value={apiToken}
+                onChange={(e) => setApiToken(e.target.value)}
+              />
+            </div>
+            {error && (
+              <Alert variant="destructive">
+                <AlertDescription>{error}</AlertDescription>
+              </Alert>
+            )}
+            <Button type="submit" disabled={submitting}>
+              Sign in
+            </Button>
+          </form>
+        </CardContent>
+      </Card>
+    </div>
+  );
+};
@@ -110,11 +110,9 @@ const Component = (props: Props) => {
     };
 
     const msg: WebSocketRequest = {
-      EventMessage: {
-        DbStoreScene: {
-          scene_id: sceneName,
-          config,
-        },
+      StoreScene: {
+        scene_id: sceneName,
+        config,
       },
     };
 
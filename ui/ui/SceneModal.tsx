@@ -44,11 +44,9 @@ const Component = (props: Props) => {
     if (sceneModalState === null) return;
 
     const msg: WebSocketRequest = {
-      EventMessage: {
-        DbEditScene: {
-          scene_id: sceneModalState,
-          name: value,
-        },
+      RenameScene: {
+        scene_id: sceneModalState,
+        name: value,
       },
     };
 
@@ -67,10 +65,8 @@ const Component = (props: Props) => {
     if (sceneModalState === null) return;
 
     const msg: WebSocketRequest = {
-      EventMessage: {
-        DbDeleteScene: {
-          scene_id: sceneModalState,
-        },
+      DeleteScene: {
+        scene_id: sceneModalState,
       },
     };
 